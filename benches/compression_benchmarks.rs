@@ -7,7 +7,7 @@ fn benchmark_compression(c: &mut Criterion) {
 
     let mut group = c.benchmark_group("compression");
 
-    for compression_type in [CompressionType::Snappy, CompressionType::Lz4, CompressionType::Zstd] {
+    for compression_type in [CompressionType::Snappy, CompressionType::Lz4, CompressionType::Zstd, CompressionType::Gzip] {
         let config = CompressionConfig::new(compression_type);
         let compressor = Compressor::new(config);
 
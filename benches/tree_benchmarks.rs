@@ -1,7 +1,7 @@
 use bincode::{Decode, Encode};
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use std::hint::black_box;
-use redish::tree::Tree;
+use redish::tree::{CompressionConfig, CompressionType, Compressor, Tree, TreeSettingsBuilder};
 use std::time::Duration;
 
 #[derive(Debug, Encode, Decode, Clone)]
@@ -36,7 +36,7 @@ fn bench_put_operations(c: &mut Criterion) {
     group.measurement_time(Duration::from_secs(20));
 
     for size in [100, 1000, 10000].iter() {
-        group.throughput(Throughput::Elements(*size as u64));
+        group.throughput(Throughput::Elements(*size));
 
         group.bench_with_input(
             BenchmarkId::new("put_typed", size),
@@ -105,6 +105,386 @@ fn bench_get_operations(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_cold_sstable_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cold_sstable_reads");
+    group.measurement_time(Duration::from_secs(10));
+
+    let mut tree = setup_tree();
+    for i in 0..50000 {
+        let user = User::new(i);
+        tree.put_typed::<User>(&format!("user_{}", i), &user).unwrap();
+    }
+    tree.flush().unwrap();
+
+    group.bench_function("get_uncached_index", |b| {
+        let mut i = 0u64;
+        b.iter(|| {
+            tree.clear_index_cache();
+            tree.clear_value_cache();
+            let result = tree.get(format!("user_{}", i % 50000).as_bytes()).unwrap();
+            black_box(result);
+            i += 7919;
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_file_pool_random_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("file_pool_random_reads");
+    group.measurement_time(Duration::from_secs(10));
+
+    for max_open_files in [0usize, 64] {
+        let temp_dir = std::env::temp_dir().join("redish_bench");
+        if temp_dir.exists() {
+            std::fs::remove_dir_all(&temp_dir).ok();
+        }
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .db_path(&temp_dir)
+                .value_cache(false)
+                .max_open_files(max_open_files)
+                .build(),
+        )
+        .unwrap();
+        for i in 0..50000 {
+            let user = User::new(i);
+            tree.put_typed::<User>(&format!("user_{}", i), &user).unwrap();
+            if i % 10000 == 9999 {
+                tree.flush().unwrap();
+            }
+        }
+        tree.flush().unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("max_open_files", max_open_files),
+            &max_open_files,
+            |b, _| {
+                let mut i = 0u64;
+                b.iter(|| {
+                    let result = tree.get(format!("user_{}", i % 50000).as_bytes()).unwrap();
+                    black_box(result);
+                    i += 7919;
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_multi_get_misses(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_get_misses");
+    group.measurement_time(Duration::from_secs(10));
+
+    let mut tree = setup_tree();
+    for i in 0..10000 {
+        let user = User::new(i);
+        tree.put_typed::<User>(&format!("user_{}", i), &user).unwrap();
+    }
+    tree.flush().unwrap();
+
+    // 90% of the requested keys are absent from the tree
+    let keys: Vec<Vec<u8>> = (0..1000)
+        .map(|i| {
+            if i % 10 == 0 {
+                format!("user_{}", i).into_bytes()
+            } else {
+                format!("missing_{}", i).into_bytes()
+            }
+        })
+        .collect();
+
+    group.bench_function("multi_get", |b| {
+        b.iter(|| {
+            let result = tree.multi_get(keys.iter().map(|k| k.as_slice()).collect()).unwrap();
+            black_box(result);
+        });
+    });
+
+    group.bench_function("get_per_key", |b| {
+        b.iter(|| {
+            for key in &keys {
+                let result = tree.get(key).unwrap();
+                black_box(result);
+            }
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_disjoint_sstable_lookups(c: &mut Criterion) {
+    let mut group = c.benchmark_group("disjoint_sstable_lookups");
+    group.measurement_time(Duration::from_secs(10));
+
+    let temp_dir = std::env::temp_dir().join("redish_bench");
+    if temp_dir.exists() {
+        std::fs::remove_dir_all(&temp_dir).ok();
+    }
+    // One flush split into 100 tables of 100 consecutive keys each
+    let mut tree = Tree::load_with_settings(
+        TreeSettingsBuilder::new()
+            .db_path(&temp_dir)
+            .value_cache(false)
+            .sstable_max_entries(100)
+            .max_queued_compactions(0)
+            .build(),
+    )
+    .unwrap();
+    for i in 0..10000 {
+        let user = User::new(i);
+        tree.put_typed::<User>(&format!("user_{:05}", i), &user).unwrap();
+    }
+    tree.flush().unwrap();
+
+    group.bench_function("get_hit", |b| {
+        let mut i = 0u64;
+        b.iter(|| {
+            let result = tree.get(format!("user_{:05}", i % 10000).as_bytes()).unwrap();
+            black_box(result);
+            i += 7919;
+        });
+    });
+
+    // Sorts after every table, so each one is skipped on its key bounds alone
+    group.bench_function("get_out_of_range", |b| {
+        let mut i = 0u64;
+        b.iter(|| {
+            let result = tree.get(format!("zzz_{:05}", i % 10000).as_bytes()).unwrap();
+            black_box(result);
+            i += 7919;
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_get_into_buffer_reuse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_into_buffer_reuse");
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(Throughput::Elements(1000));
+
+    let mut tree = setup_tree();
+    let value = vec![b'x'; 16 * 1024];
+    for i in 0..2000 {
+        tree.put(format!("key_{}", i).into_bytes(), value.clone()).unwrap();
+    }
+    // Half of the keys are read from SSTables, half from the memory table
+    tree.flush().unwrap();
+    for i in 1000..2000 {
+        tree.put(format!("key_{}", i).into_bytes(), value.clone()).unwrap();
+    }
+    let keys: Vec<Vec<u8>> = (0..2000).step_by(2).map(|i| format!("key_{}", i).into_bytes()).collect();
+
+    group.bench_function("get", |b| {
+        b.iter(|| {
+            for key in &keys {
+                black_box(tree.get(key).unwrap());
+            }
+        });
+    });
+
+    group.bench_function("get_into", |b| {
+        let mut buffer = Vec::new();
+        b.iter(|| {
+            for key in &keys {
+                black_box(tree.get_into(key, &mut buffer).unwrap());
+                black_box(&buffer);
+            }
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_compression_buffer_reuse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compression_buffer_reuse");
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(Throughput::Elements(100_000));
+
+    let values: Vec<Vec<u8>> = (0..100_000)
+        .map(|i| format!("user_{}@example.com;profile={}", i, "x".repeat(64)).into_bytes())
+        .collect();
+
+    for compression_type in [CompressionType::Lz4, CompressionType::Zstd, CompressionType::Snappy] {
+        let compressor = Compressor::new(CompressionConfig::new(compression_type));
+
+        group.bench_function(BenchmarkId::new("compress", format!("{:?}", compression_type)), |b| {
+            b.iter(|| {
+                for value in &values {
+                    black_box(compressor.compress(value).unwrap());
+                }
+            });
+        });
+
+        group.bench_function(BenchmarkId::new("compress_into", format!("{:?}", compression_type)), |b| {
+            let mut buffer = Vec::new();
+            b.iter(|| {
+                for value in &values {
+                    compressor.compress_into(value, &mut buffer).unwrap();
+                    black_box(&buffer);
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_io_buffer_sizes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("io_buffer_sizes");
+    group.sample_size(10);
+
+    let temp_dir = std::env::temp_dir().join("redish_bench");
+    let value = vec![b'x'; 64 * 1024];
+    let setup = |buffer_size: usize| {
+        if temp_dir.exists() {
+            std::fs::remove_dir_all(&temp_dir).ok();
+        }
+        Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .db_path(&temp_dir)
+                .value_cache(false)
+                .read_buffer_size(buffer_size)
+                .write_buffer_size(buffer_size)
+                .build(),
+        )
+        .unwrap()
+    };
+
+    for buffer_size in [8 * 1024usize, 64 * 1024, 256 * 1024] {
+        group.throughput(Throughput::Bytes((500 * value.len()) as u64));
+
+        group.bench_with_input(BenchmarkId::new("large_value_writes", buffer_size), &buffer_size, |b, &size| {
+            b.iter_batched(
+                || setup(size),
+                |mut tree| {
+                    for i in 0..500 {
+                        tree.put(format!("key_{:04}", i).into_bytes(), value.clone()).unwrap();
+                    }
+                    tree.flush().unwrap();
+                    tree
+                },
+                criterion::BatchSize::PerIteration,
+            );
+        });
+
+        let mut tree = setup(buffer_size);
+        for i in 0..500 {
+            tree.put(format!("key_{:04}", i).into_bytes(), value.clone()).unwrap();
+        }
+        tree.flush().unwrap();
+
+        group.bench_with_input(BenchmarkId::new("sequential_reads", buffer_size), &buffer_size, |b, _| {
+            b.iter(|| {
+                for entry in tree.iter().unwrap() {
+                    black_box(entry.unwrap());
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_cache_eviction_batching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cache_eviction_batching");
+    group.sample_size(10);
+
+    let temp_dir = std::env::temp_dir().join("redish_bench");
+    let value = vec![b'x'; 16 * 1024];
+
+    for watermark in [1.0f64, 0.9] {
+        if temp_dir.exists() {
+            std::fs::remove_dir_all(&temp_dir).ok();
+        }
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .db_path(&temp_dir)
+                .value_cache_memory_limit(256 * value.len())
+                .cache_eviction_watermark(watermark)
+                .build(),
+        )
+        .unwrap();
+        for i in 0..2000 {
+            tree.put(format!("key_{:04}", i).into_bytes(), value.clone()).unwrap();
+        }
+        tree.flush().unwrap();
+        for i in 0..256 {
+            black_box(tree.get(format!("key_{:04}", i).as_bytes()).unwrap());
+        }
+
+        // Every read misses and inserts a large value into the full value cache.
+        group.bench_with_input(BenchmarkId::new("large_value_reads", watermark), &watermark, |b, _| {
+            let mut i = 256u64;
+            b.iter(|| {
+                black_box(tree.get(format!("key_{:04}", i % 2000).as_bytes()).unwrap());
+                i += 1;
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_same_size_overwrites(c: &mut Criterion) {
+    let mut group = c.benchmark_group("same_size_overwrites");
+    group.throughput(Throughput::Elements(10000));
+
+    for in_place in [false, true] {
+        let temp_dir = std::env::temp_dir().join("redish_bench");
+        if temp_dir.exists() {
+            std::fs::remove_dir_all(&temp_dir).ok();
+        }
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .db_path(&temp_dir)
+                .wal(false)
+                .overwrite_in_place(in_place)
+                .build(),
+        )
+        .unwrap();
+
+        group.bench_with_input(BenchmarkId::new("counter_updates", in_place), &in_place, |b, _| {
+            let mut counter = 0u64;
+            b.iter(|| {
+                for _ in 0..10000 {
+                    counter += 1;
+                    tree.put(b"counter".to_vec(), counter.to_le_bytes().to_vec()).unwrap();
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_flush_100k(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flush_100k");
+    group.sample_size(10);
+
+    group.bench_function("flush", |b| {
+        b.iter_batched(
+            || {
+                let mut tree = setup_tree();
+                for i in 0..100_000 {
+                    tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", i).into_bytes())
+                        .unwrap();
+                }
+                tree
+            },
+            |mut tree| {
+                tree.flush().unwrap();
+                tree
+            },
+            criterion::BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
 fn bench_mixed_operations(c: &mut Criterion) {
     let mut group = c.benchmark_group("mixed_operations");
     group.measurement_time(Duration::from_secs(10));
@@ -178,6 +558,16 @@ criterion_group!(
     benches,
     bench_put_operations,
     bench_get_operations,
+    bench_cold_sstable_reads,
+    bench_file_pool_random_reads,
+    bench_multi_get_misses,
+    bench_disjoint_sstable_lookups,
+    bench_get_into_buffer_reuse,
+    bench_compression_buffer_reuse,
+    bench_io_buffer_sizes,
+    bench_cache_eviction_batching,
+    bench_same_size_overwrites,
+    bench_flush_100k,
     bench_mixed_operations,
     bench_ttl_operations
 );
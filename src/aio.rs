@@ -0,0 +1,121 @@
+//! Async wrapper around [`crate::Tree`], enabled by the `tokio` feature.
+//!
+//! Every call locks the shared tree and runs on tokio's blocking thread pool
+//! through `spawn_blocking`, so disk reads, WAL writes, flushes and merges
+//! never stall the async executor. Calls on clones of the same handle are
+//! serialized by that lock.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use redish::aio::Tree;
+//! use redish::TreeSettingsBuilder;
+//!
+//! let tree = Tree::load_with_settings(TreeSettingsBuilder::new().db_path("./async_db").build()).await?;
+//! tree.put(b"key".to_vec(), b"value".to_vec()).await?;
+//! assert_eq!(tree.get(b"key".to_vec()).await?, Some(b"value".to_vec()));
+//! tree.flush().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tree::tree_error::{TreeError, TreeResult};
+use crate::TreeSettings;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A cloneable async handle to a [`crate::Tree`].
+///
+/// The tree is flushed when the last handle is dropped, like a synchronous
+/// tree, but that flush then runs on the dropping thread. Call `flush` first
+/// when the handle is dropped inside an async task.
+#[derive(Clone)]
+pub struct Tree {
+    inner: Arc<Mutex<crate::Tree>>,
+}
+
+impl From<crate::Tree> for Tree {
+    fn from(tree: crate::Tree) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(tree)),
+        }
+    }
+}
+
+impl Tree {
+    /// Loads a tree with custom settings on the blocking thread pool.
+    ///
+    /// # Arguments
+    /// * `settings` - Configuration settings for the tree
+    ///
+    /// # Returns
+    /// An async handle to the loaded tree
+    ///
+    /// # Errors
+    /// Returns `TreeError` if loading fails or the blocking task panics
+    pub async fn load_with_settings(settings: TreeSettings) -> TreeResult<Self> {
+        tokio::task::spawn_blocking(move || crate::Tree::load_with_settings(settings))
+            .await
+            .map_err(|e| TreeError::internal(format!("Blocking task failed: {}", e)))?
+            .map(Self::from)
+    }
+
+    /// Runs `f` with exclusive access to the tree on the blocking thread pool.
+    ///
+    /// Gives access to every synchronous method not wrapped by this handle.
+    ///
+    /// # Arguments
+    /// * `f` - The closure to run against the tree
+    ///
+    /// # Returns
+    /// The closure's result
+    ///
+    /// # Errors
+    /// Returns the closure's error, or `TreeError` if the lock is poisoned or
+    /// the blocking task panics
+    pub async fn with<F, R>(&self, f: F) -> TreeResult<R>
+    where
+        F: FnOnce(&mut crate::Tree) -> TreeResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        tokio::task::spawn_blocking(move || {
+            let mut tree = inner
+                .lock()
+                .map_err(|_| TreeError::internal("Tree lock poisoned by a panicked call"))?;
+            f(&mut tree)
+        })
+        .await
+        .map_err(|e| TreeError::internal(format!("Blocking task failed: {}", e)))?
+    }
+
+    /// Retrieves raw bytes from the tree, as `Tree::get`.
+    pub async fn get(&self, key: Vec<u8>) -> TreeResult<Option<Vec<u8>>> {
+        self.with(move |tree| tree.get(&key)).await
+    }
+
+    /// Stores raw bytes with the default TTL, as `Tree::put`.
+    pub async fn put(&self, key: Vec<u8>, value: Vec<u8>) -> TreeResult<()> {
+        self.with(move |tree| tree.put(key, value)).await
+    }
+
+    /// Stores raw bytes with an optional TTL, as `Tree::put_with_ttl`.
+    pub async fn put_with_ttl(&self, key: Vec<u8>, value: Vec<u8>, ttl: Option<Duration>) -> TreeResult<()> {
+        self.with(move |tree| tree.put_with_ttl(key, value, ttl)).await
+    }
+
+    /// Deletes a key, as `Tree::delete`.
+    pub async fn delete(&self, key: Vec<u8>) -> TreeResult<bool> {
+        self.with(move |tree| tree.delete(&key)).await
+    }
+
+    /// Writes all memory tables to SSTables, as `Tree::flush`.
+    pub async fn flush(&self) -> TreeResult<()> {
+        self.with(|tree| tree.flush()).await
+    }
+
+    /// Merges the oldest SSTables while past the merge trigger, as
+    /// `Tree::compact_if_needed`.
+    pub async fn compact(&self) -> TreeResult<bool> {
+        self.with(|tree| tree.compact_if_needed()).await
+    }
+}
@@ -1,13 +1,31 @@
 use std::collections::BTreeMap;
+use std::time::Duration;
 use bincode::config;
 use bincode::config::Configuration;
 
 pub const BINCODE_CONFIG: Configuration = config::standard();
 pub const HEADER_MAGIC_NUMBER: &[u8; 4] = b"SSTB";
 pub const FOOTER_MAGIC_NUMBER: &[u8; 4] = b"FTTB";
-pub const CURRENT_VERSION: u32 = 2;
+/// SSTable format version written by this build.
+pub const CURRENT_VERSION: u32 = 4;
+/// Oldest SSTable format version this build can still read.
+///
+/// Files with a version in `MIN_SUPPORTED_VERSION..=CURRENT_VERSION` are loaded
+/// and can be rewritten in the current format with `Tree::migrate`. Anything
+/// outside this window is rejected on load instead of being skipped.
+pub const MIN_SUPPORTED_VERSION: u32 = 1;
+/// First SSTable format version whose files all record their compression type
+/// in the header. Older files may leave the compression byte at zero whatever
+/// compressor wrote them.
+pub const COMPRESSION_MARKER_VERSION: u32 = 4;
 pub const HEADER_SIZE: usize = 16;
-pub const FOOTER_SIZE: usize = 20;
+pub const FOOTER_SIZE: usize = 36;
+/// Footer size of version 3 SSTables: index, bloom filter and range tombstone offsets and magic, no key range offset.
+pub const V3_FOOTER_SIZE: usize = 28;
+/// Footer size of version 2 SSTables: index and bloom filter offsets and magic, no range tombstone offset.
+pub const V2_FOOTER_SIZE: usize = 20;
+/// Footer size of version 1 SSTables: index offset, reserved word and magic, no bloom filter offset.
+pub const LEGACY_FOOTER_SIZE: usize = 16;
 pub const DEFAULT_DB_PATH: &str = "./db";
 pub const DEFAULT_MEM_TABLE_SIZE: u32 = 10000;
 pub const DEFAULT_WAL_MAX_SIZE: u64 = 10 * 1024 * 1024;
@@ -16,6 +34,19 @@ pub const DEFAULT_INDEX_CACHE_LRU_MAX_CAPACITY: usize = 100;
 pub const DEFAULT_INDEX_CACHE_MEMORY_LIMIT: usize = 100 * 1024 * 1024;
 pub const DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY: usize = 200000;
 pub const DEFAULT_VALUE_CACHE_MEMORY_LIMIT: usize = 200 * 1024 * 1024;
+pub const DEFAULT_CACHE_EVICTION_WATERMARK: f64 = 1.0;
+pub const DEFAULT_MAX_OPEN_FILES: usize = 64;
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 8 * 1024;
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 8 * 1024;
+pub const DEFAULT_READ_PARALLELISM: usize = 4;
+pub const DEFAULT_INDEX_BLOCK_INTERVAL: usize = 1;
+pub const DEFAULT_MAX_QUEUED_COMPACTIONS: usize = 4;
+pub const DEFAULT_MAX_IMMUTABLE_MEM_TABLES: usize = 4;
+pub const DEFAULT_COMPACTION_MIN_TABLES: usize = 3;
+pub const DEFAULT_COMPACTION_MAX_TABLES: usize = 3;
+pub const DEFAULT_TOMBSTONE_GRACE_PERIOD: Duration = Duration::ZERO;
+pub const DEFAULT_RECOVERY_PROGRESS_INTERVAL: usize = 10_000;
+pub const RATE_LIMIT_CHUNK_SIZE: u64 = 64 * 1024;
 pub const CHECKPOINT_ENTRY_SIZE: usize = 19; //crc(4)+op(1)+key_len(4)+key(6)+value_len(4)
 pub const VEC_U8_SIZE: usize = size_of::<Vec<u8>>();
 pub const BTREEMAP_U8_SIZE: usize = size_of::<BTreeMap<Vec<u8>, u64>>();
@@ -0,0 +1,103 @@
+//! Read-only inspection of a database directory's on-disk layout.
+//!
+//! These functions only list and stat files; they never open a [`Tree`](crate::tree::Tree),
+//! take locks, or modify anything, so they are safe to call from external maintenance
+//! scripts while the database is closed. Against a live database the result is a
+//! point-in-time snapshot that a flush or compaction may already have changed.
+//!
+//! # Example
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use redish::inspect;
+//!
+//! let dir = std::path::Path::new("/path/to/db");
+//! for (number, path) in inspect::sstables(dir)? {
+//!     println!("sstable {} at {:?}", number, path);
+//! }
+//! println!("{} WAL segments", inspect::wal_segments(dir)?.len());
+//! println!("{} bytes on disk", inspect::disk_usage(dir)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Lists the SSTable files in `directory`.
+///
+/// # Arguments
+/// * `directory` - Database directory to scan
+///
+/// # Returns
+/// `(number, path)` pairs sorted by ascending SSTable number. Merged tables
+/// take new numbers, so only the `MANIFEST` tells their age order.
+/// Temporary `.tmp` and `.dup` files left by an interrupted write are not included.
+pub fn sstables(directory: &Path) -> io::Result<Vec<(usize, PathBuf)>> {
+    let mut sstables = list_numbered(directory, sstable_number)?;
+    sstables.sort_by_key(|(number, _)| *number);
+    Ok(sstables)
+}
+
+/// Lists the WAL segment files in `directory`.
+///
+/// # Arguments
+/// * `directory` - Database directory to scan
+///
+/// # Returns
+/// `(segment number, path)` pairs sorted by ascending segment number.
+pub fn wal_segments(directory: &Path) -> io::Result<Vec<(u16, PathBuf)>> {
+    let mut segments = list_numbered(directory, wal_segment_number)?;
+    segments.sort_by_key(|(number, _)| *number);
+    Ok(segments)
+}
+
+/// Returns the total size in bytes of all regular files in `directory`.
+///
+/// This includes SSTables, WAL segments, the MANIFEST and any leftover temporary
+/// files. Subdirectories are not descended into.
+///
+/// # Arguments
+/// * `directory` - Database directory to scan
+pub fn disk_usage(directory: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(directory)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            total += metadata.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Parses the number out of an SSTable file name (`sstable_<n>.sst`).
+pub(crate) fn sstable_number(file_name: &str) -> Option<usize> {
+    file_name
+        .strip_prefix("sstable_")
+        .and_then(|name| name.strip_suffix(".sst"))
+        .and_then(|num| num.parse::<usize>().ok())
+}
+
+/// Parses the segment number out of a WAL file name (`wal_<n>.log`).
+pub(crate) fn wal_segment_number(file_name: &str) -> Option<u16> {
+    file_name
+        .strip_prefix("wal_")
+        .and_then(|name| name.strip_suffix(".log"))
+        .and_then(|num| num.parse::<u16>().ok())
+}
+
+fn list_numbered<N>(directory: &Path, parse: fn(&str) -> Option<N>) -> io::Result<Vec<(N, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let number = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse);
+        if let Some(number) = number {
+            files.push((number, path));
+        }
+    }
+    Ok(files)
+}
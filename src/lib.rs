@@ -1,7 +1,8 @@
 //! Redish - simple in-memory key-value database with TTL support
 //!
 //! # Example
-//! ```
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! use std::time::Duration;
 //! use redish::tree::Tree;
 //! use bincode::{Decode, Encode};
@@ -13,10 +14,13 @@
 //! }
 //! let user = User {user_id: 3, username: "JohnDoe2020".to_string()};
 //!
-//! let mut tree = Tree::load_with_path("/path/to/db/with_file_name");
-//! tree.put("key1".to_string().into_bytes(), "value".to_string().into_bytes());
-//! tree.put_with_ttl("key2".to_string().into_bytes(), "value".to_string().into_bytes(), Some(Duration::from_secs(60)));
-//! tree.put_typed::<User>("key3", &user);
+//! // the database directory; created if it does not exist
+//! let mut tree = Tree::load_with_path("/path/to/db_dir")?;
+//! tree.put("key1".to_string().into_bytes(), "value".to_string().into_bytes())?;
+//! tree.put_with_ttl("key2".to_string().into_bytes(), "value".to_string().into_bytes(), Some(Duration::from_secs(60)))?;
+//! tree.put_typed::<User>("key3", &user)?;
+//! # Ok(())
+//! # }
 //! ```
 
 extern crate core;
@@ -24,7 +28,10 @@ extern crate core;
 pub mod tree;
 pub mod util;
 pub mod config;
+pub mod inspect;
 mod logger;
+#[cfg(feature = "tokio")]
+pub mod aio;
 
-pub use crate::tree::{Tree, DataValue, TreeSettings, TreeSettingsBuilder};
+pub use crate::tree::{Tree, DataValue, MergeIterator, TreeEvent, TreeSettings, TreeSettingsBuilder, WriteOptions};
 pub use bincode::{Decode, Encode};
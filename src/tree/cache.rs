@@ -1,9 +1,9 @@
-use crate::config::{BTREEMAP_U8_SIZE, DEFAULT_INDEX_CACHE_LRU_MAX_CAPACITY, DEFAULT_INDEX_CACHE_MEMORY_LIMIT, DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY, DEFAULT_VALUE_CACHE_MEMORY_LIMIT, VEC_U8_SIZE};
+use crate::config::{BTREEMAP_U8_SIZE, DEFAULT_CACHE_EVICTION_WATERMARK, DEFAULT_INDEX_CACHE_LRU_MAX_CAPACITY, DEFAULT_INDEX_CACHE_MEMORY_LIMIT, DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY, DEFAULT_VALUE_CACHE_MEMORY_LIMIT, VEC_U8_SIZE};
 use crate::tree::DataValue;
 use std::collections::BTreeMap;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// An LRU (Least Recently Used) cache for storing data values.
 ///
@@ -19,7 +19,8 @@ use std::path::PathBuf;
 /// - **Memory limit**: Maximum estimated memory usage
 ///
 /// When either limit is exceeded, the least recently used entries are evicted
-/// until the cache is within bounds.
+/// until the cache is back under its eviction watermark, a fraction of both
+/// limits (see [`LRUValueCache::set_eviction_watermark`]).
 ///
 /// # Thread Safety
 ///
@@ -37,6 +38,8 @@ use std::path::PathBuf;
 ///
 /// - **Get operations**: O(1) average case
 /// - **Put operations**: O(1) average case, O(n) worst case during eviction
+/// - **Key lookups**: O(1) average case through a secondary key to SSTable
+///   index, which lets `Tree::get` find a cached value without knowing its SSTable
 /// - **Memory overhead**: Approximately 40-60 bytes per entry
 ///
 /// # See Also
@@ -46,26 +49,34 @@ use std::path::PathBuf;
 /// - [`CacheStats`] - For monitoring cache performance
 pub struct LRUValueCache {
     cache: HashMap<CacheKey, DataValue>,
+    key_paths: HashMap<Vec<u8>, Vec<PathBuf>>,
     lru_queue: VecDeque<CacheKey>,
     max_capacity: usize,
     memory_limit: usize,
     current_memory_usage: usize,
+    max_cacheable_value_bytes: Option<usize>,
+    eviction_watermark: f64,
     hit_count: u64,
     miss_count: u64,
     eviction_count: u64,
+    skipped_for_size: u64,
 }
 
 impl Default for LRUValueCache {
     fn default() -> Self {
         Self {
             cache: HashMap::new(),
+            key_paths: HashMap::new(),
             lru_queue: VecDeque::new(),
             max_capacity: DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY,
             memory_limit: DEFAULT_VALUE_CACHE_MEMORY_LIMIT,
             current_memory_usage: 0,
+            max_cacheable_value_bytes: None,
+            eviction_watermark: DEFAULT_CACHE_EVICTION_WATERMARK,
             hit_count: 0,
             miss_count: 0,
             eviction_count: 0,
+            skipped_for_size: 0,
         }
     }
 }
@@ -74,19 +85,42 @@ impl LRUValueCache {
     pub fn new(max_capacity: usize, memory_limit: usize) -> Self {
         Self {
             cache: HashMap::with_capacity(max_capacity),
+            key_paths: HashMap::new(),
             lru_queue: VecDeque::with_capacity(max_capacity),
             max_capacity,
             memory_limit,
             current_memory_usage: 0,
+            max_cacheable_value_bytes: None,
+            eviction_watermark: DEFAULT_CACHE_EVICTION_WATERMARK,
             hit_count: 0,
             miss_count: 0,
             eviction_count: 0,
+            skipped_for_size: 0,
         }
     }
 
-    pub(crate) fn get(&mut self, sstable_path: &PathBuf, key: &[u8]) -> Option<DataValue> {
+    /// Sets the largest value, in stored data bytes, that `put` accepts.
+    ///
+    /// Larger values are skipped instead of evicting many small entries to
+    /// make room, and counted in `CacheStats::skipped_for_size`. `None`
+    /// accepts any value that fits the memory limit.
+    pub fn set_max_cacheable_value_bytes(&mut self, max_bytes: Option<usize>) {
+        self.max_cacheable_value_bytes = max_bytes;
+    }
+
+    /// Sets the fraction of the capacity and memory limit an overflowing
+    /// `put` evicts down to, clamped to `0.0..=1.0`.
+    ///
+    /// `1.0` evicts just enough for the new entry; lower values free room for
+    /// the following inserts as well, so a burst of large values does not
+    /// evict on every `put`.
+    pub fn set_eviction_watermark(&mut self, watermark: f64) {
+        self.eviction_watermark = watermark.clamp(0.0, 1.0);
+    }
+
+    pub(crate) fn get(&mut self, sstable_path: &Path, key: &[u8]) -> Option<DataValue> {
         let cache_key = CacheKey {
-            sstable_path: sstable_path.clone(),
+            sstable_path: sstable_path.to_path_buf(),
             key: key.to_vec(),
         };
 
@@ -100,13 +134,42 @@ impl LRUValueCache {
         }
     }
 
+    /// Returns the SSTables for which a value of `key` is cached, in no particular order.
+    pub(crate) fn cached_paths(&self, key: &[u8]) -> &[PathBuf] {
+        self.key_paths.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn index_key(&mut self, cache_key: &CacheKey) {
+        self.key_paths
+            .entry(cache_key.key.clone())
+            .or_default()
+            .push(cache_key.sstable_path.clone());
+    }
+
+    fn unindex_key(&mut self, cache_key: &CacheKey) {
+        if let Some(paths) = self.key_paths.get_mut(&cache_key.key) {
+            paths.retain(|path| *path != cache_key.sstable_path);
+            if paths.is_empty() {
+                self.key_paths.remove(&cache_key.key);
+            }
+        }
+    }
+
     pub(crate) fn put(&mut self, sstable_path: PathBuf, key: Vec<u8>, value: DataValue) {
+        if self
+            .max_cacheable_value_bytes
+            .is_some_and(|max_bytes| !fits_value_size(&value, max_bytes))
+        {
+            self.skipped_for_size += 1;
+            return;
+        }
+
         let cache_key = CacheKey { sstable_path, key };
 
-        let value_size = self.estimate_value_size(&value);
+        let value_size = value.heap_size();
 
         if let Some(old_value) = self.cache.get(&cache_key) {
-            let old_size = self.estimate_value_size(old_value);
+            let old_size = old_value.heap_size();
             self.current_memory_usage = self
                 .current_memory_usage
                 .saturating_sub(old_size)
@@ -116,34 +179,38 @@ impl LRUValueCache {
             return;
         }
 
-        while (self.cache.len() >= self.max_capacity
-            || self.current_memory_usage + value_size > self.memory_limit)
-            && !self.cache.is_empty()
+        if self.cache.len() >= self.max_capacity
+            || self.current_memory_usage + value_size > self.memory_limit
         {
-            if !self.evict_lru() {
-                break;
-            }
+            let target_len = watermark_of(self.max_capacity, self.eviction_watermark)
+                .min(self.max_capacity.saturating_sub(1));
+            let target_memory = watermark_of(self.memory_limit, self.eviction_watermark)
+                .min(self.memory_limit.saturating_sub(value_size));
+            let count = self.entries_over(target_len, target_memory);
+            self.evict_n(count);
         }
 
         if self.cache.len() < self.max_capacity
             && self.current_memory_usage + value_size <= self.memory_limit
         {
             self.cache.insert(cache_key.clone(), value);
+            self.index_key(&cache_key);
             self.lru_queue.push_back(cache_key);
             self.current_memory_usage += value_size;
         }
     }
 
-    pub(crate) fn remove(&mut self, sstable_path: &PathBuf, key: &[u8]) {
+    pub(crate) fn remove(&mut self, sstable_path: &Path, key: &[u8]) {
         let cache_key = CacheKey {
-            sstable_path: sstable_path.clone(),
+            sstable_path: sstable_path.to_path_buf(),
             key: key.to_vec(),
         };
 
         if let Some(value) = self.cache.remove(&cache_key) {
-            let value_size = self.estimate_value_size(&value);
+            let value_size = value.heap_size();
             self.current_memory_usage = self.current_memory_usage.saturating_sub(value_size);
             self.lru_queue.retain(|k| k != &cache_key);
+            self.unindex_key(&cache_key);
         }
     }
 
@@ -160,21 +227,61 @@ impl LRUValueCache {
         }
     }
 
-    pub(crate) fn rename_sstable(&mut self, old_path: &PathBuf, new_path: &PathBuf) {
-        let keys_to_rename: Vec<CacheKey> = self
-            .cache
+    /// Returns every cached value with its SSTable and key, least recently used first.
+    pub(crate) fn entries_lru(&self) -> impl Iterator<Item = (&Path, &[u8], &DataValue)> {
+        self.lru_queue.iter().filter_map(|cache_key| {
+            self.cache
+                .get(cache_key)
+                .map(|value| (cache_key.sstable_path.as_path(), cache_key.key.as_slice(), value))
+        })
+    }
+
+    /// Returns the keys cached for `sstable_path`.
+    pub(crate) fn keys_of_sstable(&self, sstable_path: &Path) -> Vec<Vec<u8>> {
+        self.cache
             .keys()
-            .filter(|k| &k.sstable_path == old_path)
-            .cloned()
-            .collect();
+            .filter(|cache_key| cache_key.sstable_path == sstable_path)
+            .map(|cache_key| cache_key.key.clone())
+            .collect()
+    }
 
-        for old_key in keys_to_rename {
-            if let Some(value) = self.cache.remove(&old_key) {
-                let mut new_key = old_key;
-                new_key.sstable_path = new_path.clone();
-                self.cache.insert(new_key, value);
+    /// Moves the values cached for the inputs of a merge to its output.
+    ///
+    /// Values listed in `current` are still what the merged SSTable holds for
+    /// their key and are re-pointed to `output`, keeping their LRU position;
+    /// every other value cached for an input is removed.
+    pub(crate) fn repoint_merged(&mut self, inputs: &[PathBuf], output: &Path, current: &HashSet<CacheKey>) {
+        let mut lru_queue = VecDeque::with_capacity(self.lru_queue.len());
+        for cache_key in std::mem::take(&mut self.lru_queue) {
+            if !inputs.contains(&cache_key.sstable_path) {
+                lru_queue.push_back(cache_key);
+                continue;
+            }
+            let Some(value) = self.cache.remove(&cache_key) else {
+                continue;
+            };
+            self.unindex_key(&cache_key);
+            let moved = CacheKey {
+                sstable_path: output.to_path_buf(),
+                key: cache_key.key.clone(),
+            };
+            if current.contains(&cache_key) && !self.cache.contains_key(&moved) {
+                self.index_key(&moved);
+                self.cache.insert(moved.clone(), value);
+                lru_queue.push_back(moved);
+            } else {
+                self.current_memory_usage = self.current_memory_usage.saturating_sub(value.heap_size());
             }
         }
+        self.lru_queue = lru_queue;
+    }
+
+    /// Removes the cached values of `key` for every SSTable.
+    pub(crate) fn invalidate_key(&mut self, key: &[u8]) {
+        let paths = self.key_paths.get(key).cloned().unwrap_or_default();
+        for path in paths {
+            self.remove(&path, key);
+        }
     }
 
     fn move_to_back(&mut self, cache_key: &CacheKey) {
@@ -184,11 +291,43 @@ impl LRUValueCache {
         }
     }
 
+    /// Counts the least recently used entries that must go for the cache to
+    /// hold at most `target_len` entries in `target_memory` bytes.
+    fn entries_over(&self, target_len: usize, target_memory: usize) -> usize {
+        let mut len = self.cache.len();
+        let mut memory = self.current_memory_usage;
+        let mut count = 0;
+        for key in &self.lru_queue {
+            if len <= target_len && memory <= target_memory {
+                break;
+            }
+            if let Some(value) = self.cache.get(key) {
+                len -= 1;
+                memory = memory.saturating_sub(value.heap_size());
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Evicts up to `n` least recently used entries and returns how many were evicted.
+    pub(crate) fn evict_n(&mut self, n: usize) -> usize {
+        let mut evicted = 0;
+        for _ in 0..n {
+            if !self.evict_lru() {
+                break;
+            }
+            evicted += 1;
+        }
+        evicted
+    }
+
     fn evict_lru(&mut self) -> bool {
         if let Some(lru_key) = self.lru_queue.pop_front() {
             if let Some(value) = self.cache.remove(&lru_key) {
-                let value_size = self.estimate_value_size(&value);
+                let value_size = value.heap_size();
                 self.current_memory_usage = self.current_memory_usage.saturating_sub(value_size);
+                self.unindex_key(&lru_key);
                 self.eviction_count += 1;
                 return true;
             }
@@ -196,10 +335,6 @@ impl LRUValueCache {
         false
     }
 
-    fn estimate_value_size(&self, value: &DataValue) -> usize {
-        size_of::<DataValue>() + value.get_data().len()
-    }
-
     pub(crate) fn stats(&self) -> CacheStats {
         CacheStats {
             size: self.cache.len(),
@@ -217,16 +352,19 @@ impl LRUValueCache {
             } else {
                 0.0
             },
+            skipped_for_size: self.skipped_for_size,
         }
     }
 
     pub fn clear(&mut self) {
         self.cache.clear();
+        self.key_paths.clear();
         self.lru_queue.clear();
         self.current_memory_usage = 0;
         self.hit_count = 0;
         self.miss_count = 0;
         self.eviction_count = 0;
+        self.skipped_for_size = 0;
     }
 }
 
@@ -244,7 +382,8 @@ impl LRUValueCache {
 /// - **Memory limit**: Maximum estimated memory usage
 ///
 /// When either limit is exceeded, the least recently used indexes are evicted
-/// until the cache is within bounds.
+/// until the cache is back under its eviction watermark, a fraction of both
+/// limits (see [`LRUIndexCache::set_eviction_watermark`]).
 ///
 /// # Index Structure
 ///
@@ -283,6 +422,7 @@ pub struct LRUIndexCache {
     max_capacity: usize,
     memory_limit: usize,
     current_memory_usage: usize,
+    eviction_watermark: f64,
     hit_count: u64,
     miss_count: u64,
     eviction_count: u64,
@@ -296,6 +436,7 @@ impl Default for LRUIndexCache {
             max_capacity: DEFAULT_INDEX_CACHE_LRU_MAX_CAPACITY,
             memory_limit: DEFAULT_INDEX_CACHE_MEMORY_LIMIT,
             current_memory_usage: 0,
+            eviction_watermark: DEFAULT_CACHE_EVICTION_WATERMARK,
             hit_count: 0,
             miss_count: 0,
             eviction_count: 0,
@@ -311,12 +452,22 @@ impl LRUIndexCache {
             max_capacity,
             memory_limit,
             current_memory_usage: 0,
+            eviction_watermark: DEFAULT_CACHE_EVICTION_WATERMARK,
             hit_count: 0,
             miss_count: 0,
             eviction_count: 0,
         }
     }
 
+    /// Sets the fraction of the capacity and memory limit an overflowing
+    /// `put` evicts down to, clamped to `0.0..=1.0`.
+    ///
+    /// `1.0` evicts just enough for the new index; lower values free room
+    /// for the following inserts as well.
+    pub fn set_eviction_watermark(&mut self, watermark: f64) {
+        self.eviction_watermark = watermark.clamp(0.0, 1.0);
+    }
+
     pub(crate) fn get(&mut self, path: &PathBuf) -> Option<&BTreeMap<Vec<u8>, u64>> {
         if self.cache.contains_key(path) {
             self.hit_count += 1;
@@ -328,6 +479,11 @@ impl LRUIndexCache {
         }
     }
 
+    /// Looks an index up without touching the LRU order or hit statistics.
+    pub(crate) fn peek(&self, path: &Path) -> Option<&BTreeMap<Vec<u8>, u64>> {
+        self.cache.get(path)
+    }
+
     pub(crate) fn put(&mut self, path: PathBuf, index: BTreeMap<Vec<u8>, u64>) {
         let index_size = self.estimate_index_size(&index);
 
@@ -340,12 +496,15 @@ impl LRUIndexCache {
             return;
         }
 
-         while (self.cache.len() >= self.max_capacity)
+        if (self.cache.len() >= self.max_capacity)
             || (self.current_memory_usage + index_size > self.memory_limit)
         {
-            if !self.evict_lru() {
-                break;
-            }
+            let target_len = watermark_of(self.max_capacity, self.eviction_watermark)
+                .min(self.max_capacity.saturating_sub(1));
+            let target_memory = watermark_of(self.memory_limit, self.eviction_watermark)
+                .min(self.memory_limit.saturating_sub(index_size));
+            let count = self.entries_over(target_len, target_memory);
+            self.evict_n(count);
         }
 
         self.cache.insert(path.clone(), index);
@@ -373,6 +532,7 @@ impl LRUIndexCache {
             },
             memory_limit: self.memory_limit,
             memory_utilization: self.current_memory_usage as f64 / self.memory_limit as f64,
+            skipped_for_size: 0,
         }
     }
 
@@ -381,6 +541,37 @@ impl LRUIndexCache {
         self.lru_queue.push_back(path.clone());
     }
 
+    /// Counts the least recently used indexes that must go for the cache to
+    /// hold at most `target_len` indexes in `target_memory` bytes.
+    fn entries_over(&self, target_len: usize, target_memory: usize) -> usize {
+        let mut len = self.cache.len();
+        let mut memory = self.current_memory_usage;
+        let mut count = 0;
+        for path in &self.lru_queue {
+            if len <= target_len && memory <= target_memory {
+                break;
+            }
+            if let Some(index) = self.cache.get(path) {
+                len -= 1;
+                memory = memory.saturating_sub(self.estimate_index_size(index));
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Evicts up to `n` least recently used indexes and returns how many were evicted.
+    pub(crate) fn evict_n(&mut self, n: usize) -> usize {
+        let mut evicted = 0;
+        for _ in 0..n {
+            if !self.evict_lru() {
+                break;
+            }
+            evicted += 1;
+        }
+        evicted
+    }
+
     fn evict_lru(&mut self) -> bool {
         if let Some(path) = self.lru_queue.pop_front() {
             if let Some(index) = self.cache.remove(&path) {
@@ -395,7 +586,7 @@ impl LRUIndexCache {
 
     fn estimate_index_size(&self, index: &BTreeMap<Vec<u8>, u64>) -> usize {
         let mut size = 0;
-        for (key, _) in index {
+        for key in index.keys() {
             size += key.len() + 8;
             size += key.capacity();
             size += VEC_U8_SIZE;
@@ -458,13 +649,8 @@ impl LRUIndexCache {
         self.max_capacity = new_capacity;
         self.memory_limit = new_memory_limit;
 
-        while (self.cache.len() > self.max_capacity)
-            || (self.current_memory_usage > self.memory_limit)
-        {
-            if !self.evict_lru() {
-                break;
-            }
-        }
+        let count = self.entries_over(self.max_capacity, self.memory_limit);
+        self.evict_n(count);
     }
 
     pub(crate) fn clear(&mut self) {
@@ -478,6 +664,42 @@ impl LRUIndexCache {
     }
 }
 
+/// Returns `watermark` of `limit`, rounded down.
+fn watermark_of(limit: usize, watermark: f64) -> usize {
+    (limit as f64 * watermark) as usize
+}
+
+/// Decides which values read from SSTables are admitted into the value cache.
+///
+/// Admission is checked before an entry reaches the cache, so it is independent
+/// of how the cache evicts or partitions its entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueCacheAdmission {
+    /// Every value read from an SSTable is cached.
+    Always,
+    /// Reads never populate the cache; already cached values are still served.
+    Never,
+    /// Only values whose stored data is at most the given number of bytes are cached.
+    MaxValueSize(usize),
+}
+
+impl ValueCacheAdmission {
+    pub(crate) fn admits(&self, value: &DataValue) -> bool {
+        match self {
+            ValueCacheAdmission::Always => true,
+            ValueCacheAdmission::Never => false,
+            ValueCacheAdmission::MaxValueSize(max_size) => fits_value_size(value, *max_size),
+        }
+    }
+}
+
+/// Shared size check of `ValueCacheAdmission::MaxValueSize` and the value
+/// cache's `max_cacheable_value_bytes`, so both limits treat a value of
+/// exactly `max_bytes` the same way.
+fn fits_value_size(value: &DataValue, max_bytes: usize) -> bool {
+    value.data.len() <= max_bytes
+}
+
 #[derive(Clone, Debug)]
 pub struct CacheKey {
     pub sstable_path: PathBuf,
@@ -508,6 +730,9 @@ pub struct CacheStats {
     pub hit_rate: f64,
     pub memory_limit: usize,
     pub memory_utilization: f64,
+    /// Inserts skipped because the value exceeded the cache's size cap; always
+    /// 0 for the index cache.
+    pub skipped_for_size: u64,
 }
 
 impl fmt::Display for CacheStats {
@@ -543,6 +768,10 @@ impl fmt::Display for CacheStats {
             limit_value,
             limit_unit,
             memory_utilization_percent
-        )
+        )?;
+        if self.skipped_for_size > 0 {
+            write!(f, ", {} skipped for size", self.skipped_for_size)?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,181 @@
+use crate::config::BINCODE_CONFIG;
+use crate::tree::tree_error::{TreeError, TreeResult};
+use crate::{DataValue, Tree};
+use bincode::{Decode, Encode};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const CACHE_SNAPSHOT_MAGIC: &[u8; 4] = b"VCSN";
+
+/// The identity of an SSTable when a snapshot was taken.
+///
+/// Migration rewrites an SSTable in place and the number of a merged-away
+/// table can be taken again, so the name alone does not tell whether a
+/// cached value still matches the file.
+#[derive(Debug, Encode, Decode, PartialEq, Eq)]
+struct SnapshotTable {
+    file_name: String,
+    len: u64,
+    modified_nanos: u128,
+}
+
+/// Value cache entries in LRU order, least recently used first, each
+/// referring to one of `tables` by position.
+#[derive(Debug, Encode, Decode)]
+struct CacheSnapshot {
+    tables: Vec<SnapshotTable>,
+    entries: Vec<(u32, Vec<u8>, DataValue)>,
+}
+
+fn table_identity(path: &Path) -> std::io::Result<SnapshotTable> {
+    let metadata = std::fs::metadata(path)?;
+    let modified_nanos = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_nanos());
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string();
+    Ok(SnapshotTable {
+        file_name,
+        len: metadata.len(),
+        modified_nanos,
+    })
+}
+
+impl Tree {
+    /// Writes the values currently in the value cache to a snapshot file.
+    ///
+    /// Each entry is stored with the name, size and modification time of the
+    /// SSTable it was read from, in LRU order, so `warm_cache_from_snapshot`
+    /// can restore the cache after a restart. The file is written next to
+    /// `path` and renamed over it. Keep it outside the database directory, or
+    /// list it in `strict_directory_ignore` when `strict_directory` is set.
+    ///
+    /// # Arguments
+    /// * `path` - The snapshot file to write
+    ///
+    /// # Returns
+    /// The number of cached values written
+    ///
+    /// # Errors
+    /// Returns `TreeError` if the snapshot cannot be encoded or written
+    pub fn save_cache_snapshot<P: AsRef<Path>>(&self, path: P) -> TreeResult<usize> {
+        let path = path.as_ref();
+        let mut snapshot = CacheSnapshot {
+            tables: Vec::new(),
+            entries: Vec::new(),
+        };
+        let mut table_positions: HashMap<&Path, u32> = HashMap::new();
+        for (sstable_path, key, value) in self.value_cache().entries_lru() {
+            let position = match table_positions.get(sstable_path) {
+                Some(&position) => position,
+                None => {
+                    let Ok(table) = table_identity(sstable_path) else {
+                        continue;
+                    };
+                    let position = snapshot.tables.len() as u32;
+                    snapshot.tables.push(table);
+                    table_positions.insert(sstable_path, position);
+                    position
+                }
+            };
+            snapshot.entries.push((position, key.to_vec(), value.clone()));
+        }
+
+        let mut body = CACHE_SNAPSHOT_MAGIC.to_vec();
+        bincode::encode_into_std_write(&snapshot, &mut body, BINCODE_CONFIG)
+            .map_err(|e| TreeError::serialization(format!("Failed to encode cache snapshot: {}", e)))?;
+        let crc = crc32fast::hash(&body);
+        body.extend_from_slice(&crc.to_le_bytes());
+
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&body)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(snapshot.entries.len())
+    }
+
+    /// Loads values saved by `save_cache_snapshot` into the value cache.
+    ///
+    /// Best effort and safe to call with a stale snapshot: an entry is only
+    /// loaded if its SSTable is still part of the tree with the same size and
+    /// modification time, so entries of merged, rewritten or deleted tables are
+    /// dropped instead of serving outdated data. Expired values are dropped too.
+    /// A missing, damaged or unreadable snapshot loads nothing. Loaded values
+    /// go through the usual capacity and memory limits of the cache.
+    ///
+    /// # Arguments
+    /// * `path` - The snapshot file to read
+    ///
+    /// # Returns
+    /// The number of values loaded into the cache; 0 when the value cache is
+    /// disabled
+    ///
+    /// # Errors
+    /// Returns `TreeError` if the snapshot exists but cannot be read
+    pub fn warm_cache_from_snapshot<P: AsRef<Path>>(&mut self, path: P) -> TreeResult<usize> {
+        let path = path.as_ref();
+        if !self.settings.enable_value_cache {
+            return Ok(0);
+        }
+
+        let mut buffer = Vec::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_end(&mut buffer)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(snapshot) = Self::decode_cache_snapshot(&buffer) else {
+            warn!("Ignoring damaged cache snapshot {:?}", path);
+            return Ok(0);
+        };
+
+        let current_tables: Vec<Option<PathBuf>> = snapshot
+            .tables
+            .iter()
+            .map(|table| {
+                let table_path = self.settings.db_path.join(&table.file_name);
+                let is_current = self.ss_tables.contains(&table_path)
+                    && table_identity(&table_path).is_ok_and(|current| current == *table);
+                is_current.then_some(table_path)
+            })
+            .collect();
+
+        let mut loaded = 0;
+        for (position, key, value) in snapshot.entries {
+            let Some(Some(table_path)) = current_tables.get(position as usize) else {
+                continue;
+            };
+            if value.is_expired() {
+                continue;
+            }
+            self.value_cache().put(table_path.clone(), key, value);
+            loaded += 1;
+        }
+
+        info!("Loaded {} cached values from snapshot {:?}", loaded, path);
+        Ok(loaded)
+    }
+
+    fn decode_cache_snapshot(buffer: &[u8]) -> Option<CacheSnapshot> {
+        if buffer.len() < CACHE_SNAPSHOT_MAGIC.len() + 4 {
+            return None;
+        }
+        let (body, crc) = buffer.split_at(buffer.len() - 4);
+        if !body.starts_with(CACHE_SNAPSHOT_MAGIC) || crc32fast::hash(body).to_le_bytes() != crc {
+            return None;
+        }
+        bincode::decode_from_slice(&body[CACHE_SNAPSHOT_MAGIC.len()..], BINCODE_CONFIG)
+            .ok()
+            .map(|(snapshot, _)| snapshot)
+    }
+}
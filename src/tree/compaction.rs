@@ -0,0 +1,600 @@
+use crate::tree::cache::CacheKey;
+use crate::tree::range_tombstone::RangeTombstone;
+use crate::tree::tree_error::{TreeError, TreeResult};
+use crate::tree::{MergeIterator, TreeEvent};
+use crate::{DataValue, Tree};
+use growable_bloom_filter::GrowableBloom;
+use log::error;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+/// The oldest SSTables selected for one merge, with the range tombstones they
+/// carry and the keys the value cache holds for them, by input index.
+///
+/// The merged table is written under a new number rather than over an input,
+/// so the inputs stay intact until the manifest lists the merged table instead.
+pub(crate) struct CompactionJob {
+    inputs: Vec<PathBuf>,
+    output: PathBuf,
+    range_tombstones: Vec<RangeTombstone>,
+    cached_keys: Vec<(usize, Vec<u8>)>,
+}
+
+impl CompactionJob {
+    fn tmp_path(&self) -> PathBuf {
+        self.output.with_extension("sst.tmp")
+    }
+}
+
+/// A merged SSTable written next to its inputs, waiting to be installed.
+pub(crate) struct MergedTable {
+    index: BTreeMap<Vec<u8>, u64>,
+    range_tombstones: Vec<RangeTombstone>,
+    bloom_filter: GrowableBloom,
+    entries_before: usize,
+    entries_after: usize,
+    current_cached: Vec<CacheKey>,
+}
+
+type CompactionResult = (CompactionJob, TreeResult<MergedTable>);
+type MergeEntry = TreeResult<(Vec<u8>, DataValue)>;
+
+/// Counters of flushes and merges, for tuning write amplification.
+///
+/// # Fields
+/// - `flushes`: Memory tables written to SSTables
+/// - `flushed_bytes`: Size of the SSTables written by flushes
+/// - `merges`: Merges installed, by the background worker or by `Tree::compact_range`
+/// - `merged_tables`: Input SSTables replaced by merges
+/// - `merged_bytes`: Size of the SSTables written by merges
+/// - `dropped_entries`: Overwritten, deleted and expired entries dropped by merges
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactionStats {
+    pub flushes: u64,
+    pub flushed_bytes: u64,
+    pub merges: u64,
+    pub merged_tables: u64,
+    pub merged_bytes: u64,
+    pub dropped_entries: u64,
+}
+
+impl CompactionStats {
+    /// Bytes written to SSTables per byte flushed, or 0.0 before the first flush.
+    ///
+    /// 1.0 means no data has been rewritten by merges yet; each time flushed data
+    /// is merged again the ratio grows by up to one.
+    pub fn write_amplification(&self) -> f64 {
+        if self.flushed_bytes == 0 {
+            return 0.0;
+        }
+        (self.flushed_bytes + self.merged_bytes) as f64 / self.flushed_bytes as f64
+    }
+}
+
+/// The SSTables on one level of the tree, as reported by `Tree::level_summary`.
+///
+/// # Fields
+/// - `level`: Level number, 0 being the level flushes write to
+/// - `tables`: Number of SSTables on the level
+/// - `bytes`: Total size of those SSTables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelInfo {
+    pub level: usize,
+    pub tables: usize,
+    pub bytes: u64,
+}
+
+/// Handle to the background compaction thread.
+///
+/// Dropping it closes the job channel, which stops the thread once the merge
+/// it is working on has finished. The result receiver sits behind a mutex only
+/// to keep `Tree` `Sync`; it is read from `&mut Tree` alone.
+pub(crate) struct CompactionWorker {
+    jobs: mpsc::Sender<CompactionJob>,
+    results: Mutex<mpsc::Receiver<CompactionResult>>,
+}
+
+impl Tree {
+    pub(crate) fn start_compaction_worker(&mut self) {
+        self.compaction_worker = None;
+        self.compaction_in_flight = false;
+        self.queued_compactions = 0;
+        if self.settings.max_queued_compactions == 0 {
+            return;
+        }
+
+        let (job_sender, job_receiver) = mpsc::channel::<CompactionJob>();
+        let (result_sender, result_receiver) = mpsc::channel::<CompactionResult>();
+        let mut context = Tree::detached(self.settings.clone(), self.file_pool.clone());
+        context.cancel_requested = Arc::clone(&self.cancel_requested);
+        thread::spawn(move || {
+            Self::compaction_worker(job_receiver, result_sender, context);
+        });
+        self.compaction_worker = Some(CompactionWorker {
+            jobs: job_sender,
+            results: Mutex::new(result_receiver),
+        });
+    }
+
+    /// Runs merges handed over by the tree. `context` is a detached tree that
+    /// shares the settings and file pool of the tree it works for.
+    fn compaction_worker(
+        jobs: mpsc::Receiver<CompactionJob>,
+        results: mpsc::Sender<CompactionResult>,
+        context: Tree,
+    ) {
+        while let Ok(job) = jobs.recv() {
+            let merged = context.run_compaction(&job);
+            if results.send((job, merged)).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Asks running and future merges and flushes to stop early.
+    ///
+    /// Merges, including those of the background worker, check the request
+    /// between input tables and entries, and a flush checks it before each
+    /// SSTable it writes. Nothing written so far is lost:
+    /// - A stopped merge is discarded and logged, its inputs stay in place and
+    ///   the merge is planned again by a later flush.
+    /// - `compact_range` fails with `TreeError::Internal` ("cancelled") before
+    ///   it changes any SSTable.
+    /// - A stopped flush fails with `TreeError::Internal` ("cancelled"); the
+    ///   memory table data it did not write stays queued and covered by the WAL.
+    ///
+    /// The request stays in effect, so dropping the tree afterwards does not
+    /// wait for a long flush either, until `resume_background_work` is called.
+    pub fn cancel_background_work(&self) {
+        self.cancel_requested.store(true, Ordering::Release);
+    }
+
+    /// Withdraws a `cancel_background_work` request, letting merges and
+    /// flushes run to completion again.
+    pub fn resume_background_work(&self) {
+        self.cancel_requested.store(false, Ordering::Release);
+    }
+
+    /// Merges the oldest SSTables if `CompactionConfig::min_tables` are on
+    /// disk, for periodic maintenance loops.
+    ///
+    /// A flush requests the same merge, so the trigger is normally met only
+    /// after loading a database with many SSTables or after merges were
+    /// cancelled or failed. Finished background merges are installed first;
+    /// while one is still queued or running nothing else is started. Otherwise
+    /// merges run on the calling thread until fewer SSTables are left, the
+    /// `size_ratio` defers the next merge or a merge makes no progress, so a
+    /// cancelled or failing merge does not loop. Cheap when there is nothing
+    /// to do.
+    ///
+    /// # Returns
+    /// `true` if at least one merge was installed by this call, `false` otherwise
+    ///
+    /// # Errors
+    /// Returns `TreeError` if installing a merge fails
+    pub fn compact_if_needed(&mut self) -> TreeResult<bool> {
+        self.poll_compactions(false)?;
+        if self.compaction_in_flight || self.queued_compactions > 0 {
+            return Ok(false);
+        }
+
+        let mut merged = false;
+        while self.merge_due() {
+            let tables_before = self.ss_tables.len();
+            self.merge_sstables()?;
+            if self.ss_tables.len() >= tables_before {
+                break;
+            }
+            merged = true;
+        }
+        Ok(merged)
+    }
+
+    /// Fails with `TreeError::Internal` if `cancel_background_work` was called.
+    pub(crate) fn check_cancelled(&self) -> TreeResult<()> {
+        if self.cancel_requested.load(Ordering::Acquire) {
+            return Err(TreeError::internal("cancelled"));
+        }
+        Ok(())
+    }
+
+    /// Requests a merge of the oldest SSTables after a flush.
+    ///
+    /// Without a background worker the merge runs right away. Otherwise the
+    /// request is queued, and a full queue makes the caller wait for the merge
+    /// in progress.
+    pub(crate) fn request_compaction(&mut self) -> TreeResult<()> {
+        if self.compaction_worker.is_none() {
+            return self.merge_sstables();
+        }
+
+        if self.queued_compactions >= self.settings.max_queued_compactions {
+            self.poll_compactions(true)?;
+        }
+        self.queued_compactions += 1;
+        self.poll_compactions(false)
+    }
+
+    /// Blocks until every requested merge has been installed.
+    pub(crate) fn wait_for_compactions(&mut self) -> TreeResult<()> {
+        while self.compaction_in_flight || self.queued_compactions > 0 {
+            self.poll_compactions(true)?;
+        }
+        Ok(())
+    }
+
+    /// Installs the merge finished by the worker, waiting for it if `wait` is
+    /// set, and hands the next queued request to the worker.
+    pub(crate) fn poll_compactions(&mut self, wait: bool) -> TreeResult<()> {
+        if self.compaction_in_flight {
+            let finished = match &mut self.compaction_worker {
+                Some(worker) if wait => worker.results.get_mut().unwrap().recv().ok(),
+                Some(worker) => match worker.results.get_mut().unwrap().try_recv() {
+                    Ok(finished) => Some(finished),
+                    Err(mpsc::TryRecvError::Empty) => return Ok(()),
+                    Err(mpsc::TryRecvError::Disconnected) => None,
+                },
+                None => None,
+            };
+            self.compaction_in_flight = false;
+            match finished {
+                Some((job, merged)) => self.install_compaction(job, merged)?,
+                None => {
+                    error!("Compaction worker stopped, merging on the write path from now on");
+                    self.compaction_worker = None;
+                }
+            }
+        }
+
+        while self.queued_compactions > 0 && !self.compaction_in_flight {
+            self.queued_compactions -= 1;
+            let Some(job) = self.plan_compaction() else {
+                continue;
+            };
+            let job = match &self.compaction_worker {
+                Some(worker) => match worker.jobs.send(job) {
+                    Ok(()) => {
+                        self.compaction_in_flight = true;
+                        continue;
+                    }
+                    Err(mpsc::SendError(job)) => job,
+                },
+                None => job,
+            };
+            let merged = self.run_compaction(&job);
+            self.install_compaction(job, merged)?;
+        }
+        Ok(())
+    }
+
+    /// Merges the oldest SSTables on the calling thread.
+    pub(crate) fn merge_sstables(&mut self) -> TreeResult<()> {
+        let Some(job) = self.plan_compaction() else {
+            return Ok(());
+        };
+        let merged = self.run_compaction(&job);
+        self.install_compaction(job, merged)
+    }
+
+    /// Whether enough SSTables exist for a flush to request a merge.
+    pub(crate) fn merge_due(&self) -> bool {
+        self.ss_tables.len() >= self.settings.compaction.min_tables.max(2)
+    }
+
+    fn plan_compaction(&mut self) -> Option<CompactionJob> {
+        if !self.merge_due() {
+            return None;
+        }
+
+        let config = self.settings.compaction;
+        let max_tables = config.max_tables.max(2);
+        let mut inputs: Vec<PathBuf> = Vec::new();
+        let mut input_sizes: Vec<u64> = Vec::new();
+        for table_path in self.ss_tables.iter().take(max_tables) {
+            let size = sstable_size(table_path);
+            let total: u64 = input_sizes.iter().sum();
+            if inputs.len() >= 2 && config.max_sstable_size.is_some_and(|max_size| total + size > max_size) {
+                break;
+            }
+            inputs.push(table_path.clone());
+            input_sizes.push(size);
+        }
+        // Newer tables accumulate instead of rewriting a much larger oldest
+        // table for each of them, until `max_tables` of them are waiting.
+        if let Some(size_ratio) = config.size_ratio {
+            let newer: u64 = input_sizes[1..].iter().sum();
+            if self.ss_tables.len() < max_tables && (newer as f64) * size_ratio < input_sizes[0] as f64 {
+                return None;
+            }
+        }
+
+        // The merge always starts at the oldest table, so every entry a range
+        // tombstone of these tables can shadow is merged here and the tombstones
+        // can be applied and, past their grace period, dropped.
+        let range_tombstones: Vec<RangeTombstone> = inputs
+            .iter()
+            .filter_map(|table_path| self.range_tombstones.get(table_path))
+            .flatten()
+            .cloned()
+            .collect();
+        let cached_keys = if self.settings.enable_value_cache && self.settings.repoint_value_cache_on_merge {
+            inputs
+                .iter()
+                .enumerate()
+                .flat_map(|(input, table_path)| {
+                    self.value_cache().keys_of_sstable(table_path).into_iter().map(move |key| (input, key))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let number = self.reserve_sstable_numbers(1);
+        let output = self.settings.db_path.join(format!("sstable_{}.sst", number));
+        Some(CompactionJob {
+            inputs,
+            output,
+            range_tombstones,
+            cached_keys,
+        })
+    }
+
+    /// Merges the inputs of a job into a temporary SSTable. Only reads the
+    /// inputs, so it can run while the tree keeps serving them.
+    fn run_compaction(&self, job: &CompactionJob) -> TreeResult<MergedTable> {
+        // Tombstones are dropped only after precedence is resolved, so an older
+        // table cannot resurrect a deleted key. No table older than the inputs
+        // exists, so a tombstone past its grace period has nothing left to hide.
+        let keep = |key: &[u8], value: &DataValue| {
+            !(value.is_tombstone && self.tombstone_past_grace(value.created_at)
+                || job.range_tombstones.iter().any(|range_tombstone| range_tombstone.shadows(key, value)))
+        };
+        // The bloom filter is sized to the surviving entries, not the input
+        // entry count, so the inputs are read once to count them and once more
+        // to stream the merged table to disk.
+        let (entries_before, entries_after) = self.count_merged_entries(&job.inputs, keep)?;
+
+        // Input versions of the keys cached for each input, to tell whether
+        // the merged table still holds them
+        let mut cached_inputs: HashMap<&[u8], Vec<usize>> = HashMap::new();
+        for (input, key) in &job.cached_keys {
+            cached_inputs.entry(key.as_slice()).or_default().push(*input);
+        }
+        let cached_versions = RefCell::new(HashMap::new());
+        let sources = self.merge_sources(&job.inputs)?.into_iter().enumerate().map(|(input, entries)| {
+            let (cached_inputs, cached_versions) = (&cached_inputs, &cached_versions);
+            entries.inspect(move |entry| {
+                if let Ok((key, value)) = entry {
+                    if cached_inputs.get(key.as_slice()).is_some_and(|inputs| inputs.contains(&input)) {
+                        cached_versions.borrow_mut().entry(key.clone()).or_insert_with(Vec::new).push((input, value.clone()));
+                    }
+                }
+            })
+        });
+
+        // A cached value stays valid if the merged table holds the very same
+        // version, i.e. it was neither superseded by a newer input nor dropped.
+        // All versions of a key are read before the merge yields it.
+        let mut current_cached = Vec::new();
+        let entries = self.merge_filtered(sources, keep).inspect(|entry| {
+            if let Ok((key, value)) = entry {
+                for (input, version) in cached_versions.borrow_mut().remove(key).unwrap_or_default() {
+                    if version == *value {
+                        current_cached.push(CacheKey {
+                            sstable_path: job.inputs[input].clone(),
+                            key: key.clone(),
+                        });
+                    }
+                }
+            }
+        });
+
+        let range_tombstones: Vec<RangeTombstone> = job
+            .range_tombstones
+            .iter()
+            .filter(|range_tombstone| !self.tombstone_past_grace(range_tombstone.created_at))
+            .cloned()
+            .collect();
+
+        let tmp_path = job.tmp_path();
+        let (index, bloom_filter) =
+            self.write_sstable_entries(&tmp_path, entries, entries_after, &range_tombstones)?;
+
+        Ok(MergedTable {
+            index,
+            range_tombstones,
+            bloom_filter,
+            entries_before,
+            entries_after,
+            current_cached,
+        })
+    }
+
+    /// Opens a sequential reader over each of `paths`, for `merge_filtered`.
+    pub(crate) fn merge_sources(
+        &self,
+        paths: &[PathBuf],
+    ) -> TreeResult<Vec<impl Iterator<Item = MergeEntry>>> {
+        let mut sources = Vec::with_capacity(paths.len());
+        for path in paths {
+            self.check_cancelled()?;
+            sources.push(self.sstable_entries(path)?.map(|entry| entry.map_err(TreeError::from)));
+        }
+        Ok(sources)
+    }
+
+    /// Merges `sources`, oldest first, into one stream in key order that
+    /// holds the newest version of each key, minus the entries `keep`
+    /// rejects. Fails once the running merge is cancelled.
+    pub(crate) fn merge_filtered<'a, I>(
+        &'a self,
+        sources: impl IntoIterator<Item = I>,
+        keep: impl Fn(&[u8], &DataValue) -> bool + 'a,
+    ) -> impl Iterator<Item = MergeEntry> + 'a
+    where
+        I: Iterator<Item = MergeEntry> + 'a,
+    {
+        MergeIterator::new(sources).filter_map(move |entry| {
+            if let Err(e) = self.check_cancelled() {
+                return Some(Err(e));
+            }
+            match entry {
+                Ok((key, value)) => keep(&key, &value).then_some(Ok((key, value))),
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+
+    /// Counts the entries read from `paths` and the entries a merge of them
+    /// keeps, without holding more than one entry per table in memory.
+    pub(crate) fn count_merged_entries(
+        &self,
+        paths: &[PathBuf],
+        keep: impl Fn(&[u8], &DataValue) -> bool,
+    ) -> TreeResult<(usize, usize)> {
+        let entries_before = Cell::new(0);
+        let sources = self.merge_sources(paths)?.into_iter().map(|entries| {
+            entries.inspect(|_| entries_before.set(entries_before.get() + 1))
+        });
+        let mut entries_after = 0;
+        for entry in self.merge_filtered(sources, keep) {
+            entry?;
+            entries_after += 1;
+        }
+        Ok((entries_before.get(), entries_after))
+    }
+
+    /// Checks whether a tombstone written at `created_at` has outlived
+    /// `tombstone_grace_period`, so a merge may drop it.
+    pub(crate) fn tombstone_past_grace(&self, created_at: SystemTime) -> bool {
+        let grace_period = self.settings.tombstone_grace_period;
+        grace_period.is_zero() || created_at.elapsed().is_ok_and(|age| age >= grace_period)
+    }
+
+    /// Replaces the inputs of a job with its merged table. A failed merge
+    /// leaves the inputs in place.
+    fn install_compaction(&mut self, job: CompactionJob, merged: TreeResult<MergedTable>) -> TreeResult<()> {
+        let tmp_path = job.tmp_path();
+        let merged = match merged {
+            Ok(merged) => merged,
+            Err(e) => {
+                error!("Error merging SSTables {:?}: {}", job.inputs, e);
+                return Ok(());
+            }
+        };
+
+        // A reload or range compaction may have replaced the inputs while the
+        // merge was running.
+        if !self.ss_tables.starts_with(&job.inputs) {
+            if let Err(e) = std::fs::remove_file(&tmp_path) {
+                error!("Error removing stale merged SSTable {:?}: {}", tmp_path, e);
+            }
+            return Ok(());
+        }
+
+        let output_path = job.output.clone();
+        self.file_pool.invalidate(&output_path);
+        if let Err(e) = std::fs::rename(&tmp_path, &output_path) {
+            error!("Error installing merged SSTable {:?}: {}", output_path, e);
+            let _ = std::fs::remove_file(&tmp_path);
+            return Ok(());
+        }
+        if let Err(e) = self.sync_db_directory() {
+            error!("Error syncing directory after installing {:?}: {}", output_path, e);
+        }
+        #[cfg(test)]
+        if self.stop_after_merge_rename {
+            return Ok(());
+        }
+
+        let current_cached: HashSet<CacheKey> = merged.current_cached.into_iter().collect();
+        self.value_cache().repoint_merged(&job.inputs, &output_path, &current_cached);
+        for path in &job.inputs {
+            self.index_cache().remove(path);
+            self.index_cache().lru_queue.retain(|p| p != path);
+            self.file_pool.invalidate(path);
+            self.bloom_filters_mut().retain(|bf| bf.path != *path);
+            self.legacy_sstables.remove(path);
+            self.key_ranges.remove(path);
+            self.range_tombstones.remove(path);
+        }
+        self.ss_tables.splice(0..job.inputs.len(), [output_path.clone()]);
+        // The manifest switches from the inputs to the merged table in one
+        // write. Until then a crash leaves the inputs live, and their WAL
+        // segments too, and the unlisted merged table is removed on load.
+        self.write_manifest()
+            .map_err(|e| TreeError::io(format!("Failed to write MANIFEST: {}", e)))?;
+        for path in &job.inputs {
+            if let Err(e) = std::fs::remove_file(path) {
+                error!("Error deleting old SSTable {:?}: {}", path, e);
+            }
+        }
+        self.record_merge(job.inputs.len(), Some(&output_path), merged.entries_before - merged.entries_after);
+
+        self.record_key_range(&output_path, &merged.index);
+        self.record_range_tombstones(&output_path, merged.range_tombstones);
+        if self.settings.enable_index_cache {
+            self.index_cache().put(output_path.clone(), merged.index);
+        }
+        self.cache_bloom_filter(output_path.clone(), merged.bloom_filter);
+
+        self.emit_event(TreeEvent::Merged {
+            inputs: job.inputs,
+            output: output_path,
+            dropped: merged.entries_before - merged.entries_after,
+        });
+        self.remove_obsolete_wal_segments();
+
+        Ok(())
+    }
+
+    /// Returns the flush and merge counters.
+    ///
+    /// # Returns
+    /// A `CompactionStats` snapshot of the counters since the tree was created
+    /// or `reset_compaction_stats` was last called
+    pub fn compaction_stats(&self) -> CompactionStats {
+        self.compaction_stats
+    }
+
+    /// Resets the flush and merge counters to zero, e.g. at the end of a
+    /// monitoring interval.
+    pub fn reset_compaction_stats(&mut self) {
+        self.compaction_stats = CompactionStats::default();
+    }
+
+    /// Reports the number and total size of SSTables per level.
+    ///
+    /// Merges are size-tiered: flushed tables and merged tables share a single
+    /// level, so the summary currently always holds exactly one entry, level 0.
+    ///
+    /// # Errors
+    /// Returns `TreeError` if the size of an SSTable cannot be read
+    pub fn level_summary(&self) -> TreeResult<Vec<LevelInfo>> {
+        let mut bytes = 0;
+        for path in &self.ss_tables {
+            bytes += std::fs::metadata(path)?.len();
+        }
+        Ok(vec![LevelInfo { level: 0, tables: self.ss_tables.len(), bytes }])
+    }
+
+    pub(crate) fn record_flush(&mut self, path: &Path) {
+        self.compaction_stats.flushes += 1;
+        self.compaction_stats.flushed_bytes += sstable_size(path);
+    }
+
+    pub(crate) fn record_merge(&mut self, inputs: usize, output: Option<&Path>, dropped: usize) {
+        self.compaction_stats.merges += 1;
+        self.compaction_stats.merged_tables += inputs as u64;
+        self.compaction_stats.merged_bytes += output.map_or(0, sstable_size);
+        self.compaction_stats.dropped_entries += dropped as u64;
+    }
+}
+
+fn sstable_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
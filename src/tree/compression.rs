@@ -7,12 +7,46 @@ use std::io::Write;
 /// Each algorithm provides different trade-offs between compression ratio,
 /// speed, and CPU usage. Choose the appropriate algorithm based on your
 /// performance requirements and data characteristics.
+///
+/// `Gzip` stores every value as a standard gzip member, so raw SSTable values
+/// can be read by external tools. It is slower than LZ4 and Zstd at a similar
+/// ratio and meant for interoperability rather than performance.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionType {
     None,
     Lz4,
     Zstd,
     Snappy,
+    Gzip,
+}
+
+impl CompressionType {
+    /// The marker stored in the compression byte of an SSTable header.
+    ///
+    /// Files written before markers were recorded carry 0, like uncompressed
+    /// ones. Values are decompressed with the configured compressor, so loading
+    /// rejects a table whose marker names another type.
+    pub(crate) fn marker(self) -> u8 {
+        match self {
+            CompressionType::None => 0,
+            CompressionType::Lz4 => 1,
+            CompressionType::Zstd => 2,
+            CompressionType::Snappy => 3,
+            CompressionType::Gzip => 4,
+        }
+    }
+
+    /// The compression type recorded by `marker`, if it is a known one.
+    pub(crate) fn from_marker(marker: u8) -> Option<Self> {
+        match marker {
+            0 => Some(CompressionType::None),
+            1 => Some(CompressionType::Lz4),
+            2 => Some(CompressionType::Zstd),
+            3 => Some(CompressionType::Snappy),
+            4 => Some(CompressionType::Gzip),
+            _ => None,
+        }
+    }
 }
 
 /// Configuration for compression settings.
@@ -63,6 +97,7 @@ impl CompressionConfig {
     /// Default values are automatically selected based on the algorithm:
     /// - **Zstd**: Level 3, checksums enabled
     /// - **LZ4**: Level 1, checksums enabled
+    /// - **Gzip**: Level 6, checksums enabled
     /// - **Snappy/None**: No level, checksums enabled
     ///
     /// # Arguments
@@ -83,6 +118,7 @@ impl CompressionConfig {
             level: match compression_type {
                 CompressionType::Zstd => Some(3),
                 CompressionType::Lz4 => Some(1),
+                CompressionType::Gzip => Some(6),
                 _ => None,
             },
             enable_checksum: true,
@@ -95,6 +131,7 @@ impl CompressionConfig {
     /// Different algorithms support different level ranges:
     /// - **LZ4**: 1-9 (1=fastest, 9=best compression)
     /// - **Zstd**: 1-22 (1=fastest, 22=best compression, 19+=ultra mode)
+    /// - **Gzip**: 0-9 (0=store only, 9=best compression)
     /// - **Snappy**: Level ignored (always uses default)
     ///
     /// # Arguments
@@ -397,6 +434,8 @@ impl Compressor {
     ///
     /// # Examples
     /// ```rust
+    /// use redish::tree::{CompressionConfig, Compressor};
+    ///
     /// let config = CompressionConfig::balanced();
     /// let compressor = Compressor::new(config);
     /// ```
@@ -411,6 +450,7 @@ impl Compressor {
     /// - **LZ4**: Fast compression with good ratio
     /// - **Zstd**: Configurable compression with excellent ratios
     /// - **Snappy**: Very fast compression with moderate ratio
+    /// - **Gzip**: Standard gzip format for interoperability, slower than LZ4 and Zstd
     ///
     /// # Arguments
     /// * `data` - The data to compress
@@ -419,15 +459,35 @@ impl Compressor {
     /// * `Ok(Vec<u8>)` - The compressed data
     /// * `Err(Box<dyn Error>)` - If compression fails
     pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        let compressed = match self.config.compression_type {
-            CompressionType::None => data.to_vec(),
-            CompressionType::Lz4 => self.compress_lz4(data)?,
-            CompressionType::Zstd => self.compress_zstd(data)?,
-            CompressionType::Snappy => self.compress_snappy(data)?,
-        };
+        let mut compressed = Vec::new();
+        self.compress_into(data, &mut compressed)?;
         Ok(compressed)
     }
 
+    /// Compresses the provided data into a caller-provided buffer.
+    ///
+    /// The buffer is cleared first and its allocation is reused, so calling this
+    /// in a loop with the same buffer avoids allocating per call.
+    ///
+    /// # Arguments
+    /// * `data` - The data to compress
+    /// * `out` - The buffer receiving the compressed data
+    ///
+    /// # Returns
+    /// * `Ok(())` - If compression succeeds
+    /// * `Err(Box<dyn Error>)` - If compression fails
+    pub fn compress_into(&self, data: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        out.clear();
+        match self.config.compression_type {
+            CompressionType::None => out.extend_from_slice(data),
+            CompressionType::Lz4 => self.compress_lz4(data, out)?,
+            CompressionType::Zstd => self.compress_zstd(data, out)?,
+            CompressionType::Snappy => self.compress_snappy(data, out)?,
+            CompressionType::Gzip => self.compress_gzip(data, out)?,
+        }
+        Ok(())
+    }
+
     /// Decompresses the provided data using the configured algorithm.
     ///
     /// The decompression algorithm must match the one used for compression.
@@ -441,70 +501,126 @@ impl Compressor {
     /// * `Ok(Vec<u8>)` - The decompressed data
     /// * `Err(Box<dyn Error>)` - If decompression fails
     pub fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        let decompressed = match self.config.compression_type {
-            CompressionType::None => compressed.to_vec(),
-            CompressionType::Lz4 => self.decompress_lz4(compressed)?,
-            CompressionType::Zstd => self.decompress_zstd(compressed)?,
-            CompressionType::Snappy => self.decompress_snappy(compressed)?,
-        };
+        let mut decompressed = Vec::new();
+        self.decompress_into(compressed, &mut decompressed)?;
         Ok(decompressed)
     }
 
-    fn compress_lz4(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        use lz4::block::{compress, CompressionMode};
-
-        let compressed = compress(data, Some(CompressionMode::DEFAULT), true)?;
-        Ok(compressed)
+    /// Decompresses the provided data into a caller-provided buffer.
+    ///
+    /// The buffer is cleared first and its allocation is reused, so calling this
+    /// in a loop with the same buffer avoids allocating per call.
+    ///
+    /// # Arguments
+    /// * `compressed` - The compressed data to decompress
+    /// * `out` - The buffer receiving the decompressed data
+    ///
+    /// # Returns
+    /// * `Ok(())` - If decompression succeeds
+    /// * `Err(Box<dyn Error>)` - If decompression fails
+    pub fn decompress_into(&self, compressed: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        out.clear();
+        match self.config.compression_type {
+            CompressionType::None => out.extend_from_slice(compressed),
+            CompressionType::Lz4 => self.decompress_lz4(compressed, out)?,
+            CompressionType::Zstd => self.decompress_zstd(compressed, out)?,
+            CompressionType::Snappy => self.decompress_snappy(compressed, out)?,
+            CompressionType::Gzip => self.decompress_gzip(compressed, out)?,
+        }
+        Ok(())
     }
 
-    fn decompress_lz4(&self, compressed: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        use lz4::block::decompress;
+    fn compress_lz4(&self, data: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use lz4::block::{compress_bound, compress_to_buffer, CompressionMode};
 
-        let decompressed = decompress(compressed, None)?;
-        Ok(decompressed)
+        out.resize(compress_bound(data.len())? + 4, 0);
+        let written = compress_to_buffer(data, Some(CompressionMode::DEFAULT), true, out)?;
+        out.truncate(written);
+        Ok(())
     }
 
-    fn compress_zstd(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        use zstd::stream::{encode_all, Encoder};
+    fn decompress_lz4(&self, compressed: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use lz4::block::decompress_to_buffer;
+
+        let size_prefix: [u8; 4] = compressed
+            .get(..4)
+            .and_then(|prefix| prefix.try_into().ok())
+            .ok_or("LZ4 data is missing its size prefix")?;
+        // A block expands at most 255 times, so a larger prefix is corrupt and
+        // must not be allocated.
+        let size = u32::from_le_bytes(size_prefix) as usize;
+        if size > (compressed.len() - 4).saturating_mul(255) + 16 {
+            return Err("LZ4 size prefix exceeds the maximum expansion".into());
+        }
+        out.resize(size, 0);
+        let written = decompress_to_buffer(compressed, None, out)?;
+        out.truncate(written);
+        Ok(())
+    }
+
+    fn compress_zstd(&self, data: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use zstd::stream::{copy_encode, Encoder};
 
         let level = self.config.level.unwrap_or(3);
 
         if data.len() > self.config.buffer_size {
-            let mut encoder = Encoder::new(Vec::new(), level)?;
+            let mut encoder = Encoder::new(out, level)?;
             encoder.include_checksum(self.config.enable_checksum)?;
             encoder.write_all(data)?;
-            encoder.finish()
+            encoder.finish()?;
         } else {
-            encode_all(data, level)
+            copy_encode(data, out, level)?;
         }
-        .map_err(|e| e.into())
+        Ok(())
     }
 
-    fn decompress_zstd(&self, compressed: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        use zstd::stream::{decode_all, Decoder};
+    fn decompress_zstd(&self, compressed: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use zstd::stream::{copy_decode, Decoder};
 
         if compressed.len() > self.config.buffer_size {
             let mut decoder = Decoder::new(compressed)?;
-            let mut decompressed = Vec::new();
-            std::io::copy(&mut decoder, &mut decompressed)?;
-            Ok(decompressed)
+            std::io::copy(&mut decoder, out)?;
         } else {
-            decode_all(compressed)
+            copy_decode(compressed, out)?;
         }
-        .map_err(|e| e.into())
+        Ok(())
     }
 
-    fn compress_snappy(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        use snap::raw::Encoder;
+    fn compress_snappy(&self, data: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use snap::raw::{max_compress_len, Encoder};
 
         let mut encoder = Encoder::new();
-        encoder.compress_vec(data).map_err(|e| e.into())
+        out.resize(max_compress_len(data.len()), 0);
+        let written = encoder.compress(data, out)?;
+        out.truncate(written);
+        Ok(())
     }
 
-    fn decompress_snappy(&self, compressed: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        use snap::raw::Decoder;
+    fn decompress_snappy(&self, compressed: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use snap::raw::{decompress_len, Decoder};
 
         let mut decoder = Decoder::new();
-        decoder.decompress_vec(compressed).map_err(|e| e.into())
+        out.resize(decompress_len(compressed)?, 0);
+        let written = decoder.decompress(compressed, out)?;
+        out.truncate(written);
+        Ok(())
+    }
+
+    fn compress_gzip(&self, data: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use flate2::{write::GzEncoder, Compression};
+
+        let level = self.config.level.unwrap_or(6).clamp(0, 9) as u32;
+        let mut encoder = GzEncoder::new(out, Compression::new(level));
+        encoder.write_all(data)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    fn decompress_gzip(&self, compressed: &[u8], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        GzDecoder::new(compressed).read_to_end(out)?;
+        Ok(())
     }
 }
@@ -1,6 +1,12 @@
 use bincode::{Decode, Encode};
 use std::time::{Duration, SystemTime};
 
+/// A stored value together with its metadata.
+///
+/// `transaction_id` holds the ID of the transaction whose commit wrote the
+/// value and is `None` for values written outside a transaction. It is
+/// persisted in the WAL and SSTables, so it survives flushes, merges and
+/// recovery, and is exposed through `Tree::get_metadata`.
 #[derive(Clone, Debug, Encode, Decode, Eq, PartialEq)]
 pub struct DataValue {
     pub data: Vec<u8>,
@@ -17,11 +23,15 @@ impl DataValue {
     /// * `data` - The raw data to store as bytes
     /// * `ttl` - Optional time-to-live duration. If None, the value never expires
     ///
+    /// A TTL so large that the expiration time is not representable as a
+    /// `SystemTime`, such as `Duration::from_secs(u64::MAX)`, is treated like
+    /// `None`: the value never expires.
+    ///
     /// # Returns
     /// A new DataValue instance with creation timestamp and calculated expiration time
     pub fn new(data: Vec<u8>, ttl: Option<Duration>) -> Self {
         let created_at = SystemTime::now();
-        let expires_at = ttl.map(|duration| created_at + duration);
+        let expires_at = ttl.and_then(|duration| created_at.checked_add(duration));
 
         Self {
             data,
@@ -87,6 +97,18 @@ impl DataValue {
         &self.data
     }
 
+    /// Returns the number of bytes the value occupies in memory.
+    ///
+    /// Counts the struct itself, which holds the timestamps and transaction ID
+    /// inline, plus the data bytes. Caches use it to enforce their memory
+    /// limits, so reported usage matches what is enforced.
+    ///
+    /// # Returns
+    /// The approximate in-memory size of the value in bytes
+    pub fn heap_size(&self) -> usize {
+        size_of::<DataValue>() + self.data.len()
+    }
+
     /// Creates a tombstone marker for deletion.
     ///
     /// A tombstone is a special marker that indicates a key has been deleted.
@@ -104,6 +126,30 @@ impl DataValue {
         }
     }
 
+    /// Copies `other` into this value, reusing the data buffer, if both hold
+    /// the same number of bytes.
+    ///
+    /// # Returns
+    /// `true` if the value was overwritten, `false` if the lengths differ and
+    /// the value is unchanged
+    pub(crate) fn overwrite_in_place(&mut self, other: &DataValue) -> bool {
+        if self.data.len() != other.data.len() {
+            return false;
+        }
+        self.data.copy_from_slice(&other.data);
+        self.expires_at = other.expires_at;
+        self.created_at = other.created_at;
+        self.is_tombstone = other.is_tombstone;
+        self.transaction_id = other.transaction_id;
+        true
+    }
+
+    /// Tags the value with the transaction whose commit writes it.
+    pub(crate) fn with_transaction_id(mut self, tx_id: u64) -> Self {
+        self.transaction_id = Some(tx_id);
+        self
+    }
+
     /// Checks if this value is a tombstone (deletion marker).
     ///
     /// # Returns
@@ -111,4 +157,28 @@ impl DataValue {
     pub fn is_tombstone(&self) -> bool {
         self.is_tombstone
     }
-}
\ No newline at end of file
+}
+
+/// Metadata of a stored value, returned by `Tree::get_metadata`.
+///
+/// # Fields
+/// - `created_at`: When the value was written
+/// - `expires_at`: When the value expires, if it has a TTL
+/// - `transaction_id`: The transaction whose commit wrote the value, `None`
+///   for values written outside a transaction
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ValueMetadata {
+    pub created_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+    pub transaction_id: Option<u64>,
+}
+
+impl From<&DataValue> for ValueMetadata {
+    fn from(value: &DataValue) -> Self {
+        Self {
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            transaction_id: value.transaction_id,
+        }
+    }
+}
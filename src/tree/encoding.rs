@@ -0,0 +1,53 @@
+use bincode::config::{self, Configuration};
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+
+/// Byte order and integer encoding used for typed values.
+///
+/// The default matches `bincode::config::standard()`: little-endian with
+/// variable-length integers. Fixed-width big-endian integers sort byte-wise in
+/// numeric order (for unsigned values), which keeps encoded numbers
+/// order-preserving for range scans.
+///
+/// Changing the encoding of an existing database does not rewrite stored
+/// values; read values written with the previous encoding through
+/// `Tree::get_typed_with_encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TypedEncoding {
+    pub big_endian: bool,
+    pub fixed_int: bool,
+}
+
+impl TypedEncoding {
+    /// Little-endian, variable-length integers (`bincode::config::standard()`).
+    pub const STANDARD: TypedEncoding = TypedEncoding {
+        big_endian: false,
+        fixed_int: false,
+    };
+
+    const LITTLE_ENDIAN_FIXED_INT: Configuration<config::LittleEndian, config::Fixint> =
+        config::standard().with_fixed_int_encoding();
+    const BIG_ENDIAN_VARINT: Configuration<config::BigEndian, config::Varint> =
+        config::standard().with_big_endian();
+    const BIG_ENDIAN_FIXED_INT: Configuration<config::BigEndian, config::Fixint> =
+        config::standard().with_big_endian().with_fixed_int_encoding();
+
+    pub(crate) fn encode<T: Encode>(&self, value: &T) -> Result<Vec<u8>, EncodeError> {
+        match (self.big_endian, self.fixed_int) {
+            (false, false) => bincode::encode_to_vec(value, config::standard()),
+            (false, true) => bincode::encode_to_vec(value, Self::LITTLE_ENDIAN_FIXED_INT),
+            (true, false) => bincode::encode_to_vec(value, Self::BIG_ENDIAN_VARINT),
+            (true, true) => bincode::encode_to_vec(value, Self::BIG_ENDIAN_FIXED_INT),
+        }
+    }
+
+    pub(crate) fn decode<T: Decode<()>>(&self, bytes: &[u8]) -> Result<T, DecodeError> {
+        let (decoded, _) = match (self.big_endian, self.fixed_int) {
+            (false, false) => bincode::decode_from_slice(bytes, config::standard())?,
+            (false, true) => bincode::decode_from_slice(bytes, Self::LITTLE_ENDIAN_FIXED_INT)?,
+            (true, false) => bincode::decode_from_slice(bytes, Self::BIG_ENDIAN_VARINT)?,
+            (true, true) => bincode::decode_from_slice(bytes, Self::BIG_ENDIAN_FIXED_INT)?,
+        };
+        Ok(decoded)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::Tree;
+use log::debug;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+/// Lifecycle events emitted by the tree for storage operations.
+///
+/// Events are delivered through a bounded channel obtained with `Tree::subscribe`
+/// or supplied via `TreeSettingsBuilder::event_subscriber`. Delivery never blocks:
+/// if no subscriber is attached or the channel is full, the event is dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeEvent {
+    /// A memory table was written to a new SSTable.
+    Flushed { path: PathBuf, entries: usize },
+    /// SSTables were merged into a single table.
+    ///
+    /// `dropped` counts entries that did not survive the merge, such as
+    /// overwritten versions, tombstones and expired values.
+    Merged {
+        inputs: Vec<PathBuf>,
+        output: PathBuf,
+        dropped: usize,
+    },
+    /// The WAL was rotated to a new segment.
+    WalRotated { segment: u16 },
+    /// Entries were replayed from the WAL into the memory table.
+    Recovered { entries: usize },
+}
+
+impl Tree {
+    /// Attaches a subscriber for lifecycle events.
+    ///
+    /// Replaces any previously attached subscriber. Events are sent without
+    /// blocking; when the receiver falls behind by more than `capacity` events,
+    /// newer events are dropped until it catches up.
+    ///
+    /// Events emitted while the tree is being loaded (such as `Recovered`) happen
+    /// before this method can be called; use `TreeSettingsBuilder::event_subscriber`
+    /// to observe them.
+    ///
+    /// # Arguments
+    /// * `capacity` - Maximum number of undelivered events kept in the channel
+    ///
+    /// # Returns
+    /// The receiving end of the event channel
+    pub fn subscribe(&mut self, capacity: usize) -> mpsc::Receiver<TreeEvent> {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        self.settings.event_sender = Some(sender);
+        receiver
+    }
+
+    pub(crate) fn emit_event(&mut self, event: TreeEvent) {
+        if let Some(ref sender) = self.settings.event_sender {
+            match sender.try_send(event) {
+                Ok(()) => {}
+                Err(mpsc::TrySendError::Full(event)) => {
+                    debug!("Event channel full, dropping {:?}", event);
+                }
+                Err(mpsc::TrySendError::Disconnected(_)) => {
+                    self.settings.event_sender = None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Seek, SeekFrom};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A bounded LRU pool of open SSTable file handles, keyed by path.
+///
+/// Readers are checked out with `open` and go back into the pool when the
+/// returned `PooledReader` is dropped, so a handle is never shared between two
+/// readers. Once more than `max_open_files` idle handles are pooled, the least
+/// recently used ones are closed. A capacity of 0 disables pooling and every
+/// read opens its own file.
+///
+/// Any handle checked out before an `invalidate` or `clear` is closed instead
+/// of being returned, so a deleted or renamed SSTable is never read through a
+/// stale handle.
+///
+/// Every reader is buffered with `read_buffer_size` bytes.
+#[derive(Clone)]
+pub(crate) struct FilePool {
+    inner: Arc<Mutex<FilePoolInner>>,
+}
+
+struct FilePoolInner {
+    files: HashMap<PathBuf, BufReader<File>>,
+    lru_queue: VecDeque<PathBuf>,
+    max_open_files: usize,
+    read_buffer_size: usize,
+    epoch: u64,
+}
+
+impl FilePool {
+    pub(crate) fn new(max_open_files: usize, read_buffer_size: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(FilePoolInner {
+                files: HashMap::new(),
+                lru_queue: VecDeque::new(),
+                max_open_files,
+                read_buffer_size,
+                epoch: 0,
+            })),
+        }
+    }
+
+    /// Checks out a reader positioned at the start of the file, reusing a
+    /// pooled handle when one is idle.
+    pub(crate) fn open(&self, path: &Path) -> std::io::Result<PooledReader> {
+        let (pooled, epoch, read_buffer_size) = {
+            let mut inner = self.inner.lock().unwrap();
+            let pooled = inner.files.remove(path);
+            if pooled.is_some() {
+                inner.lru_queue.retain(|p| p != path);
+            }
+            (pooled, inner.epoch, inner.read_buffer_size)
+        };
+
+        let reader = match pooled {
+            Some(mut reader) => {
+                reader.seek(SeekFrom::Start(0))?;
+                reader
+            }
+            None => BufReader::with_capacity(read_buffer_size, File::open(path)?),
+        };
+
+        Ok(PooledReader {
+            reader: Some(reader),
+            path: path.to_path_buf(),
+            epoch,
+            pool: self.inner.clone(),
+        })
+    }
+
+    /// Closes the pooled handle of a file that is about to be deleted,
+    /// renamed or rewritten.
+    pub(crate) fn invalidate(&self, path: &Path) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.epoch += 1;
+        inner.files.remove(path);
+        inner.lru_queue.retain(|p| p != path);
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.epoch += 1;
+        inner.files.clear();
+        inner.lru_queue.clear();
+    }
+
+    /// Returns the number of idle handles currently held open by the pool.
+    #[cfg(test)]
+    pub(crate) fn open_files(&self) -> usize {
+        self.inner.lock().unwrap().files.len()
+    }
+}
+
+impl Default for FilePool {
+    fn default() -> Self {
+        Self::new(crate::config::DEFAULT_MAX_OPEN_FILES, crate::config::DEFAULT_READ_BUFFER_SIZE)
+    }
+}
+
+/// A reader checked out of a `FilePool`, returned to it on drop.
+pub(crate) struct PooledReader {
+    reader: Option<BufReader<File>>,
+    path: PathBuf,
+    epoch: u64,
+    pool: Arc<Mutex<FilePoolInner>>,
+}
+
+impl PooledReader {
+    /// Takes the reader out of the pool for good, e.g. for a long-lived scan.
+    pub(crate) fn into_inner(mut self) -> BufReader<File> {
+        self.reader.take().expect("reader is present until dropped")
+    }
+}
+
+impl Deref for PooledReader {
+    type Target = BufReader<File>;
+
+    fn deref(&self) -> &Self::Target {
+        self.reader.as_ref().expect("reader is present until dropped")
+    }
+}
+
+impl DerefMut for PooledReader {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.reader.as_mut().expect("reader is present until dropped")
+    }
+}
+
+impl Drop for PooledReader {
+    fn drop(&mut self) {
+        let Some(reader) = self.reader.take() else {
+            return;
+        };
+        let Ok(mut inner) = self.pool.lock() else {
+            return;
+        };
+        if inner.max_open_files == 0 || inner.epoch != self.epoch || inner.files.contains_key(&self.path) {
+            return;
+        }
+
+        inner.files.insert(self.path.clone(), reader);
+        inner.lru_queue.push_back(self.path.clone());
+        while inner.files.len() > inner.max_open_files {
+            match inner.lru_queue.pop_front() {
+                Some(oldest) => {
+                    inner.files.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::tree::range_tombstone::RangeTombstone;
+use crate::tree::tree_error::{TreeError, TreeResult};
+use crate::tree::{DataValue, TreeEvent};
+use crate::Tree;
+use growable_bloom_filter::GrowableBloom;
+use log::error;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// The oldest immutable memory table handed to the background flusher, with
+/// the paths of the SSTables it is split into, oldest first, and the range
+/// tombstones the newest of them carries.
+pub(crate) struct FlushJob {
+    table: Arc<BTreeMap<Vec<u8>, DataValue>>,
+    range_tombstones: Vec<RangeTombstone>,
+    paths: Vec<PathBuf>,
+}
+
+/// An SSTable written next to its final path, waiting to be installed.
+pub(crate) struct FlushedTable {
+    index: BTreeMap<Vec<u8>, u64>,
+    bloom_filter: GrowableBloom,
+    entries: usize,
+}
+
+type FlushResult = (FlushJob, TreeResult<Vec<FlushedTable>>);
+
+fn tmp_path(path: &Path) -> PathBuf {
+    path.with_extension("sst.tmp")
+}
+
+fn remove_tmp_files(job: &FlushJob) {
+    for path in &job.paths {
+        let tmp_path = tmp_path(path);
+        if tmp_path.exists() {
+            if let Err(e) = std::fs::remove_file(&tmp_path) {
+                error!("Error removing unfinished SSTable {:?}: {}", tmp_path, e);
+            }
+        }
+    }
+}
+
+/// Handle to the background flush thread.
+///
+/// Dropping it closes the job channel, which stops the thread once the flush
+/// it is working on has finished. The result receiver sits behind a mutex only
+/// to keep `Tree` `Sync`; it is read from `&mut Tree` alone.
+pub(crate) struct FlushWorker {
+    jobs: mpsc::Sender<FlushJob>,
+    results: Mutex<mpsc::Receiver<FlushResult>>,
+}
+
+impl Tree {
+    pub(crate) fn start_flush_worker(&mut self) {
+        self.flush_worker = None;
+        self.flush_in_flight = false;
+        if !self.settings.background_flush {
+            return;
+        }
+
+        let (job_sender, job_receiver) = mpsc::channel::<FlushJob>();
+        let (result_sender, result_receiver) = mpsc::channel::<FlushResult>();
+        let mut context = Tree::detached(self.settings.clone(), self.file_pool.clone());
+        context.cancel_requested = Arc::clone(&self.cancel_requested);
+        thread::spawn(move || {
+            Self::flush_worker(job_receiver, result_sender, context);
+        });
+        self.flush_worker = Some(FlushWorker {
+            jobs: job_sender,
+            results: Mutex::new(result_receiver),
+        });
+    }
+
+    /// Writes memory tables handed over by the tree. `context` is a detached
+    /// tree that shares the settings and file pool of the tree it works for.
+    fn flush_worker(jobs: mpsc::Receiver<FlushJob>, results: mpsc::Sender<FlushResult>, context: Tree) {
+        while let Ok(job) = jobs.recv() {
+            let written = context.run_flush(&job);
+            if results.send((job, written)).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Moves the full memory table to the immutable memory tables and hands
+    /// it to the background flusher, or flushes it right away without one.
+    ///
+    /// Waits for the flush in flight first, so the WAL boundary recorded for
+    /// the new one covers exactly the memory tables it writes. Tables left
+    /// behind by a failed background flush are written on the calling thread,
+    /// and the full table is kept if `max_immutable_mem_tables` are still queued.
+    pub(crate) fn rotate_mem_table(&mut self) -> TreeResult<()> {
+        if self.flush_worker.is_none() {
+            return self.flush_mem_table();
+        }
+
+        self.poll_flush(true)?;
+        self.limit_immutable_mem_tables()?;
+        let immutable = self.take_mem_table();
+        self.immutable_mem_tables.push_back(Arc::new(immutable));
+        while self.immutable_mem_tables.len() > 1 {
+            self.compact()?;
+        }
+
+        let Some(table) = self.immutable_mem_tables.front().cloned() else {
+            return Ok(());
+        };
+        let max_entries = self.settings.sstable_max_entries.unwrap_or(usize::MAX).max(1);
+        let tables = table.len().div_ceil(max_entries).max(1);
+        let first_number = self.reserve_sstable_numbers(tables);
+        self.record_wal_boundary((first_number + tables - 1) as u64)?;
+        let job = FlushJob {
+            table,
+            range_tombstones: self.mem_range_tombstones.clone(),
+            paths: (first_number..first_number + tables)
+                .map(|number| self.settings.db_path.join(format!("sstable_{}.sst", number)))
+                .collect(),
+        };
+
+        let sent = match &self.flush_worker {
+            Some(worker) => worker.jobs.send(job).is_ok(),
+            None => false,
+        };
+        if !sent {
+            error!("Flush worker stopped, flushing on the write path from now on");
+            self.flush_worker = None;
+            return self.compact();
+        }
+        self.flush_in_flight = true;
+        Ok(())
+    }
+
+    /// Writes the memory table of a job to temporary SSTables. Only reads the
+    /// shared table, so it can run while the tree keeps serving it.
+    fn run_flush(&self, job: &FlushJob) -> TreeResult<Vec<FlushedTable>> {
+        let max_entries = self.settings.sstable_max_entries.unwrap_or(usize::MAX).max(1);
+        let newest = job.paths.len() - 1;
+        let mut entries = job.table.iter();
+        let mut written = Vec::with_capacity(job.paths.len());
+        for (i, path) in job.paths.iter().enumerate() {
+            self.check_cancelled()?;
+            // The newest table carries the range tombstones, as on the write path
+            let range_tombstones: &[RangeTombstone] = if i == newest { &job.range_tombstones } else { &[] };
+            let (index, bloom_filter, entries) = if job.paths.len() == 1 {
+                let (index, bloom_filter) = self.write_sstable_file(&tmp_path(path), &job.table, range_tombstones)?;
+                (index, bloom_filter, job.table.len())
+            } else {
+                let chunk: BTreeMap<Vec<u8>, DataValue> = entries
+                    .by_ref()
+                    .take(max_entries)
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                let (index, bloom_filter) = self.write_sstable_file(&tmp_path(path), &chunk, range_tombstones)?;
+                (index, bloom_filter, chunk.len())
+            };
+            written.push(FlushedTable {
+                index,
+                bloom_filter,
+                entries,
+            });
+        }
+        Ok(written)
+    }
+
+    /// Installs the flush finished by the worker, waiting for it if `wait` is set.
+    pub(crate) fn poll_flush(&mut self, wait: bool) -> TreeResult<()> {
+        if !self.flush_in_flight {
+            return Ok(());
+        }
+        let finished = match &mut self.flush_worker {
+            Some(worker) if wait => worker.results.get_mut().unwrap().recv().ok(),
+            Some(worker) => match worker.results.get_mut().unwrap().try_recv() {
+                Ok(finished) => Some(finished),
+                Err(mpsc::TryRecvError::Empty) => return Ok(()),
+                Err(mpsc::TryRecvError::Disconnected) => None,
+            },
+            None => None,
+        };
+        self.flush_in_flight = false;
+        match finished {
+            Some((job, written)) => self.install_flush(job, written),
+            None => {
+                error!("Flush worker stopped, flushing on the write path from now on");
+                self.flush_worker = None;
+                Ok(())
+            }
+        }
+    }
+
+    /// Waits for the flush in flight, if any, and deletes its SSTables instead
+    /// of installing them, for callers that throw the memory tables away.
+    pub(crate) fn discard_flush(&mut self) {
+        if !self.flush_in_flight {
+            return;
+        }
+        self.flush_in_flight = false;
+        if let Some(worker) = &mut self.flush_worker {
+            if let Ok((job, _)) = worker.results.get_mut().unwrap().recv() {
+                remove_tmp_files(&job);
+            }
+        }
+    }
+
+    /// Replaces the oldest immutable memory table with the SSTables written
+    /// for it. A failed flush leaves the table queued for the next flush.
+    fn install_flush(&mut self, job: FlushJob, written: TreeResult<Vec<FlushedTable>>) -> TreeResult<()> {
+        let written = match written {
+            Ok(written) => written,
+            Err(e) => {
+                error!("Error flushing memory table to SSTable in the background: {}", e);
+                remove_tmp_files(&job);
+                return Ok(());
+            }
+        };
+
+        for (installed, path) in job.paths.iter().enumerate() {
+            if let Err(e) = std::fs::rename(tmp_path(path), path) {
+                error!("Error installing flushed SSTable {:?}: {}", path, e);
+                for path in &job.paths[..installed] {
+                    let _ = std::fs::remove_file(path);
+                }
+                remove_tmp_files(&job);
+                return Ok(());
+            }
+        }
+        if let Err(e) = self.sync_db_directory() {
+            error!("Error syncing directory after installing {:?}: {}", job.paths, e);
+        }
+
+        let newest = job.paths.len() - 1;
+        for (i, (path, table)) in job.paths.iter().zip(written).enumerate() {
+            let range_tombstones = if i == newest { job.range_tombstones.clone() } else { Vec::new() };
+            self.record_key_range(path, &table.index);
+            self.record_range_tombstones(path, range_tombstones);
+            if self.settings.enable_index_cache {
+                self.index_cache().put(path.clone(), table.index);
+            }
+            self.emit_event(TreeEvent::Flushed {
+                path: path.clone(),
+                entries: table.entries,
+            });
+            self.ss_tables.push(path.clone());
+            self.record_flush(path);
+            self.cache_bloom_filter(path.clone(), table.bloom_filter);
+        }
+        self.immutable_mem_tables.pop_front();
+        // Range tombstones added while the flush ran come after the ones it wrote
+        self.mem_range_tombstones.drain(..job.range_tombstones.len());
+        self.record_flushed_sstable(&job.paths[newest])
+            .map_err(|e| TreeError::io(format!("Failed to write MANIFEST: {}", e)))?;
+        self.rotate_wal_after_flush()?;
+
+        if self.merge_due() {
+            self.request_compaction()?;
+        }
+        Ok(())
+    }
+}
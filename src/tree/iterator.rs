@@ -0,0 +1,642 @@
+use crate::tree::range_tombstone::RangeTombstone;
+use crate::tree::sstable::{SSTableIterator, SSTableRevIterator};
+use crate::tree::tree_error::{TreeError, TreeResult};
+use crate::tree::{Compressor, ReadErrorPolicy, TypedEncoding};
+use crate::{DataValue, Tree};
+use bincode::Decode;
+use log::warn;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+enum Source {
+    Memory(std::vec::IntoIter<(Vec<u8>, DataValue)>),
+    SSTable(SSTableIterator),
+    SSTableRev(SSTableRevIterator),
+}
+
+impl Iterator for Source {
+    type Item = TreeResult<(Vec<u8>, DataValue)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Source::Memory(entries) => entries.next().map(Ok),
+            Source::SSTable(entries) => entries
+                .next()
+                .map(|entry| entry.map_err(|e| TreeError::internal(format!("Failed to read SSTable entry: {}", e)))),
+            Source::SSTableRev(entries) => entries
+                .next()
+                .map(|entry| entry.map_err(|e| TreeError::internal(format!("Failed to read SSTable entry: {}", e)))),
+        }
+    }
+}
+
+/// Current head of one source; sources with a higher index are newer.
+///
+/// Heads order by key in the direction of the merge, so the smallest head is
+/// always the next key to yield, and by newest source for equal keys.
+struct Head {
+    key: Vec<u8>,
+    value: DataValue,
+    source: usize,
+    descending: bool,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key_order = if self.descending {
+            other.key.cmp(&self.key)
+        } else {
+            self.key.cmp(&other.key)
+        };
+        key_order.then_with(|| other.source.cmp(&self.source))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.source == other.source
+    }
+}
+
+impl Eq for Head {}
+
+/// K-way merge of sorted `(key, DataValue)` sources.
+///
+/// Each source must yield keys in strictly ascending order, or strictly
+/// descending order for a merge created with `new_rev`. Sources are given
+/// oldest first: when several sources hold the same key, the entry from the
+/// source given last wins and the others are discarded. The merged output
+/// follows the order of the sources, with one entry per key.
+///
+/// Tombstones and expired values are yielded like any other entry, since only
+/// the caller knows whether they still have to shadow older data; compaction
+/// and scans filter them differently. An error from a source is yielded in
+/// place, after which that source contributes no further entries.
+///
+/// # Example
+/// ```
+/// use redish::tree::{DataValue, MergeIterator};
+///
+/// let older = vec![(b"a".to_vec(), DataValue::new(b"1".to_vec(), None))];
+/// let newer = vec![(b"a".to_vec(), DataValue::new(b"2".to_vec(), None))];
+/// let merged: Vec<_> = MergeIterator::new([older, newer].map(|source| source.into_iter().map(Ok)))
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(merged.len(), 1);
+/// assert_eq!(merged[0].1.data, b"2".to_vec());
+/// ```
+pub struct MergeIterator<I> {
+    sources: Vec<I>,
+    heap: BinaryHeap<Reverse<Head>>,
+    pending_error: Option<TreeError>,
+    descending: bool,
+}
+
+impl<I> MergeIterator<I>
+where
+    I: Iterator<Item = TreeResult<(Vec<u8>, DataValue)>>,
+{
+    /// Creates a merge over sorted sources ordered from oldest to newest.
+    ///
+    /// # Arguments
+    /// * `sources` - Sorted sources; later sources take precedence over earlier ones
+    pub fn new(sources: impl IntoIterator<Item = I>) -> Self {
+        Self::with_direction(sources, false)
+    }
+
+    /// Creates a merge over sources sorted in descending key order, ordered
+    /// from oldest to newest. The newest entry of a key still wins.
+    ///
+    /// # Arguments
+    /// * `sources` - Reverse sorted sources; later sources take precedence over earlier ones
+    pub fn new_rev(sources: impl IntoIterator<Item = I>) -> Self {
+        Self::with_direction(sources, true)
+    }
+
+    fn with_direction(sources: impl IntoIterator<Item = I>, descending: bool) -> Self {
+        let mut iterator = Self {
+            sources: sources.into_iter().collect(),
+            heap: BinaryHeap::new(),
+            pending_error: None,
+            descending,
+        };
+        for source in 0..iterator.sources.len() {
+            iterator.advance(source);
+        }
+        iterator
+    }
+
+    fn advance(&mut self, source: usize) {
+        match self.sources[source].next() {
+            Some(Ok((key, value))) => self.heap.push(Reverse(Head {
+                key,
+                value,
+                source,
+                descending: self.descending,
+            })),
+            Some(Err(e)) if self.pending_error.is_none() => self.pending_error = Some(e),
+            _ => {}
+        }
+    }
+}
+
+impl<I> Iterator for MergeIterator<I>
+where
+    I: Iterator<Item = TreeResult<(Vec<u8>, DataValue)>>,
+{
+    type Item = TreeResult<(Vec<u8>, DataValue)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.pending_error.take() {
+            return Some(Err(e));
+        }
+
+        let Reverse(head) = self.heap.pop()?;
+        self.advance(head.source);
+
+        while self.heap.peek().is_some_and(|Reverse(next)| next.key == head.key) {
+            if let Some(Reverse(shadowed)) = self.heap.pop() {
+                self.advance(shadowed.source);
+            }
+        }
+
+        Some(Ok((head.key, head.value)))
+    }
+}
+
+/// Iterator over the live entries of a tree in key order.
+///
+/// Created by `Tree::iter`, which yields keys in ascending byte order (the
+/// order of `[u8]`), or by `Tree::iter_rev`, which yields the exact reverse.
+/// The order is deterministic and independent of where entries are stored.
+/// Yields `(key, value)` pairs with values decompressed.
+/// For keys present in several places, the newest version wins; deleted keys,
+/// including keys hidden by range tombstones, and expired keys are skipped.
+/// A value that cannot be decompressed is yielded as an `Err` element, or
+/// logged and skipped when `scan_error_policy` is `ReadErrorPolicy::SkipCorrupt`.
+///
+/// The memory tables are copied when the iterator is created and SSTables are
+/// read entry by entry, so memory use is bounded by the memory tables rather
+/// than by the size of the database. Writes made after creation are not seen.
+pub struct TreeIterator {
+    merge: MergeIterator<Source>,
+    start: Vec<u8>,
+    end: Option<Vec<u8>>,
+    range_tombstones: Vec<RangeTombstone>,
+    compressor: Compressor,
+    error_policy: ReadErrorPolicy,
+}
+
+impl TreeIterator {
+    /// Advances to the next live entry without decompressing its value.
+    pub(crate) fn next_stored(&mut self) -> Option<TreeResult<(Vec<u8>, DataValue)>> {
+        loop {
+            let (key, value) = match self.merge.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if self.end.as_ref().is_some_and(|end| key >= *end) && !self.merge.descending {
+                return None;
+            }
+            if key < self.start
+                || self.end.as_ref().is_some_and(|end| key >= *end)
+                || value.is_tombstone()
+                || value.is_expired()
+                || self
+                    .range_tombstones
+                    .iter()
+                    .any(|range_tombstone| range_tombstone.shadows(&key, &value))
+            {
+                continue;
+            }
+
+            return Some(Ok((key, value)));
+        }
+    }
+}
+
+impl Iterator for TreeIterator {
+    type Item = TreeResult<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, value) = match self.next_stored()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match self.compressor.decompress(&value.data) {
+                Ok(data) => return Some(Ok((key, data))),
+                Err(e) if self.error_policy == ReadErrorPolicy::SkipCorrupt => {
+                    warn!("Skipping entry {:?} that cannot be decompressed: {}", key, e);
+                }
+                Err(e) => return Some(Err(TreeError::compression(format!("Decompression failed: {}", e)))),
+            }
+        }
+    }
+}
+
+/// Iterator decoding the live entries of a tree into a typed value.
+///
+/// Created by `Tree::iter_typed`. Each value is decoded only when the iterator
+/// advances to it; a value that fails to decode yields an `Err` element and
+/// iteration can continue with the next entry.
+pub struct TypedTreeIterator<T> {
+    inner: TreeIterator,
+    encoding: TypedEncoding,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Decode<()>> Iterator for TypedTreeIterator<T> {
+    type Item = TreeResult<(Vec<u8>, T)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.inner.next()?;
+        Some(entry.and_then(|(key, bytes)| Ok((key, self.encoding.decode(&bytes)?))))
+    }
+}
+
+/// Iterator decoding the live entries of a key range into a typed value,
+/// skipping entries that do not decode.
+///
+/// Created by `Tree::iter_range_typed`. Lets a keyspace hold values of
+/// several types: an entry that fails to decode as `T` is counted instead of
+/// yielded, see `skipped` and `finish`. Errors reading or decompressing an
+/// entry are still yielded as `Err` elements.
+pub struct RangeTypedIterator<T> {
+    inner: TreeIterator,
+    encoding: TypedEncoding,
+    skipped: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RangeTypedIterator<T> {
+    /// Returns the number of entries skipped so far because they did not decode as `T`.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Consumes the iterator and returns the number of entries skipped
+    /// because they did not decode as `T`.
+    ///
+    /// Entries not yet visited are not counted; call this once the iterator
+    /// is exhausted for the count over the whole range.
+    pub fn finish(self) -> usize {
+        self.skipped
+    }
+}
+
+impl<T: Decode<()>> Iterator for RangeTypedIterator<T> {
+    type Item = TreeResult<(Vec<u8>, T)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, bytes) = match self.inner.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.encoding.decode(&bytes) {
+                Ok(value) => return Some(Ok((key, value))),
+                Err(_) => self.skipped += 1,
+            }
+        }
+    }
+}
+
+impl Tree {
+    /// Returns an iterator over all live entries in ascending key order.
+    ///
+    /// Keys are compared as byte strings, so the order is the one of `[u8]`
+    /// and does not depend on which memory table or SSTable holds an entry.
+    ///
+    /// # Returns
+    /// A `TreeIterator` yielding `(key, value)` pairs
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be opened. Read errors during
+    /// iteration are yielded as `Err` elements.
+    pub fn iter(&mut self) -> TreeResult<TreeIterator> {
+        self.iter_from(&[])
+    }
+
+    /// Returns an iterator over the live entries with keys in `range`, in
+    /// ascending key order.
+    ///
+    /// Entries are merged from the active memory table, the immutable memory
+    /// tables and every SSTable whose key range overlaps `range`; the newest
+    /// version of a key wins and deleted, range-deleted and expired keys are
+    /// skipped, exactly like `iter`. The scan stops at the first key past the
+    /// end of the range. Any range form works, e.g. `start..end`, `start..=end`,
+    /// `start..` or `..end`.
+    ///
+    /// # Arguments
+    /// * `range` - The keys to visit, compared as byte strings
+    ///
+    /// # Returns
+    /// A `TreeIterator` yielding `(key, value)` pairs
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be opened. Read errors during
+    /// iteration are yielded as `Err` elements.
+    pub fn range<'a, R: RangeBounds<&'a [u8]>>(&mut self, range: R) -> TreeResult<TreeIterator> {
+        // The smallest key greater than `key` is `key` followed by a zero byte
+        let successor = |key: &[u8]| [key, &[0]].concat();
+        let start = match range.start_bound() {
+            Bound::Included(start) => start.to_vec(),
+            Bound::Excluded(start) => successor(start),
+            Bound::Unbounded => Vec::new(),
+        };
+        let mut iter = self.iter_from(&start)?;
+        iter.end = match range.end_bound() {
+            Bound::Included(end) => Some(successor(end)),
+            Bound::Excluded(end) => Some(end.to_vec()),
+            Bound::Unbounded => None,
+        };
+        Ok(iter)
+    }
+
+    /// Returns up to `limit` live entries with keys `>= start` in ascending key order.
+    ///
+    /// The merge stops as soon as `limit` entries are produced, so only the
+    /// beginning of each SSTable's range is read. Deleted, expired and
+    /// range-deleted keys are skipped without using up the limit, which makes
+    /// this suitable for cursor pagination: pass the key after the last
+    /// returned one as the next `start`.
+    ///
+    /// # Arguments
+    /// * `start` - The smallest key to return
+    /// * `limit` - The maximum number of entries to return
+    ///
+    /// # Returns
+    /// The `(key, value)` pairs found, at most `limit` of them
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be read or a value cannot be
+    /// decompressed
+    pub fn scan_limit(&mut self, start: &[u8], limit: usize) -> TreeResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        self.iter_from(start)?.take(limit).collect()
+    }
+
+    /// Returns an iterator over all live entries in descending key order.
+    ///
+    /// Yields exactly the entries of `iter` in reverse order: the newest
+    /// version of a key wins and deleted, range-deleted and expired keys are
+    /// skipped. SSTables are read one index block at a time from the end.
+    ///
+    /// # Returns
+    /// A `TreeIterator` yielding `(key, value)` pairs
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable or its index cannot be read. Read
+    /// errors during iteration are yielded as `Err` elements.
+    pub fn iter_rev(&mut self) -> TreeResult<TreeIterator> {
+        self.iter_rev_to(None)
+    }
+
+    /// Returns up to `limit` live entries with keys `< end` in descending key order.
+    ///
+    /// The reverse counterpart to `scan_limit`, for "latest first" views over
+    /// keys that sort by time. `None` starts at the largest key. For cursor
+    /// pagination, pass the last returned key as the next `end`.
+    ///
+    /// # Arguments
+    /// * `end` - Exclusive upper bound of the returned keys
+    /// * `limit` - The maximum number of entries to return
+    ///
+    /// # Returns
+    /// The `(key, value)` pairs found, at most `limit` of them
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be read or a value cannot be
+    /// decompressed
+    pub fn scan_rev(&mut self, end: Option<&[u8]>, limit: usize) -> TreeResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        self.iter_rev_to(end)?.take(limit).collect()
+    }
+
+    /// Returns an iterator over the live entries with keys `>= start`.
+    pub(crate) fn iter_from(&mut self, start: &[u8]) -> TreeResult<TreeIterator> {
+        let mut sources = Vec::with_capacity(self.ss_tables.len() + self.immutable_mem_tables.len() + 1);
+        for table_path in &self.ss_tables {
+            if self.key_ranges.get(table_path).is_some_and(|range| range.last_key.as_slice() < start) {
+                continue;
+            }
+            let sstable_iter = self.sstable_iter_from(table_path, start).map_err(|e| {
+                TreeError::internal(format!("Failed to open SSTable {:?}: {}", table_path, e))
+            })?;
+            sources.push(Source::SSTable(sstable_iter));
+        }
+        for immutable_mem_table in &self.immutable_mem_tables {
+            let entries: Vec<_> = immutable_mem_table
+                .range::<[u8], _>((Bound::Included(start), Bound::Unbounded))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            sources.push(Source::Memory(entries.into_iter()));
+        }
+        let entries: Vec<_> = self
+            .mem_table
+            .range::<[u8], _>((Bound::Included(start), Bound::Unbounded))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        sources.push(Source::Memory(entries.into_iter()));
+
+        let range_tombstones = self
+            .mem_range_tombstones
+            .iter()
+            .chain(self.range_tombstones.values().flatten())
+            .cloned()
+            .collect();
+
+        Ok(TreeIterator {
+            merge: MergeIterator::new(sources),
+            start: start.to_vec(),
+            end: None,
+            range_tombstones,
+            compressor: self.settings.compressor.clone(),
+            error_policy: self.settings.scan_error_policy,
+        })
+    }
+
+    /// Returns an iterator over the live entries with keys in `[start, end)`.
+    pub(crate) fn iter_range(&mut self, start: &[u8], end: &[u8]) -> TreeResult<TreeIterator> {
+        let mut iter = self.iter_from(start)?;
+        iter.end = Some(end.to_vec());
+        Ok(iter)
+    }
+
+    /// Returns an iterator over the live entries with keys `< end` in
+    /// descending key order.
+    fn iter_rev_to(&mut self, end: Option<&[u8]>) -> TreeResult<TreeIterator> {
+        let upper = match end {
+            Some(end) => Bound::Excluded(end),
+            None => Bound::Unbounded,
+        };
+        let mut sources = Vec::with_capacity(self.ss_tables.len() + self.immutable_mem_tables.len() + 1);
+        for table_path in &self.ss_tables {
+            if let (Some(end), Some(range)) = (end, self.key_ranges.get(table_path)) {
+                if range.first_key.as_slice() >= end {
+                    continue;
+                }
+            }
+            let sstable_iter = self.sstable_iter_rev(table_path, end).map_err(|e| {
+                TreeError::internal(format!("Failed to open SSTable {:?}: {}", table_path, e))
+            })?;
+            sources.push(Source::SSTableRev(sstable_iter));
+        }
+        for mem_table in self.immutable_mem_tables.iter().map(Arc::as_ref).chain([&self.mem_table]) {
+            let entries: Vec<_> = mem_table
+                .range::<[u8], _>((Bound::Unbounded, upper))
+                .rev()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            sources.push(Source::Memory(entries.into_iter()));
+        }
+
+        let range_tombstones = self
+            .mem_range_tombstones
+            .iter()
+            .chain(self.range_tombstones.values().flatten())
+            .cloned()
+            .collect();
+
+        Ok(TreeIterator {
+            merge: MergeIterator::new_rev(sources),
+            start: Vec::new(),
+            end: end.map(<[u8]>::to_vec),
+            range_tombstones,
+            compressor: self.settings.compressor.clone(),
+            error_policy: self.settings.scan_error_policy,
+        })
+    }
+
+    /// Returns an iterator decoding all live entries into `T` in ascending key order.
+    ///
+    /// Values are decoded lazily with the configured `typed_encoding` as the
+    /// iterator advances, so huge typed datasets can be processed with bounded
+    /// memory. This is the streaming counterpart to `multi_get_typed`.
+    ///
+    /// # Type Parameters
+    /// * `T` - The type to deserialize values to, must implement `bincode::Decode`
+    ///
+    /// # Returns
+    /// A `TypedTreeIterator` yielding `(key, T)` pairs, or an `Err` element for
+    /// each value that cannot be read or decoded
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be opened
+    pub fn iter_typed<T: Decode<()>>(&mut self) -> TreeResult<TypedTreeIterator<T>> {
+        Ok(TypedTreeIterator {
+            inner: self.iter()?,
+            encoding: self.settings.typed_encoding,
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns an iterator decoding the live entries with keys in
+    /// `[start, end)` into `T`, in ascending key order.
+    ///
+    /// Unlike `iter_typed`, an entry whose value does not decode as `T` is
+    /// skipped rather than yielded as an error, so typed range queries work
+    /// over a keyspace that mixes value types. The number of skipped entries
+    /// is available from `RangeTypedIterator::skipped` and `finish`. The scan
+    /// stops at the first key `>= end`.
+    ///
+    /// # Arguments
+    /// * `start` - Inclusive lower bound of the keys
+    /// * `end` - Exclusive upper bound of the keys
+    ///
+    /// # Type Parameters
+    /// * `T` - The type to deserialize values to, must implement `bincode::Decode`
+    ///
+    /// # Returns
+    /// A `RangeTypedIterator` yielding the `(key, T)` pairs that decode, or an
+    /// `Err` element for each value that cannot be read or decompressed
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be opened
+    pub fn iter_range_typed<T: Decode<()>>(&mut self, start: &[u8], end: &[u8]) -> TreeResult<RangeTypedIterator<T>> {
+        Ok(RangeTypedIterator {
+            inner: self.iter_range(start, end)?,
+            encoding: self.settings.typed_encoding,
+            skipped: 0,
+            marker: PhantomData,
+        })
+    }
+
+    /// Counts live values by their stored size, for tuning compression.
+    ///
+    /// Each returned `(bound, count)` pair counts the values whose size is at
+    /// most `bound` and greater than the previous bound. `buckets` is sorted
+    /// and deduplicated first, and a final `usize::MAX` bucket collects values
+    /// larger than every bound. The size is the length of the value as stored,
+    /// i.e. after compression, so no value is decompressed.
+    ///
+    /// # Arguments
+    /// * `buckets` - Upper bounds of the buckets in bytes
+    ///
+    /// # Returns
+    /// `(bound, count)` pairs in ascending order of `bound`
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be opened or read
+    pub fn value_size_histogram(&mut self, buckets: &[usize]) -> TreeResult<Vec<(usize, usize)>> {
+        let mut histogram = empty_histogram(buckets);
+        let mut iter = self.iter()?;
+        while let Some(entry) = iter.next_stored() {
+            record_size(&mut histogram, entry?.1.data.len());
+        }
+        Ok(histogram)
+    }
+
+    /// Counts live values by their decompressed size.
+    ///
+    /// Buckets work like in `value_size_histogram`, but every value is
+    /// decompressed to measure it. Values that cannot be decompressed fail the
+    /// call, or are left out under `ReadErrorPolicy::SkipCorrupt`.
+    ///
+    /// # Arguments
+    /// * `buckets` - Upper bounds of the buckets in bytes
+    ///
+    /// # Returns
+    /// `(bound, count)` pairs in ascending order of `bound`
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be read or a value cannot be decompressed
+    pub fn value_size_histogram_decompressed(&mut self, buckets: &[usize]) -> TreeResult<Vec<(usize, usize)>> {
+        let mut histogram = empty_histogram(buckets);
+        for entry in self.iter()? {
+            record_size(&mut histogram, entry?.1.len());
+        }
+        Ok(histogram)
+    }
+}
+
+fn empty_histogram(buckets: &[usize]) -> Vec<(usize, usize)> {
+    let mut bounds = buckets.to_vec();
+    bounds.sort_unstable();
+    bounds.dedup();
+    if bounds.last() != Some(&usize::MAX) {
+        bounds.push(usize::MAX);
+    }
+    bounds.into_iter().map(|bound| (bound, 0)).collect()
+}
+
+fn record_size(histogram: &mut [(usize, usize)], size: usize) {
+    let bucket = histogram.partition_point(|(bound, _)| *bound < size);
+    histogram[bucket].1 += 1;
+}
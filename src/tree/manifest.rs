@@ -0,0 +1,161 @@
+use crate::config::BINCODE_CONFIG;
+use crate::Tree;
+use bincode::{Decode, Encode};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub(crate) const MANIFEST_FILE: &str = "MANIFEST";
+pub(crate) const MANIFEST_TMP_FILE: &str = "MANIFEST.tmp";
+const MANIFEST_MAGIC: &[u8; 4] = b"MNFV";
+const MANIFEST_VERSION: u16 = 2;
+const LEGACY_MANIFEST_MAGIC: &[u8; 4] = b"MNFT";
+const LEGACY_MANIFEST_SIZE: usize = 26; //magic(4)+segment(2)+offset(8)+sstable_number(8)+crc(4)
+
+/// The WAL position a flush has moved into an SSTable, kept in the `MANIFEST` file.
+///
+/// It is written before the SSTable, so it only counts once the manifest
+/// records a flush of SSTable `sstable_number` or a later one as installed, in
+/// `last_flushed`. From then on every WAL entry
+/// before `offset` in `segment`, and every entry of an older segment, is
+/// durable and skipped by recovery even if the checkpoint that should follow
+/// the flush never made it to the WAL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub(crate) struct WalBoundary {
+    pub(crate) segment: u16,
+    pub(crate) offset: u64,
+    pub(crate) sstable_number: u64,
+}
+
+/// The contents of the `MANIFEST` file.
+///
+/// Version 2 lists the live SSTables, oldest first, so loading can tell them
+/// from partially written or orphaned `sstable_*.sst` files. Merged tables
+/// take new numbers, so the list, not the numbers, gives the age order. It
+/// also holds the WAL segment that was being written when the list last
+/// changed and the number of the last SSTable a flush installed. Merges are
+/// size-tiered, so every table is on level 0 and no level is stored. Version 1
+/// only held the WAL boundary; `sstables` is `None` when reading one, and the
+/// live set is then found by listing the directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub(crate) struct Manifest {
+    pub(crate) wal_boundary: Option<WalBoundary>,
+    pub(crate) wal_segment: Option<u16>,
+    pub(crate) sstables: Option<Vec<String>>,
+    pub(crate) last_flushed: Option<u64>,
+}
+
+impl Manifest {
+    /// Reads the manifest in `db_path`, if there is one.
+    ///
+    /// # Errors
+    /// Returns an `InvalidData` error if the file is damaged or written by a
+    /// newer version
+    pub(crate) fn read(db_path: &Path) -> std::io::Result<Option<Self>> {
+        let mut buffer = Vec::new();
+        match File::open(db_path.join(MANIFEST_FILE)) {
+            Ok(mut file) => file.read_to_end(&mut buffer)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let damaged = || std::io::Error::new(std::io::ErrorKind::InvalidData, "MANIFEST is damaged");
+        if buffer.len() < 10 {
+            return Err(damaged());
+        }
+        let (body, crc) = buffer.split_at(buffer.len() - 4);
+        if crc32fast::hash(body).to_le_bytes() != crc {
+            return Err(damaged());
+        }
+
+        if body.starts_with(LEGACY_MANIFEST_MAGIC) && buffer.len() == LEGACY_MANIFEST_SIZE {
+            return Ok(Some(Self {
+                wal_boundary: Some(WalBoundary {
+                    segment: u16::from_le_bytes(body[4..6].try_into().unwrap()),
+                    offset: u64::from_le_bytes(body[6..14].try_into().unwrap()),
+                    sstable_number: u64::from_le_bytes(body[14..22].try_into().unwrap()),
+                }),
+                wal_segment: None,
+                sstables: None,
+                last_flushed: None,
+            }));
+        }
+        if !body.starts_with(MANIFEST_MAGIC) {
+            return Err(damaged());
+        }
+        let version = u16::from_le_bytes(body[4..6].try_into().unwrap());
+        if version != MANIFEST_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("MANIFEST has unsupported version {}", version),
+            ));
+        }
+        bincode::decode_from_slice(&body[6..], BINCODE_CONFIG)
+            .map(|(manifest, _)| Some(manifest))
+            .map_err(|_| damaged())
+    }
+
+    /// Replaces the manifest. The file is written next to the manifest and
+    /// renamed over it, so a crash leaves either version intact. The caller
+    /// syncs the directory to make the rename durable.
+    pub(crate) fn write(&self, db_path: &Path) -> std::io::Result<()> {
+        let mut body = MANIFEST_MAGIC.to_vec();
+        body.extend_from_slice(&MANIFEST_VERSION.to_le_bytes());
+        bincode::encode_into_std_write(self, &mut body, BINCODE_CONFIG)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let crc = crc32fast::hash(&body);
+        body.extend_from_slice(&crc.to_le_bytes());
+
+        let tmp_path = db_path.join(MANIFEST_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&body)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, db_path.join(MANIFEST_FILE))
+    }
+}
+
+impl Tree {
+    /// Rewrites the manifest with the live SSTables and the current WAL
+    /// segment, keeping its WAL boundary.
+    ///
+    /// Called whenever the set of SSTables changes, before files it no longer
+    /// lists are deleted and before the WAL entries they hold are dropped.
+    pub(crate) fn write_manifest(&self) -> std::io::Result<()> {
+        self.update_manifest(|_| {})
+    }
+
+    /// Rewrites the manifest like `write_manifest`, letting `update` change
+    /// the manifest read from disk before the live SSTables and the WAL
+    /// segment are filled in. A damaged manifest loses its boundary.
+    pub(crate) fn update_manifest(&self, update: impl FnOnce(&mut Manifest)) -> std::io::Result<()> {
+        let db_path = &self.settings.db_path;
+        let mut manifest = Manifest::read(db_path).ok().flatten().unwrap_or_default();
+        update(&mut manifest);
+        manifest.sstables = Some(
+            self.ss_tables
+                .iter()
+                .filter_map(|path| path.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect(),
+        );
+        manifest.wal_segment = self.wal_writer.as_ref().map(|_| self.get_last_wal_segment_number());
+        manifest.write(db_path)?;
+        self.sync_db_directory()
+    }
+
+    /// Rewrites the manifest like `write_manifest`, recording SSTable `path`
+    /// as the last one a flush installed.
+    pub(crate) fn record_flushed_sstable(&self, path: &Path) -> std::io::Result<()> {
+        let number = Self::sstable_number(path);
+        self.update_manifest(|manifest| manifest.last_flushed = number.max(manifest.last_flushed))
+    }
+
+    /// Drops the WAL boundary from the manifest, if it has one.
+    pub(crate) fn clear_wal_boundary(&self) -> std::io::Result<()> {
+        match Manifest::read(&self.settings.db_path) {
+            Ok(None) => Ok(()),
+            Ok(Some(manifest)) if manifest.wal_boundary.is_none() => Ok(()),
+            _ => self.update_manifest(|manifest| manifest.wal_boundary = None),
+        }
+    }
+}
@@ -1,12 +1,25 @@
 pub mod cache;
+mod cache_snapshot;
+mod compaction;
 mod compression;
 pub mod data_value;
+pub mod encoding;
+pub mod iterator;
+pub mod events;
+mod file_pool;
+mod flush;
+mod manifest;
 pub mod settings;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+mod range_tombstone;
+mod rate_limiter;
 mod sstable;
+#[allow(clippy::module_inception, clippy::unnecessary_to_owned, clippy::useless_vec)]
 mod test;
 mod transaction;
 mod transaction_manager;
-mod tree_error;
+pub(crate) mod tree_error;
 mod wal;
 mod wal_reader;
 mod wal_writer;
@@ -14,23 +27,38 @@ mod wal_writer;
 pub use cache::*;
 pub use compression::*;
 pub use data_value::*;
+pub use encoding::*;
+pub use iterator::*;
+pub use events::*;
 pub use settings::*;
-
-use crate::config::DEFAULT_DB_PATH;
+pub use rate_limiter::RateLimiter;
+pub use compaction::{CompactionStats, LevelInfo};
+pub use transaction::{IsolationLevel, TransactionStats, TxOptions};
+
+use crate::config::{CURRENT_VERSION, DEFAULT_DB_PATH, MIN_SUPPORTED_VERSION};
+use crate::tree::compaction::CompactionWorker;
+use crate::tree::file_pool::FilePool;
+use crate::tree::flush::FlushWorker;
+use crate::tree::manifest::{Manifest, MANIFEST_FILE, MANIFEST_TMP_FILE};
+use crate::tree::range_tombstone::RangeTombstone;
+use crate::tree::sstable::KeyRange;
 use crate::tree::transaction_manager::TransactionManager;
 use crate::tree::tree_error::{TreeError, TreeResult};
-use crate::tree::wal::WalOperation;
+use crate::tree::wal::{encode_rename_keys, WalOperation};
 use crate::tree::wal_writer::WalWriter;
-use crate::{logger, util};
+use crate::{inspect, logger, util};
 use bincode::Encode;
 use growable_bloom_filter::GrowableBloom;
-use log::{error, warn};
+use log::{error, info, warn};
 use once_cell::sync::Lazy;
-use std::collections::{BTreeMap, VecDeque};
-use std::path::PathBuf;
-use std::sync::{mpsc, Arc, Mutex};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 static INIT: Lazy<()> = Lazy::new(|| {
     logger::init_logger().expect("Logger was not initialized!");
@@ -43,16 +71,41 @@ pub struct BloomFilter {
 
 pub struct Tree {
     mem_table: BTreeMap<Vec<u8>, DataValue>,
-    immutable_mem_tables: VecDeque<BTreeMap<Vec<u8>, DataValue>>,
+    mem_table_bytes: usize,
+    immutable_mem_tables: VecDeque<Arc<BTreeMap<Vec<u8>, DataValue>>>,
     ss_tables: Vec<PathBuf>,
-    bloom_filters: Vec<BloomFilter>,
+    legacy_sstables: HashMap<PathBuf, u32>,
+    key_ranges: HashMap<PathBuf, KeyRange>,
+    range_tombstones: HashMap<PathBuf, Vec<RangeTombstone>>,
+    mem_range_tombstones: Vec<RangeTombstone>,
+    bloom_filters: RwLock<Vec<BloomFilter>>,
     settings: TreeSettings,
-    index_cache: LRUIndexCache,
-    value_cache: LRUValueCache,
+    index_cache: Mutex<LRUIndexCache>,
+    value_cache: Mutex<LRUValueCache>,
+    file_pool: FilePool,
     wal_writer: Option<WalWriter>,
     wal_segments: Vec<u16>,
     cleanup_sender: Option<mpsc::Sender<u16>>,
+    flush_timer_shutdown: Option<mpsc::Sender<()>>,
+    flush_due: Arc<AtomicBool>,
+    cancel_requested: Arc<AtomicBool>,
+    mem_table_since: Option<Instant>,
+    compaction_worker: Option<CompactionWorker>,
+    compaction_in_flight: bool,
+    queued_compactions: usize,
+    compaction_stats: CompactionStats,
+    flush_worker: Option<FlushWorker>,
+    flush_in_flight: bool,
+    next_unreserved_sstable: usize,
     tx_manager: Arc<Mutex<TransactionManager>>,
+    #[cfg(test)]
+    sstable_write_limit: Option<u64>,
+    #[cfg(test)]
+    stop_after_merge_rename: bool,
+    #[cfg(test)]
+    fail_sstable_rename: bool,
+    #[cfg(test)]
+    fail_directory_sync: bool,
 }
 
 impl Drop for Tree {
@@ -60,6 +113,9 @@ impl Drop for Tree {
         if let Err(e) = self.flush() {
             error!("Error during flush on drop: {}", e);
         }
+        self.flush_timer_shutdown = None;
+        self.flush_worker = None;
+        self.compaction_worker = None;
         self.wal_writer = None;
     }
 }
@@ -84,19 +140,9 @@ impl Tree {
             Self::wal_background_cleanup_worker(cleanup_receiver, db_path);
         });
 
-        let mut tree = Self {
-            mem_table: BTreeMap::new(),
-            immutable_mem_tables: VecDeque::new(),
-            ss_tables: Vec::new(),
-            bloom_filters: Vec::new(),
-            settings: TreeSettings::default(),
-            index_cache: LRUIndexCache::default(),
-            value_cache: LRUValueCache::default(),
-            wal_writer: None,
-            wal_segments: Vec::new(),
-            cleanup_sender: Some(cleanup_sender),
-            tx_manager: Arc::new(Mutex::new(TransactionManager::new())),
-        };
+        let mut tree = Self::detached(TreeSettings::default(), FilePool::default());
+        tree.cleanup_sender = Some(cleanup_sender);
+        tree.start_compaction_worker();
 
         if tree.settings.enable_wal {
             if let Err(e) = tree.init_wal() {
@@ -107,6 +153,51 @@ impl Tree {
         Ok(tree)
     }
 
+    /// Creates a tree with no tables, WAL or background threads.
+    ///
+    /// Serves as the base of the public constructors and as the compaction
+    /// worker's handle for reading and writing SSTable files.
+    fn detached(settings: TreeSettings, file_pool: FilePool) -> Self {
+        Self {
+            mem_table: BTreeMap::new(),
+            mem_table_bytes: 0,
+            immutable_mem_tables: VecDeque::new(),
+            ss_tables: Vec::new(),
+            legacy_sstables: HashMap::new(),
+            key_ranges: HashMap::new(),
+            range_tombstones: HashMap::new(),
+            mem_range_tombstones: Vec::new(),
+            bloom_filters: RwLock::new(Vec::new()),
+            settings,
+            index_cache: Mutex::new(LRUIndexCache::default()),
+            value_cache: Mutex::new(LRUValueCache::default()),
+            file_pool,
+            wal_writer: None,
+            wal_segments: Vec::new(),
+            cleanup_sender: None,
+            flush_timer_shutdown: None,
+            flush_due: Arc::new(AtomicBool::new(false)),
+            cancel_requested: Arc::new(AtomicBool::new(false)),
+            mem_table_since: None,
+            compaction_worker: None,
+            compaction_in_flight: false,
+            queued_compactions: 0,
+            compaction_stats: CompactionStats::default(),
+            flush_worker: None,
+            flush_in_flight: false,
+            next_unreserved_sstable: 0,
+            tx_manager: Arc::new(Mutex::new(TransactionManager::new())),
+            #[cfg(test)]
+            sstable_write_limit: None,
+            #[cfg(test)]
+            stop_after_merge_rename: false,
+            #[cfg(test)]
+            fail_sstable_rename: false,
+            #[cfg(test)]
+            fail_directory_sync: false,
+        }
+    }
+
     /// Creates a new Tree with a specific database path.
     ///
     /// # Arguments
@@ -146,8 +237,13 @@ impl Tree {
     /// A new Tree instance with the specified settings
     ///
     /// # Errors
-    /// Returns `TreeError` if initialization fails
+    /// Returns `TreeError::Configuration` if `check_writable` is enabled and
+    /// the database directory is not writable, or `TreeError` if
+    /// initialization fails
     pub fn new_with_settings(settings: TreeSettings) -> TreeResult<Self> {
+        if settings.check_writable {
+            settings.validate_path_writable()?;
+        }
         let mut tree = Self::new()?;
 
         if let Some(sender) = tree.cleanup_sender.take() {
@@ -159,15 +255,22 @@ impl Tree {
             Self::wal_background_cleanup_worker(cleanup_receiver, db_path);
         });
         tree.settings = settings;
-        tree.index_cache = LRUIndexCache::new(
+        *tree.index_cache() = LRUIndexCache::new(
             tree.settings.index_cache_max_capacity,
             tree.settings.index_cache_memory_limit,
         );
-        tree.value_cache = LRUValueCache::new(
+        *tree.value_cache() = LRUValueCache::new(
             tree.settings.value_cache_max_capacity,
             tree.settings.value_cache_memory_limit,
         );
+        tree.value_cache().set_max_cacheable_value_bytes(tree.settings.max_cacheable_value_bytes);
+        tree.index_cache().set_eviction_watermark(tree.settings.cache_eviction_watermark);
+        tree.value_cache().set_eviction_watermark(tree.settings.cache_eviction_watermark);
+        tree.file_pool = FilePool::new(tree.settings.max_open_files, tree.settings.read_buffer_size);
         tree.cleanup_sender = Some(cleanup_sender);
+        tree.start_flush_timer();
+        tree.start_flush_worker();
+        tree.start_compaction_worker();
 
         Ok(tree)
     }
@@ -187,7 +290,7 @@ impl Tree {
     /// - Hit rate: Percentage of successful cache hits
     /// - Memory utilization: Current memory usage percentage
     pub fn get_index_cache_stats(&self) -> CacheStats {
-        self.index_cache.stats()
+        self.index_cache().stats()
     }
 
     /// Retrieves statistics for the value cache.
@@ -199,7 +302,67 @@ impl Tree {
     /// # Returns
     /// A `CacheStats` struct containing cache performance metrics
     pub fn get_value_cache_stats(&self) -> CacheStats {
-        self.value_cache.stats()
+        self.value_cache().stats()
+    }
+
+    // Reads through `&self` update the caches, so each cache sits behind its
+    // own lock and concurrent readers only contend on the one they touch. A
+    // panic while a lock was held leaves at worst a stale LRU order or a
+    // missing entry, so a poisoned lock is recovered rather than propagated.
+
+    pub(crate) fn index_cache(&self) -> MutexGuard<'_, LRUIndexCache> {
+        self.index_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn value_cache(&self) -> MutexGuard<'_, LRUValueCache> {
+        self.value_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn bloom_filters(&self) -> RwLockReadGuard<'_, Vec<BloomFilter>> {
+        self.bloom_filters.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn bloom_filters_mut(&self) -> RwLockWriteGuard<'_, Vec<BloomFilter>> {
+        self.bloom_filters.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns a 0.0 to 1.0 signal of memory pressure, for callers that throttle writes.
+    ///
+    /// The value is a weighted sum of three ratios, each between 0.0 and 1.0:
+    ///
+    /// `0.2 * cache + 0.4 * mem_table + 0.4 * backlog`
+    ///
+    /// - `cache`: bytes held by the index and value caches over their combined
+    ///   memory limits, from `CacheStats::memory_utilization`. Warm caches sit
+    ///   near their limit by design, so this term carries the least weight
+    /// - `mem_table`: entries in the active memory table over `mem_table_max_size`,
+    ///   or its bytes over `mem_table_max_bytes` when that is set and fuller
+    /// - `backlog`: `n / (n + 1)` for `n` immutable memory tables waiting to be
+    ///   written to SSTables, so one queued table counts 0.5 and the term
+    ///   approaches 1.0 as more pile up
+    ///
+    /// Only in-memory counters are read; no file I/O is done, so this is cheap
+    /// enough to poll before every write.
+    pub fn memory_pressure(&self) -> f64 {
+        let (used, limit) = [self.index_cache().stats(), self.value_cache().stats()]
+            .iter()
+            .filter(|stats| stats.memory_limit > 0)
+            .fold((0.0, 0.0), |(used, limit), stats| {
+                (
+                    used + stats.memory_utilization * stats.memory_limit as f64,
+                    limit + stats.memory_limit as f64,
+                )
+            });
+        let cache = if limit > 0.0 { used / limit } else { 0.0 };
+        let mem_table_bytes = match self.settings.mem_table_max_bytes {
+            Some(max_bytes) => self.mem_table_bytes as f64 / max_bytes.max(1) as f64,
+            None => 0.0,
+        };
+        let mem_table = (self.mem_table.len() as f64 / self.settings.mem_table_max_size.max(1) as f64).max(mem_table_bytes);
+        let queued = self.immutable_mem_tables.len() as f64;
+        let backlog = queued / (queued + 1.0);
+
+        (0.2 * cache.min(1.0) + 0.4 * mem_table.min(1.0) + 0.4 * backlog).clamp(0.0, 1.0)
     }
 
     /// Clears all entries from the index cache.
@@ -208,7 +371,7 @@ impl Tree {
     /// subsequent reads to reload index data from disk. This can be useful
     /// for freeing memory or ensuring fresh index data is loaded.
     pub fn clear_index_cache(&mut self) {
-        self.index_cache.clear();
+        self.index_cache().clear();
     }
 
     /// Clears all entries from the value cache.
@@ -217,7 +380,18 @@ impl Tree {
     /// subsequent reads to reload data from disk or memory tables. This
     /// can help free memory or ensure fresh data is read.
     pub fn clear_value_cache(&mut self) {
-        self.value_cache.clear();
+        self.value_cache().clear();
+    }
+
+    /// Changes which values read from SSTables are admitted into the value cache.
+    ///
+    /// Useful around large scans: switching to `ValueCacheAdmission::Never` for
+    /// the duration of a scan keeps it from evicting hot entries.
+    ///
+    /// # Arguments
+    /// * `admission` - The new admission policy
+    pub fn set_value_cache_admission(&mut self, admission: ValueCacheAdmission) {
+        self.settings.value_cache_admission = admission;
     }
 
     fn apply_compression(&mut self, data: Vec<u8>) -> TreeResult<Vec<u8>> {
@@ -260,6 +434,9 @@ impl Tree {
 
     /// Creates and loads a Tree from a specific database path.
     ///
+    /// The path names the database directory itself, holding the SSTables,
+    /// WAL segments and `MANIFEST`; it is created if it does not exist.
+    ///
     /// # Arguments
     /// * `path` - The database directory path to load from
     ///
@@ -267,7 +444,8 @@ impl Tree {
     /// A new Tree instance loaded with existing data from the specified path
     ///
     /// # Errors
-    /// Returns `TreeError` if loading fails
+    /// Returns `TreeError::Configuration` if `path` is an existing file, or
+    /// `TreeError` if loading fails
     pub fn load_with_path(path: &str) -> TreeResult<Self> {
         let mut tree = Self::new()?;
         tree.settings.db_path = PathBuf::from(path);
@@ -275,6 +453,52 @@ impl Tree {
         Ok(tree)
     }
 
+    /// Loads an existing database from a specific path without ever creating one.
+    ///
+    /// `load_with_path` creates a missing directory, so a mistyped path opens a
+    /// new, empty database. This method instead requires `path` to be a
+    /// directory holding at least one SSTable, WAL segment or `MANIFEST`.
+    ///
+    /// # Arguments
+    /// * `path` - The database directory path to load from
+    ///
+    /// # Returns
+    /// A new Tree instance loaded with existing data from the specified path
+    ///
+    /// # Errors
+    /// Returns `TreeError::Configuration` if the directory does not exist, is a
+    /// file or contains no database files, or `TreeError` if loading fails
+    pub fn open_existing(path: &str) -> TreeResult<Self> {
+        let db_path = Path::new(path);
+        if db_path.is_file() {
+            return Err(TreeError::configuration(format!(
+                "Database path {:?} is a file; the database path must be a directory",
+                db_path
+            )));
+        }
+        if !db_path.is_dir() {
+            return Err(TreeError::configuration(format!(
+                "Database directory {:?} does not exist",
+                db_path
+            )));
+        }
+
+        let entries = std::fs::read_dir(db_path).map_err(|e| {
+            TreeError::configuration(format!("Error reading database directory {:?}: {}", db_path, e))
+        })?;
+        let has_database_files = entries.flatten().any(|entry| {
+            entry.path().is_file() && Self::is_database_file(&entry.file_name().to_string_lossy())
+        });
+        if !has_database_files {
+            return Err(TreeError::configuration(format!(
+                "Database directory {:?} contains no database files",
+                db_path
+            )));
+        }
+
+        Self::load_with_path(path)
+    }
+
     /// Creates and loads a Tree with custom settings.
     ///
     /// # Arguments
@@ -284,28 +508,217 @@ impl Tree {
     /// A new Tree instance loaded with existing data using the specified settings
     ///
     /// # Errors
-    /// Returns `TreeError` if loading fails
+    /// Returns `TreeError::Configuration` if `check_writable` is enabled and
+    /// the database directory is not writable, or `TreeError` if loading fails
     pub fn load_with_settings(settings: TreeSettings) -> TreeResult<Self> {
+        if settings.check_writable {
+            settings.validate_path_writable()?;
+        }
         let mut tree = Self::new()?;
         tree.settings = settings;
-        tree.index_cache = LRUIndexCache::new(
+        *tree.index_cache() = LRUIndexCache::new(
             tree.settings.index_cache_max_capacity,
             tree.settings.index_cache_memory_limit,
         );
-        tree.value_cache = LRUValueCache::new(
+        *tree.value_cache() = LRUValueCache::new(
             tree.settings.value_cache_max_capacity,
             tree.settings.value_cache_memory_limit,
         );
+        tree.value_cache().set_max_cacheable_value_bytes(tree.settings.max_cacheable_value_bytes);
+        tree.index_cache().set_eviction_watermark(tree.settings.cache_eviction_watermark);
+        tree.value_cache().set_eviction_watermark(tree.settings.cache_eviction_watermark);
+        tree.file_pool = FilePool::new(tree.settings.max_open_files, tree.settings.read_buffer_size);
+        tree.start_flush_worker();
+        tree.start_compaction_worker();
         tree.load_tree()?;
+        tree.start_flush_timer();
         Ok(tree)
     }
 
+    fn sstable_number(path: &Path) -> Option<u64> {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| {
+                name.strip_prefix("sstable_")?
+                    .strip_suffix(".sst")?
+                    .parse::<u64>()
+                    .ok()
+            })
+    }
+
+    /// Picks which of two valid SSTables claiming the same number is kept.
+    ///
+    /// The table with more index entries wins, as a crash during merge or rename
+    /// leaves the complete table next to a partial or stale one. Ties go to the
+    /// lexicographically smaller file name so the choice is deterministic.
+    ///
+    /// # Returns
+    /// The kept table and the path of the table to quarantine
+    fn resolve_duplicate_sstable(
+        &self,
+        first: (PathBuf, u32),
+        second: (PathBuf, u32),
+    ) -> ((PathBuf, u32), PathBuf) {
+        let entry_count = |path: &Path| self.read_index_entry_count(path).unwrap_or(0);
+        let first_count = entry_count(&first.0);
+        let second_count = entry_count(&second.0);
+
+        let keep_second = second_count > first_count
+            || (second_count == first_count && second.0.file_name() < first.0.file_name());
+        if keep_second {
+            (second, first.0)
+        } else {
+            (first, second.0)
+        }
+    }
+
+    fn quarantine_sstable(&self, path: &Path) {
+        let quarantine_path = path.with_extension("sst.dup");
+        self.file_pool.invalidate(path);
+        match std::fs::rename(path, &quarantine_path).and_then(|()| self.sync_db_directory()) {
+            Ok(()) => warn!(
+                "SSTable {:?} duplicates the number of another table, moved to {:?}",
+                path, quarantine_path
+            ),
+            Err(e) => error!("Error quarantining duplicate SSTable {:?}: {}", path, e),
+        }
+    }
+
+    /// Syncs the database directory so that files created or renamed in it
+    /// survive a crash. Does nothing when `sync_directory` is disabled.
+    pub(crate) fn sync_db_directory(&self) -> std::io::Result<()> {
+        #[cfg(test)]
+        if self.fail_directory_sync {
+            return Err(std::io::Error::other("injected directory sync failure"));
+        }
+        if self.settings.sync_directory {
+            std::fs::File::open(&self.settings.db_path)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the SSTable files the manifest lists as live, in `sstable_files`,
+    /// and puts them in the manifest's order, oldest first.
+    ///
+    /// Files the manifest does not list are left out. The ones numbered above
+    /// every listed table are flushes or merges that crashed before the
+    /// manifest was updated, whose entries are still in the WAL or in their
+    /// inputs, and are deleted; others are kept on disk for inspection.
+    /// Without a manifest listing the live tables, every file is kept in
+    /// number order, as in databases written before it did.
+    ///
+    /// # Returns
+    /// `true` if the manifest has to be rewritten to match the loaded tables
+    fn select_live_sstables(&self, sstable_files: &mut Vec<PathBuf>) -> bool {
+        let listed = match Manifest::read(&self.settings.db_path) {
+            Ok(manifest) => manifest.and_then(|manifest| manifest.sstables),
+            Err(e) => {
+                warn!("Ignoring unreadable MANIFEST, loading every SSTable file: {}", e);
+                None
+            }
+        };
+        let Some(listed) = listed else {
+            return !sstable_files.is_empty();
+        };
+
+        let mut rewrite = false;
+        let listed: HashMap<&str, usize> =
+            listed.iter().enumerate().map(|(position, name)| (name.as_str(), position)).collect();
+        let position = |path: &Path| {
+            let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            listed.get(name.as_ref()).copied()
+        };
+        let last_listed = listed
+            .keys()
+            .filter_map(|name| Self::sstable_number(Path::new(name)))
+            .max();
+        sstable_files.retain(|path| {
+            if position(path).is_some() {
+                return true;
+            }
+            let unfinished = match (Self::sstable_number(path), last_listed) {
+                (Some(number), Some(last_listed)) => number > last_listed,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if !unfinished {
+                warn!("SSTable {:?} is not listed in the MANIFEST, ignoring it", path);
+                return false;
+            }
+            match std::fs::remove_file(path) {
+                Ok(()) => warn!("Removed SSTable {:?} left behind by an unfinished flush or merge", path),
+                Err(e) => error!("Error removing unfinished SSTable {:?}: {}", path, e),
+            }
+            false
+        });
+        sstable_files.sort_by_key(|path| position(path));
+        for name in listed.keys() {
+            if !sstable_files.iter().any(|path| path.file_name().is_some_and(|file_name| file_name == *name)) {
+                warn!("SSTable {} is listed in the MANIFEST but missing", name);
+                rewrite = true;
+            }
+        }
+        rewrite
+    }
+
+    fn is_database_file(file_name: &str) -> bool {
+        let is_sstable = |name: &str| inspect::sstable_number(name).is_some();
+        let is_wal_segment = inspect::wal_segment_number(file_name).is_some();
+
+        is_wal_segment
+            || file_name == MANIFEST_FILE
+            || file_name == MANIFEST_TMP_FILE
+            || is_sstable(file_name)
+            || file_name.strip_suffix(".tmp").is_some_and(is_sstable)
+            || file_name.strip_suffix(".dup").is_some_and(is_sstable)
+    }
+
+    fn check_directory_contents(&self, db_path: &Path) -> TreeResult<()> {
+        let entries = std::fs::read_dir(db_path).map_err(|e| TreeError::IoExtended {
+            message: format!("Error reading database folder: {}", e),
+        })?;
+
+        let mut unknown_files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+            if let Some(file_name) = file_name {
+                if !Self::is_database_file(&file_name)
+                    && !self.settings.strict_directory_ignore.contains(&file_name)
+                {
+                    unknown_files.push(file_name);
+                }
+            }
+        }
+
+        if unknown_files.is_empty() {
+            Ok(())
+        } else {
+            unknown_files.sort();
+            Err(TreeError::configuration(format!(
+                "Database directory {:?} contains unknown files: {}",
+                db_path,
+                unknown_files.join(", ")
+            )))
+        }
+    }
+
     fn load_tree(&mut self) -> TreeResult<()> {
+        self.wait_for_compactions()?;
         let db_path: PathBuf = if self.settings.db_path.as_os_str().is_empty() {
             PathBuf::from(DEFAULT_DB_PATH)
         } else {
             self.settings.db_path.clone()
         };
+        if db_path.exists() && !db_path.is_dir() {
+            return Err(TreeError::configuration(format!(
+                "Database path {:?} is a file; the database path must be a directory",
+                db_path
+            )));
+        }
         if !db_path.exists() {
             std::fs::create_dir_all(&db_path).map_err(|e| {
                 TreeError::configuration(format!("Error creating database directory: {}", e))
@@ -313,13 +726,18 @@ impl Tree {
         }
 
         self.settings.db_path = db_path.clone();
+        if self.settings.strict_directory {
+            self.check_directory_contents(&db_path)?;
+        }
         self.mem_table.clear();
+        self.mem_table_bytes = 0;
         self.immutable_mem_tables.clear();
         self.ss_tables.clear();
-
-        if self.settings.enable_wal {
-            self.recover_from_wal()?;
-        }
+        self.legacy_sstables.clear();
+        self.key_ranges.clear();
+        self.range_tombstones.clear();
+        self.mem_range_tombstones.clear();
+        self.file_pool.clear();
 
         let entries = std::fs::read_dir(&db_path).map_err(|e| TreeError::IoExtended {
             message: format!("Error reading database folder: {}", e),
@@ -330,7 +748,7 @@ impl Tree {
         for entry in entries {
             let entry = entry?;
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "sst") {
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "sst") {
                 if let Some(filename) = path.file_name() {
                     if filename.to_string_lossy().starts_with("sstable_") {
                         sstable_files.push(path);
@@ -339,23 +757,85 @@ impl Tree {
             }
         }
 
-        sstable_files.sort_by_cached_key(|path| {
-            path.file_name()
-                .and_then(|name| name.to_str())
-                .and_then(|name| {
-                    name.strip_prefix("sstable_")?
-                        .strip_suffix(".sst")?
-                        .parse::<u64>()
-                        .ok()
-                })
-                .unwrap_or(0)
-        });
+        sstable_files.sort_by_cached_key(|path| Self::sstable_number(path).unwrap_or(0));
+        let rewrite_manifest = self.select_live_sstables(&mut sstable_files);
 
+        let mut loaded: Vec<(u64, PathBuf, u32)> = Vec::with_capacity(sstable_files.len());
         for sstable_path in sstable_files {
-            if self.validate_sstable(&sstable_path) {
-                self.ss_tables.push(sstable_path);
-            } else {
+            let version = match self.read_sstable_version(&sstable_path) {
+                Ok(version) => version,
+                Err(e) => {
+                    warn!("Damaged SSTable file {:?}: {}", sstable_path, e);
+                    continue;
+                }
+            };
+
+            if !(MIN_SUPPORTED_VERSION..=CURRENT_VERSION).contains(&version) {
+                return Err(TreeError::incompatible_version(format!(
+                    "SSTable {:?} has format version {}, supported versions are {}..={}",
+                    sstable_path, version, MIN_SUPPORTED_VERSION, CURRENT_VERSION
+                )));
+            }
+
+            if !self.validate_sstable(&sstable_path) {
                 warn!("Damaged SSTable file: {:?}", sstable_path);
+                continue;
+            }
+            self.check_compression_marker(&sstable_path, version)?;
+
+            let number = Self::sstable_number(&sstable_path).unwrap_or(0);
+            match loaded.last() {
+                Some((last_number, _, _)) if *last_number == number => {
+                    let (_, last_path, last_version) = loaded.pop().unwrap();
+                    let (kept, quarantined) =
+                        self.resolve_duplicate_sstable((last_path, last_version), (sstable_path, version));
+                    self.quarantine_sstable(&quarantined);
+                    loaded.push((number, kept.0, kept.1));
+                }
+                _ => loaded.push((number, sstable_path, version)),
+            }
+        }
+
+        for (_, sstable_path, version) in loaded {
+            if version < CURRENT_VERSION {
+                info!(
+                    "SSTable {:?} uses format version {}, call Tree::migrate to upgrade it",
+                    sstable_path, version
+                );
+                self.legacy_sstables.insert(sstable_path.clone(), version);
+            }
+            let range_tombstones = self.read_range_tombstones(&sstable_path).map_err(|e| {
+                TreeError::internal(format!(
+                    "Failed to read range tombstones of SSTable {:?}: {}",
+                    sstable_path, e
+                ))
+            })?;
+            if !range_tombstones.is_empty() {
+                self.range_tombstones.insert(sstable_path.clone(), range_tombstones);
+            }
+            // Reads skip tables whose key range cannot hold the key they look for.
+            self.sstable_key_range(&sstable_path);
+            self.ss_tables.push(sstable_path);
+        }
+        if rewrite_manifest {
+            // A manifest that did not list the tables did not record flushes
+            // either; the tables were numbered in age order then, so the newest
+            // of them is the last one flushed.
+            let last_loaded = self.ss_tables.iter().filter_map(|path| Self::sstable_number(path)).max();
+            self.update_manifest(|manifest| {
+                if manifest.sstables.is_none() {
+                    manifest.last_flushed = last_loaded;
+                }
+            })
+            .map_err(|e| TreeError::io(format!("Failed to write MANIFEST: {}", e)))?;
+        }
+
+        // Recovery runs after loading the SSTables, which decide how much of
+        // the WAL is already durable.
+        if self.settings.enable_wal {
+            self.recover_from_wal()?;
+            if !self.mem_table.is_empty() {
+                self.mem_table_since = Some(Instant::now());
             }
         }
 
@@ -377,7 +857,7 @@ impl Tree {
     where
         T: Encode,
     {
-        self.put_typed_with_ttl_optional::<T>(key, value, None)
+        self.put_typed_opt_ttl::<T>(key, value, None)
     }
 
     /// Stores a typed value in the tree with a TTL.
@@ -395,10 +875,22 @@ impl Tree {
     where
         T: Encode,
     {
-        self.put_typed_with_ttl_optional::<T>(key, value, Some(ttl))
+        self.put_typed_opt_ttl::<T>(key, value, Some(ttl))
     }
 
-    fn put_typed_with_ttl_optional<T>(
+    /// Stores a typed value in the tree with an optional TTL.
+    ///
+    /// Like `put_with_ttl` for raw bytes, this suits callers that hold an
+    /// `Option<Duration>` at runtime, such as a TTL read from configuration.
+    ///
+    /// # Arguments
+    /// * `key` - The string key to store the value under
+    /// * `value` - The value to store (must implement Encode trait)
+    /// * `ttl` - Optional time-to-live duration
+    ///
+    /// # Type Parameters
+    /// * `T` - The type of value to store, must implement bincode::Encode
+    pub fn put_typed_opt_ttl<T>(
         &mut self,
         key: &str,
         value: &T,
@@ -407,7 +899,7 @@ impl Tree {
     where
         T: Encode,
     {
-        let serialized = bincode::encode_to_vec(value, self.settings.bincode_config)?;
+        let serialized = self.settings.typed_encoding.encode(value)?;
         self.put_with_ttl(key.as_bytes().to_vec(), serialized, ttl)
     }
 
@@ -450,16 +942,220 @@ impl Tree {
         value: Vec<u8>,
         ttl: Option<Duration>,
     ) -> TreeResult<()> {
+        self.put_with_options(key, value, ttl, WriteOptions::default())
+    }
+
+    /// Stores raw bytes in the tree with per-write durability options.
+    ///
+    /// Lets bulk imports skip the WAL while critical writes interleaved with them
+    /// are synced immediately.
+    ///
+    /// # Arguments
+    /// * `key` - The key as a byte vector
+    /// * `value` - The value as a byte vector
+    /// * `ttl` - Optional time-to-live duration
+    /// * `opts` - Durability options for this write
+    ///
+    /// # Durability
+    /// A write with `log_to_wal: false` is lost if the process crashes before the
+    /// memory table is flushed; after such a crash, recovery restores the last
+    /// logged value for that key, if any. `sync: true` syncs the WAL segment like
+    /// `Tree::sync` and has no effect when the write is not logged or the WAL is
+    /// disabled.
+    ///
+    /// # Errors
+    /// Returns `TreeError` if compression, the WAL write or the sync fails, and
+    /// `TreeError::InvalidValue` if the TTL exceeds `max_ttl` under
+    /// `TtlLimitPolicy::Reject`
+    pub fn put_with_options(
+        &mut self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+        opts: WriteOptions,
+    ) -> TreeResult<()> {
+        self.put_sequenced(key, value, ttl, opts).map(|_| ())
+    }
+
+    /// Stores raw bytes like `put_with_ttl` and returns the write's sequence number.
+    ///
+    /// Sequence numbers come from the transaction manager's global version
+    /// counter, which every write advances, so a later write always gets a
+    /// larger number. A client can keep the number of its last write and
+    /// compare it against `Tree::key_version` to tell whether a replica has
+    /// observed that write.
+    ///
+    /// The number is assigned once the write is in the WAL and the memory
+    /// table, so a failed write advances neither the counter nor the key's
+    /// version. The counter lives in memory and starts over when the tree is
+    /// opened again, so sequence numbers are only comparable within one open tree.
+    ///
+    /// # Arguments
+    /// * `key` - The key as a byte vector
+    /// * `value` - The value as a byte vector
+    /// * `ttl` - Optional time-to-live duration
+    ///
+    /// # Returns
+    /// The sequence number assigned to this write
+    ///
+    /// # Errors
+    /// Same as `Tree::put_with_options`
+    pub fn put_seq(&mut self, key: Vec<u8>, value: Vec<u8>, ttl: Option<Duration>) -> TreeResult<u64> {
+        self.put_sequenced(key, value, ttl, WriteOptions::default())
+    }
+
+    fn put_sequenced(
+        &mut self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+        opts: WriteOptions,
+    ) -> TreeResult<u64> {
+        let ttl = self.resolve_ttl(ttl)?;
+        self.flush_if_due()?;
         let data = self.apply_compression(value)?;
-        let data_value = DataValue::new(data, ttl);
-        self.write_to_wal(WalOperation::Put, &key, Some(&data_value))?;
-        self.mem_table.insert(key, data_value);
-        if self.mem_table.len() > self.settings.mem_table_max_size {
-            self.flush_mem_table()?;
+        // A write that fails, e.g. in the WAL, keeps the key's version
+        self.insert_value(key.clone(), DataValue::new(data, ttl), opts)?;
+        Ok(self.tx_manager.lock().unwrap().bump_key_version(&key))
+    }
+
+    /// Applies `default_ttl` and `max_ttl` to the TTL requested by a write.
+    ///
+    /// An explicit TTL takes precedence over `default_ttl`; the result is then
+    /// clamped to `max_ttl` or rejected, depending on `max_ttl_policy`.
+    pub(crate) fn resolve_ttl(&self, ttl: Option<Duration>) -> TreeResult<Option<Duration>> {
+        self.limit_ttl(ttl.or(self.settings.default_ttl))
+    }
+
+    /// Clamps or rejects a TTL beyond `max_ttl`; `None` counts as unbounded.
+    fn limit_ttl(&self, ttl: Option<Duration>) -> TreeResult<Option<Duration>> {
+        let Some(max_ttl) = self.settings.max_ttl else {
+            return Ok(ttl);
+        };
+        match ttl {
+            Some(ttl) if ttl <= max_ttl => Ok(Some(ttl)),
+            _ if self.settings.max_ttl_policy == TtlLimitPolicy::Clamp => Ok(Some(max_ttl)),
+            Some(ttl) => Err(TreeError::invalid_value(format!(
+                "TTL of {:?} exceeds the maximum of {:?}", ttl, max_ttl
+            ))),
+            None => Err(TreeError::invalid_value(format!(
+                "values must expire within the maximum TTL of {:?}", max_ttl
+            ))),
+        }
+    }
+
+    /// Stores a value committed by a transaction, tagging it with the transaction ID.
+    ///
+    /// `put_tx` already applied `default_ttl` and `max_ttl` when it staged the
+    /// value, so a put the policy rejects fails there, before the commit could
+    /// write part of the transaction. `ttl` is what remains of that TTL.
+    pub(crate) fn put_committed(
+        &mut self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+        tx_id: u64,
+    ) -> TreeResult<()> {
+        self.flush_if_due()?;
+        let data = self.apply_compression(value)?;
+        let data_value = DataValue::new(data, ttl).with_transaction_id(tx_id);
+        self.insert_value(key, data_value, WriteOptions::default())
+    }
+
+    fn insert_value(&mut self, key: Vec<u8>, data_value: DataValue, opts: WriteOptions) -> TreeResult<()> {
+        self.rotate_if_full()?;
+        if opts.log_to_wal {
+            self.write_to_wal(WalOperation::Put, &key, Some(&data_value))?;
+            if opts.sync {
+                self.sync()?;
+            }
+        }
+        self.value_cache().invalidate_key(&key);
+        let overwritten = self.settings.overwrite_in_place
+            && self
+                .mem_table
+                .get_mut(&key)
+                .is_some_and(|existing| existing.overwrite_in_place(&data_value));
+        if !overwritten {
+            self.mem_table_insert(key, data_value);
+        }
+        self.mem_table_since.get_or_insert_with(Instant::now);
+        if self.mem_table_full() {
+            self.rotate_mem_table()?;
+        }
+        Ok(())
+    }
+
+    /// Inserts into the active memory table, keeping `mem_table_bytes` in step.
+    pub(crate) fn mem_table_insert(&mut self, key: Vec<u8>, value: DataValue) {
+        let key_len = key.len();
+        self.mem_table_bytes += value.heap_size();
+        match self.mem_table.insert(key, value) {
+            Some(replaced) => self.mem_table_bytes = self.mem_table_bytes.saturating_sub(replaced.heap_size()),
+            None => self.mem_table_bytes += key_len,
+        }
+    }
+
+    /// Removes from the active memory table, keeping `mem_table_bytes` in step.
+    fn mem_table_remove(&mut self, key: &[u8]) -> Option<DataValue> {
+        let removed = self.mem_table.remove(key)?;
+        self.mem_table_bytes = self.mem_table_bytes.saturating_sub(key.len() + removed.heap_size());
+        Some(removed)
+    }
+
+    /// Takes the active memory table, leaving an empty one behind.
+    pub(crate) fn take_mem_table(&mut self) -> BTreeMap<Vec<u8>, DataValue> {
+        self.mem_table_since = None;
+        self.mem_table_bytes = 0;
+        std::mem::take(&mut self.mem_table)
+    }
+
+    /// Retries rotating a memory table left full because the immutable memory
+    /// tables were at `max_immutable_mem_tables`, so the write fails instead of
+    /// growing it further.
+    fn rotate_if_full(&mut self) -> TreeResult<()> {
+        if self.mem_table_full() {
+            self.rotate_mem_table()?;
         }
         Ok(())
     }
 
+    /// Writes the oldest immutable memory tables on the calling thread until
+    /// fewer than `max_immutable_mem_tables` are queued, waiting for the flush
+    /// in flight first.
+    pub(crate) fn limit_immutable_mem_tables(&mut self) -> TreeResult<()> {
+        while self.immutable_mem_tables.len() >= self.settings.max_immutable_mem_tables {
+            let queued = self.immutable_mem_tables.len();
+            self.write_oldest_mem_table().map_err(|e| {
+                TreeError::io(format!(
+                    "Write stalled: {} memory tables are waiting to be flushed and flushing the oldest failed: {}",
+                    queued, e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks whether the active memory table exceeds `mem_table_max_size`
+    /// entries or `mem_table_max_bytes` bytes.
+    pub(crate) fn mem_table_full(&self) -> bool {
+        self.mem_table.len() > self.settings.mem_table_max_size
+            || self.settings.mem_table_max_bytes.is_some_and(|max_bytes| self.mem_table_bytes > max_bytes)
+    }
+
+    /// Returns the approximate number of bytes held by the active memory table.
+    ///
+    /// Counts each key plus the in-memory size of its value, as
+    /// `DataValue::heap_size` reports it, and is compared against
+    /// `mem_table_max_bytes`. Immutable memory tables waiting to be flushed are
+    /// not included.
+    ///
+    /// # Returns
+    /// The size of the active memory table in bytes
+    pub fn mem_table_bytes(&self) -> usize {
+        self.mem_table_bytes
+    }
+
     /// Retrieves and deserializes a typed value from the tree.
     ///
     /// # Arguments
@@ -470,43 +1166,84 @@ impl Tree {
     ///
     /// # Returns
     /// `Some(T)` if the key exists and can be deserialized, `None` otherwise
-    pub fn get_typed<T>(&mut self, key: &str) -> TreeResult<Option<T>>
+    pub fn get_typed<T>(&self, key: &str) -> TreeResult<Option<T>>
     where
         T: bincode::Decode<()>,
     {
-        let key_bytes = key.as_bytes();
-        match self.get(key_bytes)? {
-            Some(value_bytes) => {
-                let (decoded, _) =
-                    bincode::decode_from_slice(&value_bytes, self.settings.bincode_config)?;
-                Ok(Some(decoded))
-            }
-            None => Ok(None),
-        }
+        let encoding = self.settings.typed_encoding;
+        self.get_typed_with_encoding(key, encoding)
     }
 
-    /// Retrieves multiple typed values from the tree in a single operation.
+    /// Retrieves and deserializes a typed value using an explicit encoding.
     ///
-    /// This method allows efficient batch retrieval of multiple keys, returning
-    /// the deserialized values in the same order as the input keys. For each key,
-    /// the result will be `Some(T)` if the key exists and can be deserialized,
-    /// or `None` if the key doesn't exist, has expired, or deserialization fails.
+    /// Overrides the configured `typed_encoding` for this call, which is needed
+    /// to read values written before the database's encoding was changed.
     ///
     /// # Arguments
-    /// * `keys` - A vector of string keys to retrieve
-    ///
-    /// # Type Parameters
-    /// * `T` - The type to deserialize values to, must implement `bincode::Decode`
+    /// * `key` - The string key to look up
+    /// * `encoding` - The encoding the value was written with
     ///
     /// # Returns
-    /// A `Vec<Option<T>>` where each element corresponds to the key at the same
-    /// index in the input vector. `Some(T)` if the key exists and is valid,
-    /// `None` otherwise.
-    ///
-    /// # Performance
-    /// This method is more efficient than calling `get_typed` multiple times
-    /// for the same keys, as it can optimize lookups and reduce repeated
-    /// deserialization overhead.
+    /// `Some(T)` if the key exists and can be deserialized, `None` otherwise
+    pub fn get_typed_with_encoding<T>(
+        &self,
+        key: &str,
+        encoding: TypedEncoding,
+    ) -> TreeResult<Option<T>>
+    where
+        T: bincode::Decode<()>,
+    {
+        match self.get(key.as_bytes())? {
+            Some(value_bytes) => Ok(Some(encoding.decode(&value_bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieves and deserializes a typed value that must exist.
+    ///
+    /// # Arguments
+    /// * `key` - The string key to look up
+    ///
+    /// # Type Parameters
+    /// * `T` - The type to deserialize to, must implement bincode::Decode
+    ///
+    /// # Returns
+    /// The deserialized value
+    ///
+    /// # Errors
+    /// Returns `TreeError::InvalidKey` if the key is absent or expired, and
+    /// `TreeError` if the lookup or deserialization fails
+    pub fn get_typed_required<T>(&self, key: &str) -> TreeResult<T>
+    where
+        T: bincode::Decode<()>,
+    {
+        self.get_typed(key)?.ok_or_else(|| TreeError::invalid_key("key not found"))
+    }
+
+    /// Retrieves multiple typed values from the tree in a single operation.
+    ///
+    /// This method allows efficient batch retrieval of multiple keys, returning
+    /// the deserialized values in the same order as the input keys. For each key,
+    /// the result will be `Some(T)` if the key exists and can be deserialized,
+    /// or `None` if the key doesn't exist, has expired, or deserialization fails.
+    ///
+    /// # Arguments
+    /// * `keys` - A vector of string keys to retrieve
+    ///
+    /// # Type Parameters
+    /// * `T` - The type to deserialize values to, must implement `bincode::Decode`
+    ///
+    /// # Returns
+    /// A `Vec<Option<T>>` where each element corresponds to the key at the same
+    /// index in the input vector. `Some(T)` if the key exists and is valid,
+    /// `None` otherwise.
+    ///
+    /// # Performance
+    /// This method is more efficient than calling `get_typed` multiple times
+    /// for the same keys, as it can optimize lookups and reduce repeated
+    /// deserialization overhead. Keys that are absent from the memory tables
+    /// and rejected by every SSTable bloom filter resolve to `None` without
+    /// touching any index or data block, see [`multi_get`].
     /// # Error Handling
     /// If deserialization fails for any key, that entry will be `None` in the
     /// result vector, and an error will be logged. The operation continues
@@ -514,14 +1251,20 @@ impl Tree {
     ///
     /// # See Also
     /// - [`get_typed`] - For retrieving a single typed value
-    /// - [`get_vec`] - For retrieving multiple raw byte values
-    pub fn multi_get_typed<T>(&mut self, keys: Vec<&str>) -> TreeResult<Vec<Option<T>>>
+    /// - [`multi_get`] - For retrieving multiple raw byte values
+    pub fn multi_get_typed<T>(&self, keys: Vec<&str>) -> TreeResult<Vec<Option<T>>>
     where
         T: bincode::Decode<()>,
     {
-        let mut results = Vec::with_capacity(keys.len());
-        for key in keys {
-            results.push(self.get_typed::<T>(key)?);
+        let raw_values = self.multi_get(keys.iter().map(|key| key.as_bytes()).collect())?;
+        let mut results = Vec::with_capacity(raw_values.len());
+        for value_bytes in raw_values {
+            match value_bytes {
+                Some(value_bytes) => {
+                    results.push(Some(self.settings.typed_encoding.decode(&value_bytes)?));
+                }
+                None => results.push(None),
+            }
         }
         Ok(results)
     }
@@ -538,44 +1281,243 @@ impl Tree {
     /// A `Vec<Option<Vec<u8>>>` where each element corresponds to the key at the
     /// same index in the input vector. `Some(Vec<u8>)` if the key exists and is valid,
     /// `None` otherwise.
-    pub fn multi_get(&mut self, keys: Vec<&[u8]>) -> TreeResult<Vec<Option<Vec<u8>>>> {
+    ///
+    /// # Performance
+    /// Before any SSTable is read, every key missing from the memory tables is
+    /// checked against the SSTable bloom filters. Keys that no filter claims
+    /// are answered with `None` straight away, so batches dominated by absent
+    /// keys skip the index and data reads entirely. This works best with the
+    /// bloom filter cache enabled, otherwise each filter is loaded from disk.
+    pub fn multi_get(&self, keys: Vec<&[u8]>) -> TreeResult<Vec<Option<Vec<u8>>>> {
+        let candidates: Vec<bool> = keys
+            .iter()
+            .map(|key| self.mem_tables_contain(key) || self.sstables_may_contain(key))
+            .collect();
+
         let mut results = Vec::with_capacity(keys.len());
-        for key in keys {
-            results.push(self.get(key)?);
+        for (key, is_candidate) in keys.into_iter().zip(candidates) {
+            if is_candidate {
+                results.push(self.get(key)?);
+            } else {
+                results.push(None);
+            }
         }
         Ok(results)
     }
 
+    /// Retrieves multiple raw byte values, reading SSTables on several threads.
+    ///
+    /// Keys found in the memory tables are answered directly. The remaining
+    /// keys are sorted and split into up to `read_parallelism` contiguous
+    /// batches, each resolved on its own thread by visiting the SSTables newest
+    /// first and reading every key a table may hold through one pooled file
+    /// handle. Results are returned in input order, as with `multi_get`.
+    ///
+    /// Deleted keys are returned as `None`.
+    ///
+    /// Unlike `multi_get`, this leaves the caches untouched: indexes and bloom
+    /// filters are only used when already cached, and values read are not
+    /// admitted to the value cache.
+    ///
+    /// # Arguments
+    /// * `keys` - A vector of byte slice keys to retrieve
+    ///
+    /// # Returns
+    /// A `Vec<Option<Vec<u8>>>` where each element corresponds to the key at the
+    /// same index in the input vector
+    ///
+    /// # Errors
+    /// Returns `TreeError` if a value cannot be decompressed
+    pub fn par_multi_get(&self, keys: Vec<&[u8]>) -> TreeResult<Vec<Option<Vec<u8>>>> {
+        let mut values: Vec<Option<DataValue>> = vec![None; keys.len()];
+        let mut pending: Vec<(usize, &[u8])> = Vec::new();
+
+        for (idx, key) in keys.iter().enumerate() {
+            let mem_value = std::iter::once(&self.mem_table)
+                .chain(self.immutable_mem_tables.iter().rev().map(Arc::as_ref))
+                .filter_map(|table| table.get(*key))
+                .find(|value| self.is_range_deleted(key, value) || !value.is_expired());
+            match mem_value {
+                Some(value) if !self.is_range_deleted(key, value) => values[idx] = Some(value.clone()),
+                Some(_) => {}
+                None => pending.push((idx, *key)),
+            }
+        }
+
+        if !pending.is_empty() {
+            pending.sort_by(|a, b| a.1.cmp(b.1));
+            let threads = self.settings.read_parallelism.max(1).min(pending.len());
+            let batch_size = pending.len().div_ceil(threads);
+            let resolved: Vec<(usize, Option<DataValue>)> = thread::scope(|scope| {
+                let workers: Vec<_> = pending
+                    .chunks(batch_size)
+                    .map(|batch| scope.spawn(move || self.read_keys_from_sstables(batch)))
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().expect("SSTable reader thread panicked"))
+                    .collect()
+            });
+            for (idx, value) in resolved {
+                values[idx] = value;
+            }
+        }
+
+        values
+            .into_iter()
+            .map(|value| match value {
+                Some(value) if !value.is_tombstone => self.decompress_value_data(value.get_data()),
+                _ => Ok(None),
+            })
+            .collect()
+    }
+
+    fn mem_tables_contain(&self, key: &[u8]) -> bool {
+        self.mem_table.contains_key(key)
+            || self
+                .immutable_mem_tables
+                .iter()
+                .any(|table| table.contains_key(key))
+    }
+
     /// Retrieves raw bytes from the tree.
     ///
     /// Searches through memory tables and SSTable files in order.
     /// Returns None if the key doesn't exist or has expired.
     ///
+    /// Before any SSTable is searched, the value cache is probed by key alone, so
+    /// a cached value held by an older SSTable is returned without visiting the
+    /// newer ones, as long as their cached bloom filters rule the key out.
+    ///
+    /// A `get` always sees the latest write made through this `Tree`, wherever
+    /// that write currently lives. A flush registers the new SSTable together
+    /// with its index and bloom filter before returning, and a merge swaps its
+    /// inputs for the merged table in a single step.
+    ///
+    /// `get` takes `&self`. The index cache, value cache and bloom filters it
+    /// fills each sit behind their own lock and `Tree` is `Send + Sync`, so an
+    /// `Arc<Tree>` serves reads from many threads at once. Writes still take
+    /// `&mut self`; a tree that is written while it is read is shared as an
+    /// `Arc<RwLock<Tree>>`, with every reader under the read lock. A due flush,
+    /// like finished background flushes and merges, is taken care of by the
+    /// next write or `flush_if_due`, not by `get`.
+    ///
     /// # Arguments
     /// * `key` - The key to look up as a byte slice
     ///
     /// # Returns
     /// `Some(Vec<u8>)` if the key exists and is valid, `None` otherwise
-    pub fn get(&mut self, key: &[u8]) -> TreeResult<Option<Vec<u8>>> {
-        if let Some(value) = self.mem_table.get(key) {
-            if !value.is_expired() {
-                return Ok(self.decompress_value_data(value.get_data())?);
-            }
+    pub fn get(&self, key: &[u8]) -> TreeResult<Option<Vec<u8>>> {
+        match self.get_data_value(key)? {
+            Some(value) => self.decompress_value_data(value.get_data()),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieves raw bytes into a caller-provided buffer.
+    ///
+    /// Behaves like `get`, but `buf` is cleared and the value is decompressed
+    /// straight into it, so reusing one buffer across many reads avoids
+    /// allocating per read. A value still in a memory table is not cloned.
+    ///
+    /// # Arguments
+    /// * `key` - The key to look up as a byte slice
+    /// * `buf` - The buffer receiving the value; left empty if the key is not found
+    ///
+    /// # Returns
+    /// `true` if the key exists and is valid, `false` otherwise
+    ///
+    /// # Errors
+    /// Returns `TreeError` if the decompression fails
+    pub fn get_into(&self, key: &[u8], buf: &mut Vec<u8>) -> TreeResult<bool> {
+        buf.clear();
+        let value = match self.get_memory_value(key) {
+            Some(Some(value)) => return self.decompress_value_into(value.get_data(), buf).map(|()| true),
+            Some(None) => return Ok(false),
+            None => self.get_sstable_value(key)?,
+        };
+        match value {
+            Some(value) => self.decompress_value_into(value.get_data(), buf).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Retrieves raw bytes of a key that must exist.
+    ///
+    /// # Arguments
+    /// * `key` - The key to look up as a byte slice
+    ///
+    /// # Returns
+    /// The stored value
+    ///
+    /// # Errors
+    /// Returns `TreeError::InvalidKey` if the key is absent or expired, and
+    /// `TreeError` if the lookup fails
+    pub fn get_required(&self, key: &[u8]) -> TreeResult<Vec<u8>> {
+        self.get(key)?.ok_or_else(|| TreeError::invalid_key("key not found"))
+    }
+
+    /// Retrieves the metadata of the newest live value of a key.
+    ///
+    /// # Arguments
+    /// * `key` - The key to look up as a byte slice
+    ///
+    /// # Returns
+    /// `Some(ValueMetadata)` if the key holds a live value, `None` if it is
+    /// absent, deleted or expired
+    ///
+    pub fn get_metadata(&self, key: &[u8]) -> TreeResult<Option<ValueMetadata>> {
+        Ok(self
+            .get_data_value(key)?
+            .filter(|value| !value.is_tombstone)
+            .map(|value| ValueMetadata::from(&value)))
+    }
+
+    /// Finds the newest stored value of a key, still compressed.
+    ///
+    /// A tombstone hides every older version, so a deleted key is `None`
+    /// however many older SSTables still hold a value.
+    fn get_data_value(&self, key: &[u8]) -> TreeResult<Option<DataValue>> {
+        if let Some(found) = self.get_memory_value(key) {
+            return Ok(found.cloned());
         }
+        self.get_sstable_value(key)
+    }
 
-        for immutable_mem_table in self.immutable_mem_tables.iter().rev() {
-            if let Some(value) = immutable_mem_table.get(key) {
+    /// Looks a key up in the memory tables, newest first.
+    ///
+    /// Returns `None` if the SSTables have to be searched, `Some(None)` if a
+    /// tombstone or range deletion hides the key and `Some(Some(value))` otherwise.
+    fn get_memory_value(&self, key: &[u8]) -> Option<Option<&DataValue>> {
+        let tables = std::iter::once(&self.mem_table).chain(self.immutable_mem_tables.iter().rev().map(Arc::as_ref));
+        for table in tables {
+            if let Some(value) = table.get(key) {
+                if value.is_tombstone || self.is_range_deleted(key, value) {
+                    return Some(None);
+                }
                 if !value.is_expired() {
-                    return Ok(self.decompress_value_data(value.get_data())?);
+                    return Some(Some(value));
                 }
             }
         }
+        None
+    }
+
+    fn get_sstable_value(&self, key: &[u8]) -> TreeResult<Option<DataValue>> {
+        if let Some(value) = self.read_key_from_value_cache(key) {
+            if value.is_tombstone || self.is_range_deleted(key, &value) {
+                return Ok(None);
+            }
+            return Ok(Some(value));
+        }
 
-        let sstables = self.ss_tables.clone();
-        for sst_path in sstables.iter().rev() {
+        for sst_path in self.ss_tables.iter().rev() {
             if let Some(value) = self.read_key_from_sstable(sst_path, key) {
+                if value.is_tombstone || self.is_range_deleted(key, &value) {
+                    return Ok(None);
+                }
                 if !value.is_expired() {
-                    return Ok(self.decompress_value_data(value.get_data())?);
+                    return Ok(Some(value));
                 }
             }
         }
@@ -590,6 +1532,9 @@ impl Tree {
     /// # Arguments
     /// * `key` - The key to look up as a byte slice
     ///
+    /// Changing the size of the value through the reference is not reflected in
+    /// `mem_table_bytes` until the key is written again.
+    ///
     /// # Returns
     /// `Some(&mut DataValue)` if the key exists in the memory table, `None` otherwise
     pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut DataValue> {
@@ -605,14 +1550,242 @@ impl Tree {
     /// `true` if the key existed and was marked for deletion, `false` otherwise
     pub fn delete(&mut self, key: &[u8]) -> TreeResult<bool> {
         if self.contains_key(key)? {
-            self.write_to_wal(WalOperation::Delete, key, None)?;
-            self.mem_table.insert(key.to_vec(), DataValue::tombstone());
+            self.write_tombstone(key)?;
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Deletes a key and returns the value it held.
+    ///
+    /// # Arguments
+    /// * `key` - The key to delete as a byte slice
+    ///
+    /// # Returns
+    /// `Some(Vec<u8>)` with the previous value if the key existed, `None` otherwise
+    pub fn take(&mut self, key: &[u8]) -> TreeResult<Option<Vec<u8>>> {
+        let value = self.get(key)?;
+        if value.is_some() {
+            self.write_tombstone(key)?;
+        }
+        Ok(value)
+    }
+
+    /// Deletes a key and returns the value it held, deserialized into `T`.
+    ///
+    /// Suits "pop and process" patterns over queue-like keyspaces. Like
+    /// `get_typed`, a value that does not decode as `T` is an error rather
+    /// than `None`; the key is then left in place.
+    ///
+    /// # Arguments
+    /// * `key` - The string key to delete
+    ///
+    /// # Type Parameters
+    /// * `T` - The type to deserialize to, must implement bincode::Decode
+    ///
+    /// # Returns
+    /// `Some(T)` with the previous value if the key existed, `None` otherwise
+    ///
+    /// # Errors
+    /// Returns `TreeError` if the lookup, deserialization or deletion fails
+    pub fn take_typed<T>(&mut self, key: &str) -> TreeResult<Option<T>>
+    where
+        T: bincode::Decode<()>,
+    {
+        let Some(value_bytes) = self.get(key.as_bytes())? else {
+            return Ok(None);
+        };
+        let value = self.settings.typed_encoding.decode(&value_bytes)?;
+        self.write_tombstone(key.as_bytes())?;
+        Ok(Some(value))
+    }
+
+    /// Copies every live entry of `src` into this tree, the source's value
+    /// winning on keys both trees hold.
+    ///
+    /// Shorthand for `merge_into_with(src, MergeConflictPolicy::SourceWins)`.
+    ///
+    /// # Arguments
+    /// * `src` - The tree to copy entries from; it is not modified
+    ///
+    /// # Errors
+    /// Same as `Tree::merge_into_with`
+    pub fn merge_into(&mut self, src: &mut Tree) -> TreeResult<()> {
+        self.merge_into_with(src, MergeConflictPolicy::SourceWins)
+    }
+
+    /// Copies every live entry of `src` into this tree, for folding one shard
+    /// into another.
+    ///
+    /// The source is read in key order with its merge iterator, so deleted,
+    /// range-deleted and expired entries are skipped. Each entry is written
+    /// through this tree's regular write path, logged to its WAL and
+    /// recompressed with its compressor, and keeps the `created_at` and
+    /// expiration time it had in `src`. An entry older than a `delete_prefix`
+    /// of this tree covering its key is dated now instead, as `rename_key`
+    /// does, so the range tombstone does not hide it. This tree's `default_ttl` and `max_ttl`
+    /// are not applied to copied entries, and their `transaction_id` is cleared
+    /// since transaction IDs of `src` mean nothing here.
+    ///
+    /// # Conflict resolution
+    /// When both trees hold a live value for a key:
+    /// - `MergeConflictPolicy::SourceWins` writes the source's value.
+    /// - `MergeConflictPolicy::KeepNewest` writes it only if its `created_at` is
+    ///   later than the destination's; a tie keeps the destination's value.
+    ///
+    /// Keys deleted or expired in this tree count as absent. Entries are written
+    /// one at a time, so an error leaves the entries copied so far in place.
+    ///
+    /// # Arguments
+    /// * `src` - The tree to copy entries from; it is not modified
+    /// * `policy` - Which value wins on keys both trees hold
+    ///
+    /// # Errors
+    /// Returns `TreeError` if `src` cannot be read or a value cannot be
+    /// decompressed, recompressed or written
+    pub fn merge_into_with(&mut self, src: &mut Tree, policy: MergeConflictPolicy) -> TreeResult<()> {
+        self.flush_if_due()?;
+        let mut entries = src.iter_from(&[])?;
+        while let Some(entry) = entries.next_stored() {
+            let (key, source_value) = entry?;
+            if policy == MergeConflictPolicy::KeepNewest {
+                let existing = self
+                    .get_data_value(&key)?
+                    .filter(|value| !value.is_tombstone && !value.is_expired());
+                if existing.is_some_and(|existing| existing.created_at >= source_value.created_at) {
+                    continue;
+                }
+            }
+
+            let data = src.apply_decompression(&source_value.data)?;
+            let mut data_value = DataValue {
+                data: self.apply_compression(data)?,
+                transaction_id: None,
+                ..source_value
+            };
+            if self.is_range_deleted(&key, &data_value) {
+                data_value.created_at = SystemTime::now();
+            }
+            self.insert_value(key.clone(), data_value, WriteOptions::default())?;
+            self.tx_manager.lock().unwrap().bump_key_version(&key);
+        }
+        Ok(())
+    }
+
+    fn write_tombstone(&mut self, key: &[u8]) -> TreeResult<()> {
+        self.rotate_if_full()?;
+        self.write_to_wal(WalOperation::Delete, key, None)?;
+        self.tx_manager.lock().unwrap().bump_key_version(key);
+        self.value_cache().invalidate_key(key);
+        self.mem_table_insert(key.to_vec(), DataValue::tombstone());
+        self.mem_table_since.get_or_insert_with(Instant::now);
+        Ok(())
+    }
+
+    /// Deletes every key starting with `prefix` with a single range tombstone.
+    ///
+    /// Instead of one tombstone per key, one marker covering
+    /// `[prefix, prefix_upper_bound)` is logged to the WAL and written to the
+    /// next SSTable, so the write size does not depend on how many keys are
+    /// deleted. Covered entries still in memory are removed right away; entries
+    /// in SSTables are hidden from reads and scans and dropped by compaction.
+    ///
+    /// # Interaction with point writes
+    /// The newest timestamp wins: the range tombstone hides values written at
+    /// or before the call, while a `put` made afterwards under a covered key is
+    /// visible again. Point tombstones inside the range are superseded by it.
+    ///
+    /// # Arguments
+    /// * `prefix` - The key prefix to delete; an empty prefix deletes every key
+    ///
+    /// # Errors
+    /// Returns `TreeError` if the WAL write fails
+    pub fn delete_prefix(&mut self, prefix: &[u8]) -> TreeResult<()> {
+        self.flush_if_due()?;
+        let tombstone = DataValue::tombstone();
+        self.write_to_wal(WalOperation::DeletePrefix, prefix, Some(&tombstone))?;
+        self.tx_manager.lock().unwrap().bump_prefix_versions(prefix);
+        self.apply_range_tombstone(RangeTombstone::for_prefix(prefix, tombstone.created_at));
+        self.mem_table_since.get_or_insert_with(Instant::now);
+        Ok(())
+    }
+
+    /// Moves the live value of `from` to `to` and deletes `from`.
+    ///
+    /// Both halves are logged as a single WAL entry, so after a crash either
+    /// the whole move is recovered or none of it. The moved value keeps its
+    /// data, TTL and `created_at`. Only when a `delete_prefix` newer than the
+    /// value covers `to` is it dated at the move instead, as the range
+    /// tombstone would otherwise hide it. Any value already stored under `to`
+    /// is overwritten.
+    ///
+    /// # Arguments
+    /// * `from` - The key to move the value from
+    /// * `to` - The key to move the value to
+    ///
+    /// # Returns
+    /// `true` if the value was moved, `false` if `from` has no live value
+    ///
+    /// # Errors
+    /// Returns `TreeError` if reading `from` or the WAL write fails
+    pub fn rename_key(&mut self, from: &[u8], to: &[u8]) -> TreeResult<bool> {
+        let Some(mut value) = self.get_data_value(from)?.filter(|value| !value.is_tombstone) else {
+            return Ok(false);
+        };
+        if from == to {
+            return Ok(true);
+        }
+        if self.is_range_deleted(to, &value) {
+            value.created_at = SystemTime::now();
+        }
+
+        self.rotate_if_full()?;
+        self.write_to_wal(WalOperation::Rename, &encode_rename_keys(from, to), Some(&value))?;
+        {
+            let tx_manager = self.tx_manager.lock().unwrap();
+            tx_manager.bump_key_version(from);
+            tx_manager.bump_key_version(to);
+        }
+        self.value_cache().invalidate_key(from);
+        self.value_cache().invalidate_key(to);
+        self.mem_table_insert(to.to_vec(), value);
+        self.mem_table_insert(from.to_vec(), DataValue::tombstone());
+        self.mem_table_since.get_or_insert_with(Instant::now);
+        if self.mem_table_full() {
+            self.rotate_mem_table()?;
+        }
+        Ok(true)
+    }
+
+    /// Removes shadowed entries from the memory tables and keeps the range
+    /// tombstone until the next SSTable is written.
+    pub(crate) fn apply_range_tombstone(&mut self, range_tombstone: RangeTombstone) {
+        for key in range_tombstone.shadowed_keys(&self.mem_table) {
+            self.mem_table_remove(&key);
+        }
+        for immutable_mem_table in &mut self.immutable_mem_tables {
+            // A table still being written by the background flusher is shared
+            // with it, so it is only copied when something has to be removed.
+            let shadowed = range_tombstone.shadowed_keys(immutable_mem_table);
+            if !shadowed.is_empty() {
+                let immutable_mem_table = Arc::make_mut(immutable_mem_table);
+                for key in shadowed {
+                    immutable_mem_table.remove(&key);
+                }
+            }
+        }
+        self.mem_range_tombstones.push(range_tombstone);
+    }
+
+    /// Checks whether a range tombstone, in memory or in any SSTable, hides `value`.
+    pub(crate) fn is_range_deleted(&self, key: &[u8], value: &DataValue) -> bool {
+        self.mem_range_tombstones
+            .iter()
+            .chain(self.range_tombstones.values().flatten())
+            .any(|range_tombstone| range_tombstone.shadows(key, value))
+    }
+
     /// Clears all entries from the active memory table.
     ///
     /// This method removes all key-value pairs from the current memory table,
@@ -624,6 +1797,8 @@ impl Tree {
     /// trigger any disk I/O operations or compaction processes.
     pub fn clear_mem_table(&mut self) {
         self.mem_table.clear();
+        self.mem_table_bytes = 0;
+        self.mem_table_since = None;
     }
 
     /// Clears all data from the tree, including memory tables and SSTable references.
@@ -637,14 +1812,69 @@ impl Tree {
     /// This method does NOT delete the actual SSTable files from disk. It only
     /// removes the references to them from the tree's internal state. The files
     /// will remain on disk and can be reloaded by calling `load_tree()` or
-    /// creating a new tree instance with the same database path.
+    /// creating a new tree instance with the same database path, as long as no
+    /// SSTable is written in between: the next flush records the emptied set of
+    /// live SSTables in the `MANIFEST`.
     /// # See Also
     /// - [`clear_mem_table`] - For clearing only the active memory table
     /// - [`load_tree`] - For reloading data from disk after clearing
     pub fn clear_all(&mut self) {
+        self.discard_flush();
         self.mem_table.clear();
+        self.mem_table_bytes = 0;
+        self.mem_table_since = None;
         self.immutable_mem_tables.clear();
         self.ss_tables.clear();
+        self.legacy_sstables.clear();
+        self.key_ranges.clear();
+        self.range_tombstones.clear();
+        self.mem_range_tombstones.clear();
+        self.queued_compactions = 0;
+        self.file_pool.clear();
+    }
+
+    /// Discards all data in the tree while keeping the database usable.
+    ///
+    /// Unlike `clear_all`, the data is removed from disk as well:
+    /// - Every `.sst` file in the database directory is deleted
+    /// - The memory tables, caches, bloom filters and range tombstones are cleared
+    /// - All WAL segments are deleted and a fresh, empty segment is started
+    ///
+    /// The tree is immediately writable afterwards and a reload finds an empty
+    /// database. Transactions in progress are not affected.
+    ///
+    /// # Note
+    /// Truncation is not atomic: a crash part-way through can leave some
+    /// SSTables behind.
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable or WAL segment cannot be deleted or the
+    /// new WAL segment cannot be created
+    pub fn truncate(&mut self) -> TreeResult<()> {
+        self.wait_for_compactions()?;
+        self.clear_all();
+        self.bloom_filters_mut().clear();
+        self.index_cache().clear();
+        self.value_cache().clear();
+
+        if self.settings.enable_wal {
+            self.reset_wal()?;
+        }
+
+        let entries = std::fs::read_dir(&self.settings.db_path).map_err(|e| {
+            TreeError::io(format!("Error reading database directory: {}", e))
+        })?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "sst") {
+                std::fs::remove_file(&path).map_err(|e| {
+                    TreeError::io(format!("Error deleting SSTable {:?}: {}", path, e))
+                })?;
+            }
+        }
+
+        info!("Truncated database at {:?}", self.settings.db_path);
+        Ok(())
     }
 
     /// Removes expired entries from memory tables.
@@ -660,7 +1890,7 @@ impl Tree {
             .collect();
 
         for key in expired_keys {
-            self.mem_table.remove(&key);
+            self.mem_table_remove(&key);
         }
 
         for mem_table in &mut self.immutable_mem_tables {
@@ -670,8 +1900,11 @@ impl Tree {
                 .map(|(key, _)| key.clone())
                 .collect();
 
-            for key in expired_keys {
-                mem_table.remove(&key);
+            if !expired_keys.is_empty() {
+                let mem_table = Arc::make_mut(mem_table);
+                for key in expired_keys {
+                    mem_table.remove(&key);
+                }
             }
         }
         Ok(())
@@ -684,7 +1917,7 @@ impl Tree {
     ///
     /// # Returns
     /// `true` if the key exists and is valid, `false` otherwise
-    pub fn contains_key(&mut self, key: &[u8]) -> TreeResult<bool> {
+    pub fn contains_key(&self, key: &[u8]) -> TreeResult<bool> {
         Ok(self.get(key)?.is_some())
     }
 
@@ -695,6 +1928,7 @@ impl Tree {
     ///
     /// # Returns
     /// The total number of active entries
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         let mem_count = self
             .mem_table
@@ -723,17 +1957,55 @@ impl Tree {
         mem_count + immutable_count + sstable_count
     }
 
-    fn count_sstable_entries(&self, path: &PathBuf) -> TreeResult<usize> {
-        match self.load_sstable_with_bloom_filter(path) {
-            Ok((table, _)) => Ok(table
-                .values()
-                .filter(|value| !value.is_expired() && !value.is_tombstone)
-                .count()),
-            Err(e) => Err(TreeError::internal(format!(
-                "Failed to count SSTable entries: {}",
-                e
-            ))),
+    /// Returns a cheap approximation of the number of entries in the tree.
+    ///
+    /// Live entries in the memory tables are counted exactly, while each SSTable
+    /// contributes the entry count stored in its header (or in front of the index
+    /// for older files), without reading any data entries.
+    ///
+    /// # Approximation
+    /// The estimate over-counts: a key overwritten after a flush is counted once
+    /// per SSTable and memory table holding it, and tombstones and expired entries
+    /// in SSTables are included. It never under-counts live entries, so it is
+    /// suitable for dashboards and capacity checks; use `len` for an exact count.
+    ///
+    /// # Returns
+    /// The estimated number of entries
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable index cannot be read
+    pub fn estimate_count(&mut self) -> TreeResult<usize> {
+        let is_live = |value: &DataValue| !value.is_tombstone && !value.is_expired();
+        let mem_count = self.mem_table.values().filter(|value| is_live(value)).count();
+        let immutable_count: usize = self
+            .immutable_mem_tables
+            .iter()
+            .map(|table| table.values().filter(|value| is_live(value)).count())
+            .sum();
+
+        let mut sstable_count = 0;
+        for table_path in &self.ss_tables {
+            sstable_count += self.read_index_entry_count(table_path).map_err(|e| {
+                TreeError::internal(format!(
+                    "Failed to read index of SSTable {:?}: {}",
+                    table_path, e
+                ))
+            })?;
         }
+
+        Ok(mem_count + immutable_count + sstable_count)
+    }
+
+    fn count_sstable_entries(&self, path: &Path) -> TreeResult<usize> {
+        let count_failed = |e: std::io::Error| TreeError::internal(format!("Failed to count SSTable entries: {}", e));
+        let mut count = 0;
+        for entry in self.sstable_entries(path).map_err(count_failed)? {
+            let (key, value) = entry.map_err(count_failed)?;
+            if !value.is_expired() && !value.is_tombstone && !self.is_range_deleted(&key, &value) {
+                count += 1;
+            }
+        }
+        Ok(count)
     }
 
     /// Gets the remaining TTL for a key.
@@ -756,79 +2028,386 @@ impl Tree {
         None
     }
 
+    /// Lists live keys that expire within the given window.
+    ///
+    /// Keys are collected from the memory tables and all SSTables, with the newest
+    /// version of each key taking precedence. Keys without a TTL, deleted keys and
+    /// already expired keys are excluded.
+    ///
+    /// # Arguments
+    /// * `window` - How far ahead of now to look for expirations
+    ///
+    /// # Returns
+    /// Pairs of key and remaining TTL, sorted by remaining TTL ascending
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be read
+    pub fn keys_expiring_within(&mut self, window: Duration) -> TreeResult<Vec<(Vec<u8>, Duration)>> {
+        let now = SystemTime::now();
+        let mut expiring: Vec<(Vec<u8>, Duration)> = self
+            .merged_entries()?
+            .into_iter()
+            .filter(|(_, value)| !value.is_tombstone)
+            .filter_map(|(key, value)| {
+                let remaining = value.expires_at?.duration_since(now).ok()?;
+                (remaining <= window).then_some((key, remaining))
+            })
+            .collect();
+
+        expiring.sort_by(|(key_a, ttl_a), (key_b, ttl_b)| ttl_a.cmp(ttl_b).then_with(|| key_a.cmp(key_b)));
+        Ok(expiring)
+    }
+
+    /// Returns up to `n` pseudo-randomly selected live keys.
+    ///
+    /// Candidates are drawn by reservoir sampling over the keys of the memory
+    /// tables and the index keys of every SSTable, so no data entry is read
+    /// while sampling. Candidates are then checked to still be live. With a
+    /// sparse index only indexed keys of an SSTable can be drawn.
+    ///
+    /// Results are non-deterministic and not statistically exact, which is
+    /// enough for monitoring such as value size distributions. Fewer than `n`
+    /// keys are returned on a small database or when many candidates turn out
+    /// to be deleted or expired. Sampling does not populate the value cache.
+    ///
+    /// # Arguments
+    /// * `n` - The maximum number of keys to return
+    ///
+    /// # Returns
+    /// Distinct live keys in random order
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable index cannot be read
+    pub fn sample(&mut self, n: usize) -> TreeResult<Vec<Vec<u8>>> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Oversample, as some candidates are deleted, expired or duplicated.
+        let capacity = n.saturating_mul(2);
+        let mut rng = rand::rng();
+        let mut reservoir: Vec<Vec<u8>> = Vec::with_capacity(capacity);
+        let mut seen = 0usize;
+        let mut offer = |key: &[u8]| {
+            seen += 1;
+            if reservoir.len() < capacity {
+                reservoir.push(key.to_vec());
+            } else {
+                let slot = rng.random_range(0..seen);
+                if slot < capacity {
+                    reservoir[slot] = key.to_vec();
+                }
+            }
+        };
+
+        for mem_table in self.immutable_mem_tables.iter().map(Arc::as_ref).chain([&self.mem_table]) {
+            mem_table.keys().for_each(|key| offer(key));
+        }
+        for table_path in &self.ss_tables {
+            match self.index_cache().peek(table_path) {
+                Some(index) => index.keys().for_each(|key| offer(key)),
+                None => self
+                    .read_sstable_index(table_path)
+                    .map_err(|e| TreeError::io(format!("Failed to read index of SSTable {:?}: {}", table_path, e)))?
+                    .keys()
+                    .for_each(|key| offer(key)),
+            }
+        }
+
+        reservoir.shuffle(&mut rng);
+        let mut distinct = HashSet::with_capacity(reservoir.len());
+        reservoir.retain(|key| distinct.insert(key.clone()));
+
+        let admission = std::mem::replace(&mut self.settings.value_cache_admission, ValueCacheAdmission::Never);
+        let mut sample = Vec::with_capacity(n);
+        let mut result = Ok(());
+        for key in reservoir {
+            if sample.len() == n {
+                break;
+            }
+            match self.get_data_value(&key) {
+                Ok(Some(value)) if !value.is_tombstone => sample.push(key),
+                Ok(_) => {}
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.settings.value_cache_admission = admission;
+        result.map(|_| sample)
+    }
+
+    /// Collects the newest version of every key across SSTables and memory tables.
+    ///
+    /// Tombstones and expired values are kept so callers can decide how to treat them;
+    /// entries hidden by range tombstones are left out.
+    pub(crate) fn merged_entries(&mut self) -> TreeResult<BTreeMap<Vec<u8>, DataValue>> {
+        let mut merged = BTreeMap::new();
+        for table_path in &self.ss_tables {
+            for entry in self.sstable_entries(table_path)? {
+                let (key, value) = entry?;
+                merged.insert(key, value);
+            }
+        }
+        for immutable_mem_table in &self.immutable_mem_tables {
+            merged.extend(immutable_mem_table.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        merged.extend(self.mem_table.iter().map(|(k, v)| (k.clone(), v.clone())));
+        merged.retain(|key, value| !self.is_range_deleted(key, value));
+        Ok(merged)
+    }
+
     /// Updates the TTL for an existing key.
     ///
     /// Only works for keys currently in the active memory table.
     ///
     /// # Arguments
     /// * `key` - The key to update as a byte slice
-    /// * `new_ttl` - The new TTL duration, or None to remove expiration
+    /// * `new_ttl` - The new TTL duration, or None to remove expiration. A TTL
+    ///   too large to be represented also removes expiration
     ///
     /// # Returns
     /// `true` if the key was found and updated, `false` otherwise
+    ///
+    /// # Errors
+    /// Returns `TreeError::InvalidValue` if `new_ttl` exceeds `max_ttl` and
+    /// `max_ttl_policy` is `TtlLimitPolicy::Reject`
     pub fn update_ttl(&mut self, key: &[u8], new_ttl: Option<Duration>) -> TreeResult<bool> {
-        if let Some(mut value) = self.mem_table.remove(key) {
+        let new_ttl = self.limit_ttl(new_ttl)?;
+        if let Some(mut value) = self.mem_table_remove(key) {
             if !value.is_expired() {
-                value.expires_at = new_ttl.map(|duration| SystemTime::now() + duration);
-                self.mem_table.insert(key.to_vec(), value);
+                value.expires_at = new_ttl.and_then(|duration| SystemTime::now().checked_add(duration));
+                self.tx_manager.lock().unwrap().bump_key_version(key);
+                self.mem_table_insert(key.to_vec(), value);
                 return Ok(true);
             }
         }
         Ok(false)
     }
 
+    /// Rewrites SSTables stored in an older format version into the current format.
+    ///
+    /// SSTables with a format version between `MIN_SUPPORTED_VERSION` and
+    /// `CURRENT_VERSION` (currently 1 to 4) are readable as-is, so migrating is
+    /// not required to access their data. It does, however, give version 1 files a
+    /// stored bloom filter and per-entry checksums, and it must be done before the next
+    /// format bump drops support for the oldest version. Each file is rewritten
+    /// under the same name, so the relative age of SSTables is preserved.
+    ///
+    /// Files outside the supported window are rejected by `load` rather than
+    /// skipped, so they never reach this method.
+    ///
+    /// # Errors
+    /// Returns `TreeError` if an SSTable cannot be read or rewritten. Files
+    /// migrated before the failure keep their new format.
+    pub fn migrate(&mut self) -> TreeResult<()> {
+        let legacy_tables: Vec<PathBuf> = self
+            .ss_tables
+            .iter()
+            .filter(|path| self.legacy_sstables.contains_key(*path))
+            .cloned()
+            .collect();
+
+        for path in legacy_tables {
+            self.migrate_sstable(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Compacts only the SSTables whose key ranges overlap `[start, end)`.
+    ///
+    /// Tables are selected by their first and last keys, merged into a single
+    /// table, and tombstones and expired entries inside the range are dropped.
+    /// Keys outside the range that live in the selected tables are carried over
+    /// unchanged. This is useful after heavy churn in one key range, when a full
+    /// compaction would rewrite far more data than necessary.
+    ///
+    /// Memory tables are not affected; call `flush` first to include recent writes.
+    ///
+    /// # Arguments
+    /// * `start` - Inclusive lower bound of the key range
+    /// * `end` - Exclusive upper bound of the key range
+    ///
+    /// # Errors
+    /// Returns `TreeError` if a selected SSTable cannot be read or the merged
+    /// table cannot be written.
+    pub fn compact_range(&mut self, start: &[u8], end: &[u8]) -> TreeResult<()> {
+        if start >= end {
+            return Ok(());
+        }
+        self.wait_for_compactions()?;
+        self.compact_sstable_range(start, end)
+    }
+
     /// Flushes the current memory table to disk.
     ///
-    /// This forces all data in the active memory table to be written
-    /// to an SSTable file on disk.
+    /// This forces all data in the active memory table, and any memory table
+    /// left queued by an earlier failed flush, to be written to SSTable files on disk.
+    /// It then waits for the SSTable merges requested so far to be installed.
     pub fn flush(&mut self) -> TreeResult<()> {
-        if !self.mem_table.is_empty() {
+        if !self.mem_table.is_empty() || !self.mem_range_tombstones.is_empty() {
             self.flush_mem_table()?;
         }
-        Ok(())
+        while !self.immutable_mem_tables.is_empty() {
+            self.compact()?;
+        }
+        self.wait_for_compactions()
+    }
+
+    /// Flushes the memory table if the configured `flush_interval` has elapsed.
+    ///
+    /// The background timer only marks a flush as due; this method performs it.
+    /// It is called automatically at the start of `put` and `delete`, and can be
+    /// called directly by callers that only read or leave the tree idle for long periods.
+    /// Flushes and merges finished by the background workers are installed here too.
+    ///
+    /// # Returns
+    /// `true` if the memory table was flushed, `false` otherwise; with
+    /// `background_flush` its SSTable may still be written when this returns
+    pub fn flush_if_due(&mut self) -> TreeResult<bool> {
+        self.poll_flush(false)?;
+        self.poll_compactions(false)?;
+        if !self.flush_due.load(Ordering::Acquire) {
+            return Ok(false);
+        }
+
+        if self.mem_table.is_empty() {
+            self.flush_due.store(false, Ordering::Release);
+            return Ok(false);
+        }
+
+        let is_due = match (self.settings.flush_interval, self.mem_table_since) {
+            (Some(interval), Some(since)) => since.elapsed() >= interval,
+            _ => false,
+        };
+        if !is_due {
+            return Ok(false);
+        }
+
+        self.flush_due.store(false, Ordering::Release);
+        self.rotate_mem_table()?;
+        Ok(true)
     }
 
-    fn flush_mem_table(&mut self) -> TreeResult<()> {
-        let immutable = std::mem::take(&mut self.mem_table);
-        self.immutable_mem_tables.push_back(immutable);
+    fn start_flush_timer(&mut self) {
+        self.flush_timer_shutdown = None;
+        if let Some(interval) = self.settings.flush_interval {
+            let (shutdown_sender, shutdown_receiver) = mpsc::channel::<()>();
+            let flush_due = Arc::clone(&self.flush_due);
+            thread::spawn(move || {
+                Self::flush_timer_worker(shutdown_receiver, flush_due, interval);
+            });
+            self.flush_timer_shutdown = Some(shutdown_sender);
+        }
+    }
+
+    fn flush_timer_worker(receiver: mpsc::Receiver<()>, flush_due: Arc<AtomicBool>, interval: Duration) {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = receiver.recv_timeout(interval) {
+            flush_due.store(true, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn flush_mem_table(&mut self) -> TreeResult<()> {
+        self.limit_immutable_mem_tables()?;
+        let immutable = self.take_mem_table();
+        self.immutable_mem_tables.push_back(Arc::new(immutable));
         self.compact()
     }
 
-    fn compact(&mut self) -> TreeResult<()> {
+    pub(crate) fn compact(&mut self) -> TreeResult<()> {
+        self.write_oldest_mem_table()?;
+        if self.merge_due() {
+            self.request_compaction()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the oldest immutable memory table to one or more SSTables without
+    /// requesting a merge.
+    pub(crate) fn write_oldest_mem_table(&mut self) -> TreeResult<()> {
+        self.poll_flush(true)?;
         if self.immutable_mem_tables.is_empty() {
             return Ok(());
         }
 
         let immutable_table = match self.immutable_mem_tables.pop_front() {
-            Some(table) => table,
+            Some(table) => Arc::unwrap_or_clone(table),
             None => return Ok(()),
         };
 
-        let (path, bloom_filter) = self.write_sstable(&immutable_table)?;
-
-        self.ss_tables.push(path.clone());
-        if self.settings.enable_bloom_filter_cache {
-            self.bloom_filters.push(BloomFilter { path, bloom_filter });
+        // A split flush writes consecutive SSTable numbers, and the boundary only
+        // counts once the last of them is on disk.
+        let max_entries = self.settings.sstable_max_entries.unwrap_or(usize::MAX).max(1);
+        let tables = immutable_table.len().div_ceil(max_entries).max(1);
+        if self.immutable_mem_tables.is_empty() {
+            self.record_wal_boundary((self.next_sstable_number() + tables - 1) as u64)?;
         }
 
-        if let Some(ref mut wal_writer) = self.wal_writer {
-            wal_writer
-                .write_checkpoint()
-                .map_err(|e| TreeError::wal(format!("Failed to write checkpoint: {}", e)))?;
-
-            self.check_wal_segments_need_to_be_shifted()?;
-
-            let next_segment = self.get_next_wal_segment_number();
-            self.create_new_wal_segment(next_segment)?;
+        let mut remaining = immutable_table;
+        loop {
+            let rest = match remaining.keys().nth(max_entries).cloned() {
+                Some(split_key) => remaining.split_off(&split_key),
+                None => BTreeMap::new(),
+            };
+            let table = std::mem::replace(&mut remaining, rest);
+            // The newest table carries the range tombstones, so a merge, which
+            // starts at the oldest table, includes every table they can shadow.
+            let range_tombstones = if remaining.is_empty() {
+                std::mem::take(&mut self.mem_range_tombstones)
+            } else {
+                Vec::new()
+            };
+            let written = match self.check_cancelled() {
+                Ok(()) => self.write_sstable(&table, range_tombstones.clone()).map_err(TreeError::from),
+                Err(e) => Err(e),
+            };
+            let (path, bloom_filter) = match written {
+                Ok(written) => written,
+                Err(e) => {
+                    error!("Error flushing memory table to SSTable: {}", e);
+                    if !range_tombstones.is_empty() {
+                        self.mem_range_tombstones = range_tombstones;
+                    }
+                    let mut unwritten = table;
+                    unwritten.append(&mut remaining);
+                    self.immutable_mem_tables.push_front(Arc::new(unwritten));
+                    return Err(e);
+                }
+            };
+
+            self.emit_event(TreeEvent::Flushed {
+                path: path.clone(),
+                entries: table.len(),
+            });
+            self.ss_tables.push(path.clone());
+            self.record_flush(&path);
+            self.cache_bloom_filter(path, bloom_filter);
+            if remaining.is_empty() {
+                break;
+            }
         }
+        let newest = self.ss_tables.last().expect("a flush writes at least one SSTable");
+        self.record_flushed_sstable(newest)
+            .map_err(|e| TreeError::io(format!("Failed to write MANIFEST: {}", e)))?;
 
-        if self.ss_tables.len() > 2 {
-            self.merge_sstables()?;
+        // A checkpoint claims everything logged so far is in SSTables, which only
+        // holds once no older memory table is still waiting to be written.
+        if self.immutable_mem_tables.is_empty() {
+            self.checkpoint_wal()?;
         }
 
         Ok(())
     }
 
+    fn decompress_value_into(&self, data: &[u8], buf: &mut Vec<u8>) -> TreeResult<()> {
+        self.settings.compressor.decompress_into(data, buf).map_err(|e| {
+            buf.clear();
+            error!("Error decompressing value: {}", e);
+            TreeError::compression(format!("Decompression failed: {}", e))
+        })
+    }
+
     fn decompress_value_data(&self, data: &[u8]) -> TreeResult<Option<Vec<u8>>> {
         match self.apply_decompression(data) {
             Ok(decompressed) => Ok(Some(decompressed)),
@@ -0,0 +1,72 @@
+use crate::DataValue;
+use bincode::{Decode, Encode};
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::time::SystemTime;
+
+/// Deletion marker covering every key in `[start, end)`.
+///
+/// A range tombstone shadows each value in its range that was written at or
+/// before `created_at`. Values written later are not affected, so the newest
+/// timestamp wins between a range tombstone, point writes and point tombstones.
+#[derive(Clone, Debug, Encode, Decode, Eq, PartialEq)]
+pub(crate) struct RangeTombstone {
+    pub(crate) start: Vec<u8>,
+    /// Exclusive upper bound, `None` when the range is unbounded above.
+    pub(crate) end: Option<Vec<u8>>,
+    pub(crate) created_at: SystemTime,
+}
+
+impl RangeTombstone {
+    /// Creates a range tombstone covering every key starting with `prefix`.
+    pub(crate) fn for_prefix(prefix: &[u8], created_at: SystemTime) -> Self {
+        Self {
+            start: prefix.to_vec(),
+            end: prefix_upper_bound(prefix),
+            created_at,
+        }
+    }
+
+    pub(crate) fn covers(&self, key: &[u8]) -> bool {
+        key >= self.start.as_slice() && self.end.as_ref().is_none_or(|end| key < end.as_slice())
+    }
+
+    /// Checks whether `value` stored under `key` is deleted by this tombstone.
+    pub(crate) fn shadows(&self, key: &[u8], value: &DataValue) -> bool {
+        self.covers(key) && value.created_at <= self.created_at
+    }
+
+    /// Checks whether the tombstone lies entirely inside `[start, end)`.
+    pub(crate) fn is_within(&self, start: &[u8], end: &[u8]) -> bool {
+        self.start.as_slice() >= start && self.end.as_ref().is_some_and(|own_end| own_end.as_slice() <= end)
+    }
+
+    /// Returns the keys of `table` whose values this tombstone shadows.
+    pub(crate) fn shadowed_keys(&self, table: &BTreeMap<Vec<u8>, DataValue>) -> Vec<Vec<u8>> {
+        let bounds = (
+            Bound::Included(self.start.as_slice()),
+            match &self.end {
+                Some(end) => Bound::Excluded(end.as_slice()),
+                None => Bound::Unbounded,
+            },
+        );
+        table
+            .range::<[u8], _>(bounds)
+            .filter(|(_, value)| value.created_at <= self.created_at)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+}
+
+/// Returns the smallest key greater than every key starting with `prefix`,
+/// or `None` if no such key exists (the prefix is empty or all `0xff`).
+pub(crate) fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper = prefix.to_vec();
+    while let Some(last) = upper.pop() {
+        if last < u8::MAX {
+            upper.push(last + 1);
+            return Some(upper);
+        }
+    }
+    None
+}
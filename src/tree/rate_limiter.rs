@@ -0,0 +1,65 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Token bucket limiting the bytes per second written to SSTables.
+///
+/// Flushes and merges ask it for permission before writing each chunk of an
+/// SSTable, so a long merge cannot saturate the disk and starve foreground
+/// reads. The bucket holds up to one second of writes, letting short bursts
+/// through at full speed. One limiter can be shared by several trees through
+/// `TreeSettingsBuilder::rate_limiter`, in which case they split the rate.
+pub struct RateLimiter {
+    bytes_per_second: u64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    available: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `bytes_per_second` bytes of SSTable writes
+    /// per second; a rate of 0 is raised to 1.
+    pub fn new(bytes_per_second: u64) -> Self {
+        let bytes_per_second = bytes_per_second.max(1);
+        Self {
+            bytes_per_second,
+            bucket: Mutex::new(Bucket {
+                available: bytes_per_second as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Returns the configured rate in bytes per second.
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    /// Blocks until `bytes` more bytes may be written.
+    ///
+    /// The bytes are taken from the bucket right away, possibly driving it
+    /// negative, and the caller sleeps until the debt is paid off; callers
+    /// sharing the limiter queue up behind each other's debt.
+    pub(crate) fn acquire(&self, bytes: u64) {
+        let rate = self.bytes_per_second as f64;
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * rate;
+            bucket.available = (bucket.available + refill).min(rate);
+            bucket.refilled_at = now;
+            bucket.available -= bytes as f64;
+            if bucket.available < 0.0 {
+                Duration::from_secs_f64(-bucket.available / rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
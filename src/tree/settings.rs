@@ -1,10 +1,23 @@
 use crate::config::{
-    BINCODE_CONFIG, DEFAULT_BLOOM_FILTER_ERROR_PROBABILITY, DEFAULT_DB_PATH,
-    DEFAULT_INDEX_CACHE_LRU_MAX_CAPACITY, DEFAULT_INDEX_CACHE_MEMORY_LIMIT, DEFAULT_MEM_TABLE_SIZE,
-    DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY, DEFAULT_VALUE_CACHE_MEMORY_LIMIT, DEFAULT_WAL_MAX_SIZE,
+    BINCODE_CONFIG, DEFAULT_BLOOM_FILTER_ERROR_PROBABILITY, DEFAULT_CACHE_EVICTION_WATERMARK, DEFAULT_COMPACTION_MAX_TABLES,
+    DEFAULT_COMPACTION_MIN_TABLES, DEFAULT_DB_PATH,
+    DEFAULT_INDEX_BLOCK_INTERVAL, DEFAULT_INDEX_CACHE_LRU_MAX_CAPACITY, DEFAULT_INDEX_CACHE_MEMORY_LIMIT, DEFAULT_MAX_OPEN_FILES,
+    DEFAULT_MAX_IMMUTABLE_MEM_TABLES, DEFAULT_MAX_QUEUED_COMPACTIONS, DEFAULT_MEM_TABLE_SIZE, DEFAULT_READ_BUFFER_SIZE, DEFAULT_READ_PARALLELISM,
+    DEFAULT_RECOVERY_PROGRESS_INTERVAL, DEFAULT_TOMBSTONE_GRACE_PERIOD,
+    DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY, DEFAULT_VALUE_CACHE_MEMORY_LIMIT, DEFAULT_WAL_MAX_SIZE, DEFAULT_WRITE_BUFFER_SIZE,
 };
-use crate::tree::{CompressionConfig, Compressor};
+use crate::tree::tree_error::{TreeError, TreeResult};
+use crate::tree::{CompressionConfig, Compressor, RateLimiter, TreeEvent, TypedEncoding, ValueCacheAdmission};
 use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+/// Callback reporting WAL recovery progress, see
+/// `TreeSettingsBuilder::recovery_progress`.
+///
+/// Called with the number of entries read for replay so far, the number of
+/// WAL segments and the 1-based number of the segment being read.
+pub type RecoveryProgress = Arc<dyn Fn(usize, usize, usize) + Send + Sync>;
 
 /// Configuration settings for the LSM Tree database.
 ///
@@ -17,20 +30,67 @@ use std::path::PathBuf;
 /// ## Storage Configuration
 /// - `db_path`: The filesystem path where the database files will be stored
 /// - `bincode_config`: Configuration for the bincode serialization library
+/// - `typed_encoding`: Byte order and integer encoding of values stored with `put_typed`
 ///
 /// ## Memory Management
 /// - `mem_table_max_size`: Maximum number of entries in the memory table before flushing to disk
+/// - `mem_table_max_bytes`: Optional maximum size of the memory table in bytes before flushing to disk
+/// - `flush_interval`: Optional maximum age of unflushed memory table data
+/// - `sstable_max_entries`: Optional number of entries after which a flush starts a new SSTable
+/// - `overwrite_in_place`: Whether same-size overwrites reuse the memory table's value buffer
+/// - `background_flush`: Whether full memory tables are written to SSTables by a background thread
+/// - `max_immutable_mem_tables`: Full memory tables that may wait to be written before writes block
+///
+/// ## Expiration
+/// - `default_ttl`: Optional TTL applied to writes that do not specify one
+/// - `max_ttl`: Optional upper bound on the TTL of any write
+/// - `max_ttl_policy`: Whether writes beyond `max_ttl` are clamped or rejected
+///
+/// ## Directory Checks
+/// - `strict_directory`: Whether loading fails on files that do not belong to the database
+/// - `strict_directory_ignore`: File names tolerated in strict mode
+/// - `check_writable`: Whether opening a tree first probes that the database directory is writable
 ///
 /// ## Bloom Filter Desired Error Probability
 /// - `bloom_filter_error_probability`: The desired error probability (eg. 0.05, 0.01)
 ///
 /// ## Caching Options
 /// - `enable_index_cache`: Whether to enable caching of SSTable indexes in memory
+/// - `index_block_interval`: Data entries per SSTable index entry; 1 keeps a dense index
 /// - `enable_value_cache`: Whether to enable caching of frequently accessed values
+/// - `value_cache_admission`: Which values read from SSTables are stored in the value cache
+/// - `max_cacheable_value_bytes`: Optional size above which values are never cached
+/// - `cache_eviction_watermark`: Fraction of a cache's limits an overflowing insert evicts down to
+/// - `repoint_value_cache_on_merge`: Whether cached values still current after a merge move to the merged SSTable
+/// - `scan_error_policy`: Whether scans fail or skip entries that cannot be decompressed
+/// - `max_open_files`: Number of SSTable file handles kept open for reads (0 disables pooling)
+/// - `read_parallelism`: Number of threads `Tree::par_multi_get` reads SSTables with
+///
+/// ## I/O Buffers
+/// - `read_buffer_size`: Capacity in bytes of the buffered readers over SSTables and WAL segments
+/// - `write_buffer_size`: Capacity in bytes of the buffered writers of SSTables and WAL segments
+///
+/// ## Write-Ahead Log
+/// - `enable_wal`: Whether writes are logged to the WAL
+/// - `wal_max_size`: Segment size in bytes after which the WAL rotates to a new segment
+/// - `wal_max_entries`: Optional number of entries after which the WAL rotates
+/// - `wal_rotate_on_flush`: Whether every flush also starts a new segment
+/// - `sync_directory`: Whether the database directory is synced after files are created or renamed
+/// - `recovery_progress`: Optional callback reporting WAL recovery progress
+/// - `recovery_progress_interval`: Entries read between two progress reports
+///
+/// ## Compaction
+/// - `max_queued_compactions`: Merge requests the background worker may have waiting (0 merges on the write path)
+/// - `tombstone_grace_period`: Minimum age of a tombstone before a merge may drop it
+/// - `compaction`: When SSTables are merged and how many at once
+/// - `rate_limiter`: Optional limit on the bytes per second flushes and merges write to SSTables
 ///
 /// ## Compression
 /// - `compressor`: The compression algorithm and settings to use for data storage
 ///
+/// ## Events
+/// - `event_sender`: Optional channel receiving `TreeEvent` lifecycle events
+///
 /// # Performance Tuning
 ///
 /// ## Memory Table Size
@@ -48,22 +108,56 @@ use std::path::PathBuf;
 /// - **LZ4**: Fast compression/decompression, moderate compression ratio
 /// - **Zstd**: Better compression ratio, moderate speed
 /// - **Snappy**: Very fast, good for high-throughput scenarios
+/// - **Gzip**: Standard gzip values readable by external tools; slowest, for interoperability
 #[derive(Clone)]
 pub struct TreeSettings {
     pub db_path: PathBuf,
     pub bincode_config: bincode::config::Configuration,
+    pub typed_encoding: TypedEncoding,
     pub mem_table_max_size: usize,
+    pub mem_table_max_bytes: Option<usize>,
     pub bloom_filter_error_probability: f64,
     pub enable_bloom_filter_cache: bool,
     pub enable_index_cache: bool,
+    pub index_block_interval: usize,
     pub index_cache_memory_limit: usize,
     pub index_cache_max_capacity: usize,
     pub enable_value_cache: bool,
     pub value_cache_memory_limit: usize,
     pub value_cache_max_capacity: usize,
+    pub value_cache_admission: ValueCacheAdmission,
+    pub max_cacheable_value_bytes: Option<usize>,
+    pub cache_eviction_watermark: f64,
+    pub repoint_value_cache_on_merge: bool,
+    pub scan_error_policy: ReadErrorPolicy,
+    pub max_open_files: usize,
+    pub read_parallelism: usize,
+    pub read_buffer_size: usize,
+    pub write_buffer_size: usize,
     pub enable_wal: bool,
     pub wal_max_size: u64,
+    pub wal_max_entries: Option<usize>,
+    pub wal_rotate_on_flush: bool,
+    pub sync_directory: bool,
+    pub recovery_progress: Option<RecoveryProgress>,
+    pub recovery_progress_interval: usize,
+    pub max_queued_compactions: usize,
+    pub tombstone_grace_period: Duration,
+    pub compaction: CompactionConfig,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
     pub compressor: Compressor,
+    pub flush_interval: Option<Duration>,
+    pub sstable_max_entries: Option<usize>,
+    pub overwrite_in_place: bool,
+    pub background_flush: bool,
+    pub max_immutable_mem_tables: usize,
+    pub default_ttl: Option<Duration>,
+    pub max_ttl: Option<Duration>,
+    pub max_ttl_policy: TtlLimitPolicy,
+    pub strict_directory: bool,
+    pub strict_directory_ignore: Vec<String>,
+    pub check_writable: bool,
+    pub event_sender: Option<mpsc::SyncSender<TreeEvent>>,
 }
 
 impl Default for TreeSettings {
@@ -71,22 +165,93 @@ impl Default for TreeSettings {
         Self {
             db_path: PathBuf::from(DEFAULT_DB_PATH),
             bincode_config: BINCODE_CONFIG,
+            typed_encoding: TypedEncoding::STANDARD,
             mem_table_max_size: DEFAULT_MEM_TABLE_SIZE as usize,
+            mem_table_max_bytes: None,
             bloom_filter_error_probability: DEFAULT_BLOOM_FILTER_ERROR_PROBABILITY,
             enable_bloom_filter_cache: true,
             enable_index_cache: true,
+            index_block_interval: DEFAULT_INDEX_BLOCK_INTERVAL,
             index_cache_memory_limit: DEFAULT_INDEX_CACHE_MEMORY_LIMIT,
             index_cache_max_capacity: DEFAULT_INDEX_CACHE_LRU_MAX_CAPACITY,
             enable_value_cache: true,
             value_cache_memory_limit: DEFAULT_VALUE_CACHE_MEMORY_LIMIT,
             value_cache_max_capacity: DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY,
+            value_cache_admission: ValueCacheAdmission::Always,
+            max_cacheable_value_bytes: None,
+            cache_eviction_watermark: DEFAULT_CACHE_EVICTION_WATERMARK,
+            repoint_value_cache_on_merge: true,
+            scan_error_policy: ReadErrorPolicy::FailFast,
+            max_open_files: DEFAULT_MAX_OPEN_FILES,
+            read_parallelism: DEFAULT_READ_PARALLELISM,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
             enable_wal: true,
             wal_max_size: DEFAULT_WAL_MAX_SIZE,
+            wal_max_entries: None,
+            wal_rotate_on_flush: false,
+            sync_directory: true,
+            recovery_progress: None,
+            recovery_progress_interval: DEFAULT_RECOVERY_PROGRESS_INTERVAL,
+            max_queued_compactions: DEFAULT_MAX_QUEUED_COMPACTIONS,
+            tombstone_grace_period: DEFAULT_TOMBSTONE_GRACE_PERIOD,
+            compaction: CompactionConfig::default(),
+            rate_limiter: None,
             compressor: Compressor::new(CompressionConfig::none()),
+            flush_interval: None,
+            sstable_max_entries: None,
+            overwrite_in_place: false,
+            background_flush: false,
+            max_immutable_mem_tables: DEFAULT_MAX_IMMUTABLE_MEM_TABLES,
+            default_ttl: None,
+            max_ttl: None,
+            max_ttl_policy: TtlLimitPolicy::Clamp,
+            strict_directory: false,
+            strict_directory_ignore: Vec::new(),
+            check_writable: true,
+            event_sender: None,
         }
     }
 }
 
+impl TreeSettings {
+    /// Checks that the database directory can be written to.
+    ///
+    /// Creates the directory if needed, then writes, syncs and removes a small
+    /// probe file, so a read-only filesystem or missing permissions are
+    /// reported when the tree is opened rather than by the first flush.
+    /// `Tree::new_with_settings` and `Tree::load_with_settings` call it unless
+    /// `check_writable` is disabled.
+    ///
+    /// # Errors
+    /// Returns `TreeError::Configuration` naming the directory if any step fails
+    pub fn validate_path_writable(&self) -> TreeResult<()> {
+        let db_path = if self.db_path.as_os_str().is_empty() {
+            PathBuf::from(DEFAULT_DB_PATH)
+        } else {
+            self.db_path.clone()
+        };
+        if db_path.is_file() {
+            return Err(TreeError::configuration(format!(
+                "Database path {:?} is a file; the database path must be a directory",
+                db_path
+            )));
+        }
+        let not_writable = |e: std::io::Error| {
+            TreeError::configuration(format!("Database directory {:?} is not writable: {}", db_path, e))
+        };
+
+        std::fs::create_dir_all(&db_path).map_err(not_writable)?;
+        let probe_path = db_path.join(".write_probe");
+        let probe = std::fs::File::create(&probe_path).and_then(|mut file| {
+            std::io::Write::write_all(&mut file, b"redish")?;
+            file.sync_all()
+        });
+        let removed = std::fs::remove_file(&probe_path);
+        probe.and(removed).map_err(not_writable)
+    }
+}
+
 /// A builder for creating `TreeSettings` with a fluent API.
 ///
 /// `TreeSettingsBuilder` provides a convenient way to construct `TreeSettings` instances
@@ -99,6 +264,9 @@ impl Default for TreeSettings {
 /// 2. Configure options using the various setter methods
 /// 3. Build the final `TreeSettings` with `build()`
 ///
+/// Instead of `new()`, a builder can start from a workload preset:
+/// `for_write_heavy()`, `for_read_heavy()` or `for_low_memory()`.
+///
 /// # Default Values
 /// Any options not explicitly set will use their default values:
 /// - `db_path`: Uses `DEFAULT_DB_PATH` from config
@@ -112,18 +280,51 @@ impl Default for TreeSettings {
 pub struct TreeSettingsBuilder {
     db_path: Option<PathBuf>,
     bincode_config: Option<bincode::config::Configuration>,
+    typed_encoding: TypedEncoding,
     mem_table_max_size: Option<usize>,
+    mem_table_max_bytes: Option<usize>,
     bloom_filter_error_probability: Option<f64>,
     enable_bloom_filter_cache: Option<bool>,
     enable_index_cache: Option<bool>,
+    index_block_interval: Option<usize>,
     index_cache_memory_limit: Option<usize>,
     index_cache_max_capacity: Option<usize>,
     enable_value_cache: Option<bool>,
     value_cache_memory_limit: Option<usize>,
     value_cache_max_capacity: Option<usize>,
+    value_cache_admission: Option<ValueCacheAdmission>,
+    max_cacheable_value_bytes: Option<usize>,
+    cache_eviction_watermark: Option<f64>,
+    repoint_value_cache_on_merge: Option<bool>,
+    scan_error_policy: Option<ReadErrorPolicy>,
+    max_open_files: Option<usize>,
+    read_parallelism: Option<usize>,
+    read_buffer_size: Option<usize>,
+    write_buffer_size: Option<usize>,
     enable_wal: Option<bool>,
     wal_max_size: Option<u64>,
+    wal_max_entries: Option<usize>,
+    wal_rotate_on_flush: Option<bool>,
+    sync_directory: Option<bool>,
+    recovery_progress: Option<RecoveryProgress>,
+    recovery_progress_interval: Option<usize>,
+    max_queued_compactions: Option<usize>,
+    tombstone_grace_period: Option<Duration>,
+    compaction: Option<CompactionConfig>,
+    rate_limiter: Option<Arc<RateLimiter>>,
     compressor: Option<Compressor>,
+    flush_interval: Option<Duration>,
+    sstable_max_entries: Option<usize>,
+    overwrite_in_place: Option<bool>,
+    background_flush: Option<bool>,
+    max_immutable_mem_tables: Option<usize>,
+    default_ttl: Option<Duration>,
+    max_ttl: Option<Duration>,
+    max_ttl_policy: Option<TtlLimitPolicy>,
+    strict_directory: Option<bool>,
+    strict_directory_ignore: Vec<String>,
+    check_writable: Option<bool>,
+    event_sender: Option<mpsc::SyncSender<TreeEvent>>,
 }
 
 impl TreeSettingsBuilder {
@@ -131,25 +332,130 @@ impl TreeSettingsBuilder {
     ///
     /// # Returns
     /// A new builder with all fields set to None
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             db_path: None,
             bincode_config: None,
+            typed_encoding: TypedEncoding::STANDARD,
             mem_table_max_size: None,
+            mem_table_max_bytes: None,
             bloom_filter_error_probability: None,
             enable_bloom_filter_cache: None,
             enable_index_cache: None,
+            index_block_interval: None,
             index_cache_memory_limit: None,
             index_cache_max_capacity: None,
             enable_value_cache: None,
             value_cache_memory_limit: None,
             value_cache_max_capacity: None,
+            value_cache_admission: None,
+            max_cacheable_value_bytes: None,
+            cache_eviction_watermark: None,
+            repoint_value_cache_on_merge: None,
+            scan_error_policy: None,
+            max_open_files: None,
+            read_parallelism: None,
+            read_buffer_size: None,
+            write_buffer_size: None,
             enable_wal: None,
             wal_max_size: None,
+            wal_max_entries: None,
+            wal_rotate_on_flush: None,
+            sync_directory: None,
+            recovery_progress: None,
+            recovery_progress_interval: None,
+            max_queued_compactions: None,
+            tombstone_grace_period: None,
+            compaction: None,
+            rate_limiter: None,
             compressor: None,
+            flush_interval: None,
+            sstable_max_entries: None,
+            overwrite_in_place: None,
+            background_flush: None,
+            max_immutable_mem_tables: None,
+            default_ttl: None,
+            max_ttl: None,
+            max_ttl_policy: None,
+            strict_directory: None,
+            strict_directory_ignore: Vec::new(),
+            check_writable: None,
+            event_sender: None,
         }
     }
 
+    /// Creates a builder preset for write-heavy workloads.
+    ///
+    /// Changes exactly these fields, everything else keeps its default:
+    /// - `mem_table_max_size`: 100 000 entries, so flushes and merges happen less often
+    /// - `wal_max_size`: 64 MiB, so WAL segments rotate less often
+    /// - `wal_rotate_on_flush`: `false`; writes are not synced to disk unless
+    ///   requested through `WriteOptions::sync` or `Tree::sync`
+    /// - `compressor`: `CompressionConfig::balanced()` (LZ4)
+    ///
+    /// Any setter called on the returned builder overrides the preset.
+    ///
+    /// # Returns
+    /// A builder configured for write-heavy workloads
+    pub fn for_write_heavy() -> Self {
+        Self::new()
+            .mem_table_max_size(100_000)
+            .wal_max_size(64 * 1024 * 1024)
+            .wal_rotate_on_flush(false)
+            .compressor(CompressionConfig::balanced())
+    }
+
+    /// Creates a builder preset for read-heavy workloads.
+    ///
+    /// Changes exactly these fields, everything else keeps its default:
+    /// - `bloom_filter_error_probability`: 0.001, so fewer lookups read an SSTable needlessly
+    /// - `index_cache_max_capacity`: 1 000 indexes
+    /// - `index_cache_memory_limit`: 512 MiB
+    /// - `value_cache_max_capacity`: 1 000 000 values
+    /// - `value_cache_memory_limit`: 1 GiB
+    /// - `max_open_files`: 256 handles
+    /// - `compressor`: `CompressionConfig::best()` (Zstd level 9)
+    ///
+    /// Any setter called on the returned builder overrides the preset.
+    ///
+    /// # Returns
+    /// A builder configured for read-heavy workloads
+    pub fn for_read_heavy() -> Self {
+        Self::new()
+            .bloom_filter_error_probability(0.001)
+            .index_cache_max_capacity(1_000)
+            .index_cache_memory_limit(512 * 1024 * 1024)
+            .value_cache_max_capacity(1_000_000)
+            .value_cache_memory_limit(1024 * 1024 * 1024)
+            .max_open_files(256)
+            .compressor(CompressionConfig::best())
+    }
+
+    /// Creates a builder preset for memory-constrained environments.
+    ///
+    /// Changes exactly these fields, everything else keeps its default:
+    /// - `mem_table_max_size`: 1 000 entries, flushing the memory table by entry count early
+    /// - `index_cache_max_capacity`: 10 indexes
+    /// - `index_cache_memory_limit`: 8 MiB
+    /// - `value_cache_max_capacity`: 10 000 values
+    /// - `value_cache_memory_limit`: 16 MiB
+    /// - `max_open_files`: 16 handles
+    ///
+    /// Any setter called on the returned builder overrides the preset.
+    ///
+    /// # Returns
+    /// A builder configured for low memory usage
+    pub fn for_low_memory() -> Self {
+        Self::new()
+            .mem_table_max_size(1_000)
+            .index_cache_max_capacity(10)
+            .index_cache_memory_limit(8 * 1024 * 1024)
+            .value_cache_max_capacity(10_000)
+            .value_cache_memory_limit(16 * 1024 * 1024)
+            .max_open_files(16)
+    }
+
     /// Sets the database path.
     ///
     /// # Arguments
@@ -174,6 +480,38 @@ impl TreeSettingsBuilder {
         self
     }
 
+    /// Encodes integers in typed values with a fixed width instead of varints.
+    ///
+    /// Can be combined with `bincode_big_endian` for fixed-width, order-preserving
+    /// numeric encodings.
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Existing Data
+    /// Stored values are not re-encoded. Values written with a different encoding
+    /// must be read with `Tree::get_typed_with_encoding`.
+    pub fn bincode_fixed_int(mut self) -> Self {
+        self.typed_encoding.fixed_int = true;
+        self
+    }
+
+    /// Encodes typed values in big-endian byte order.
+    ///
+    /// Together with `bincode_fixed_int`, unsigned integers encode to bytes that
+    /// sort in numeric order.
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Existing Data
+    /// Stored values are not re-encoded. Values written with a different encoding
+    /// must be read with `Tree::get_typed_with_encoding`.
+    pub fn bincode_big_endian(mut self) -> Self {
+        self.typed_encoding.big_endian = true;
+        self
+    }
+
     /// Sets the maximum memory table size.
     ///
     /// # Arguments
@@ -186,6 +524,27 @@ impl TreeSettingsBuilder {
         self
     }
 
+    /// Sets the maximum memory table size in bytes.
+    ///
+    /// The memory table is flushed once its keys and values, counted with
+    /// `DataValue::heap_size`, take more than `bytes`, so the flush size stays
+    /// predictable whether values are 10 bytes or 1 MB. Applies alongside
+    /// `mem_table_max_size`: whichever limit is exceeded first triggers the
+    /// flush. Values are counted after compression.
+    ///
+    /// # Arguments
+    /// * `bytes` - Maximum size of the memory table in bytes
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// None; only `mem_table_max_size` limits the memory table.
+    pub fn mem_table_max_bytes(mut self, bytes: usize) -> Self {
+        self.mem_table_max_bytes = Some(bytes);
+        self
+    }
+
     /// Sets the bloom filter desired error probability.
     ///
     /// # Arguments
@@ -214,7 +573,7 @@ impl TreeSettingsBuilder {
     ///
     /// # Arguments
     /// * `is_enabled` - `true` to enable bloom filter caching,
-    ///                  `false` to disable it
+    ///   `false` to disable it
     ///
     /// # Returns
     /// Returns `Self` to enable method chaining
@@ -274,6 +633,115 @@ impl TreeSettingsBuilder {
         self
     }
 
+    /// Sets which values read from SSTables are admitted into the value cache.
+    ///
+    /// Large one-shot scans otherwise fill the cache with values that are read
+    /// once and evict genuinely hot entries. `ValueCacheAdmission::Never` keeps
+    /// reads from populating the cache while still serving cached values, and
+    /// `ValueCacheAdmission::MaxValueSize` only admits values up to a size.
+    /// The policy can be changed at runtime with `Tree::set_value_cache_admission`.
+    ///
+    /// # Arguments
+    /// * `admission` - The admission policy
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `ValueCacheAdmission::Always`
+    pub fn value_cache_admission(mut self, admission: ValueCacheAdmission) -> Self {
+        self.value_cache_admission = Some(admission);
+        self
+    }
+
+    /// Sets the largest value, in stored data bytes, the value cache accepts.
+    ///
+    /// Unlike the admission policy this is a fixed limit of the cache itself:
+    /// a larger value is never cached, however the policy is switched at
+    /// runtime, so one read of a big blob cannot evict many small hot entries.
+    /// Skipped inserts are counted in `CacheStats::skipped_for_size`.
+    ///
+    /// Both limits are inclusive: a value of exactly `max_bytes` is cached, as
+    /// `ValueCacheAdmission::MaxValueSize(max_bytes)` would admit it. A value is
+    /// cached only if it passes both, so the effective limit is the smaller
+    /// one; values the admission policy rejects never reach this check and are
+    /// not counted as skipped.
+    ///
+    /// # Arguments
+    /// * `max_bytes` - Largest cacheable value in bytes
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// No limit beyond the value cache memory limit.
+    pub fn max_cacheable_value_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_cacheable_value_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Sets how far the index and value caches evict once an insert overflows them.
+    ///
+    /// An overflowing insert evicts least recently used entries until the
+    /// cache holds at most this fraction of its capacity and memory limit,
+    /// in one pass. `1.0` frees just enough room for the new entry; a lower
+    /// value such as `0.9` leaves headroom, so a burst of large inserts does
+    /// not evict on every one of them. Values are clamped to `0.0..=1.0`.
+    ///
+    /// # Arguments
+    /// * `watermark` - Fraction of the cache limits to evict down to
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `1.0`
+    pub fn cache_eviction_watermark(mut self, watermark: f64) -> Self {
+        self.cache_eviction_watermark = Some(watermark.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Sets whether values cached for the inputs of a merge follow them into
+    /// the merged SSTable.
+    ///
+    /// When enabled, a merge checks every cached value of its inputs against
+    /// the merged data; values the merged table still holds unchanged are
+    /// re-pointed to it, so hot keys keep hitting the cache right after a
+    /// merge. When disabled, all values cached for the inputs are dropped and
+    /// refilled by later reads.
+    ///
+    /// # Arguments
+    /// * `enabled` - Whether to re-point cached values on merges
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `true`
+    pub fn repoint_value_cache_on_merge(mut self, enabled: bool) -> Self {
+        self.repoint_value_cache_on_merge = Some(enabled);
+        self
+    }
+
+    /// Sets how scans treat an entry whose value cannot be decompressed.
+    ///
+    /// `ReadErrorPolicy::SkipCorrupt` logs and skips such an entry so one
+    /// corrupt value does not end `Tree::iter`, `iter_rev`, `scan_limit` or
+    /// `scan_rev`. Point reads such as `get` always fail on it.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy for scans
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `ReadErrorPolicy::FailFast`
+    pub fn scan_error_policy(mut self, policy: ReadErrorPolicy) -> Self {
+        self.scan_error_policy = Some(policy);
+        self
+    }
+
     /// Enables or disables Write-Ahead Logging (WAL).
     ///
     /// WAL provides durability guarantees by logging all write operations before
@@ -299,9 +767,9 @@ impl TreeSettingsBuilder {
 
     /// Sets the maximum size for WAL segments before rotation.
     ///
-    /// When a WAL segment reaches this size, the system will create a new segment
-    /// and eventually clean up old segments that are no longer needed for recovery.
-    /// This helps manage disk space and recovery time.
+    /// When a WAL segment exceeds this size, the next write or flush starts a new
+    /// segment, and old segments that are no longer needed for recovery are
+    /// eventually cleaned up. This helps manage disk space and recovery time.
     ///
     /// # Arguments
     /// * `size` - Maximum size in bytes for each WAL segment
@@ -317,6 +785,113 @@ impl TreeSettingsBuilder {
         self
     }
 
+    /// Sets the maximum number of entries written to a WAL segment before rotation.
+    ///
+    /// Complements `wal_max_size` for workloads of many small writes: the WAL
+    /// rotates as soon as either limit is reached. Entries are counted from the
+    /// moment the segment is opened by this process.
+    ///
+    /// # Arguments
+    /// * `entries` - Maximum number of entries per WAL segment
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// No entry limit; segments rotate on size only.
+    pub fn wal_max_entries(mut self, entries: usize) -> Self {
+        self.wal_max_entries = Some(entries);
+        self
+    }
+
+    /// Sets whether every flush starts a new WAL segment.
+    ///
+    /// After each flush a checkpoint marker is appended to the current segment,
+    /// telling recovery that everything logged before it is stored in SSTables.
+    /// By default the segment is kept and only rotated once `wal_max_size` or
+    /// `wal_max_entries` is reached, so frequent small flushes do not produce a
+    /// trail of tiny segments. When enabled, each flush also rotates the segment.
+    ///
+    /// # Arguments
+    /// * `enabled` - `true` to rotate on every flush
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `false`: segments rotate on size or entry count only.
+    pub fn wal_rotate_on_flush(mut self, enabled: bool) -> Self {
+        self.wal_rotate_on_flush = Some(enabled);
+        self
+    }
+
+    /// Enables or disables syncing the database directory after a file is
+    /// created or renamed in it.
+    ///
+    /// Syncing a file only makes its contents durable. Its name lives in the
+    /// directory, and on filesystems such as ext4 and xfs a crash can lose a
+    /// new or renamed directory entry even though the file data reached disk.
+    /// A flushed SSTable could then vanish after its WAL segment was already
+    /// discarded, or a merge could lose its output while its inputs are gone.
+    /// With this enabled, the directory is synced after every new SSTable and
+    /// WAL segment and after every rename, including each `MANIFEST` update,
+    /// at the cost of one extra sync each.
+    ///
+    /// # Arguments
+    /// * `enabled` - `false` to skip directory syncs
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `true`
+    pub fn sync_directory(mut self, enabled: bool) -> Self {
+        self.sync_directory = Some(enabled);
+        self
+    }
+
+    /// Sets a callback reporting progress while the WAL is replayed on load.
+    ///
+    /// The callback receives the number of entries read for replay so far, the
+    /// number of WAL segments and the 1-based number of the segment being read.
+    /// It runs on the loading thread when a segment is opened, every
+    /// `recovery_progress_interval` entries and once more when replay has
+    /// finished, so a slow callback slows recovery down. The count can drop when a later checkpoint
+    /// shows earlier entries to be in SSTables already.
+    ///
+    /// # Arguments
+    /// * `callback` - Called with `(recovered, total_segments, current_segment)`
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// No callback; recovery only logs the number of recovered entries.
+    pub fn recovery_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(usize, usize, usize) + Send + Sync + 'static,
+    {
+        self.recovery_progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets how many WAL entries are read between two progress reports.
+    ///
+    /// Only has an effect together with `recovery_progress`.
+    ///
+    /// # Arguments
+    /// * `entries` - Entries between reports, at least 1
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `DEFAULT_RECOVERY_PROGRESS_INTERVAL` (10 000 entries).
+    pub fn recovery_progress_interval(mut self, entries: usize) -> Self {
+        self.recovery_progress_interval = Some(entries.max(1));
+        self
+    }
+
     /// Sets the memory limit for the index cache.
     ///
     /// The index cache stores SSTable index data in memory to speed up key lookups.
@@ -379,12 +954,205 @@ impl TreeSettingsBuilder {
         self
     }
 
+    /// Sets how many SSTable file handles are kept open for reads.
+    ///
+    /// Point lookups and merges reuse pooled handles instead of opening the
+    /// SSTable on every access; the least recently used handles are closed once
+    /// the limit is reached. Set to 0 to open a fresh handle for every read.
+    ///
+    /// # Arguments
+    /// * `max_open_files` - Maximum number of idle SSTable handles to keep open
+    ///
+    /// # Returns
+    /// Self for method chaining
+    pub fn max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = Some(max_open_files);
+        self
+    }
+
+    /// Sets how many threads `Tree::par_multi_get` uses to read SSTables.
+    ///
+    /// A value of 0 is treated as 1.
+    ///
+    /// # Arguments
+    /// * `threads` - Maximum number of reader threads per batch
+    ///
+    /// # Returns
+    /// Self for method chaining
+    pub fn read_parallelism(mut self, threads: usize) -> Self {
+        self.read_parallelism = Some(threads);
+        self
+    }
+
+    /// Sets the buffer capacity of readers over SSTables and WAL segments.
+    ///
+    /// Entries larger than the buffer are read with several system calls, so
+    /// trees storing large values read faster with a larger buffer. Every
+    /// SSTable handle kept open by the file pool holds one buffer of this size.
+    ///
+    /// # Arguments
+    /// * `bytes` - Buffer capacity in bytes; values below 1 are treated as 1
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `DEFAULT_READ_BUFFER_SIZE` (8 KiB), the capacity of `BufReader::new`.
+    pub fn read_buffer_size(mut self, bytes: usize) -> Self {
+        self.read_buffer_size = Some(bytes.max(1));
+        self
+    }
+
+    /// Sets the buffer capacity of writers of SSTables and WAL segments.
+    ///
+    /// A larger buffer issues fewer, larger writes when flushing, merging or
+    /// logging large values. WAL entries still reach the file on every write,
+    /// as the WAL writer flushes its buffer after each entry.
+    ///
+    /// # Arguments
+    /// * `bytes` - Buffer capacity in bytes; values below 1 are treated as 1
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `DEFAULT_WRITE_BUFFER_SIZE` (8 KiB), the capacity of `BufWriter::new`.
+    pub fn write_buffer_size(mut self, bytes: usize) -> Self {
+        self.write_buffer_size = Some(bytes.max(1));
+        self
+    }
+
+    /// Sets how many data entries share one SSTable index entry.
+    ///
+    /// With an interval of `n`, new SSTables index every `n`-th key plus the
+    /// last one, and a lookup scans forward from the nearest preceding indexed
+    /// key. Larger intervals shrink the indexes and fit more of them into the
+    /// index cache, at the cost of reading up to `n - 1` extra entries per
+    /// lookup. The default of 1 keeps a dense index; 0 is treated as 1.
+    /// Existing SSTables keep the index they were written with.
+    ///
+    /// # Arguments
+    /// * `interval` - Number of data entries per index entry
+    ///
+    /// # Returns
+    /// Self for method chaining
+    pub fn index_block_interval(mut self, interval: usize) -> Self {
+        self.index_block_interval = Some(interval);
+        self
+    }
+
+    /// Sets how many SSTable merge requests may wait for the background
+    /// compaction worker.
+    ///
+    /// Every flush that leaves `CompactionConfig::min_tables` SSTables or more
+    /// requests a merge, which the worker runs off the write path. Once this many requests are waiting,
+    /// the next flush blocks until the merge in progress has finished. A value
+    /// of 0 disables the worker and merges synchronously after each flush.
+    ///
+    /// # Arguments
+    /// * `max_queued` - Maximum number of waiting merge requests
+    ///
+    /// # Returns
+    /// Self for method chaining
+    pub fn max_queued_compactions(mut self, max_queued: usize) -> Self {
+        self.max_queued_compactions = Some(max_queued);
+        self
+    }
+
+    /// Sets how long point and range tombstones survive compaction.
+    ///
+    /// A merge drops a tombstone only once it is older than `grace_period`,
+    /// measured from its `created_at`, *and* no SSTable older than the merged
+    /// ones could still hold a value it deletes. The second condition does not
+    /// depend on this setting: a regular merge always starts at the oldest
+    /// SSTable, and `Tree::compact_range` only drops tombstones inside its key
+    /// range, which no older SSTable overlaps. Younger tombstones are carried
+    /// into the merged table and dropped by a later merge. Values shadowed by a
+    /// tombstone are still removed right away.
+    ///
+    /// The default of zero drops tombstones at the first eligible merge. Point
+    /// tombstones recovered from the WAL are dated from the recovery.
+    ///
+    /// # Arguments
+    /// * `grace_period` - Minimum tombstone age before it may be dropped
+    ///
+    /// # Returns
+    /// Self for method chaining
+    pub fn tombstone_grace_period(mut self, grace_period: Duration) -> Self {
+        self.tombstone_grace_period = Some(grace_period);
+        self
+    }
+
+    /// Sets when SSTables are merged and how many one merge takes.
+    ///
+    /// `min_tables` and `max_tables` below 2 are raised to 2. Raising
+    /// `max_tables` and setting `size_ratio` trades more SSTables per read for
+    /// fewer rewrites of the oldest, largest table.
+    ///
+    /// # Arguments
+    /// * `config` - The merge trigger and width
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `CompactionConfig::default()`: the oldest three SSTables are merged as
+    /// soon as three exist.
+    pub fn compaction(mut self, config: CompactionConfig) -> Self {
+        self.compaction = Some(CompactionConfig {
+            min_tables: config.min_tables.max(2),
+            max_tables: config.max_tables.max(2),
+            ..config
+        });
+        self
+    }
+
+    /// Limits how many bytes per second flushes and merges write to SSTables.
+    ///
+    /// Throttles the background compaction and flush workers so they leave
+    /// disk bandwidth to foreground reads. Flushes and merges that run on the
+    /// write path, and `compact_range`, are throttled as well. Up to one second
+    /// of writes passes at full speed.
+    ///
+    /// # Arguments
+    /// * `bytes_per_second` - Maximum SSTable write rate in bytes per second
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// None; SSTables are written as fast as the disk allows.
+    pub fn max_write_rate(mut self, bytes_per_second: u64) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(bytes_per_second)));
+        self
+    }
+
+    /// Sets a rate limiter shared with other trees, which then split its rate.
+    ///
+    /// # Arguments
+    /// * `rate_limiter` - The limiter throttling SSTable writes
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// None; see `max_write_rate`.
+    pub fn rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
     /// Sets the compression configuration for the tree.
     ///
     /// This method configures how data is compressed before being written to disk.
     /// Different compression algorithms offer different trade-offs between compression
     /// ratio, speed, and CPU usage.
     ///
+    /// Values are decompressed with this configuration, so an existing database
+    /// has to be opened with the compression type it was written with. Loading
+    /// fails with `TreeError::Configuration` when an SSTable header records
+    /// another type.
+    ///
     /// # Arguments
     /// * `config` - A `CompressionConfig` instance specifying the compression settings
     ///
@@ -396,6 +1164,7 @@ impl TreeSettingsBuilder {
     /// - **Snappy**: Fast compression with decent ratio
     /// - **Lz4**: Good balance of speed and compression
     /// - **Zstd**: Best compression ratio, slower
+    /// - **Gzip**: Standard gzip format for interoperability; slower than LZ4 and Zstd
     ///
     /// # Default
     /// No compression is used by default.
@@ -404,6 +1173,249 @@ impl TreeSettingsBuilder {
         self
     }
 
+    /// Sets the maximum age of unflushed data in the memory table.
+    ///
+    /// A background timer wakes up every `interval` and marks a flush as due
+    /// when the memory table is non-empty and its oldest unflushed write is at
+    /// least `interval` old. The flush itself runs on the next write or on an
+    /// explicit call to `Tree::flush_if_due`; reads through `&self` never flush.
+    ///
+    /// # Arguments
+    /// * `interval` - Maximum time data may stay in the memory table
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # WAL Interaction
+    /// Every flush writes a WAL checkpoint and rotates to a new segment, so the
+    /// amount of WAL replayed on recovery is bounded by roughly one interval of
+    /// writes. Short intervals produce many small SSTables, which are merged
+    /// in the background as usual.
+    ///
+    /// # Default
+    /// Disabled; the memory table is only flushed when it is full.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Sets the maximum number of entries written to one SSTable by a flush.
+    ///
+    /// A memory table holding more entries is split at key boundaries into
+    /// several SSTables, each with its own index and bloom filter, so a large
+    /// flush does not produce a single file that is expensive to merge. Merged
+    /// tables are not split.
+    ///
+    /// # Arguments
+    /// * `entries` - Maximum entries per flushed SSTable; values below 1 are treated as 1
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// None; every flush writes a single SSTable.
+    pub fn sstable_max_entries(mut self, entries: usize) -> Self {
+        self.sstable_max_entries = Some(entries.max(1));
+        self
+    }
+
+    /// Sets whether overwrites reuse the value buffer already in the memory table.
+    ///
+    /// When enabled, a write to a key whose memory table value has the same
+    /// stored length copies the new bytes into the existing buffer and updates
+    /// its timestamps, instead of replacing the value. Aimed at fixed-width
+    /// values such as counters that are rewritten at a high rate.
+    ///
+    /// # Arguments
+    /// * `enabled` - Whether to overwrite same-size values in place
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `false`
+    pub fn overwrite_in_place(mut self, enabled: bool) -> Self {
+        self.overwrite_in_place = Some(enabled);
+        self
+    }
+
+    /// Sets whether full memory tables are written to SSTables off the write path.
+    ///
+    /// When enabled, a write that fills the memory table only moves it to the
+    /// immutable memory tables and hands it to a background thread, which
+    /// writes the SSTable while reads keep being served from memory. At most
+    /// one such flush runs at a time: the next write that fills the memory
+    /// table waits for it. A failed background flush is logged and its table
+    /// is written on the write path by the next flush, which reports the error.
+    /// `flush` still writes everything before it returns.
+    ///
+    /// # Arguments
+    /// * `enabled` - Whether to flush full memory tables in the background
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `false`; a write that fills the memory table writes the SSTable itself.
+    pub fn background_flush(mut self, enabled: bool) -> Self {
+        self.background_flush = Some(enabled);
+        self
+    }
+
+    /// Sets how many full memory tables may wait to be written to SSTables.
+    ///
+    /// Tables pile up when flushes fall behind or keep failing, for example on
+    /// a full disk. Once this many are queued, the write that fills the memory
+    /// table blocks while the oldest one is written on its own thread. If that
+    /// write fails, the error is returned and the full memory table is kept;
+    /// later writes then retry the flush and fail with it instead of being
+    /// accepted, until the tables can be written again. The table written by
+    /// the background flusher counts toward the limit.
+    ///
+    /// # Arguments
+    /// * `max_tables` - Maximum number of queued memory tables; values below 1 are treated as 1
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `DEFAULT_MAX_IMMUTABLE_MEM_TABLES` (4).
+    pub fn max_immutable_mem_tables(mut self, max_tables: usize) -> Self {
+        self.max_immutable_mem_tables = Some(max_tables.max(1));
+        self
+    }
+
+    /// Sets the TTL given to writes that do not specify one.
+    ///
+    /// Applies to `put`, `put_typed` and any other write whose `ttl` is `None`,
+    /// including transactional writes. An explicit TTL always takes precedence;
+    /// the result is then subject to `max_ttl`.
+    ///
+    /// # Arguments
+    /// * `ttl` - TTL for writes without one
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// None; writes without a TTL never expire.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the longest TTL any write may have.
+    ///
+    /// After `default_ttl` has been applied, a write whose TTL is longer than
+    /// `max_ttl`, or which has no TTL at all, is handled according to
+    /// `max_ttl_policy`. `Tree::update_ttl` is bounded the same way, but
+    /// does not apply `default_ttl`.
+    ///
+    /// # Arguments
+    /// * `ttl` - Maximum TTL
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// None; TTLs are not bounded.
+    pub fn max_ttl(mut self, ttl: Duration) -> Self {
+        self.max_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets what happens to a write whose TTL exceeds `max_ttl`.
+    ///
+    /// Has no effect unless `max_ttl` is set.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy for writes beyond `max_ttl`
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// `TtlLimitPolicy::Clamp`
+    pub fn max_ttl_policy(mut self, policy: TtlLimitPolicy) -> Self {
+        self.max_ttl_policy = Some(policy);
+        self
+    }
+
+    /// Enables or disables strict checking of the database directory on load.
+    ///
+    /// In strict mode, loading fails with `TreeError::Configuration` when the
+    /// directory holds files that are neither SSTables (`sstable_N.sst`, including
+    /// their `.sst.tmp` and `.sst.dup` companions), WAL segments (`wal_NNNN.log`)
+    /// nor the `MANIFEST` (and its `MANIFEST.tmp`), unless their names are listed via `strict_directory_ignore`. This catches
+    /// databases accidentally sharing a directory.
+    ///
+    /// # Arguments
+    /// * `is_enabled` - `true` to reject unknown files, `false` to ignore them
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// Disabled; unknown files are ignored.
+    pub fn strict_directory(mut self, is_enabled: bool) -> Self {
+        self.strict_directory = Some(is_enabled);
+        self
+    }
+
+    /// Sets file names tolerated in the database directory in strict mode.
+    ///
+    /// # Arguments
+    /// * `file_names` - Exact file names to ignore, e.g. `.DS_Store`
+    ///
+    /// # Returns
+    /// Self for method chaining
+    pub fn strict_directory_ignore<I, S>(mut self, file_names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.strict_directory_ignore = file_names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether opening a tree first checks that its directory is writable.
+    ///
+    /// The check runs `TreeSettings::validate_path_writable`, turning a
+    /// permission or read-only filesystem error that would otherwise surface
+    /// on the first flush into an error from `Tree::new_with_settings` or
+    /// `Tree::load_with_settings`. Disable it to open a database on read-only
+    /// storage.
+    ///
+    /// # Arguments
+    /// * `is_enabled` - Whether to probe the database directory
+    ///
+    /// # Returns
+    /// Self for method chaining
+    ///
+    /// # Default
+    /// Enabled
+    pub fn check_writable(mut self, is_enabled: bool) -> Self {
+        self.check_writable = Some(is_enabled);
+        self
+    }
+
+    /// Sets the channel receiving lifecycle events.
+    ///
+    /// Unlike `Tree::subscribe`, a sender supplied here is attached before the
+    /// tree is loaded, so events raised during loading such as
+    /// `TreeEvent::Recovered` are delivered too. Sends never block; events are
+    /// dropped when the channel is full or the receiver is gone.
+    ///
+    /// # Arguments
+    /// * `sender` - Sending end of a bounded channel
+    ///
+    /// # Returns
+    /// Self for method chaining
+    pub fn event_subscriber(mut self, sender: mpsc::SyncSender<TreeEvent>) -> Self {
+        self.event_sender = Some(sender);
+        self
+    }
+
     /// Builds the TreeSettings from the configured options.
     ///
     /// Any unset options will use their default values.
@@ -414,14 +1426,19 @@ impl TreeSettingsBuilder {
         TreeSettings {
             db_path: self.db_path.unwrap_or(PathBuf::from(DEFAULT_DB_PATH)),
             bincode_config: self.bincode_config.unwrap_or(BINCODE_CONFIG),
+            typed_encoding: self.typed_encoding,
             mem_table_max_size: self
                 .mem_table_max_size
                 .unwrap_or(DEFAULT_MEM_TABLE_SIZE as usize),
+            mem_table_max_bytes: self.mem_table_max_bytes,
             bloom_filter_error_probability: self
                 .bloom_filter_error_probability
                 .unwrap_or(DEFAULT_BLOOM_FILTER_ERROR_PROBABILITY),
             enable_bloom_filter_cache: self.enable_bloom_filter_cache.unwrap_or(true),
             enable_index_cache: self.enable_index_cache.unwrap_or(true),
+            index_block_interval: self
+                .index_block_interval
+                .unwrap_or(DEFAULT_INDEX_BLOCK_INTERVAL),
             index_cache_memory_limit: self
                 .index_cache_memory_limit
                 .unwrap_or(DEFAULT_INDEX_CACHE_MEMORY_LIMIT),
@@ -435,11 +1452,141 @@ impl TreeSettingsBuilder {
             value_cache_max_capacity: self
                 .value_cache_max_capacity
                 .unwrap_or(DEFAULT_VALUE_CACHE_LRU_MAX_CAPACITY),
+            value_cache_admission: self
+                .value_cache_admission
+                .unwrap_or(ValueCacheAdmission::Always),
+            max_cacheable_value_bytes: self.max_cacheable_value_bytes,
+            cache_eviction_watermark: self
+                .cache_eviction_watermark
+                .unwrap_or(DEFAULT_CACHE_EVICTION_WATERMARK),
+            repoint_value_cache_on_merge: self.repoint_value_cache_on_merge.unwrap_or(true),
+            scan_error_policy: self.scan_error_policy.unwrap_or(ReadErrorPolicy::FailFast),
+            max_open_files: self.max_open_files.unwrap_or(DEFAULT_MAX_OPEN_FILES),
+            read_parallelism: self.read_parallelism.unwrap_or(DEFAULT_READ_PARALLELISM),
+            read_buffer_size: self.read_buffer_size.unwrap_or(DEFAULT_READ_BUFFER_SIZE),
+            write_buffer_size: self.write_buffer_size.unwrap_or(DEFAULT_WRITE_BUFFER_SIZE),
             enable_wal: self.enable_wal.unwrap_or(true),
             wal_max_size: self.wal_max_size.unwrap_or(DEFAULT_WAL_MAX_SIZE),
+            wal_max_entries: self.wal_max_entries,
+            wal_rotate_on_flush: self.wal_rotate_on_flush.unwrap_or(false),
+            sync_directory: self.sync_directory.unwrap_or(true),
+            recovery_progress: self.recovery_progress,
+            recovery_progress_interval: self
+                .recovery_progress_interval
+                .unwrap_or(DEFAULT_RECOVERY_PROGRESS_INTERVAL),
+            max_queued_compactions: self
+                .max_queued_compactions
+                .unwrap_or(DEFAULT_MAX_QUEUED_COMPACTIONS),
+            tombstone_grace_period: self
+                .tombstone_grace_period
+                .unwrap_or(DEFAULT_TOMBSTONE_GRACE_PERIOD),
+            compaction: self.compaction.unwrap_or_default(),
+            rate_limiter: self.rate_limiter,
             compressor: self
                 .compressor
                 .unwrap_or(Compressor::new(CompressionConfig::none())),
+            flush_interval: self.flush_interval,
+            sstable_max_entries: self.sstable_max_entries,
+            overwrite_in_place: self.overwrite_in_place.unwrap_or(false),
+            background_flush: self.background_flush.unwrap_or(false),
+            max_immutable_mem_tables: self
+                .max_immutable_mem_tables
+                .unwrap_or(DEFAULT_MAX_IMMUTABLE_MEM_TABLES),
+            default_ttl: self.default_ttl,
+            max_ttl: self.max_ttl,
+            max_ttl_policy: self.max_ttl_policy.unwrap_or(TtlLimitPolicy::Clamp),
+            strict_directory: self.strict_directory.unwrap_or(false),
+            strict_directory_ignore: self.strict_directory_ignore,
+            check_writable: self.check_writable.unwrap_or(true),
+            event_sender: self.event_sender,
+        }
+    }
+}
+
+/// What a read does with an entry whose value cannot be decompressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadErrorPolicy {
+    /// The error is returned to the caller; a scan yields it as an `Err` element.
+    FailFast,
+    /// The entry is logged and skipped, and the read continues with the next one.
+    SkipCorrupt,
+}
+
+/// Which value `Tree::merge_into_with` keeps when both trees hold a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeConflictPolicy {
+    /// The source tree's value replaces the destination's.
+    #[default]
+    SourceWins,
+    /// The value with the later `created_at` is kept; a tie keeps the
+    /// destination's value.
+    KeepNewest,
+}
+
+/// What a write does when its TTL exceeds `TreeSettings::max_ttl`.
+///
+/// A write without a TTL counts as exceeding any `max_ttl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TtlLimitPolicy {
+    /// The TTL is shortened to `max_ttl`, so every value eventually expires.
+    Clamp,
+    /// The write fails with `TreeError::InvalidValue` and nothing is stored.
+    Reject,
+}
+
+/// Per-write durability options for `Tree::put_with_options`.
+///
+/// # Fields
+/// - `sync`: Sync the WAL to disk before the write returns, regardless of the
+///   global WAL policy
+/// - `log_to_wal`: Record the write in the WAL; when `false` the write is only
+///   durable once the memory table is flushed to an SSTable
+///
+/// The default logs to the WAL without syncing, matching `Tree::put`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOptions {
+    pub sync: bool,
+    pub log_to_wal: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            sync: false,
+            log_to_wal: true,
+        }
+    }
+}
+
+/// When SSTables are merged and how many one merge takes, see
+/// `TreeSettingsBuilder::compaction`.
+///
+/// A merge always starts at the oldest SSTable and takes consecutive newer
+/// ones, so the tombstones it drops cannot hide a value in an older table.
+///
+/// # Fields
+/// - `min_tables`: Number of SSTables at which a merge is requested
+/// - `max_tables`: Most SSTables one merge takes
+/// - `size_ratio`: Optional size-tiering bound; while fewer than `max_tables`
+///   SSTables exist, a merge waits until its newer inputs add up to at least
+///   the oldest input's size divided by this ratio
+/// - `max_sstable_size`: Optional size in bytes at which a merge stops taking
+///   newer inputs, bounding the merged SSTable; the oldest two are always taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompactionConfig {
+    pub min_tables: usize,
+    pub max_tables: usize,
+    pub size_ratio: Option<f64>,
+    pub max_sstable_size: Option<u64>,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            min_tables: DEFAULT_COMPACTION_MIN_TABLES,
+            max_tables: DEFAULT_COMPACTION_MAX_TABLES,
+            size_ratio: None,
+            max_sstable_size: None,
         }
     }
 }
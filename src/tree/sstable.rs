@@ -1,51 +1,58 @@
-use crate::config::{CURRENT_VERSION, FOOTER_MAGIC_NUMBER, FOOTER_SIZE, HEADER_MAGIC_NUMBER};
-use crate::tree::tree_error::TreeResult;
-use crate::tree::BloomFilter;
+use crate::config::{
+    COMPRESSION_MARKER_VERSION, CURRENT_VERSION, FOOTER_MAGIC_NUMBER, FOOTER_SIZE, HEADER_MAGIC_NUMBER, HEADER_SIZE,
+    LEGACY_FOOTER_SIZE, RATE_LIMIT_CHUNK_SIZE,
+    MIN_SUPPORTED_VERSION, V2_FOOTER_SIZE, V3_FOOTER_SIZE,
+};
+use crate::tree::range_tombstone::RangeTombstone;
+use crate::tree::tree_error::{TreeError, TreeResult};
+use crate::tree::{BloomFilter, CompressionType, TreeEvent};
 use crate::{util, DataValue, Tree};
 use crc32fast::Hasher;
 use growable_bloom_filter::GrowableBloom;
-use log::error;
-use std::cmp::PartialEq;
-use std::collections::{BTreeMap, BinaryHeap};
+use log::{error, info, warn};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt::Display;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
 
 impl Tree {
     pub(crate) fn read_key_from_sstable(
-        &mut self,
+        &self,
         path: &PathBuf,
         key: &[u8],
     ) -> Option<DataValue> {
+        if self.key_ranges.get(path).is_some_and(|range| !range.contains(key)) {
+            return None;
+        }
+
         if self.settings.enable_value_cache {
-            if let Some(cached_value) = self.value_cache.get(path, key) {
+            let mut value_cache = self.value_cache();
+            if let Some(cached_value) = value_cache.get(path, key) {
                 if !cached_value.is_expired() {
                     return Some(cached_value);
                 } else {
-                    self.value_cache.remove(path, key);
+                    value_cache.remove(path, key);
                 }
             }
         }
 
         if self.settings.enable_index_cache {
-            if let Some(cached_index) = self.index_cache.get(path) {
-                if let Some(&offset) = cached_index.get(key) {
-                    let file = File::open(path).ok()?;
-                    let mut reader = BufReader::new(file);
-                    match self.read_data_entry(&mut reader, offset) {
-                        Ok(data_value) => {
-                            if self.settings.enable_value_cache {
-                                self.value_cache.put(
-                                    path.clone(),
-                                    key.to_vec(),
-                                    data_value.clone(),
-                                );
-                            }
-                            return Some(data_value);
-                        }
-                        Err(e) => {
-                            error!("Error reading data entry from SSTable {:?}: {}", path, e);
-                        }
+            let cached_location = self.index_cache().get(path).map(|cached_index| locate_in_index(cached_index, key));
+            if let Some(location) = cached_location {
+                let location = location?;
+                let version = self.sstable_version(path);
+                let mut reader = self.file_pool.open(path).ok()?;
+                match self.read_located_entry(&mut reader, location, key, version) {
+                    Ok(data_value) => {
+                        let data_value = data_value?;
+                        self.admit_to_value_cache(path, key, &data_value);
+                        return Some(data_value);
+                    }
+                    Err(e) => {
+                        error!("Error reading data entry from SSTable {:?}: {}", path, e);
                     }
                 }
             }
@@ -55,35 +62,31 @@ impl Tree {
             return None;
         }
 
-        let file = File::open(path).ok()?;
-        let mut reader = BufReader::new(file);
-
-        if self.validate_header(&mut reader).is_err() {
-            return None;
-        }
+        let mut reader = self.file_pool.open(path).ok()?;
 
-        let (index_offset, _) = self.read_footer(&mut reader).ok()?;
-        let data_offset = self.find_key_in_index(&mut reader, index_offset, key)?;
+        let version = self.validate_header(&mut reader).ok()?;
 
-        if self.settings.enable_index_cache {
-            if let Ok(index) = self.read_index(&mut reader, index_offset) {
-                self.index_cache.put(path.clone(), index);
-            }
-        }
+        let (index_offset, _) = self.read_footer_for_version(&mut reader, version).ok()?;
+        let location = if self.settings.enable_index_cache {
+            let index = self.read_index(&mut reader, index_offset).ok()?;
+            let location = locate_in_index(&index, key);
+            self.index_cache().put(path.clone(), index);
+            location?
+        } else {
+            self.find_key_in_index(&mut reader, index_offset, key)?
+        };
 
-        match self.read_data_entry(&mut reader, data_offset) {
+        match self.read_located_entry(&mut reader, location, key, version) {
             Ok(data_value) => {
-                if self.settings.enable_value_cache {
-                    self.value_cache
-                        .put(path.clone(), key.to_vec(), data_value.clone());
-                }
+                let data_value = data_value?;
+                self.admit_to_value_cache(path, key, &data_value);
                 Some(data_value)
             }
             Err(e) => {
                 error!(
                     "Error reading data entry from SSTable {:?} with offset {:?}: {}",
                     path,
-                    data_offset,
+                    location.0,
                     e
                 );
                 None
@@ -91,9 +94,159 @@ impl Tree {
         }
     }
 
-    fn check_bloom_filter(&mut self, key: &[u8], path: &PathBuf) -> bool {
+    /// Looks a key up in the value cache without knowing which SSTable holds it.
+    ///
+    /// The newest SSTable with a cached value for the key answers the lookup,
+    /// provided the cached bloom filters of all newer SSTables rule the key out.
+    /// Returns `None` whenever that cannot be decided from memory, in which case
+    /// the SSTables have to be searched one by one.
+    pub(crate) fn read_key_from_value_cache(&self, key: &[u8]) -> Option<DataValue> {
+        if !self.settings.enable_value_cache || !self.settings.enable_bloom_filter_cache {
+            return None;
+        }
+
+        let newest_idx = self
+            .value_cache()
+            .cached_paths(key)
+            .iter()
+            .filter_map(|path| self.ss_tables.iter().position(|p| p == path))
+            .max()?;
+        {
+            let bloom_filters = self.bloom_filters();
+            for newer_path in &self.ss_tables[newest_idx + 1..] {
+                let bloom_filter = bloom_filters.iter().find(|bf| bf.path == *newer_path)?;
+                if bloom_filter.bloom_filter.contains(key) {
+                    return None;
+                }
+            }
+        }
+
+        let path = &self.ss_tables[newest_idx];
+        let mut value_cache = self.value_cache();
+        let cached_value = value_cache.get(path, key)?;
+        if cached_value.is_expired() {
+            value_cache.remove(path, key);
+            return None;
+        }
+        Some(cached_value)
+    }
+
+    fn admit_to_value_cache(&self, path: &Path, key: &[u8], data_value: &DataValue) {
+        if self.settings.enable_value_cache
+            && self.settings.value_cache_admission.admits(data_value)
+        {
+            self.value_cache()
+                .put(path.to_path_buf(), key.to_vec(), data_value.clone());
+        }
+    }
+
+    /// Looks a batch of keys up in the SSTables without touching any cache.
+    ///
+    /// SSTables are visited newest first and each one is opened once for all
+    /// keys it may hold, as decided by its cached key range and bloom filter.
+    /// A key is resolved by the first live value or range tombstone found for
+    /// it. Returns the input position and value of every key that resolved to
+    /// a value; read errors are logged and treated as misses, like in `get`.
+    pub(crate) fn read_keys_from_sstables(&self, keys: &[(usize, &[u8])]) -> Vec<(usize, Option<DataValue>)> {
+        let mut pending: Vec<(usize, &[u8])> = keys.to_vec();
+        let mut resolved = Vec::with_capacity(keys.len());
+
+        for path in self.ss_tables.iter().rev() {
+            if pending.is_empty() {
+                break;
+            }
+            let bloom_filters = self.bloom_filters();
+            let bloom_filter = bloom_filters.iter().find(|bf| bf.path == *path);
+            let key_range = self.key_ranges.get(path);
+            let candidates: Vec<usize> = pending
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, key))| {
+                    key_range.is_none_or(|range| range.contains(key))
+                        && bloom_filter.is_none_or(|bf| bf.bloom_filter.contains(key))
+                })
+                .map(|(pos, _)| pos)
+                .collect();
+            drop(bloom_filters);
+            if candidates.is_empty() {
+                continue;
+            }
+
+            let values = match self.read_sstable_values(path, candidates.iter().map(|&pos| pending[pos].1)) {
+                Ok(values) => values,
+                Err(e) => {
+                    error!("Error reading SSTable {:?}: {}", path, e);
+                    continue;
+                }
+            };
+
+            let mut done = vec![false; pending.len()];
+            for (&pos, value) in candidates.iter().zip(values) {
+                let Some(value) = value else {
+                    continue;
+                };
+                let (idx, key) = pending[pos];
+                if self.is_range_deleted(key, &value) {
+                    resolved.push((idx, None));
+                    done[pos] = true;
+                } else if !value.is_expired() {
+                    resolved.push((idx, Some(value)));
+                    done[pos] = true;
+                }
+            }
+            let mut done = done.into_iter();
+            pending.retain(|_| !done.next().unwrap_or(false));
+        }
+
+        resolved
+    }
+
+    /// Reads the values of several keys from one SSTable through a single reader.
+    fn read_sstable_values<'a>(
+        &self,
+        path: &Path,
+        keys: impl Iterator<Item = &'a [u8]>,
+    ) -> std::io::Result<Vec<Option<DataValue>>> {
+        let mut reader = self.file_pool.open(path)?;
+        let version = self.validate_header(&mut reader)?;
+        let keys: Vec<&[u8]> = keys.collect();
+        let locate_all = |index: &BTreeMap<Vec<u8>, u64>| -> Vec<_> {
+            keys.iter().map(|key| locate_in_index(index, key)).collect()
+        };
+        let cached_locations = self.index_cache().peek(path).map(locate_all);
+        let locations = match cached_locations {
+            Some(locations) => locations,
+            None => {
+                let (index_offset, _) = self.read_footer_for_version(&mut reader, version)?;
+                locate_all(&self.read_index(&mut reader, index_offset)?)
+            }
+        };
+
+        let mut values = Vec::new();
+        for (key, location) in keys.into_iter().zip(locations) {
+            let value = match location {
+                Some(location) => self.read_located_entry(&mut reader, location, key, version)?,
+                None => None,
+            };
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    /// Returns `true` if at least one SSTable whose key range holds the key
+    /// has a bloom filter claiming it.
+    pub(crate) fn sstables_may_contain(&self, key: &[u8]) -> bool {
+        self.ss_tables
+            .iter()
+            .rev()
+            .any(|path| {
+                self.key_ranges.get(path).is_none_or(|range| range.contains(key)) && self.check_bloom_filter(key, path)
+            })
+    }
+
+    fn check_bloom_filter(&self, key: &[u8], path: &PathBuf) -> bool {
         if self.settings.enable_bloom_filter_cache {
-            if let Some(bf) = self.bloom_filters
+            if let Some(bf) = self.bloom_filters()
                 .iter()
                 .find(|bf| bf.path == *path) {
                 return bf.bloom_filter.contains(key)
@@ -103,12 +256,7 @@ impl Tree {
         match self.load_bloom_filter(path) {
             Ok(bloom_filter) => {
                 let contains_key = bloom_filter.contains(key);
-                if self.settings.enable_bloom_filter_cache {
-                    self.bloom_filters.push(BloomFilter {
-                        path: path.clone(),
-                        bloom_filter,
-                    });
-                }
+                self.cache_bloom_filter(path.clone(), bloom_filter);
                 contains_key
             }
             Err(e) => {
@@ -118,7 +266,37 @@ impl Tree {
         }
     }
 
-    fn validate_header(&self, reader: &mut BufReader<File>) -> std::io::Result<()> {
+    /// Caches the bloom filter of an SSTable, replacing any filter already
+    /// cached for the same path so each table has at most one entry.
+    pub(crate) fn cache_bloom_filter(&self, path: PathBuf, bloom_filter: GrowableBloom) {
+        if !self.settings.enable_bloom_filter_cache {
+            return;
+        }
+        let mut bloom_filters = self.bloom_filters_mut();
+        match bloom_filters.iter_mut().find(|bf| bf.path == path) {
+            Some(cached) => cached.bloom_filter = bloom_filter,
+            None => bloom_filters.push(BloomFilter { path, bloom_filter }),
+        }
+    }
+
+    fn validate_header(&self, reader: &mut BufReader<File>) -> std::io::Result<u32> {
+        let version = Self::read_header_version(reader)?;
+
+        if !(MIN_SUPPORTED_VERSION..=CURRENT_VERSION).contains(&version) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unsupported version: {}", version),
+            ));
+        }
+
+        // Skipping the remaining header bytes: reserved flags and the entry count
+        let mut reserved = [0u8; 8];
+        reader.read_exact(&mut reserved)?;
+
+        Ok(version)
+    }
+
+    fn read_header_version(reader: &mut BufReader<File>) -> std::io::Result<u32> {
         let mut magic = [0u8; 4];
         reader.read_exact(&mut magic)?;
 
@@ -131,24 +309,108 @@ impl Tree {
 
         let mut version = [0u8; 4];
         reader.read_exact(&mut version)?;
-        let version = u32::from_le_bytes(version);
+        Ok(u32::from_le_bytes(version))
+    }
+
+    /// Reads the format version from the SSTable header without checking it
+    /// against the supported window.
+    pub(crate) fn read_sstable_version(&self, path: &Path) -> std::io::Result<u32> {
+        let mut reader = self.file_pool.open(path)?;
+        Self::read_header_version(&mut reader)
+    }
+
+    /// Rejects an SSTable recorded with another compression type than the
+    /// configured compressor, which would fail to decompress every value.
+    ///
+    /// A zero compression byte is only trusted from format version
+    /// `COMPRESSION_MARKER_VERSION` on; older files may carry it for any type.
+    ///
+    /// # Errors
+    /// Returns `TreeError::Configuration` on a mismatch
+    pub(crate) fn check_compression_marker(&self, path: &Path, version: u32) -> TreeResult<()> {
+        let mut reader = self.file_pool.open(path)?;
+        reader.seek(SeekFrom::Start(8))?;
+        let mut marker = [0u8; 1];
+        reader.read_exact(&mut marker)?;
+        let marker = marker[0];
+
+        let configured = self.settings.compressor.config.compression_type;
+        if marker == configured.marker() || marker == 0 && version < COMPRESSION_MARKER_VERSION {
+            return Ok(());
+        }
+        let written = match CompressionType::from_marker(marker) {
+            Some(compression_type) => format!("{:?}", compression_type),
+            None => format!("unknown compression type {}", marker),
+        };
+        Err(TreeError::configuration(format!(
+            "SSTable {:?} was written with {} compression, but the tree is configured for {:?}",
+            path, written, configured
+        )))
+    }
+
+    pub(crate) fn sstable_version(&self, path: &Path) -> u32 {
+        self.legacy_sstables
+            .get(path)
+            .copied()
+            .unwrap_or(CURRENT_VERSION)
+    }
+
+    /// Reads the footer using the layout of the given format version.
+    ///
+    /// Version 1 files carry no bloom filter, so the bloom offset is `None` for them.
+    fn read_footer_for_version(
+        &self,
+        reader: &mut BufReader<File>,
+        version: u32,
+    ) -> std::io::Result<(u64, Option<u64>)> {
+        if version == 1 {
+            let index_offset = self.read_legacy_footer(reader)?;
+            Ok((index_offset, None))
+        } else {
+            let (index_offset, bloom_offset, _, _) = self.read_footer(reader, version)?;
+            Ok((index_offset, Some(bloom_offset)))
+        }
+    }
+
+    fn read_legacy_footer(&self, reader: &mut BufReader<File>) -> std::io::Result<u64> {
+        reader.seek(SeekFrom::End(-(LEGACY_FOOTER_SIZE as i64)))?;
+
+        let mut index_offset_bytes = [0u8; 8];
+        reader.read_exact(&mut index_offset_bytes)?;
+        let index_offset = u64::from_le_bytes(index_offset_bytes);
 
-        if version != CURRENT_VERSION {
+        // Reserved word, never used by version 1 writers
+        let mut reserved = [0u8; 4];
+        reader.read_exact(&mut reserved)?;
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != FOOTER_MAGIC_NUMBER {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                format!("Unsupported version: {}", version),
+                "Incorrect footer magic number",
             ));
         }
 
-        // Skipping other header bytes, as they are reserved for now
-        let mut reserved = [0u8; 8];
-        reader.read_exact(&mut reserved)?;
-
-        Ok(())
+        Ok(index_offset)
     }
 
-    fn read_footer(&self, reader: &mut BufReader<File>) -> std::io::Result<(u64, u64)> {
-        reader.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+    /// Reads the footer of a version 2 or later SSTable.
+    ///
+    /// Version 2 files carry no range tombstones and files before version 4 no
+    /// key range, so those offsets are `None` for them.
+    fn read_footer(
+        &self,
+        reader: &mut BufReader<File>,
+        version: u32,
+    ) -> std::io::Result<(u64, u64, Option<u64>, Option<u64>)> {
+        let footer_size = match version {
+            2 => V2_FOOTER_SIZE,
+            3 => V3_FOOTER_SIZE,
+            _ => FOOTER_SIZE,
+        };
+        reader.seek(SeekFrom::End(-(footer_size as i64)))?;
 
         let mut index_offset_bytes = [0u8; 8];
         reader.read_exact(&mut index_offset_bytes)?;
@@ -158,6 +420,22 @@ impl Tree {
         reader.read_exact(&mut bloom_offset_bytes)?;
         let bloom_offset = u64::from_le_bytes(bloom_offset_bytes);
 
+        let range_tombstone_offset = if version == 2 {
+            None
+        } else {
+            let mut range_tombstone_offset_bytes = [0u8; 8];
+            reader.read_exact(&mut range_tombstone_offset_bytes)?;
+            Some(u64::from_le_bytes(range_tombstone_offset_bytes))
+        };
+
+        let key_range_offset = if version < 4 {
+            None
+        } else {
+            let mut key_range_offset_bytes = [0u8; 8];
+            reader.read_exact(&mut key_range_offset_bytes)?;
+            Some(u64::from_le_bytes(key_range_offset_bytes))
+        };
+
         let mut magic = [0u8; 4];
         reader.read_exact(&mut magic)?;
 
@@ -168,7 +446,98 @@ impl Tree {
             ));
         }
 
-        Ok((index_offset, bloom_offset))
+        Ok((index_offset, bloom_offset, range_tombstone_offset, key_range_offset))
+    }
+
+    /// Reads the range tombstones stored in an SSTable.
+    ///
+    /// Files written before format version 3 carry none.
+    pub(crate) fn read_range_tombstones(&self, path: &Path) -> std::io::Result<Vec<RangeTombstone>> {
+        let mut reader = self.file_pool.open(path)?;
+        let version = self.validate_header(&mut reader)?;
+        if version < 3 {
+            return Ok(Vec::new());
+        }
+
+        let (_, _, range_tombstone_offset, _) = self.read_footer(&mut reader, version)?;
+        let Some(range_tombstone_offset) = range_tombstone_offset else {
+            return Ok(Vec::new());
+        };
+        reader.seek(SeekFrom::Start(range_tombstone_offset))?;
+
+        let mut size_bytes = [0u8; 4];
+        reader.read_exact(&mut size_bytes)?;
+        let size = u32::from_le_bytes(size_bytes) as usize;
+
+        let mut serialized_data = vec![0u8; size];
+        reader.read_exact(&mut serialized_data)?;
+
+        bincode::decode_from_slice(&serialized_data, self.settings.bincode_config)
+            .map(|(range_tombstones, _)| range_tombstones)
+            .map_err(|e| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to deserialize range tombstones: {}", e),
+            ))
+    }
+
+    /// Reads the first and last key stored in the footer of an SSTable.
+    ///
+    /// Files written before format version 4 carry none, and neither do
+    /// tables without entries; both return `None`.
+    pub(crate) fn read_key_range(&self, path: &Path) -> std::io::Result<Option<KeyRange>> {
+        let mut reader = self.file_pool.open(path)?;
+        let version = self.validate_header(&mut reader)?;
+        if version < 4 {
+            return Ok(None);
+        }
+
+        let (_, _, _, key_range_offset) = self.read_footer(&mut reader, version)?;
+        let Some(key_range_offset) = key_range_offset else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Start(key_range_offset))?;
+
+        let mut has_keys = [0u8; 1];
+        reader.read_exact(&mut has_keys)?;
+        if has_keys[0] == 0 {
+            return Ok(None);
+        }
+        let mut read_key = || -> std::io::Result<Vec<u8>> {
+            let mut len_bytes = [0u8; 4];
+            reader.read_exact(&mut len_bytes)?;
+            let mut key = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+            reader.read_exact(&mut key)?;
+            Ok(key)
+        };
+        let first_key = read_key()?;
+        let last_key = read_key()?;
+        Ok(Some(KeyRange { first_key, last_key }))
+    }
+
+    /// Reads the number of data entries in an SSTable.
+    ///
+    /// Format version 3 files record the count in the header, since a sparse
+    /// index holds fewer entries than the data block. Files that leave it zero
+    /// (older versions, or tables without entries) have a dense index, so the
+    /// count stored in front of the index block is used instead.
+    pub(crate) fn read_index_entry_count(&self, path: &Path) -> std::io::Result<usize> {
+        let mut reader = self.file_pool.open(path)?;
+        let version = self.validate_header(&mut reader)?;
+        if version >= 3 {
+            reader.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+            let mut count_bytes = [0u8; 4];
+            reader.read_exact(&mut count_bytes)?;
+            let entry_count = u32::from_le_bytes(count_bytes) as usize;
+            if entry_count > 0 {
+                return Ok(entry_count);
+            }
+        }
+        let (index_offset, _) = self.read_footer_for_version(&mut reader, version)?;
+        reader.seek(SeekFrom::Start(index_offset))?;
+
+        let mut count_bytes = [0u8; 4];
+        reader.read_exact(&mut count_bytes)?;
+        Ok(u32::from_le_bytes(count_bytes) as usize)
     }
 
     fn read_index(
@@ -202,71 +571,61 @@ impl Tree {
         Ok(index)
     }
 
-    pub(crate) fn load_sstable(&mut self, path: &PathBuf) -> BTreeMap<Vec<u8>, DataValue> {
-        match self.load_sstable_with_bloom_filter(path) {
-            Ok((table, bloom_filter)) => {
-                if self.settings.enable_bloom_filter_cache {
-                    self.bloom_filters.push(BloomFilter {
-                        path: path.clone(),
-                        bloom_filter,
-                    });
-                }
-                table
-            },
-            Err(e) => {
-                error!("Error loading SSTable {:?}: {}", path, e);
-                BTreeMap::new()
-            }
-        }
-    }
-
+    #[cfg(test)]
     pub(crate) fn load_sstable_with_bloom_filter(
         &self,
         path: &PathBuf,
     ) -> Result<(BTreeMap<Vec<u8>, DataValue>, GrowableBloom), std::io::Error> {
         let mut table = BTreeMap::new();
 
-        match File::open(path) {
-            Ok(file) => {
-                let mut reader = BufReader::new(file);
-
-                if let Err(e) = self.validate_header(&mut reader) {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        format!("Wrong header SSTable {:?}: {}", path, e)));
-                }
+        match self.file_pool.open(path) {
+            Ok(mut reader) => {
 
-                let (index_offset, bloom_offset) = match self.read_footer(&mut reader) {
-                    Ok(offsets) => offsets,
+                let version = match self.validate_header(&mut reader) {
+                    Ok(version) => version,
                     Err(e) => {
                         return Err(std::io::Error::new(
                             std::io::ErrorKind::InvalidData,
-                            format!("Error reading footer SSTable {:?}: {}", path, e)));
+                            format!("Wrong header SSTable {:?}: {}", path, e)));
                     }
                 };
 
-                let index = match self.read_index(&mut reader, index_offset) {
-                    Ok(idx) => idx,
-                    Err(e) => {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!("Error reading index SSTable {:?}: {}", path, e)));
-                    }
-                };
+                let (index_offset, bloom_offset) =
+                    match self.read_footer_for_version(&mut reader, version) {
+                        Ok(offsets) => offsets,
+                        Err(e) => {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                format!("Error reading footer SSTable {:?}: {}", path, e)));
+                        }
+                    };
 
-                for (key, offset) in index {
-                    if let Ok(value) = self.read_data_entry(&mut reader, offset) {
-                        table.insert(key, value);
+                reader.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+                let mut position = HEADER_SIZE as u64;
+                while position < index_offset {
+                    let entry = match read_raw_entry(&mut reader, version) {
+                        Ok(entry) => entry,
+                        Err(e) => {
+                            error!("Error reading data entry from SSTable {:?}: {}", path, e);
+                            break;
+                        }
+                    };
+                    position += entry.size;
+                    if let Ok(value) = self.decode_raw_entry(&entry) {
+                        table.insert(entry.key, value);
                     }
                 }
 
-                let bloom_filter = match self.read_bloom_filter(&mut reader, bloom_offset) {
-                    Ok(bloom_filter) => bloom_filter,
-                    Err(e) => {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!("Error reading bloom filter SSTable {:?}: {}", path, e)));
-                    }
+                let bloom_filter = match bloom_offset {
+                    Some(bloom_offset) => match self.read_bloom_filter(&mut reader, bloom_offset) {
+                        Ok(bloom_filter) => bloom_filter,
+                        Err(e) => {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                format!("Error reading bloom filter SSTable {:?}: {}", path, e)));
+                        }
+                    },
+                    None => self.build_bloom_filter(table.keys()),
                 };
 
                 Ok((table, bloom_filter))
@@ -283,22 +642,33 @@ impl Tree {
         &self,
         path: &PathBuf,
     ) -> Result<GrowableBloom, std::io::Error> {
-        match File::open(path) {
-            Ok(file) => {
-                let mut reader = BufReader::new(file);
-
-                if let Err(e) = self.validate_header(&mut reader) {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        format!("Wrong header SSTable {:?}: {}", path, e)));
-                }
+        match self.file_pool.open(path) {
+            Ok(mut reader) => {
 
-                let (_, bloom_offset) = match self.read_footer(&mut reader) {
-                    Ok(offsets) => offsets,
+                let version = match self.validate_header(&mut reader) {
+                    Ok(version) => version,
                     Err(e) => {
                         return Err(std::io::Error::new(
                             std::io::ErrorKind::InvalidData,
-                            format!("Error reading footer SSTable {:?}: {}", path, e)));
+                            format!("Wrong header SSTable {:?}: {}", path, e)));
+                    }
+                };
+
+                let (index_offset, bloom_offset) =
+                    match self.read_footer_for_version(&mut reader, version) {
+                        Ok(offsets) => offsets,
+                        Err(e) => {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                format!("Error reading footer SSTable {:?}: {}", path, e)));
+                        }
+                    };
+
+                let bloom_offset = match bloom_offset {
+                    Some(bloom_offset) => bloom_offset,
+                    None => {
+                        let index = self.read_index(&mut reader, index_offset)?;
+                        return Ok(self.build_bloom_filter(index.keys()));
                     }
                 };
 
@@ -324,33 +694,359 @@ impl Tree {
     pub(crate) fn write_sstable(
         &mut self,
         table: &BTreeMap<Vec<u8>, DataValue>,
+        range_tombstones: Vec<RangeTombstone>,
     ) -> Result<(PathBuf, GrowableBloom), std::io::Error> {
-        let new_sstable_number = match util::find_last_sstable_number(&self.settings.db_path) {
+        let number = self.reserve_sstable_numbers(1);
+        let table_path = self.settings.db_path.join(format!("sstable_{}.sst", number));
+
+        let (index, bloom_filter) = self.write_sstable_file(&table_path, table, &range_tombstones)?;
+        self.sync_db_directory()?;
+        self.record_key_range(&table_path, &index);
+        self.record_range_tombstones(&table_path, range_tombstones);
+        if self.settings.enable_index_cache {
+            self.index_cache().put(table_path.clone(), index);
+        }
+        Ok((table_path, bloom_filter))
+    }
+
+    /// Number the next new SSTable is written under: one above the newest
+    /// on disk, skipping the numbers handed out to tables still being written.
+    pub(crate) fn next_sstable_number(&self) -> usize {
+        let after_last = match util::find_last_sstable_number(&self.settings.db_path) {
             None => 0,
             Some(number) => number + 1,
         };
-        let table_path = self
-            .settings
-            .db_path
-            .join(format!("sstable_{}.sst", new_sstable_number));
+        after_last.max(self.next_unreserved_sstable)
+    }
+
+    /// Hands out `count` consecutive SSTable numbers, so a flush and a merge
+    /// running in the background never write under the same name.
+    ///
+    /// # Returns
+    /// The first of the numbers
+    pub(crate) fn reserve_sstable_numbers(&mut self, count: usize) -> usize {
+        let first = self.next_sstable_number();
+        self.next_unreserved_sstable = first + count;
+        first
+    }
+
+    pub(crate) fn record_range_tombstones(&mut self, path: &Path, range_tombstones: Vec<RangeTombstone>) {
+        if range_tombstones.is_empty() {
+            self.range_tombstones.remove(path);
+        } else {
+            self.range_tombstones.insert(path.to_path_buf(), range_tombstones);
+        }
+    }
+
+    pub(crate) fn record_key_range(&mut self, path: &Path, index: &BTreeMap<Vec<u8>, u64>) {
+        match (index.keys().next(), index.keys().next_back()) {
+            (Some(first_key), Some(last_key)) => {
+                self.key_ranges.insert(
+                    path.to_path_buf(),
+                    KeyRange {
+                        first_key: first_key.clone(),
+                        last_key: last_key.clone(),
+                    },
+                );
+            }
+            _ => {
+                self.key_ranges.remove(path);
+            }
+        }
+    }
+
+    /// Returns the first and last key stored in an SSTable.
+    ///
+    /// Bounds are remembered once known; unknown bounds are read from the
+    /// footer, or for files written before format version 4 taken from the
+    /// cached index or the file's index block.
+    pub(crate) fn sstable_key_range(&mut self, path: &PathBuf) -> Option<KeyRange> {
+        if let Some(range) = self.key_ranges.get(path) {
+            return Some(range.clone());
+        }
+        match self.read_key_range(path) {
+            Ok(Some(range)) => {
+                self.key_ranges.insert(path.clone(), range.clone());
+                return Some(range);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Error reading key range of SSTable {:?}, using its index: {}", path, e),
+        }
+
+        let index = match self.index_cache().get(path) {
+            Some(index) => index.clone(),
+            None => match self.read_sstable_index(path) {
+                Ok(index) => index,
+                Err(e) => {
+                    error!("Error reading index of SSTable {:?}: {}", path, e);
+                    return None;
+                }
+            },
+        };
+        self.record_key_range(path, &index);
+        self.key_ranges.get(path).cloned()
+    }
+
+    pub(crate) fn read_sstable_index(&self, path: &Path) -> std::io::Result<BTreeMap<Vec<u8>, u64>> {
+        let mut reader = self.file_pool.open(path)?;
+        let version = self.validate_header(&mut reader)?;
+        let (index_offset, _) = self.read_footer_for_version(&mut reader, version)?;
+        self.read_index(&mut reader, index_offset)
+    }
+
+    /// Merges the SSTables overlapping `[start, end)` into a single table.
+    ///
+    /// The merged run spans from the oldest to the newest overlapping table,
+    /// including any table in between, so newest-wins ordering against the
+    /// untouched tables is preserved. The output is written under a new SSTable
+    /// number and replaces the run in the manifest before the run's files are
+    /// deleted. Until the output is renamed into place and the directory is
+    /// synced, the tree keeps serving the run unchanged, so a failed write
+    /// loses nothing. Tombstones and expired entries are dropped only for keys
+    /// inside the range: no table outside the run can hold such keys.
+    /// Entries shadowed by the run's range tombstones are dropped as well; a
+    /// range tombstone itself is dropped once it lies inside the range or the
+    /// run starts at the oldest table, and carried into the output otherwise.
+    pub(crate) fn compact_sstable_range(&mut self, start: &[u8], end: &[u8]) -> TreeResult<()> {
+        let sstables = self.ss_tables.clone();
+        let overlapping: Vec<usize> = sstables
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| {
+                self.sstable_key_range(path)
+                    .is_some_and(|range| range.overlaps(start, end))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+
+        let (first_idx, last_idx) = match (overlapping.first(), overlapping.last()) {
+            (Some(&first_idx), Some(&last_idx)) => (first_idx, last_idx),
+            _ => return Ok(()),
+        };
+
+        let run: Vec<PathBuf> = sstables[first_idx..=last_idx].to_vec();
+        let run_range_tombstones: Vec<RangeTombstone> = run
+            .iter()
+            .filter_map(|path| self.range_tombstones.get(path))
+            .flatten()
+            .cloned()
+            .collect();
+        let range_tombstones: Vec<RangeTombstone> = run_range_tombstones
+            .iter()
+            .filter(|range_tombstone| {
+                let droppable = first_idx == 0 || range_tombstone.is_within(start, end);
+                !(droppable && self.tombstone_past_grace(range_tombstone.created_at))
+            })
+            .cloned()
+            .collect();
+
+        // The output's bloom filter is sized to the surviving entries, so the
+        // run is read once to count them and once more to write the output.
+        let (entries_before, entries_after) = self.count_merged_entries(&run, |key, value| {
+            self.survives_range_compaction(start, end, &run_range_tombstones, key, value)
+        })?;
+
+        self.check_cancelled()?;
+        // Like a background merge, the output takes a new number, so the run
+        // stays intact until the manifest lists the output instead. The tree
+        // keeps serving the run until the output is durable under its name.
+        let output = if entries_after == 0 && range_tombstones.is_empty() {
+            None
+        } else {
+            let number = self.reserve_sstable_numbers(1);
+            let output_path = self.settings.db_path.join(format!("sstable_{}.sst", number));
+            let entries = self.merge_filtered(self.merge_sources(&run)?, |key, value| {
+                self.survives_range_compaction(start, end, &run_range_tombstones, key, value)
+            });
+            let (index, bloom_filter) =
+                self.write_range_output(&output_path, entries, entries_after, &range_tombstones)?;
+            Some((index, bloom_filter, output_path))
+        };
+        let dropped = entries_before - entries_after;
+
+        for path in &run {
+            self.index_cache().remove(path);
+            self.index_cache().lru_queue.retain(|p| p != path);
+            self.value_cache().invalidate_sstable(path);
+            self.file_pool.invalidate(path);
+            self.bloom_filters_mut().retain(|bf| bf.path != *path);
+            self.legacy_sstables.remove(path);
+            self.key_ranges.remove(path);
+            self.range_tombstones.remove(path);
+        }
+        self.ss_tables.drain(first_idx..=last_idx);
+
+        let mut output_written = None;
+        if let Some((index, bloom_filter, output_path)) = output {
+            self.record_key_range(&output_path, &index);
+            self.record_range_tombstones(&output_path, range_tombstones);
+            if self.settings.enable_index_cache {
+                self.index_cache().put(output_path.clone(), index);
+            }
+            self.cache_bloom_filter(output_path.clone(), bloom_filter);
+            self.ss_tables.insert(first_idx, output_path.clone());
+            self.emit_event(TreeEvent::Merged {
+                inputs: run.clone(),
+                output: output_path.clone(),
+                dropped,
+            });
+            output_written = Some(output_path);
+        }
+        self.write_manifest()?;
+        for path in &run {
+            if let Err(e) = std::fs::remove_file(path) {
+                error!("Error deleting old SSTable {:?}: {}", path, e);
+            }
+        }
+        self.record_merge(run.len(), output_written.as_deref(), dropped);
+
+        info!("Compacted {} SSTables for range, dropped {} entries", run.len(), dropped);
+        Ok(())
+    }
+
+    /// Whether `compact_sstable_range` keeps an entry of its run. No table
+    /// older than the run overlaps the range, so tombstones inside it have
+    /// nothing left to hide once past their grace period.
+    fn survives_range_compaction(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        range_tombstones: &[RangeTombstone],
+        key: &[u8],
+        value: &DataValue,
+    ) -> bool {
+        let in_range = key >= start && key < end;
+        let shadowed = range_tombstones.iter().any(|range_tombstone| range_tombstone.shadows(key, value));
+        let droppable = value.is_expired() || value.is_tombstone && self.tombstone_past_grace(value.created_at);
+        !(shadowed || in_range && droppable)
+    }
+
+    /// Writes the output of `compact_sstable_range` next to `output_path` and
+    /// renames it into place. Nothing is left behind if a step fails.
+    fn write_range_output(
+        &self,
+        output_path: &Path,
+        entries: impl Iterator<Item = TreeResult<(Vec<u8>, DataValue)>>,
+        entry_count: usize,
+        range_tombstones: &[RangeTombstone],
+    ) -> TreeResult<(BTreeMap<Vec<u8>, u64>, GrowableBloom)> {
+        let tmp_path = output_path.with_extension("sst.tmp");
+        let written = self.write_sstable_entries(&tmp_path, entries, entry_count, range_tombstones)?;
+        self.file_pool.invalidate(output_path);
+        #[cfg(test)]
+        if self.fail_sstable_rename {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(std::io::Error::other("injected SSTable rename failure").into());
+        }
+        if let Err(e) = std::fs::rename(&tmp_path, output_path) {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+        if let Err(e) = self.sync_db_directory() {
+            let _ = std::fs::remove_file(output_path);
+            return Err(e.into());
+        }
+        Ok(written)
+    }
+
+    /// Writes an SSTable file, removing the partial file if any write fails
+    /// (for example when the disk is full).
+    pub(crate) fn write_sstable_file(
+        &self,
+        table_path: &Path,
+        table: &BTreeMap<Vec<u8>, DataValue>,
+        range_tombstones: &[RangeTombstone],
+    ) -> Result<(BTreeMap<Vec<u8>, u64>, GrowableBloom), std::io::Error> {
+        self.write_sstable_entries(table_path, table.iter().map(Ok), table.len(), range_tombstones)
+    }
+
+    /// Writes an SSTable file from `entries` in ascending key order, holding
+    /// one entry in memory at a time. `entry_count` must be the number of
+    /// entries the iterator yields; it sizes the bloom filter. The partial
+    /// file is removed if reading an entry or any write fails.
+    pub(crate) fn write_sstable_entries<K, V, E>(
+        &self,
+        table_path: &Path,
+        entries: impl Iterator<Item = Result<(K, V), E>>,
+        entry_count: usize,
+        range_tombstones: &[RangeTombstone],
+    ) -> Result<(BTreeMap<Vec<u8>, u64>, GrowableBloom), E>
+    where
+        K: AsRef<[u8]>,
+        V: Borrow<DataValue>,
+        E: From<std::io::Error> + Display,
+    {
+        self.file_pool.invalidate(table_path);
+        self.write_sstable_contents(table_path, entries, entry_count, range_tombstones).inspect_err(|e| {
+            error!("Error writing SSTable {:?}: {}", table_path, e);
+            if let Err(remove_err) = std::fs::remove_file(table_path) {
+                if remove_err.kind() != std::io::ErrorKind::NotFound {
+                    error!("Error removing partial SSTable {:?}: {}", table_path, remove_err);
+                }
+            }
+        })
+    }
+
+    fn write_sstable_contents<K, V, E>(
+        &self,
+        table_path: &Path,
+        entries: impl Iterator<Item = Result<(K, V), E>>,
+        entry_count: usize,
+        range_tombstones: &[RangeTombstone],
+    ) -> Result<(BTreeMap<Vec<u8>, u64>, GrowableBloom), E>
+    where
+        K: AsRef<[u8]>,
+        V: Borrow<DataValue>,
+        E: From<std::io::Error>,
+    {
         if let Some(parent_dir) = table_path.parent() {
             std::fs::create_dir_all(parent_dir)?;
         }
 
-        let file = File::create(&table_path)?;
-        let mut writer = BufWriter::new(file);
+        let file = File::create(table_path)?;
+        let mut writer = BufWriter::with_capacity(self.settings.write_buffer_size, file);
 
-        self.write_header(&mut writer)?;
+        self.write_header(&mut writer, entry_count)?;
 
+        let index_block_interval = self.settings.index_block_interval.max(1);
         let mut index = BTreeMap::new();
         let mut bloom_filter =
-            GrowableBloom::new(self.settings.bloom_filter_error_probability, table.len());
-
-        for (key, value) in table {
+            GrowableBloom::new(self.settings.bloom_filter_error_probability, entry_count.max(1));
+
+        let mut scratch = Vec::new();
+        let mut throttled_to = 0;
+        let mut written = 0;
+        for entry in entries {
+            let (key, value) = entry?;
+            let (key, value) = (key.as_ref(), value.borrow());
             let offset = writer.stream_position()?;
-            self.write_data_entry(&mut writer, key, value)?;
-            index.insert(key.clone(), offset);
+            if offset - throttled_to >= RATE_LIMIT_CHUNK_SIZE {
+                self.throttle_write(offset - throttled_to);
+                throttled_to = offset;
+            }
+            self.write_data_entry(&mut writer, key, value, &mut scratch)?;
+            if written % index_block_interval == 0 || written + 1 == entry_count {
+                index.insert(key.to_vec(), offset);
+            }
             bloom_filter.insert(key);
+            written += 1;
+
+            #[cfg(test)]
+            if self.sstable_write_limit.is_some_and(|limit| offset > limit) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::StorageFull,
+                    "injected SSTable write failure",
+                )
+                .into());
+            }
+        }
+
+        if written != entry_count {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Expected {} SSTable entries, got {}", entry_count, written),
+            )
+            .into());
         }
 
         let index_offset = writer.stream_position()?;
@@ -359,19 +1055,80 @@ impl Tree {
         let bloom_offset = writer.stream_position()?;
         self.write_bloom_filter(&mut writer, &bloom_filter)?;
 
-        self.write_footer(&mut writer, index_offset, bloom_offset)?;
+        let range_tombstone_offset = writer.stream_position()?;
+        self.write_range_tombstones(&mut writer, range_tombstones)?;
+
+        let key_range_offset = writer.stream_position()?;
+        // The first and the last entry are always indexed
+        self.write_key_range(&mut writer, index.keys().next(), index.keys().next_back())?;
+
+        self.write_footer(&mut writer, index_offset, bloom_offset, range_tombstone_offset, key_range_offset)?;
+        self.throttle_write(writer.stream_position()? - throttled_to);
 
         writer.flush()?;
+        // Flushed tables replace WAL segments and merged tables replace their
+        // inputs, so the data has to be on disk before either is discarded.
+        writer.get_ref().sync_data()?;
+        Ok((index, bloom_filter))
+    }
+
+    /// Waits for the configured rate limiter, if any, to allow `bytes` more
+    /// bytes of SSTable writes.
+    fn throttle_write(&self, bytes: u64) {
+        if let Some(rate_limiter) = &self.settings.rate_limiter {
+            rate_limiter.acquire(bytes);
+        }
+    }
+
+    fn build_bloom_filter<'a>(&self, keys: impl ExactSizeIterator<Item = &'a Vec<u8>>) -> GrowableBloom {
+        let mut bloom_filter =
+            GrowableBloom::new(self.settings.bloom_filter_error_probability, keys.len().max(1));
+        for key in keys {
+            bloom_filter.insert(key);
+        }
+        bloom_filter
+    }
+
+    /// Rewrites an SSTable written in an older format version in place, using the current format.
+    pub(crate) fn migrate_sstable(&mut self, path: &PathBuf) -> TreeResult<()> {
+        let from_version = self.sstable_version(path);
+        // Older formats may not record their entry count, so the table is read
+        // once to count its entries and once more to rewrite it.
+        let entry_count = self.sstable_entries(path)?.try_fold(0, |count, entry| entry.map(|_| count + 1))?;
+
+        let range_tombstones = self.range_tombstones.get(path).cloned().unwrap_or_default();
+
+        let tmp_path = path.with_extension("sst.tmp");
+        let (index, bloom_filter) =
+            self.write_sstable_entries(&tmp_path, self.sstable_entries(path)?, entry_count, &range_tombstones)?;
+        self.file_pool.invalidate(path);
+        std::fs::rename(&tmp_path, path)?;
+        self.sync_db_directory()?;
+
+        self.legacy_sstables.remove(path);
+        self.index_cache().remove(path);
+        self.index_cache().lru_queue.retain(|p| p != path);
+        self.value_cache().invalidate_sstable(path);
+        self.bloom_filters_mut().retain(|bf| bf.path != *path);
+
         if self.settings.enable_index_cache {
-            self.index_cache.put(table_path.clone(), index);
+            self.index_cache().put(path.clone(), index);
         }
-        Ok((table_path, bloom_filter))
+        self.cache_bloom_filter(path.clone(), bloom_filter);
+
+        info!(
+            "Migrated SSTable {:?} from format version {} to {}",
+            path, from_version, CURRENT_VERSION
+        );
+        Ok(())
     }
 
-    fn write_header(&self, writer: &mut BufWriter<File>) -> std::io::Result<()> {
+    fn write_header(&self, writer: &mut BufWriter<File>, entry_count: usize) -> std::io::Result<()> {
         writer.write_all(HEADER_MAGIC_NUMBER)?;
         writer.write_all(&CURRENT_VERSION.to_le_bytes())?;
-        writer.write_all(&[0u8; 8])?; // compression, checksum_type, reserved
+        let compression = self.settings.compressor.config.compression_type.marker();
+        writer.write_all(&[compression, 0, 0, 0])?; // compression, checksum_type, reserved
+        writer.write_all(&(entry_count as u32).to_le_bytes())?;
         Ok(())
     }
 
@@ -380,18 +1137,21 @@ impl Tree {
         writer: &mut BufWriter<File>,
         key: &[u8],
         value: &DataValue,
+        value_bytes: &mut Vec<u8>,
     ) -> std::io::Result<()> {
-        let value_bytes = bincode::encode_to_vec(value, self.settings.bincode_config).unwrap();
+        value_bytes.clear();
+        bincode::encode_into_std_write(value, value_bytes, self.settings.bincode_config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
 
         writer.write_all(&(key.len() as u32).to_le_bytes())?;
         writer.write_all(key)?;
 
         writer.write_all(&(value_bytes.len() as u32).to_le_bytes())?;
-        writer.write_all(&value_bytes)?;
+        writer.write_all(value_bytes)?;
 
         let mut hasher = Hasher::new();
         hasher.update(key);
-        hasher.update(&value_bytes);
+        hasher.update(value_bytes);
         let checksum = hasher.finalize();
         writer.write_all(&checksum.to_le_bytes())?;
 
@@ -432,6 +1192,40 @@ impl Tree {
         }
     }
 
+    fn write_range_tombstones(
+        &self,
+        writer: &mut BufWriter<File>,
+        range_tombstones: &[RangeTombstone],
+    ) -> std::io::Result<()> {
+        let serialized_data = bincode::encode_to_vec(range_tombstones, self.settings.bincode_config)
+            .map_err(|e| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to serialize range tombstones: {}", e),
+            ))?;
+        writer.write_all(&(serialized_data.len() as u32).to_le_bytes())?;
+        writer.write_all(&serialized_data)?;
+        Ok(())
+    }
+
+    /// Writes the first and last key of the table, behind a flag byte that is
+    /// 0 for a table without entries.
+    fn write_key_range(
+        &self,
+        writer: &mut BufWriter<File>,
+        first_key: Option<&Vec<u8>>,
+        last_key: Option<&Vec<u8>>,
+    ) -> std::io::Result<()> {
+        let (Some(first_key), Some(last_key)) = (first_key, last_key) else {
+            return writer.write_all(&[0]);
+        };
+        writer.write_all(&[1])?;
+        for key in [first_key, last_key] {
+            writer.write_all(&(key.len() as u32).to_le_bytes())?;
+            writer.write_all(key)?;
+        }
+        Ok(())
+    }
+
     fn read_bloom_filter(
         &self,
         reader: &mut BufReader<File>,
@@ -460,184 +1254,24 @@ impl Tree {
         writer: &mut BufWriter<File>,
         index_offset: u64,
         bloom_filter_offset: u64,
+        range_tombstone_offset: u64,
+        key_range_offset: u64,
     ) -> std::io::Result<()> {
         writer.write_all(&index_offset.to_le_bytes())?;
         writer.write_all(&bloom_filter_offset.to_le_bytes())?;
+        writer.write_all(&range_tombstone_offset.to_le_bytes())?;
+        writer.write_all(&key_range_offset.to_le_bytes())?;
         writer.write_all(FOOTER_MAGIC_NUMBER)?;
         Ok(())
     }
 
-    pub(crate) fn merge_sstables(&mut self) -> TreeResult<()> {
-        let tables_to_merge_count = std::cmp::min(self.ss_tables.len(), 3);
-        if tables_to_merge_count < 2 {
-            return Ok(());
-        }
-
-        let tables_to_merge: Vec<PathBuf> =
-            self.ss_tables.drain(0..tables_to_merge_count).collect();
-
-        let mut table_data: Vec<BTreeMap<Vec<u8>, DataValue>> =
-            Vec::with_capacity(tables_to_merge.len());
-        for table_path in &tables_to_merge {
-            table_data.push(self.load_sstable(table_path));
-        }
-
-        let mut iterators: Vec<_> = table_data
-            .iter()
-            .map(|table| table.iter())
-            .collect();
-
-        let mut min_heap = BinaryHeap::new();
-
-        for (idx, iterator) in iterators.iter_mut().enumerate() {
-            if let Some((key, value)) = iterator.next() {
-                min_heap.push(HeapEntry {
-                    key: key.clone(),
-                    value: value.clone(),
-                    table_index: idx,
-                });
-            }
-        }
-
-        let mut merged_data = BTreeMap::new();
-        let mut last_key: Option<Vec<u8>> = None;
-
-        while let Some(entry) = min_heap.pop() {
-            if entry.value.is_empty() || entry.value.is_tombstone {
-                continue;
-            }
-
-            let HeapEntry {
-                key,
-                value,
-                table_index,
-            } = entry;
-
-            if let Some(ref last) = last_key {
-                if *last == key {
-                    if let Some((next_key, next_value)) = iterators[table_index].next() {
-                        min_heap.push(HeapEntry {
-                            key: next_key.clone(),
-                            value: next_value.clone(),
-                            table_index,
-                        });
-                    }
-                    continue;
-                }
-            }
-
-            last_key = Some(key.clone());
-            merged_data.insert(key, value);
-            if let Some((next_key, next_value)) = iterators[table_index].next() {
-                min_heap.push(HeapEntry {
-                    key: next_key.clone(),
-                    value: next_value.clone(),
-                    table_index,
-                });
-            }
-        }
-
-        if self.settings.enable_index_cache {
-            for path in &tables_to_merge {
-                self.index_cache.remove(path);
-                self.index_cache.lru_queue.retain(|p| !p.eq(path));
-                self.index_cache.remove(path);
-            }
-        }
-        if self.settings.enable_value_cache {
-            for path in &tables_to_merge {
-                self.value_cache.invalidate_sstable(path);
-            }
-        }
-
-        match self.write_sstable(&merged_data) {
-            Ok((path, bloom_filter)) => {
-                self.ss_tables.push(path.clone());
-                if self.settings.enable_bloom_filter_cache {
-                    self.bloom_filters.push(BloomFilter { path, bloom_filter })
-                }
-            }
-            Err(e) => {
-                error!("Error writing merged SSTable: {}", e);
-                return Ok(());
-            }
-        };
-
-        for path in tables_to_merge {
-            if let Err(e) = std::fs::remove_file(&path) {
-                error!("Error deleting old SSTable {:?}: {}", path, e);
-            }
-            self.ss_tables.retain(|p| p != &path);
-            self.bloom_filters.retain(|bf| bf.path != path);
-        }
-
-        if let Err(e) = self.rename_sstables_after_merge() {
-            error!("Error renaming SSTable files: {}", e);
-            return Ok(());
-        }
-
-        self.remove_obsolete_wal_segments();
-
-        Ok(())
-    }
-
-    fn rename_sstables_after_merge(&mut self) -> std::io::Result<()> {
-        let mut sstables_with_numbers: Vec<(usize, PathBuf)> = Vec::new();
-
-        for path in &self.ss_tables {
-            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                if file_name.starts_with("sstable_") && file_name.ends_with(".sst") {
-                    let number_part = &file_name[8..file_name.len() - 4];
-                    if let Ok(number) = number_part.parse::<usize>() {
-                        sstables_with_numbers.push((number, path.clone()));
-                    }
-                }
-            }
-        }
-
-        if sstables_with_numbers.is_empty() {
-            return Ok(());
-        }
-
-        if sstables_with_numbers[0].0 > self.ss_tables.len() {
-            let mut updated_paths = Vec::new();
-            for (new_index, (_, old_path)) in sstables_with_numbers.into_iter().enumerate() {
-                let new_name = format!("sstable_{}.sst", new_index);
-                let new_path = old_path.parent().unwrap().join(&new_name);
-
-                if old_path != new_path {
-                    std::fs::rename(&old_path, &new_path)?;
-
-                    if self.settings.enable_index_cache {
-                        if let Some(cached_index) = self.index_cache.remove(&old_path) {
-                            self.index_cache.put(new_path.clone(), cached_index.clone());
-                        }
-                    }
-
-                    if self.settings.enable_value_cache {
-                        self.value_cache.rename_sstable(&old_path, &new_path);
-                    }
-
-                    for bloom_filter in &mut self.bloom_filters {
-                        if bloom_filter.path == old_path {
-                            bloom_filter.path = new_path.clone();
-                        }
-                    }
-                }
-                updated_paths.push(new_path);
-            }
-            self.ss_tables = updated_paths;
-        }
-
-        Ok(())
-    }
-
+    /// Reads the index block and locates a key in it, see `locate_in_index`.
     fn find_key_in_index(
         &self,
         reader: &mut BufReader<File>,
         index_offset: u64,
         key: &[u8],
-    ) -> Option<u64> {
+    ) -> Option<(u64, bool)> {
         reader.seek(SeekFrom::Start(index_offset)).ok()?;
 
         let mut index_num_entries_count_bytes = [0u8; 4];
@@ -670,31 +1304,105 @@ impl Tree {
             entries.push((index_key, data_entry_offset));
         }
 
-        let mut left = 0;
-        let mut right = entries.len();
+        let following = entries.partition_point(|(index_key, _)| index_key.as_slice() <= key);
+        let (floor_key, offset) = entries.get(following.checked_sub(1)?)?;
+        if floor_key.as_slice() == key {
+            Some((*offset, true))
+        } else if following < entries.len() {
+            Some((*offset, false))
+        } else {
+            None
+        }
+    }
 
-        while left < right {
-            let mid = left + (right - left) / 2;
-            let (index_key, offset) = &entries[mid];
+    /// Reads the value of `key` from a location found by `locate_in_index`.
+    ///
+    /// An exact location is read directly. Otherwise the data entries are
+    /// scanned forward from the preceding indexed key until the key is found or
+    /// passed; the scan cannot leave the data block because the last entry of
+    /// every SSTable is indexed.
+    fn read_located_entry(
+        &self,
+        reader: &mut BufReader<File>,
+        location: (u64, bool),
+        key: &[u8],
+        version: u32,
+    ) -> std::io::Result<Option<DataValue>> {
+        let (offset, exact) = location;
+        if exact {
+            return self.read_data_entry(reader, offset, version).map(Some);
+        }
 
-            match index_key.as_slice().cmp(key) {
-                std::cmp::Ordering::Equal => {
-                    return Some(*offset);
-                }
-                std::cmp::Ordering::Less => {
-                    left = mid + 1;
-                }
-                std::cmp::Ordering::Greater => right = mid,
+        reader.seek(SeekFrom::Start(offset))?;
+        loop {
+            let entry = read_raw_entry(reader, version)?;
+            match entry.key.as_slice().cmp(key) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Equal => return self.decode_raw_entry(&entry).map(Some),
+                std::cmp::Ordering::Greater => return Ok(None),
             }
         }
+    }
 
-        None
+    fn decode_raw_entry(&self, entry: &RawEntry) -> std::io::Result<DataValue> {
+        if !entry.checksum_ok {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Data entry checksum mismatch",
+            ));
+        }
+        self.decode_data_value(&entry.value_bytes)
     }
 
+    /// Reads a data entry using the layout of the given format version.
+    ///
+    /// Version 2 entries end with a CRC32 over key and value which is verified here;
+    /// version 1 entries carry no checksum.
     fn read_data_entry(
         &self,
         reader: &mut BufReader<File>,
         offset: u64,
+        version: u32,
+    ) -> std::io::Result<DataValue> {
+        if version == 1 {
+            return self.read_legacy_data_entry(reader, offset);
+        }
+
+        reader.seek(SeekFrom::Start(offset))?;
+
+        let mut key_len_bytes = [0u8; 4];
+        reader.read_exact(&mut key_len_bytes)?;
+        let key_len = u32::from_le_bytes(key_len_bytes) as usize;
+
+        let mut key = vec![0u8; key_len];
+        reader.read_exact(&mut key)?;
+
+        let mut value_len_bytes = [0u8; 4];
+        reader.read_exact(&mut value_len_bytes)?;
+        let value_len = u32::from_le_bytes(value_len_bytes) as usize;
+
+        let mut value_bytes = vec![0u8; value_len];
+        reader.read_exact(&mut value_bytes)?;
+
+        let mut checksum_bytes = [0u8; 4];
+        reader.read_exact(&mut checksum_bytes)?;
+        let mut hasher = Hasher::new();
+        hasher.update(&key);
+        hasher.update(&value_bytes);
+        if hasher.finalize() != u32::from_le_bytes(checksum_bytes) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Data entry checksum mismatch",
+            ));
+        }
+
+        self.decode_data_value(&value_bytes)
+    }
+
+    fn read_legacy_data_entry(
+        &self,
+        reader: &mut BufReader<File>,
+        offset: u64,
     ) -> std::io::Result<DataValue> {
         reader.seek(SeekFrom::Start(offset))?;
 
@@ -711,7 +1419,11 @@ impl Tree {
         let mut value_bytes = vec![0u8; value_len];
         reader.read_exact(&mut value_bytes)?;
 
-        match bincode::decode_from_slice(&value_bytes, self.settings.bincode_config) {
+        self.decode_data_value(&value_bytes)
+    }
+
+    fn decode_data_value(&self, value_bytes: &[u8]) -> std::io::Result<DataValue> {
+        match bincode::decode_from_slice(value_bytes, self.settings.bincode_config) {
             Ok((decoded, _)) => Ok(decoded),
             Err(e) => Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -721,14 +1433,16 @@ impl Tree {
     }
 
     pub(crate) fn validate_sstable(&self, path: &PathBuf) -> bool {
-        match File::open(path) {
-            Ok(file) => {
-                let mut reader = BufReader::new(file);
-                if self.validate_header(&mut reader).is_err() {
-                    error!("Error validating header SSTable : {:?}", path);
-                    return false;
-                }
-                if self.read_footer(&mut reader).is_err() {
+        match self.file_pool.open(path) {
+            Ok(mut reader) => {
+                let version = match self.validate_header(&mut reader) {
+                    Ok(version) => version,
+                    Err(_) => {
+                        error!("Error validating header SSTable : {:?}", path);
+                        return false;
+                    }
+                };
+                if self.read_footer_for_version(&mut reader, version).is_err() {
                     error!("Error validating footer SSTable {:?}", path);
                     return false;
                 }
@@ -742,28 +1456,282 @@ impl Tree {
     }
 }
 
-#[derive(Debug, Eq)]
-struct HeapEntry {
+/// Reads the data entries of an SSTable sequentially, in key order.
+///
+/// Only one entry is held in memory at a time, so tables of any size can be
+/// scanned with bounded memory.
+pub(crate) struct SSTableIterator {
+    reader: BufReader<File>,
+    version: u32,
+    position: u64,
+    data_end: u64,
+    bincode_config: bincode::config::Configuration,
+    skip_corrupt: bool,
+}
+
+/// A data entry read without decoding its value.
+struct RawEntry {
     key: Vec<u8>,
-    value: DataValue,
-    table_index: usize,
+    value_bytes: Vec<u8>,
+    checksum_ok: bool,
+    size: u64,
 }
 
-impl Ord for HeapEntry {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // reverse order
-        other.key.cmp(&self.key)
+/// Reads the data entry at the reader's position; version 1 entries carry no
+/// checksum and always pass.
+fn read_raw_entry(reader: &mut BufReader<File>, version: u32) -> std::io::Result<RawEntry> {
+    let mut key_len_bytes = [0u8; 4];
+    reader.read_exact(&mut key_len_bytes)?;
+    let key_len = u32::from_le_bytes(key_len_bytes) as usize;
+
+    let mut key = vec![0u8; key_len];
+    reader.read_exact(&mut key)?;
+
+    let mut value_len_bytes = [0u8; 4];
+    reader.read_exact(&mut value_len_bytes)?;
+    let value_len = u32::from_le_bytes(value_len_bytes) as usize;
+
+    let mut value_bytes = vec![0u8; value_len];
+    reader.read_exact(&mut value_bytes)?;
+    let mut size = (8 + key_len + value_len) as u64;
+
+    let mut checksum_ok = true;
+    if version >= 2 {
+        let mut checksum_bytes = [0u8; 4];
+        reader.read_exact(&mut checksum_bytes)?;
+        size += 4;
+
+        let mut hasher = Hasher::new();
+        hasher.update(&key);
+        hasher.update(&value_bytes);
+        checksum_ok = hasher.finalize() == u32::from_le_bytes(checksum_bytes);
+    }
+
+    Ok(RawEntry {
+        key,
+        value_bytes,
+        checksum_ok,
+        size,
+    })
+}
+
+/// Locates a key in an SSTable index that may be sparse.
+///
+/// Returns the offset of the key itself with `true` when it is indexed, or the
+/// offset of the nearest preceding indexed key with `false` when the key can
+/// only be found by scanning forward from there. Returns `None` when the key
+/// sorts before the first or after the last indexed key and so cannot be in
+/// the SSTable.
+pub(crate) fn locate_in_index(index: &BTreeMap<Vec<u8>, u64>, key: &[u8]) -> Option<(u64, bool)> {
+    let (floor_key, &offset) = index
+        .range::<[u8], _>((Bound::Unbounded, Bound::Included(key)))
+        .next_back()?;
+    if floor_key.as_slice() == key {
+        return Some((offset, true));
+    }
+    index
+        .range::<[u8], _>((Bound::Excluded(key), Bound::Unbounded))
+        .next()?;
+    Some((offset, false))
+}
+
+impl SSTableIterator {
+    fn read_next_entry(&mut self) -> std::io::Result<(Vec<u8>, DataValue)> {
+        let entry = read_raw_entry(&mut self.reader, self.version)?;
+        self.position += entry.size;
+        if !entry.checksum_ok {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Data entry checksum mismatch",
+            ));
+        }
+
+        let (value, _) = bincode::decode_from_slice(&entry.value_bytes, self.bincode_config)
+            .map_err(|e| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Deserialization error: {}", e),
+            ))?;
+        Ok((entry.key, value))
+    }
+}
+
+impl Iterator for SSTableIterator {
+    type Item = std::io::Result<(Vec<u8>, DataValue)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.position >= self.data_end {
+                return None;
+            }
+
+            let position = self.position;
+            let entry = self.read_next_entry();
+            match entry {
+                // The entry was read in full, so the next one can still be reached
+                Err(e) if self.skip_corrupt && self.position > position => {
+                    error!("Skipping corrupt SSTable entry at offset {}: {}", position, e);
+                }
+                Err(e) => {
+                    self.position = self.data_end;
+                    return Some(Err(e));
+                }
+                Ok(entry) => return Some(Ok(entry)),
+            }
+        }
     }
 }
 
-impl PartialOrd for HeapEntry {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl Tree {
+    /// Opens a sequential reader over the data entries of an SSTable,
+    /// positioned at the nearest indexed key at or before `start`.
+    ///
+    /// An empty `start` reads the whole SSTable. Entries between that indexed key and `start` are still yielded, so
+    /// callers skip keys below `start` themselves.
+    pub(crate) fn sstable_iter_from(&self, path: &Path, start: &[u8]) -> std::io::Result<SSTableIterator> {
+        let mut reader = self.file_pool.open(path)?.into_inner();
+        let version = self.validate_header(&mut reader)?;
+        let (index_offset, _) = self.read_footer_for_version(&mut reader, version)?;
+
+        let mut position = HEADER_SIZE as u64;
+        if !start.is_empty() {
+            let floor_offset = |index: &BTreeMap<Vec<u8>, u64>| {
+                index
+                    .range::<[u8], _>((Bound::Unbounded, Bound::Included(start)))
+                    .next_back()
+                    .map(|(_, &offset)| offset)
+            };
+            let cached_offset = self.index_cache().peek(path).map(floor_offset);
+            let offset = match cached_offset {
+                Some(offset) => offset,
+                None => floor_offset(&self.read_index(&mut reader, index_offset)?),
+            };
+            if let Some(offset) = offset {
+                position = offset;
+            }
+        }
+        reader.seek(SeekFrom::Start(position))?;
+
+        Ok(SSTableIterator {
+            reader,
+            version,
+            position,
+            data_end: index_offset,
+            bincode_config: self.settings.bincode_config,
+            skip_corrupt: false,
+        })
+    }
+
+    /// Opens a sequential reader over all data entries of an SSTable for
+    /// merges and counts.
+    ///
+    /// Like `load_sstable_with_bloom_filter`, entries failing their checksum
+    /// or decoding are logged and skipped, but only one entry is held in
+    /// memory at a time. An entry that cannot be read ends the iteration
+    /// with an error.
+    pub(crate) fn sstable_entries(&self, path: &Path) -> std::io::Result<SSTableIterator> {
+        let mut entries = self.sstable_iter_from(path, &[])?;
+        entries.skip_corrupt = true;
+        Ok(entries)
+    }
+}
+
+/// Reads the data entries of an SSTable in descending key order.
+///
+/// Walks the index blocks from the last one backwards and buffers one block at
+/// a time, so memory use is bounded by the index block interval.
+pub(crate) struct SSTableRevIterator {
+    block: SSTableIterator,
+    /// Start and end offsets of the blocks still to read, in ascending order.
+    blocks: Vec<(u64, u64)>,
+    buffered: Vec<(Vec<u8>, DataValue)>,
+}
+
+impl Iterator for SSTableRevIterator {
+    type Item = std::io::Result<(Vec<u8>, DataValue)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buffered.is_empty() {
+            let (start, end) = self.blocks.pop()?;
+            if let Err(e) = self.block.reader.seek(SeekFrom::Start(start)) {
+                self.blocks.clear();
+                return Some(Err(e));
+            }
+            self.block.position = start;
+            self.block.data_end = end;
+            for entry in &mut self.block {
+                match entry {
+                    Ok(entry) => self.buffered.push(entry),
+                    Err(e) => {
+                        self.blocks.clear();
+                        self.buffered.clear();
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        self.buffered.pop().map(Ok)
     }
 }
 
-impl PartialEq for HeapEntry {
-    fn eq(&self, other: &Self) -> bool {
-        self.key == other.key
+impl Tree {
+    /// Opens a reader over the data entries of an SSTable in descending key
+    /// order, starting at the last index block holding keys below `end`.
+    ///
+    /// `None` reads the whole SSTable. Entries of that block at or above `end`
+    /// are still yielded, so callers skip them themselves.
+    pub(crate) fn sstable_iter_rev(&self, path: &Path, end: Option<&[u8]>) -> std::io::Result<SSTableRevIterator> {
+        let mut reader = self.file_pool.open(path)?.into_inner();
+        let version = self.validate_header(&mut reader)?;
+        let (index_offset, _) = self.read_footer_for_version(&mut reader, version)?;
+
+        // Every entry lies between its nearest indexed key and the next one,
+        // and the last key is always indexed.
+        let block_offsets = |index: &BTreeMap<Vec<u8>, u64>| {
+            let offsets: Vec<u64> = index.values().copied().collect();
+            let block_count = match end {
+                Some(end) => index.range::<[u8], _>((Bound::Unbounded, Bound::Excluded(end))).count(),
+                None => offsets.len(),
+            };
+            (offsets, block_count)
+        };
+        let cached_offsets = self.index_cache().peek(path).map(block_offsets);
+        let (offsets, block_count) = match cached_offsets {
+            Some(offsets) => offsets,
+            None => block_offsets(&self.read_index(&mut reader, index_offset)?),
+        };
+        let blocks = (0..block_count)
+            .map(|i| (offsets[i], offsets.get(i + 1).copied().unwrap_or(index_offset)))
+            .collect();
+
+        Ok(SSTableRevIterator {
+            block: SSTableIterator {
+                reader,
+                version,
+                position: index_offset,
+                data_end: index_offset,
+                bincode_config: self.settings.bincode_config,
+                skip_corrupt: false,
+            },
+            blocks,
+            buffered: Vec::new(),
+        })
+    }
+}
+
+/// First and last key stored in an SSTable.
+#[derive(Debug, Clone)]
+pub(crate) struct KeyRange {
+    pub(crate) first_key: Vec<u8>,
+    pub(crate) last_key: Vec<u8>,
+}
+
+impl KeyRange {
+    /// Checks whether the range intersects the half-open range `[start, end)`.
+    pub(crate) fn overlaps(&self, start: &[u8], end: &[u8]) -> bool {
+        self.first_key.as_slice() < end && self.last_key.as_slice() >= start
+    }
+
+    pub(crate) fn contains(&self, key: &[u8]) -> bool {
+        self.first_key.as_slice() <= key && key <= self.last_key.as_slice()
     }
 }
@@ -1,15 +1,20 @@
 #[cfg(test)]
 mod test {
-    use crate::config::DEFAULT_DB_PATH;
-    use crate::tree::compression::CompressionConfig;
-    use crate::tree::tree_error::TreeResult;
-    use crate::tree::{Tree, TreeSettings, TreeSettingsBuilder};
+    use crate::config::{BINCODE_CONFIG, CHECKPOINT_ENTRY_SIZE, CURRENT_VERSION, DEFAULT_DB_PATH};
+    use crate::inspect;
+    use crate::tree::compression::{CompressionConfig, CompressionType, Compressor};
+    use crate::tree::manifest::{Manifest, MANIFEST_FILE};
+    use crate::tree::tree_error::{TreeError, TreeResult};
+    use crate::tree::wal_writer::WalWriter;
+    use crate::tree::{CompactionConfig, DataValue, LRUValueCache, MergeIterator, RateLimiter, ReadErrorPolicy, Tree, TransactionStats, TreeEvent, TreeIterator, TxOptions, IsolationLevel, MergeConflictPolicy, TreeSettings, TreeSettingsBuilder, TtlLimitPolicy, TypedEncoding, ValueCacheAdmission, WriteOptions};
     use bincode::{Decode, Encode};
     use rand::prelude::*;
     use serial_test::serial;
     use std::collections::HashMap;
     use std::mem;
+    use std::ops::Bound;
     use std::path::PathBuf;
+    use std::sync::{mpsc, Arc, Mutex};
     use std::time::{Duration, Instant, SystemTime};
 
     #[derive(Debug, Encode, Decode, PartialEq)]
@@ -24,6 +29,14 @@ mod test {
         username: String,
     }
 
+    struct Unencodable;
+
+    impl Encode for Unencodable {
+        fn encode<E: bincode::enc::Encoder>(&self, _encoder: &mut E) -> Result<(), bincode::error::EncodeError> {
+            Err(bincode::error::EncodeError::Other("value refuses to encode"))
+        }
+    }
+
     fn clean_temp_dir() {
         let db_path = PathBuf::from(DEFAULT_DB_PATH);
         if db_path.exists() {
@@ -239,6 +252,330 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_transaction_stats_count_outcomes() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"hot_key".to_vec(), b"original".to_vec())?;
+
+        let reader_tx = tree.begin_transaction()?;
+        tree.get_tx(reader_tx, b"hot_key")?;
+        tree.put_tx(reader_tx, b"hot_key".to_vec(), b"reader".to_vec(), None)?;
+
+        let writer_tx = tree.begin_transaction()?;
+        tree.put_tx(writer_tx, b"hot_key".to_vec(), b"writer".to_vec(), None)?;
+        tree.commit_transaction(writer_tx)?;
+
+        assert!(tree.commit_transaction(reader_tx).is_err());
+
+        let rolled_back_tx = tree.begin_transaction()?;
+        tree.rollback_transaction(rolled_back_tx)?;
+
+        assert_eq!(
+            tree.transaction_stats(),
+            TransactionStats { commits: 1, rollbacks: 1, validation_failures: 1 }
+        );
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_would_commit_succeed_is_a_dry_run() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"read".to_vec(), b"original".to_vec())?;
+
+        let tx_id = tree.begin_transaction()?;
+        assert_eq!(tree.transaction_footprint(tx_id), Some((0, 0)));
+        tree.get_tx(tx_id, b"read")?;
+        tree.get_tx(tx_id, b"missing")?;
+        tree.put_tx(tx_id, b"written".to_vec(), b"tx".to_vec(), None)?;
+        tree.put_tx(tx_id, b"also_written".to_vec(), b"tx".to_vec(), None)?;
+        assert_eq!(tree.transaction_footprint(tx_id), Some((1, 2)));
+        assert!(tree.would_commit_succeed(tx_id)?);
+
+        tree.put(b"read".to_vec(), b"changed".to_vec())?;
+        assert!(!tree.would_commit_succeed(tx_id)?);
+        assert!(!tree.would_commit_succeed(tx_id)?);
+        assert_eq!(tree.transaction_footprint(tx_id), Some((1, 2)));
+        assert_eq!(tree.transaction_stats(), TransactionStats::default());
+
+        tree.rollback_transaction(tx_id)?;
+        assert_eq!(tree.transaction_footprint(tx_id), None);
+        assert!(tree.would_commit_succeed(tx_id).is_err());
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_non_transactional_writes_fail_conflicting_commits() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"user:1".to_vec(), b"original".to_vec())?;
+        tree.put(b"other".to_vec(), b"original".to_vec())?;
+
+        let tx_id = tree.begin_transaction()?;
+        assert_eq!(tree.get_tx(tx_id, b"user:1")?, Some(b"original".to_vec()));
+        tree.put_tx(tx_id, b"user:1".to_vec(), b"from_tx".to_vec(), None)?;
+        tree.put(b"user:1".to_vec(), b"direct".to_vec())?;
+        assert!(tree.commit_transaction(tx_id).is_err());
+        assert_eq!(tree.get(b"user:1")?, Some(b"direct".to_vec()));
+
+        let tx_id = tree.begin_transaction()?;
+        tree.get_tx(tx_id, b"user:1")?;
+        tree.delete(b"user:1")?;
+        assert!(tree.commit_transaction(tx_id).is_err());
+
+        tree.put(b"user:2".to_vec(), b"original".to_vec())?;
+        let tx_id = tree.begin_transaction()?;
+        assert_eq!(tree.get_tx(tx_id, b"user:2")?, Some(b"original".to_vec()));
+        tree.put_tx(tx_id, b"user:2".to_vec(), b"from_tx".to_vec(), None)?;
+        tree.delete_prefix(b"user:")?;
+        assert!(tree.commit_transaction(tx_id).is_err());
+
+        let tx_id = tree.begin_transaction()?;
+        tree.get_tx(tx_id, b"other")?;
+        tree.put_tx(tx_id, b"other".to_vec(), b"from_tx".to_vec(), None)?;
+        tree.put(b"unrelated".to_vec(), b"direct".to_vec())?;
+        tree.commit_transaction(tx_id)?;
+        assert_eq!(tree.get(b"other")?, Some(b"from_tx".to_vec()));
+
+        assert_eq!(tree.transaction_stats().validation_failures, 3);
+        drop(tree);
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        let tx_id = tree.begin_transaction()?;
+        assert_eq!(tree.get_tx(tx_id, b"other")?, Some(b"from_tx".to_vec()));
+        tree.put_tx(tx_id, b"other".to_vec(), b"again".to_vec(), None)?;
+        tree.delete_prefix(b"oth")?;
+        assert!(tree.commit_transaction(tx_id).is_err());
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_put_if_version_checks_shared_key_versions() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(tree.key_version(b"counter"), 0);
+        assert!(tree.put_if_version(b"counter".to_vec(), b"1".to_vec(), 0)?);
+
+        let version = tree.key_version(b"counter");
+        assert!(version > 0);
+        assert!(!tree.put_if_version(b"counter".to_vec(), b"stale".to_vec(), 0)?);
+        assert_eq!(tree.get(b"counter")?, Some(b"1".to_vec()));
+
+        let tx_id = tree.begin_transaction()?;
+        tree.put_tx(tx_id, b"counter".to_vec(), b"2".to_vec(), None)?;
+        tree.commit_transaction(tx_id)?;
+        assert!(!tree.put_if_version(b"counter".to_vec(), b"stale".to_vec(), version)?);
+        assert_eq!(tree.get(b"counter")?, Some(b"2".to_vec()));
+
+        let reader_tx = tree.begin_transaction()?;
+        tree.get_tx(reader_tx, b"counter")?;
+        tree.put_tx(reader_tx, b"counter".to_vec(), b"tx".to_vec(), None)?;
+        let version = tree.key_version(b"counter");
+        assert!(tree.put_if_version(b"counter".to_vec(), b"3".to_vec(), version)?);
+        assert!(tree.commit_transaction(reader_tx).is_err());
+        assert_eq!(tree.get(b"counter")?, Some(b"3".to_vec()));
+
+        let version = tree.key_version(b"counter");
+        tree.delete(b"counter")?;
+        assert!(tree.key_version(b"counter") > version);
+
+        // A write the WAL rejects keeps the version, so the caller can retry
+        let version = tree.key_version(b"counter");
+        tree.wal_writer = Some(WalWriter::open(&PathBuf::from("/dev/full"), 64)?);
+        assert!(tree.put_if_version(b"counter".to_vec(), b"4".to_vec(), version).is_err());
+        assert_eq!(tree.key_version(b"counter"), version);
+        tree.wal_writer = None;
+        assert!(tree.put_if_version(b"counter".to_vec(), b"4".to_vec(), version)?);
+        assert_eq!(tree.get(b"counter")?, Some(b"4".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_put_seq_returns_increasing_sequence_numbers() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        let first = tree.put_seq(b"a".to_vec(), b"1".to_vec(), None)?;
+        assert_eq!(tree.key_version(b"a"), first);
+
+        tree.put(b"b".to_vec(), b"1".to_vec())?;
+        let tx_id = tree.begin_transaction()?;
+        tree.put_tx(tx_id, b"c".to_vec(), b"1".to_vec(), None)?;
+        tree.commit_transaction(tx_id)?;
+
+        let second = tree.put_seq(b"a".to_vec(), b"2".to_vec(), Some(Duration::from_secs(60)))?;
+        assert!(second > tree.key_version(b"c"));
+        assert!(tree.key_version(b"c") > tree.key_version(b"b"));
+        assert!(tree.key_version(b"b") > first);
+        assert_eq!(tree.key_version(b"a"), second);
+        assert_eq!(tree.get(b"a")?, Some(b"2".to_vec()));
+        assert!(tree.get_ttl(b"a").is_some());
+
+        // A write the WAL rejects takes no sequence number
+        tree.wal_writer = Some(WalWriter::open(&PathBuf::from("/dev/full"), 64)?);
+        assert!(tree.put_seq(b"a".to_vec(), b"3".to_vec(), None).is_err());
+        assert_eq!(tree.key_version(b"a"), second);
+        tree.wal_writer = None;
+        assert_eq!(tree.put_seq(b"a".to_vec(), b"3".to_vec(), None)?, second + 1);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_overwrite_in_place_keeps_latest_value() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let settings = TreeSettingsBuilder::new().overwrite_in_place(true).build();
+        {
+            let mut tree = Tree::load_with_settings(settings.clone())?;
+            for counter in 0..100u64 {
+                tree.put(b"counter".to_vec(), counter.to_le_bytes().to_vec())?;
+            }
+            assert_eq!(tree.get(b"counter")?, Some(99u64.to_le_bytes().to_vec()));
+
+            tree.put_with_ttl(b"counter".to_vec(), 100u64.to_le_bytes().to_vec(), Some(Duration::from_secs(60)))?;
+            assert!(tree.get_ttl(b"counter").is_some());
+            tree.put(b"counter".to_vec(), 101u64.to_le_bytes().to_vec())?;
+            assert_eq!(tree.get_ttl(b"counter"), None);
+
+            tree.put(b"counter".to_vec(), b"wider value".to_vec())?;
+            assert_eq!(tree.get(b"counter")?, Some(b"wider value".to_vec()));
+
+            mem::forget(tree);
+        }
+
+        let tree = Tree::load_with_settings(settings)?;
+        assert_eq!(tree.get(b"counter")?, Some(b"wider value".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_pessimistic_transactions_lock_contended_key() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"hot_key".to_vec(), b"original".to_vec())?;
+
+        let pessimistic = TxOptions { pessimistic: true, ..TxOptions::default() };
+        let tx1_id = tree.begin_transaction_with(pessimistic)?;
+        let tx2_id = tree.begin_transaction_with(pessimistic)?;
+
+        assert_eq!(tree.get_tx(tx1_id, b"hot_key")?, Some(b"original".to_vec()));
+        match tree.put_tx(tx2_id, b"hot_key".to_vec(), b"tx2".to_vec(), None) {
+            Err(TreeError::Transaction { .. }) => {}
+            other => panic!("Expected a lock conflict, got {:?}", other),
+        }
+        assert!(tree.get_tx(tx2_id, b"hot_key").is_err());
+
+        let optimistic_tx = tree.begin_transaction()?;
+        tree.put_tx(optimistic_tx, b"hot_key".to_vec(), b"optimistic".to_vec(), None)?;
+        assert!(tree.commit_transaction(optimistic_tx).is_err());
+
+        tree.put_tx(tx1_id, b"hot_key".to_vec(), b"tx1".to_vec(), None)?;
+        tree.commit_transaction(tx1_id)?;
+        assert_eq!(tree.get(b"hot_key")?, Some(b"tx1".to_vec()));
+
+        tree.put_tx(tx2_id, b"hot_key".to_vec(), b"tx2".to_vec(), None)?;
+        tree.commit_transaction(tx2_id)?;
+        assert_eq!(tree.get(b"hot_key")?, Some(b"tx2".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_serializable_transactions_detect_phantom_inserts() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"order_1".to_vec(), b"10".to_vec())?;
+        tree.put(b"order_3".to_vec(), b"30".to_vec())?;
+
+        let serializable = TxOptions { isolation: IsolationLevel::Serializable, ..TxOptions::default() };
+        let serializable_tx = tree.begin_transaction_with(serializable)?;
+        let snapshot_tx = tree.begin_transaction()?;
+        for tx_id in [serializable_tx, snapshot_tx] {
+            tree.put_tx(tx_id, b"order_4".to_vec(), b"40".to_vec(), None)?;
+            let orders = tree.scan_tx(tx_id, b"order_", b"order_~")?;
+            assert_eq!(
+                orders,
+                vec![
+                    (b"order_1".to_vec(), b"10".to_vec()),
+                    (b"order_3".to_vec(), b"30".to_vec()),
+                    (b"order_4".to_vec(), b"40".to_vec()),
+                ]
+            );
+        }
+
+        // A write outside the scanned range does not conflict
+        tree.put(b"user_1".to_vec(), b"alice".to_vec())?;
+        assert!(tree.would_commit_succeed(serializable_tx)?);
+
+        let writer = tree.begin_transaction()?;
+        tree.put_tx(writer, b"order_2".to_vec(), b"20".to_vec(), None)?;
+        tree.commit_transaction(writer)?;
+
+        // Neither transaction read order_2, so only the range check catches it
+        assert!(!tree.would_commit_succeed(serializable_tx)?);
+        assert!(tree.commit_transaction(serializable_tx).is_err());
+        tree.commit_transaction(snapshot_tx)?;
+        assert_eq!(tree.get(b"order_4")?, Some(b"40".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_committed_values_record_transaction_id() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"plain".to_vec(), b"value".to_vec())?;
+        let tx_id = tree.begin_transaction()?;
+        tree.put_tx(tx_id, b"transactional".to_vec(), b"value".to_vec(), None)?;
+        tree.commit_transaction(tx_id)?;
+
+        assert_eq!(tree.get_metadata(b"plain")?.unwrap().transaction_id, None);
+        assert_eq!(tree.get_metadata(b"transactional")?.unwrap().transaction_id, Some(tx_id));
+        assert_eq!(tree.get_metadata(b"missing")?, None);
+
+        tree.flush()?;
+        drop(tree);
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(tree.get_metadata(b"transactional")?.unwrap().transaction_id, Some(tx_id));
+
+        tree.delete(b"transactional")?;
+        assert_eq!(tree.get_metadata(b"transactional")?, None);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_transaction_update_existing() -> TreeResult<()> {
@@ -298,6 +635,84 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_max_ttl_clamps_long_and_missing_ttls() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let max_ttl = Duration::from_secs(60);
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .default_ttl(Duration::from_secs(30))
+            .max_ttl(max_ttl)
+            .build())?;
+
+        tree.put_with_ttl(b"short".to_vec(), b"value".to_vec(), Some(Duration::from_secs(10)))?;
+        tree.put_with_ttl(b"long".to_vec(), b"value".to_vec(), Some(Duration::from_secs(3600)))?;
+        tree.put(b"default".to_vec(), b"value".to_vec())?;
+        tree.update_ttl(b"short", None)?;
+
+        let ttl_of = |tree: &Tree, key: &[u8]| tree.get_ttl(key).expect("key has a TTL");
+        assert!(ttl_of(&tree, b"long") <= max_ttl && ttl_of(&tree, b"long") > Duration::from_secs(50));
+        assert!(ttl_of(&tree, b"default") <= Duration::from_secs(30) && ttl_of(&tree, b"default") > Duration::from_secs(20));
+        assert!(ttl_of(&tree, b"short") <= max_ttl && ttl_of(&tree, b"short") > Duration::from_secs(50));
+
+        // A transactional put resolves its TTL when staged, like a plain one
+        let tx_id = tree.begin_transaction()?;
+        tree.put_tx(tx_id, b"tx_long".to_vec(), b"value".to_vec(), Some(Duration::from_secs(3600)))?;
+        tree.put_tx(tx_id, b"tx_default".to_vec(), b"value".to_vec(), None)?;
+        tree.commit_transaction(tx_id)?;
+        assert!(ttl_of(&tree, b"tx_long") <= max_ttl && ttl_of(&tree, b"tx_long") > Duration::from_secs(50));
+        assert!(ttl_of(&tree, b"tx_default") <= Duration::from_secs(30) && ttl_of(&tree, b"tx_default") > Duration::from_secs(20));
+
+        tree.settings.default_ttl = None;
+        tree.put(b"forever".to_vec(), b"value".to_vec())?;
+        assert!(ttl_of(&tree, b"forever") <= max_ttl);
+
+        let tx_id = tree.begin_transaction()?;
+        tree.put_tx(tx_id, b"tx_forever".to_vec(), b"value".to_vec(), None)?;
+        tree.commit_transaction(tx_id)?;
+        assert!(ttl_of(&tree, b"tx_forever") <= max_ttl);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_ttl_reject_policy_refuses_forever_values() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .max_ttl(Duration::from_secs(60))
+            .max_ttl_policy(TtlLimitPolicy::Reject)
+            .build())?;
+
+        tree.put_with_ttl(b"short".to_vec(), b"value".to_vec(), Some(Duration::from_secs(60)))?;
+        assert!(matches!(tree.put(b"forever".to_vec(), b"value".to_vec()), Err(TreeError::InvalidValue { .. })));
+        assert!(matches!(
+            tree.put_with_ttl(b"long".to_vec(), b"value".to_vec(), Some(Duration::from_secs(61))),
+            Err(TreeError::InvalidValue { .. })
+        ));
+        assert!(matches!(tree.update_ttl(b"short", None), Err(TreeError::InvalidValue { .. })));
+        let tx_id = tree.begin_transaction()?;
+        assert!(matches!(tree.put_tx(tx_id, b"tx".to_vec(), b"value".to_vec(), None), Err(TreeError::InvalidValue { .. })));
+        assert!(matches!(
+            tree.put_tx(tx_id, b"tx".to_vec(), b"value".to_vec(), Some(Duration::from_secs(61))),
+            Err(TreeError::InvalidValue { .. })
+        ));
+        tree.commit_transaction(tx_id)?;
+
+        assert_eq!(tree.scan_limit(b"", 10)?.len(), 1);
+        assert!(tree.get_ttl(b"short").is_some());
+
+        tree.settings.default_ttl = Some(Duration::from_secs(30));
+        tree.put(b"defaulted".to_vec(), b"value".to_vec())?;
+        assert!(tree.get_ttl(b"defaulted").is_some());
+
+        clean_temp_dir();
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_transaction_with_ttl() -> TreeResult<()> {
@@ -568,7 +983,7 @@ mod test {
         }
 
         {
-            let mut recovered_tree = Tree::load_with_settings(
+            let recovered_tree = Tree::load_with_settings(
                 TreeSettingsBuilder::new()
                     .mem_table_max_size(2000)
                     .compressor(CompressionConfig::balanced())
@@ -585,59 +1000,2873 @@ mod test {
 
     #[test]
     #[serial]
-    fn test_rw_100k_from_memtable() -> TreeResult<()> {
+    fn test_recovery_reports_progress() -> TreeResult<()> {
         clean_temp_dir();
 
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().wal_max_entries(10).build())?;
+            for i in 0..35 {
+                tree.put(format!("key_{:02}", i).into_bytes(), b"value".to_vec())?;
+            }
+            mem::forget(tree);
+        }
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
         let mut tree = Tree::load_with_settings(
             TreeSettingsBuilder::new()
-                .compressor(CompressionConfig::none())
-                .index_cache(false)
-                .value_cache(false)
-                .mem_table_max_size(100000)
+                .wal_max_entries(10)
+                .recovery_progress(move |recovered, total, current| {
+                    sink.lock().unwrap().push((recovered, total, current));
+                })
+                .recovery_progress_interval(5)
                 .build(),
         )?;
-        let max_entries: u64 = 99999;
+        assert_eq!(tree.scan_limit(b"", 100)?.len(), 35);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.last(), Some(&(35, 4, 4)));
+        assert!(reports.iter().all(|(_, total, current)| *total == 4 && (1..=4).contains(current)));
+        assert!(reports.windows(2).all(|pair| pair[0].2 <= pair[1].2));
+        for recovered in (5..=35).step_by(5) {
+            assert!(reports.iter().any(|report| report.0 == recovered));
+        }
 
-        let start_time = Instant::now();
-        for i in 0..=max_entries {
-            let key = format!("test_key_{}", i);
-            tree.put(key.as_bytes().to_vec(), key.as_bytes().to_vec())?;
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_flush_splits_tables_above_max_entries() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let (sender, receiver) = mpsc::sync_channel(100);
+        let settings = TreeSettingsBuilder::new().sstable_max_entries(10).event_subscriber(sender).build();
+        {
+            let mut tree = Tree::load_with_settings(settings.clone())?;
+            for i in 0..15 {
+                tree.put(format!("key_{:02}", i).into_bytes(), b"value".to_vec())?;
+            }
+            tree.flush()?;
+
+            let sstables = inspect::sstables(&tree.settings.db_path)?;
+            assert_eq!(sstables.len(), 2);
+            assert_eq!(tree.ss_tables, sstables.iter().map(|(_, path)| path.clone()).collect::<Vec<_>>());
+            let ranges: Vec<_> = tree.ss_tables.iter().map(|path| tree.key_ranges[path].clone()).collect();
+            assert_eq!((ranges[0].first_key.as_slice(), ranges[0].last_key.as_slice()), (&b"key_00"[..], &b"key_09"[..]));
+            assert_eq!((ranges[1].first_key.as_slice(), ranges[1].last_key.as_slice()), (&b"key_10"[..], &b"key_14"[..]));
+
+            let flushed: Vec<usize> = receiver.try_iter().filter_map(|event| match event {
+                TreeEvent::Flushed { entries, .. } => Some(entries),
+                _ => None,
+            }).collect();
+            assert_eq!(flushed, vec![10, 5]);
+
+            tree.put(b"unflushed".to_vec(), b"value".to_vec())?;
+            mem::forget(tree);
         }
-        let write_duration = start_time.elapsed();
-        println!(
-            "Write time for {} entries: {:?}",
-            max_entries, write_duration
-        );
 
-        let start_time = Instant::now();
-        for i in 0..=max_entries {
-            let key = format!("test_key_{}", i);
-            tree.get(key.as_bytes())?;
+        let mut tree = Tree::load_with_settings(settings)?;
+        assert_eq!(tree.scan_limit(b"", 100)?.len(), 16);
+        for i in 0..25 {
+            tree.put(format!("more_{:02}", i).into_bytes(), b"value".to_vec())?;
         }
-        let read_duration = start_time.elapsed();
+        tree.flush()?;
+        assert_eq!(tree.scan_limit(b"", 100)?.len(), 41);
 
-        println!("===> Performance statistics:");
-        println!(
-            "Write speed: {:.2} entries/ms",
-            max_entries as f64 / write_duration.as_millis() as f64
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_merged_bloom_filter_is_sized_to_surviving_entries() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .max_queued_compactions(0)
+                .tombstone_grace_period(Duration::ZERO)
+                .build(),
+        )?;
+        for i in 0..2000 {
+            tree.put(format!("key_{:05}", i).into_bytes(), b"value".to_vec())?;
+        }
+        tree.flush()?;
+        for i in 200..2000 {
+            tree.delete(format!("key_{:05}", i).as_bytes())?;
+        }
+        tree.flush()?;
+        tree.put(b"other".to_vec(), b"value".to_vec())?;
+        tree.flush()?;
+        assert_eq!(tree.ss_tables.len(), 1);
+
+        let (table, merged_filter) = tree.load_sstable_with_bloom_filter(&tree.ss_tables[0].clone())?;
+        assert_eq!(table.len(), 201);
+
+        // Same size as a filter built for the surviving keys alone
+        let mut exact_filter = growable_bloom_filter::GrowableBloom::new(
+            tree.settings.bloom_filter_error_probability,
+            table.len(),
         );
-        println!(
-            "Read speed: {:.2} entries/ms",
-            max_entries as f64 / read_duration.as_millis() as f64
+        for key in table.keys() {
+            exact_filter.insert(key);
+        }
+        assert_eq!(
+            serde_json::to_vec(&merged_filter).unwrap().len(),
+            serde_json::to_vec(&exact_filter).unwrap().len()
+        );
+
+        let false_positives = (0..10000)
+            .filter(|i| merged_filter.contains(format!("absent_{}", i).as_bytes()))
+            .count();
+        assert!(
+            false_positives as f64 / 10000.0 <= tree.settings.bloom_filter_error_probability * 2.0,
+            "false positive rate {} above target",
+            false_positives as f64 / 10000.0
         );
 
-        println!("{}", tree.get_index_cache_stats());
-        println!("{}", tree.get_value_cache_stats());
         clean_temp_dir();
         Ok(())
     }
 
     #[test]
     #[serial]
-    fn test_compression_performance() -> TreeResult<()> {
+    fn test_cache_snapshot_warms_value_cache_and_ignores_stale_tables() -> TreeResult<()> {
         clean_temp_dir();
+        let snapshot_path = std::env::temp_dir().join("redish_test_cache_snapshot");
 
-        let test_data = generate_compressible_data(10000);
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            for prefix in ["a", "b"] {
+                for i in 0..10 {
+                    tree.put(format!("{}_{}", prefix, i).into_bytes(), b"value".to_vec())?;
+                }
+                tree.flush()?;
+            }
+            for i in 0..10 {
+                tree.get(format!("a_{}", i).as_bytes())?;
+                tree.get(format!("b_{}", i).as_bytes())?;
+            }
+            assert_eq!(tree.save_cache_snapshot(&snapshot_path)?, 20);
+        }
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_eq!(tree.warm_cache_from_snapshot(&snapshot_path)?, 20);
+            let hits_before = tree.get_value_cache_stats().hit_count;
+            assert_eq!(tree.get(b"a_3")?, Some(b"value".to_vec()));
+            assert_eq!(tree.get_value_cache_stats().hit_count, hits_before + 1);
+
+            // The merge replaces every table the snapshot refers to
+            tree.put(b"c_0".to_vec(), b"value".to_vec())?;
+            tree.flush()?;
+            assert_eq!(tree.ss_tables.len(), 1);
+        }
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_eq!(tree.warm_cache_from_snapshot(&snapshot_path)?, 0);
+            assert_eq!(tree.get_value_cache_stats().size, 0);
+            assert_eq!(tree.get(b"b_3")?, Some(b"value".to_vec()));
+
+            std::fs::write(&snapshot_path, b"not a snapshot")?;
+            assert_eq!(tree.warm_cache_from_snapshot(&snapshot_path)?, 0);
+            std::fs::remove_file(&snapshot_path)?;
+            assert_eq!(tree.warm_cache_from_snapshot(&snapshot_path)?, 0);
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_missing_key_reads_cache_one_bloom_filter_per_sstable() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let settings = || TreeSettingsBuilder::new().index_cache(false).value_cache(false).build();
+        {
+            let mut tree = Tree::load_with_settings(settings())?;
+            for i in 0..100 {
+                tree.put(format!("key_{:03}", i).into_bytes(), b"value".to_vec())?;
+            }
+            tree.flush()?;
+        }
+
+        let tree = Tree::load_with_settings(settings())?;
+        assert_eq!(tree.ss_tables.len(), 1);
+        let path = tree.ss_tables[0].clone();
+        assert!(tree.bloom_filters().is_empty());
+
+        // Missing keys inside the table's key range all reach the bloom filter
+        for i in 0..500 {
+            assert_eq!(tree.get(format!("key_{:03}_missing", i % 100).as_bytes())?, None);
+        }
+        assert_eq!(tree.bloom_filters().iter().filter(|bf| bf.path == path).count(), 1);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_reads_skip_sstables_outside_key_range() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            for prefix in ["a", "b"] {
+                for i in 0..10 {
+                    tree.put(format!("{}_{}", prefix, i).into_bytes(), b"value".to_vec())?;
+                }
+                tree.flush()?;
+            }
+        }
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(tree.ss_tables.len(), 2);
+        let newer = tree.ss_tables[1].clone();
+        assert_eq!(tree.key_ranges[&tree.ss_tables[0]].last_key, b"a_9".to_vec());
+        assert_eq!(tree.key_ranges[&newer].first_key, b"b_0".to_vec());
+        assert!(tree.bloom_filters().is_empty());
+
+        assert_eq!(tree.get(b"a_3")?, Some(b"value".to_vec()));
+        assert!(tree.bloom_filters().iter().all(|bf| bf.path != newer));
+        assert_eq!(tree.get(b"c")?, None);
+        assert!(tree.bloom_filters().iter().all(|bf| bf.path != newer));
+
+        let keys = |entries: Vec<(Vec<u8>, Vec<u8>)>| entries.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(keys(tree.scan_limit(b"a_8", 3)?), vec![b"a_8".to_vec(), b"a_9".to_vec(), b"b_0".to_vec()]);
+        assert_eq!(keys(tree.scan_limit(b"b_9", 3)?), vec![b"b_9".to_vec()]);
+        assert_eq!(keys(tree.scan_rev(Some(b"b_1"), 3)?), vec![b"b_0".to_vec(), b"a_9".to_vec(), b"a_8".to_vec()]);
+        assert_eq!(keys(tree.scan_rev(Some(b"a_1"), 3)?), vec![b"a_0".to_vec()]);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_inspect_lists_on_disk_layout() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        for batch in 0..3 {
+            for i in 0..15 {
+                tree.put(format!("key_{}_{:02}", batch, i).into_bytes(), b"value".to_vec())?;
+            }
+            tree.flush()?;
+        }
+        tree.put(b"unflushed".to_vec(), b"value".to_vec())?;
+        tree.wait_for_compactions()?;
+
+        let db_path = tree.settings.db_path.clone();
+        let listing = || -> Vec<PathBuf> {
+            let mut files: Vec<_> = std::fs::read_dir(&db_path).unwrap().map(|e| e.unwrap().path()).collect();
+            files.sort();
+            files
+        };
+        let before = listing();
+
+        let sstables = inspect::sstables(&db_path)?;
+        let mut expected: Vec<_> = tree.ss_tables.clone();
+        expected.sort();
+        let mut listed: Vec<_> = sstables.iter().map(|(_, path)| path.clone()).collect();
+        listed.sort();
+        assert_eq!(listed, expected);
+        assert!(sstables.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        let segments = inspect::wal_segments(&db_path)?;
+        assert!(!segments.is_empty());
+        assert!(segments.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(segments.iter().all(|(number, path)| path.ends_with(format!("wal_{:04}.log", number))));
+
+        let sstable_bytes: u64 = sstables.iter().map(|(_, path)| std::fs::metadata(path).unwrap().len()).sum();
+        assert!(inspect::disk_usage(&db_path)? >= sstable_bytes);
+        assert_eq!(listing(), before);
+
+        assert!(inspect::sstables(&db_path.join("missing")).is_err());
+
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_flush_and_merge_without_directory_sync() -> TreeResult<()> {
+        clean_temp_dir();
+
+        assert!(TreeSettingsBuilder::new().build().sync_directory);
+        let settings = TreeSettingsBuilder::new()
+            .mem_table_max_size(10)
+            .sync_directory(false)
+            .build();
+        assert!(!settings.sync_directory);
+
+        {
+            let mut tree = Tree::load_with_settings(settings.clone())?;
+            for i in 0..100 {
+                tree.put(format!("key_{:03}", i).into_bytes(), b"value".to_vec())?;
+            }
+            tree.flush()?;
+            tree.wait_for_compactions()?;
+        }
+
+        let mut tree = Tree::load_with_settings(settings)?;
+        assert_eq!(tree.scan_limit(b"", 200)?.len(), 100);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_sync_makes_writes_durable_without_flush() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            tree.put(b"key1".to_vec(), b"value1".to_vec())?;
+            tree.put(b"key2".to_vec(), b"value2".to_vec())?;
+            tree.sync()?;
+
+            assert!(tree.ss_tables.is_empty());
+            assert_eq!(tree.mem_table.len(), 2);
+            mem::forget(tree);
+        }
+
+        {
+            let recovered_tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_eq!(recovered_tree.get(b"key1")?, Some(b"value1".to_vec()));
+            assert_eq!(recovered_tree.get(b"key2")?, Some(b"value2".to_vec()));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_flush_wal_buffer_keeps_writes_across_crash() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            for i in 0..100 {
+                tree.put(format!("key_{}", i).into_bytes(), b"value".to_vec())?;
+            }
+            tree.flush_wal_buffer()?;
+
+            assert!(tree.ss_tables.is_empty());
+            mem::forget(tree);
+        }
+
+        {
+            let recovered_tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            for i in 0..100 {
+                assert_eq!(recovered_tree.get(format!("key_{}", i).as_bytes())?, Some(b"value".to_vec()));
+            }
+        }
+
+        clean_temp_dir();
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().wal(false).build())?;
+        tree.put(b"key".to_vec(), b"value".to_vec())?;
+        tree.flush_wal_buffer()?;
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_memory_pressure_tracks_mem_table_and_backlog() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().mem_table_max_size(100).build())?;
+        assert_eq!(tree.memory_pressure(), 0.0);
+
+        for i in 0..50 {
+            tree.put(format!("key_{}", i).into_bytes(), b"value".to_vec())?;
+        }
+        assert!((tree.memory_pressure() - 0.2).abs() < 1e-9);
+
+        tree.immutable_mem_tables.push_back(Arc::new(std::mem::take(&mut tree.mem_table)));
+        assert!((tree.memory_pressure() - 0.2).abs() < 1e-9);
+        tree.immutable_mem_tables.push_back(Default::default());
+        tree.immutable_mem_tables.push_back(Default::default());
+        assert!((tree.memory_pressure() - 0.3).abs() < 1e-9);
+
+        tree.flush()?;
+        assert!(tree.immutable_mem_tables.is_empty());
+        tree.clear_index_cache();
+        assert_eq!(tree.memory_pressure(), 0.0);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_merge_into_copies_live_entries_with_conflict_policy() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let open = |shard: &str, compressor: CompressionConfig| {
+            Tree::load_with_settings(
+                TreeSettingsBuilder::new()
+                    .db_path(PathBuf::from(DEFAULT_DB_PATH).join(shard))
+                    .compressor(compressor)
+                    .build(),
+            )
+        };
+        let value = |tag: &str| tag.repeat(200).into_bytes();
+        let mut dest = open("shard_dest", CompressionConfig::none())?;
+        let mut src = open("shard_src", CompressionConfig::balanced())?;
+
+        dest.put(b"older_in_dest".to_vec(), value("dest"))?;
+        src.put(b"newer_in_dest".to_vec(), value("src"))?;
+        src.put(b"older_in_dest".to_vec(), value("src"))?;
+        dest.put(b"newer_in_dest".to_vec(), value("dest"))?;
+        src.put_with_ttl(b"expiring".to_vec(), value("src"), Some(Duration::from_secs(3600)))?;
+        src.put(b"deleted_in_src".to_vec(), value("src"))?;
+        src.delete(b"deleted_in_src")?;
+        src.flush()?;
+        let expiring = src.get_metadata(b"expiring")?.unwrap();
+
+        dest.merge_into_with(&mut src, MergeConflictPolicy::KeepNewest)?;
+        assert_eq!(dest.get(b"older_in_dest")?, Some(value("src")));
+        assert_eq!(dest.get(b"newer_in_dest")?, Some(value("dest")));
+        assert_eq!(dest.get(b"deleted_in_src")?, None);
+        assert_eq!(dest.get(b"expiring")?, Some(value("src")));
+        let copied = dest.get_metadata(b"expiring")?.unwrap();
+        assert_eq!(copied.created_at, expiring.created_at);
+        assert_eq!(copied.expires_at, expiring.expires_at);
+
+        dest.merge_into(&mut src)?;
+        assert_eq!(dest.get(b"newer_in_dest")?, Some(value("src")));
+        assert_eq!(src.get(b"newer_in_dest")?, Some(value("src")));
+
+        // A source entry older than a delete_prefix in the destination is
+        // copied anyway, like any other key deleted there
+        src.put(b"prefix_a".to_vec(), value("src"))?;
+        dest.put(b"prefix_b".to_vec(), value("dest"))?;
+        std::thread::sleep(Duration::from_millis(5));
+        dest.delete_prefix(b"prefix_")?;
+        dest.merge_into(&mut src)?;
+        assert_eq!(dest.get(b"prefix_a")?, Some(value("src")));
+        assert_eq!(dest.get(b"prefix_b")?, None);
+        dest.flush()?;
+        assert_eq!(dest.get(b"prefix_a")?, Some(value("src")));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_range_merges_all_sources_within_bounds() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        for key in ["a", "b", "c", "d", "e"] {
+            tree.put(key.as_bytes().to_vec(), b"old".to_vec())?;
+        }
+        tree.flush()?;
+        tree.put(b"b".to_vec(), b"new".to_vec())?;
+        tree.delete(b"c")?;
+        tree.put_with_ttl(b"bb".to_vec(), b"short".to_vec(), Some(Duration::from_millis(1)))?;
+        tree.put(b"cc".to_vec(), b"memory".to_vec())?;
+        std::thread::sleep(Duration::from_millis(5));
+
+        let collect = |iter: TreeIterator| -> TreeResult<Vec<(Vec<u8>, Vec<u8>)>> { iter.collect() };
+        let keys = |entries: Vec<(Vec<u8>, Vec<u8>)>| entries.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+
+        let entries = collect(tree.range(b"b".as_slice()..b"d".as_slice())?)?;
+        assert_eq!(
+            entries,
+            vec![(b"b".to_vec(), b"new".to_vec()), (b"cc".to_vec(), b"memory".to_vec())]
+        );
+        assert_eq!(keys(collect(tree.range(b"b".as_slice()..=b"d".as_slice())?)?), vec![b"b".to_vec(), b"cc".to_vec(), b"d".to_vec()]);
+        assert_eq!(
+            keys(collect(tree.range((Bound::Excluded(b"b".as_slice()), Bound::Unbounded))?)?),
+            vec![b"cc".to_vec(), b"d".to_vec(), b"e".to_vec()]
+        );
+        assert_eq!(keys(collect(tree.range(..b"b".as_slice())?)?), vec![b"a".to_vec()]);
+        assert_eq!(keys(collect(tree.range::<std::ops::RangeFull>(..)?)?).len(), 5);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_take_typed_pops_values() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put_typed("queue_1", &TestStruct { a: 1, b: "first".to_string() })?;
+        tree.flush()?;
+        tree.put_typed("queue_2", &TestStruct { a: 2, b: "second".to_string() })?;
+        tree.put(b"raw".to_vec(), b"bytes".to_vec())?;
+        tree.put(b"bad".to_vec(), vec![0xff])?;
+
+        for (key, expected) in [("queue_1", 1), ("queue_2", 2)] {
+            let popped = tree.take_typed::<TestStruct>(key)?;
+            assert_eq!(popped.map(|value| value.a), Some(expected));
+            assert_eq!(tree.get(key.as_bytes())?, None);
+            assert!(tree.take_typed::<TestStruct>(key)?.is_none());
+        }
+
+        assert_eq!(tree.take(b"raw")?, Some(b"bytes".to_vec()));
+        assert_eq!(tree.take(b"raw")?, None);
+
+        assert!(tree.take_typed::<TestStruct>("bad").is_err());
+        assert_eq!(tree.get(b"bad")?, Some(vec![0xff]));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_recovery_skips_missing_wal_segment() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().wal_rotate_on_flush(true).build())?;
+            tree.put(b"key1".to_vec(), b"value1".to_vec())?;
+            tree.flush()?;
+            tree.put(b"key2".to_vec(), b"value2".to_vec())?;
+            tree.flush()?;
+            tree.put(b"key3".to_vec(), b"value3".to_vec())?;
+            mem::forget(tree);
+        }
+
+        let mut wal_files: Vec<PathBuf> = std::fs::read_dir(DEFAULT_DB_PATH)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("wal_")))
+            .collect();
+        wal_files.sort();
+        assert!(wal_files.len() >= 3);
+        std::fs::remove_file(&wal_files[wal_files.len() / 2])?;
+
+        {
+            let recovered_tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_eq!(recovered_tree.get(b"key1")?, Some(b"value1".to_vec()));
+            assert_eq!(recovered_tree.get(b"key2")?, Some(b"value2".to_vec()));
+            assert_eq!(recovered_tree.get(b"key3")?, Some(b"value3".to_vec()));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_recovery_flushes_replayed_entries_in_chunks() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+                .mem_table_max_size(10_000)
+                .max_queued_compactions(0)
+                .build())?;
+            for i in 0..450 {
+                tree.put(format!("key_{:03}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+            }
+            mem::forget(tree);
+        }
+
+        let settings = || TreeSettingsBuilder::new()
+            .mem_table_max_size(100)
+            .max_queued_compactions(0)
+            .build();
+        {
+            let recovered_tree = Tree::load_with_settings(settings())?;
+            assert!(recovered_tree.mem_table.len() <= 100);
+            assert_eq!(recovered_tree.ss_tables.len(), 4);
+            for path in recovered_tree.ss_tables.clone() {
+                assert_eq!(recovered_tree.count_sstable_entries(&path)?, 101);
+            }
+            for i in 0..450 {
+                assert_eq!(
+                    recovered_tree.get(format!("key_{:03}", i).as_bytes())?,
+                    Some(format!("value_{}", i).into_bytes())
+                );
+            }
+            mem::forget(recovered_tree);
+        }
+
+        // A crash before the next flush replays the same entries on top of the chunks
+        let mut recovered_tree = Tree::load_with_settings(settings())?;
+        assert!(recovered_tree.mem_table.len() <= 100);
+        assert_eq!(recovered_tree.iter()?.count(), 450);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_torn_wal_tail_is_truncated_before_appending() -> TreeResult<()> {
+        use std::io::Write;
+
+        let cut_short: &[u8] = &[7, 7, 7, 7, 2, 100, 0, 0, 0, b'k'];
+        let bad_crc: &[u8] = &[0, 0, 0, 0, 2, 3, 0, 0, 0, b'a', b'b', b'c', 1, 0, 0, 0, 0];
+        for torn_tail in [cut_short, bad_crc] {
+            clean_temp_dir();
+            {
+                let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+                for i in 0..10 {
+                    tree.put(format!("key_{}", i).into_bytes(), b"before".to_vec())?;
+                }
+                mem::forget(tree);
+            }
+
+            let (_, wal_segment) = inspect::wal_segments(std::path::Path::new(DEFAULT_DB_PATH))?.pop().unwrap();
+            let intact_len = std::fs::metadata(&wal_segment)?.len();
+            std::fs::OpenOptions::new().append(true).open(&wal_segment)?.write_all(torn_tail)?;
+
+            {
+                let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+                assert_eq!(std::fs::metadata(&wal_segment)?.len(), intact_len);
+                for i in 0..10 {
+                    assert_eq!(tree.get(format!("key_{}", i).as_bytes())?, Some(b"before".to_vec()));
+                }
+                for i in 5..15 {
+                    tree.put(format!("key_{}", i).into_bytes(), b"after".to_vec())?;
+                }
+                mem::forget(tree);
+            }
+
+            let tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            for i in 0..15 {
+                let expected = if i < 5 { b"before".to_vec() } else { b"after".to_vec() };
+                assert_eq!(tree.get(format!("key_{}", i).as_bytes())?, Some(expected));
+            }
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_recovery_skips_flushed_entries_without_checkpoint() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            tree.put(b"deleted".to_vec(), b"value".to_vec())?;
+            tree.flush()?;
+            tree.delete(b"deleted")?;
+            tree.put(b"kept".to_vec(), b"value".to_vec())?;
+            tree.flush()?;
+            mem::forget(tree);
+        }
+
+        // Crash after the SSTable was written but before its checkpoint reached the WAL
+        let wal_segment = std::fs::read_dir(DEFAULT_DB_PATH)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("wal_")))
+            .max()
+            .expect("a WAL segment exists");
+        let wal_file = std::fs::OpenOptions::new().write(true).open(&wal_segment)?;
+        wal_file.set_len(wal_file.metadata()?.len() - CHECKPOINT_ENTRY_SIZE as u64)?;
+        drop(wal_file);
+
+        {
+            let mut recovered_tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert!(recovered_tree.mem_table.is_empty());
+            assert_eq!(recovered_tree.scan_limit(b"", 10)?, vec![(b"kept".to_vec(), b"value".to_vec())]);
+            recovered_tree.put(b"after".to_vec(), b"crash".to_vec())?;
+            mem::forget(recovered_tree);
+        }
+
+        // Before its flush was installed the boundary is ignored and the entries are replayed
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        let mut manifest = Manifest::read(&db_path)?.expect("a MANIFEST exists");
+        let newest_sstable = manifest.sstables.as_mut().and_then(|sstables| sstables.pop()).unwrap();
+        manifest.last_flushed = manifest.last_flushed.map(|number| number - 1);
+        manifest.write(&db_path)?;
+        std::fs::remove_file(db_path.join(newest_sstable))?;
+
+        let mut recovered_tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(
+            recovered_tree.scan_limit(b"", 10)?,
+            vec![(b"after".to_vec(), b"crash".to_vec()), (b"kept".to_vec(), b"value".to_vec())]
+        );
+        drop(recovered_tree);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_small_flushes_keep_wal_segment_count_bounded() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let count_wal_segments = || -> TreeResult<usize> {
+            Ok(std::fs::read_dir(DEFAULT_DB_PATH)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with("wal_"))
+                .count())
+        };
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            for round in 0..50 {
+                tree.put(b"counter".to_vec(), format!("{}", round).into_bytes())?;
+                tree.put(format!("key_{}", round).into_bytes(), b"value".to_vec())?;
+                tree.flush()?;
+            }
+            assert_eq!(count_wal_segments()?, 1);
+
+            tree.put(b"unflushed".to_vec(), b"value".to_vec())?;
+            mem::forget(tree);
+        }
+
+        {
+            let mut recovered = Tree::load_with_settings(TreeSettingsBuilder::new().wal_max_entries(10).build())?;
+            assert_eq!(recovered.mem_table.len(), 1);
+            assert_eq!(recovered.get(b"counter")?, Some(b"49".to_vec()));
+            assert_eq!(recovered.get(b"unflushed")?, Some(b"value".to_vec()));
+
+            for i in 0..25 {
+                recovered.put(format!("more_{}", i).into_bytes(), b"value".to_vec())?;
+            }
+            assert!(count_wal_segments()? >= 3);
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_value_cache_admission_policy() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .compressor(CompressionConfig::none())
+            .value_cache_admission(ValueCacheAdmission::MaxValueSize(16))
+            .build())?;
+
+        tree.put(b"small".to_vec(), b"tiny".to_vec())?;
+        tree.put(b"large".to_vec(), vec![7u8; 1024])?;
+        tree.flush()?;
+
+        assert_eq!(tree.get(b"small")?, Some(b"tiny".to_vec()));
+        assert_eq!(tree.get(b"large")?, Some(vec![7u8; 1024]));
+        assert_eq!(tree.get_value_cache_stats().size, 1);
+
+        tree.clear_value_cache();
+        tree.set_value_cache_admission(ValueCacheAdmission::Never);
+        assert_eq!(tree.get(b"small")?, Some(b"tiny".to_vec()));
+        assert_eq!(tree.get_value_cache_stats().size, 0);
+
+        tree.set_value_cache_admission(ValueCacheAdmission::Always);
+        tree.get(b"small")?;
+        tree.get(b"large")?;
+        assert_eq!(tree.get_value_cache_stats().size, 2);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_value_cache_skips_values_above_size_cap() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .compressor(CompressionConfig::none())
+            .max_cacheable_value_bytes(64)
+            .build())?;
+
+        tree.put(b"small".to_vec(), vec![1u8; 64])?;
+        tree.put(b"large".to_vec(), vec![2u8; 4096])?;
+        tree.flush()?;
+
+        assert_eq!(tree.get(b"small")?, Some(vec![1u8; 64]));
+        assert_eq!(tree.get(b"large")?, Some(vec![2u8; 4096]));
+        assert_eq!(tree.get(b"large")?, Some(vec![2u8; 4096]));
+        let stats = tree.get_value_cache_stats();
+        assert_eq!(stats.size, 1);
+        assert_eq!(stats.skipped_for_size, 2);
+        assert!(stats.to_string().ends_with(", 2 skipped for size"));
+        assert_eq!(tree.get_index_cache_stats().skipped_for_size, 0);
+
+        tree.clear_value_cache();
+        assert_eq!(tree.get_value_cache_stats().skipped_for_size, 0);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_value_cache_size_limits_share_boundary() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .compressor(CompressionConfig::none())
+            .value_cache_admission(ValueCacheAdmission::MaxValueSize(64))
+            .max_cacheable_value_bytes(64)
+            .build())?;
+
+        tree.put(b"exact".to_vec(), vec![1u8; 64])?;
+        tree.put(b"over".to_vec(), vec![2u8; 65])?;
+        tree.flush()?;
+
+        assert_eq!(tree.get(b"exact")?, Some(vec![1u8; 64]));
+        assert_eq!(tree.get(b"over")?, Some(vec![2u8; 65]));
+        let stats = tree.get_value_cache_stats();
+        assert_eq!(stats.size, 1);
+        assert_eq!(stats.skipped_for_size, 0);
+
+        tree.clear_value_cache();
+        tree.set_value_cache_admission(ValueCacheAdmission::Always);
+        tree.get(b"exact")?;
+        tree.get(b"over")?;
+        let stats = tree.get_value_cache_stats();
+        assert_eq!(stats.size, 1);
+        assert_eq!(stats.skipped_for_size, 1);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_estimate_count() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .index_cache(false)
+            .build())?;
+
+        for i in 0..100 {
+            tree.put(format!("key_{}", i).into_bytes(), b"value".to_vec())?;
+        }
+        tree.flush()?;
+        assert_eq!(tree.estimate_count()?, 100);
+
+        for i in 0..10 {
+            tree.put(format!("key_{}", i).into_bytes(), b"updated".to_vec())?;
+        }
+        tree.delete(b"key_50")?;
+        assert_eq!(tree.estimate_count()?, 110);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_put_with_options() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            let unlogged = WriteOptions { sync: false, log_to_wal: false };
+            let synced = WriteOptions { sync: true, log_to_wal: true };
+
+            tree.put_with_options(b"bulk".to_vec(), b"value".to_vec(), None, unlogged)?;
+            tree.put_with_options(b"critical".to_vec(), b"value".to_vec(), None, synced)?;
+            assert_eq!(tree.get(b"bulk")?, Some(b"value".to_vec()));
+            mem::forget(tree);
+        }
+
+        {
+            let recovered_tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_eq!(recovered_tree.get(b"bulk")?, None);
+            assert_eq!(recovered_tree.get(b"critical")?, Some(b"value".to_vec()));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_manifest_lists_live_sstables() -> TreeResult<()> {
+        clean_temp_dir();
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        let listed = || -> TreeResult<Vec<String>> {
+            Ok(Manifest::read(&db_path)?.and_then(|manifest| manifest.sstables).unwrap_or_default())
+        };
+
+        let settings = TreeSettingsBuilder::new().max_queued_compactions(0).build();
+        let mut tree = Tree::load_with_settings(settings.clone())?;
+        for round in 0..3 {
+            tree.put(format!("key_{}", round).into_bytes(), b"value".to_vec())?;
+            tree.flush()?;
+        }
+        // The third flush triggered a merge into a table with a new number
+        assert_eq!(listed()?, vec!["sstable_3.sst".to_string()]);
+        assert!(!db_path.join("sstable_2.sst").exists());
+        tree.put(b"key_3".to_vec(), b"value".to_vec())?;
+        tree.flush()?;
+        assert_eq!(listed()?, vec!["sstable_3.sst".to_string(), "sstable_4.sst".to_string()]);
+        drop(tree);
+
+        // An unlisted table above the live ones is an unfinished flush and is
+        // removed; an unlisted one below them is ignored but kept
+        std::fs::copy(db_path.join("sstable_4.sst"), db_path.join("sstable_9.sst"))?;
+        std::fs::write(db_path.join("sstable_10.sst"), b"partial")?;
+        std::fs::copy(db_path.join("sstable_4.sst"), db_path.join("sstable_1.sst"))?;
+        let tree = Tree::load_with_settings(settings.clone())?;
+        assert_eq!(tree.ss_tables.len(), 2);
+        assert!(!db_path.join("sstable_9.sst").exists());
+        assert!(!db_path.join("sstable_10.sst").exists());
+        assert!(db_path.join("sstable_1.sst").exists());
+        for i in 0..4 {
+            assert_eq!(tree.get(format!("key_{}", i).as_bytes())?, Some(b"value".to_vec()));
+        }
+        drop(tree);
+        std::fs::remove_file(db_path.join("sstable_1.sst"))?;
+
+        // Without a manifest, as before it listed tables, every file is loaded
+        // and the manifest is written
+        std::fs::remove_file(db_path.join(MANIFEST_FILE))?;
+        let mut tree = Tree::load_with_settings(settings)?;
+        assert_eq!(tree.ss_tables.len(), 2);
+        assert_eq!(listed()?, vec!["sstable_3.sst".to_string(), "sstable_4.sst".to_string()]);
+        assert_eq!(tree.get(b"key_0")?, Some(b"value".to_vec()));
+
+        tree.truncate()?;
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_crash_before_merge_is_listed_keeps_inputs() -> TreeResult<()> {
+        clean_temp_dir();
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        let config = CompactionConfig { min_tables: 2, ..CompactionConfig::default() };
+        let settings = TreeSettingsBuilder::new().wal(false).max_queued_compactions(0).compaction(config).build();
+
+        let mut tree = Tree::load_with_settings(settings.clone())?;
+        tree.put(b"deleted".to_vec(), b"value".to_vec())?;
+        tree.put(b"kept".to_vec(), b"value".to_vec())?;
+        tree.flush()?;
+        tree.delete(b"deleted")?;
+        // Crash right after the merged table, which no longer holds the
+        // tombstone, was renamed into place
+        tree.stop_after_merge_rename = true;
+        tree.flush()?;
+        assert!(db_path.join("sstable_2.sst").exists());
+        mem::forget(tree);
+
+        let tree = Tree::load_with_settings(settings)?;
+        assert_eq!(tree.ss_tables, vec![db_path.join("sstable_0.sst"), db_path.join("sstable_1.sst")]);
+        assert!(!db_path.join("sstable_2.sst").exists());
+        assert_eq!(tree.get(b"deleted")?, None);
+        assert_eq!(tree.get(b"kept")?, Some(b"value".to_vec()));
+
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_compact_range_output_keeps_age_order_on_reload() -> TreeResult<()> {
+        clean_temp_dir();
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        let config = CompactionConfig { min_tables: 100, max_tables: 100, ..CompactionConfig::default() };
+        let settings = TreeSettingsBuilder::new().max_queued_compactions(0).compaction(config).build();
+
+        let mut tree = Tree::load_with_settings(settings.clone())?;
+        tree.put(b"b".to_vec(), b"value".to_vec())?;
+        tree.put(b"y".to_vec(), b"old".to_vec())?;
+        tree.flush()?;
+        tree.put(b"y".to_vec(), b"new".to_vec())?;
+        tree.flush()?;
+        // Only the older table overlaps the range; its output is numbered
+        // above the newer table but stays before it
+        tree.compact_range(b"a", b"c")?;
+        assert_eq!(tree.ss_tables, vec![db_path.join("sstable_2.sst"), db_path.join("sstable_1.sst")]);
+        assert!(!db_path.join("sstable_0.sst").exists());
+        drop(tree);
+
+        let tree = Tree::load_with_settings(settings)?;
+        assert_eq!(tree.ss_tables, vec![db_path.join("sstable_2.sst"), db_path.join("sstable_1.sst")]);
+        assert_eq!(tree.get(b"y")?, Some(b"new".to_vec()));
+        assert_eq!(tree.get(b"b")?, Some(b"value".to_vec()));
+
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_failed_compact_range_keeps_run_readable() -> TreeResult<()> {
+        clean_temp_dir();
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        let config = CompactionConfig { min_tables: 100, max_tables: 100, ..CompactionConfig::default() };
+        let settings = TreeSettingsBuilder::new().max_queued_compactions(0).compaction(config).build();
+
+        let mut tree = Tree::load_with_settings(settings)?;
+        for i in 0..10 {
+            tree.put(format!("a_{}", i).into_bytes(), b"value".to_vec())?;
+        }
+        tree.flush()?;
+        tree.delete_prefix(b"a_")?;
+        tree.put(b"b_0".to_vec(), b"value".to_vec())?;
+        tree.flush()?;
+        let tables = tree.ss_tables.clone();
+
+        let assert_unchanged = |tree: &mut Tree| -> TreeResult<()> {
+            assert_eq!(tree.ss_tables, tables);
+            assert_eq!(tree.get(b"a_1")?, None);
+            assert_eq!(tree.scan_limit(b"", 20)?, vec![(b"b_0".to_vec(), b"value".to_vec())]);
+            let leftovers = std::fs::read_dir(&db_path)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with("sstable_"))
+                .count();
+            assert_eq!(leftovers, tables.len());
+            Ok(())
+        };
+
+        // The merged table cannot be written
+        tree.sstable_write_limit = Some(0);
+        assert!(tree.compact_range(b"a", b"c").is_err());
+        tree.sstable_write_limit = None;
+        assert_unchanged(&mut tree)?;
+
+        // It is written but cannot be renamed into place
+        tree.fail_sstable_rename = true;
+        assert!(tree.compact_range(b"a", b"c").is_err());
+        tree.fail_sstable_rename = false;
+        assert_unchanged(&mut tree)?;
+
+        // It is renamed but the directory cannot be synced
+        tree.fail_directory_sync = true;
+        assert!(tree.compact_range(b"a", b"c").is_err());
+        tree.fail_directory_sync = false;
+        assert_unchanged(&mut tree)?;
+
+        tree.compact_range(b"a", b"c")?;
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert_eq!(tree.get(b"a_1")?, None);
+        assert_eq!(tree.scan_limit(b"", 20)?, vec![(b"b_0".to_vec(), b"value".to_vec())]);
+
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_sstable_footer_records_key_range() -> TreeResult<()> {
+        clean_temp_dir();
+        let settings = TreeSettingsBuilder::new().index_block_interval(4).index_cache(false).build();
+        let mut tree = Tree::load_with_settings(settings.clone())?;
+        for i in 10..30 {
+            tree.put(format!("key_{}", i).into_bytes(), b"value".to_vec())?;
+        }
+        tree.flush()?;
+        tree.delete_prefix(b"zzz")?;
+        tree.flush()?;
+        drop(tree);
+
+        let mut tree = Tree::load_with_settings(settings)?;
+        let path = tree.ss_tables[0].clone();
+        let range = tree.read_key_range(&path)?.expect("key range in footer");
+        assert_eq!(range.first_key, b"key_10".to_vec());
+        assert_eq!(range.last_key, b"key_29".to_vec());
+        assert_eq!(tree.key_ranges.get(&path).map(|range| range.last_key.clone()), Some(b"key_29".to_vec()));
+
+        // A table holding only a range tombstone has no key range
+        assert!(tree.read_key_range(&tree.ss_tables[1].clone())?.is_none());
+
+        assert_eq!(tree.get(b"key_29")?, Some(b"value".to_vec()));
+        assert_eq!(tree.get(b"key_3")?, None);
+        assert_eq!(tree.get(b"key_0")?, None);
+
+        tree.truncate()?;
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_failed_flush_keeps_mem_table_data() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        for i in 0..50 {
+            tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+        }
+
+        tree.sstable_write_limit = Some(256);
+        assert!(tree.flush().is_err());
+
+        assert!(tree.ss_tables.is_empty());
+        let sstable_files = std::fs::read_dir(DEFAULT_DB_PATH)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("sstable_"))
+            .count();
+        assert_eq!(sstable_files, 0);
+        for i in 0..50 {
+            assert_eq!(tree.get(format!("key_{}", i).as_bytes())?, Some(format!("value_{}", i).into_bytes()));
+        }
+
+        tree.sstable_write_limit = None;
+        tree.flush()?;
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert!(tree.immutable_mem_tables.is_empty());
+        assert_eq!(tree.get(b"key_42")?, Some(b"value_42".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_immutable_mem_tables_rejects_writes_when_flushes_fail() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let settings = TreeSettingsBuilder::new().mem_table_max_size(10).max_immutable_mem_tables(2).build();
+        let mut tree = Tree::load_with_settings(settings)?;
+        tree.sstable_write_limit = Some(64);
+
+        // Each failed flush leaves its table queued, up to the limit
+        let mut accepted = Vec::new();
+        let mut failures = 0;
+        for i in 0..33 {
+            let key = format!("key_{:02}", i).into_bytes();
+            if tree.put(key.clone(), b"value".to_vec()).is_err() {
+                failures += 1;
+            }
+            accepted.push(key);
+        }
+        assert_eq!(failures, 3);
+        assert_eq!(tree.immutable_mem_tables.len(), 2);
+        assert_eq!(tree.mem_table.len(), 11);
+
+        // With the queue full and the flush still failing, writes are refused
+        assert!(tree.put(b"rejected".to_vec(), b"value".to_vec()).is_err());
+        assert!(tree.delete(b"key_00").is_err());
+        assert_eq!(tree.immutable_mem_tables.len(), 2);
+        assert_eq!(tree.get(b"rejected")?, None);
+        assert_eq!(tree.get(b"key_00")?, Some(b"value".to_vec()));
+
+        // Once flushes succeed again the next write goes through and the
+        // backlog shrinks by the tables it had to write
+        tree.sstable_write_limit = None;
+        tree.put(b"accepted".to_vec(), b"value".to_vec())?;
+        assert_eq!(tree.immutable_mem_tables.len(), 1);
+        tree.flush()?;
+        assert!(tree.immutable_mem_tables.is_empty());
+        for key in &accepted {
+            assert_eq!(tree.get(key)?, Some(b"value".to_vec()));
+        }
+        assert_eq!(tree.get(b"accepted")?, Some(b"value".to_vec()));
+
+        tree.truncate()?;
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_keys_expiring_within() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put_with_ttl(b"flushed_soon".to_vec(), b"v".to_vec(), Some(Duration::from_secs(20)))?;
+        tree.put_with_ttl(b"overwritten".to_vec(), b"v".to_vec(), Some(Duration::from_secs(5)))?;
+        tree.put(b"no_ttl".to_vec(), b"v".to_vec())?;
+        tree.flush()?;
+
+        tree.put_with_ttl(b"soonest".to_vec(), b"v".to_vec(), Some(Duration::from_secs(10)))?;
+        tree.put_with_ttl(b"later".to_vec(), b"v".to_vec(), Some(Duration::from_secs(3600)))?;
+        tree.put(b"overwritten".to_vec(), b"v".to_vec())?;
+        tree.put_with_ttl(b"deleted".to_vec(), b"v".to_vec(), Some(Duration::from_secs(15)))?;
+        tree.delete(b"deleted")?;
+
+        let expiring = tree.keys_expiring_within(Duration::from_secs(60))?;
+        let keys: Vec<&[u8]> = expiring.iter().map(|(key, _)| key.as_slice()).collect();
+        assert_eq!(keys, vec![b"soonest".as_slice(), b"flushed_soon".as_slice()]);
+        assert!(expiring[0].1 <= Duration::from_secs(10));
+        assert!(expiring[1].1 > Duration::from_secs(10));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_compaction_stats_count_flushes_and_merges() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(tree.compaction_stats().write_amplification(), 0.0);
+        for round in 0..3 {
+            for i in 0..10 {
+                tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", round).into_bytes())?;
+            }
+            tree.flush()?;
+        }
+
+        let stats = tree.compaction_stats();
+        assert_eq!(stats.flushes, 3);
+        assert_eq!(stats.merges, 1);
+        assert_eq!(stats.merged_tables, 3);
+        assert_eq!(stats.dropped_entries, 20);
+        assert!(stats.flushed_bytes > stats.merged_bytes && stats.merged_bytes > 0);
+        assert!(stats.write_amplification() > 1.0);
+
+        let levels = tree.level_summary()?;
+        assert_eq!(levels.len(), 1);
+        assert_eq!((levels[0].level, levels[0].tables), (0, 1));
+        assert_eq!(levels[0].bytes, std::fs::metadata(&tree.ss_tables[0])?.len());
+        assert_eq!(levels[0].bytes, stats.merged_bytes);
+
+        tree.reset_compaction_stats();
+        assert_eq!(tree.compaction_stats(), Default::default());
+
+        tree.delete(b"key_1")?;
+        tree.flush()?;
+        tree.compact_range(b"key_0", b"key_9")?;
+        let stats = tree.compaction_stats();
+        assert_eq!((stats.flushes, stats.merges, stats.merged_tables), (1, 1, 2));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_into_matches_get_and_reuses_buffer() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().compressor(CompressionConfig::balanced()).build())?;
+        let value = |i: usize| format!("value_{}_{}", i, "x".repeat(200)).into_bytes();
+        for i in 0..20 {
+            tree.put(format!("key_{:02}", i).into_bytes(), value(i))?;
+        }
+        tree.flush()?;
+        for i in 10..20 {
+            tree.put(format!("key_{:02}", i).into_bytes(), value(i + 100))?;
+        }
+        tree.put_with_ttl(b"key_expired".to_vec(), value(0), Some(Duration::from_millis(1)))?;
+        tree.delete_prefix(b"key_05")?;
+        std::thread::sleep(Duration::from_millis(5));
+
+        let mut buf = Vec::with_capacity(1024);
+        let allocation = buf.as_ptr();
+        let keys: Vec<Vec<u8>> = (0..20).map(|i| format!("key_{:02}", i).into_bytes())
+            .chain([b"key_05".to_vec(), b"key_expired".to_vec(), b"missing".to_vec()])
+            .collect();
+        for key in &keys {
+            buf.extend_from_slice(b"stale");
+            let found = tree.get_into(key, &mut buf)?;
+            let expected = tree.get(key)?;
+            assert_eq!(found, expected.is_some(), "{:?}", key);
+            assert_eq!(buf, expected.unwrap_or_default(), "{:?}", key);
+        }
+        assert!(!tree.get_into(b"key_05", &mut buf)? && buf.is_empty());
+        assert_eq!(buf.as_ptr(), allocation);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_reads_own_writes_across_flushes() -> TreeResult<()> {
+        let configurations = [
+            TreeSettingsBuilder::new().mem_table_max_size(7),
+            TreeSettingsBuilder::new().mem_table_max_size(7).index_block_interval(3),
+            TreeSettingsBuilder::new().mem_table_max_size(7).bloom_filter_cache(false).index_cache(false),
+            TreeSettingsBuilder::new().mem_table_max_size(7).value_cache(false).max_queued_compactions(0),
+        ];
+
+        for builder in configurations {
+            clean_temp_dir();
+            let mut tree = Tree::load_with_settings(builder.build())?;
+            let mut rng = StdRng::seed_from_u64(42);
+            let mut expected: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+
+            for step in 0..600 {
+                let key = format!("key_{:03}", rng.random_range(0..60)).into_bytes();
+                let value = format!("value_{}", step).into_bytes();
+                tree.put(key.clone(), value.clone())?;
+                expected.insert(key.clone(), value.clone());
+                assert_eq!(tree.get(&key)?, Some(value), "step {}", step);
+
+                match rng.random_range(0..20) {
+                    0 => tree.flush()?,
+                    1 => tree.compact_range(b"key_010", b"key_030")?,
+                    _ => {}
+                }
+
+                let probe = format!("key_{:03}", rng.random_range(0..60)).into_bytes();
+                assert_eq!(tree.get(&probe)?.as_ref(), expected.get(&probe), "step {}", step);
+            }
+
+            tree.wait_for_compactions()?;
+            for (key, value) in &expected {
+                assert_eq!(tree.get(key)?.as_ref(), Some(value));
+            }
+            drop(tree);
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_put_typed_opt_ttl_accepts_runtime_ttl() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        let configured_ttls = [None, Some(Duration::from_secs(60))];
+        let user = User { user_id: 7, username: "ttl".to_string() };
+        tree.put_typed_opt_ttl::<User>("persistent", &user, configured_ttls[0])?;
+        tree.put_typed_opt_ttl::<User>("expiring", &user, configured_ttls[1])?;
+
+        assert_eq!(tree.get_typed::<User>("persistent")?.map(|u| u.user_id), Some(7));
+        assert_eq!(tree.get_typed::<User>("expiring")?.map(|u| u.user_id), Some(7));
+        assert_eq!(tree.get_ttl(b"persistent"), None);
+        assert!(tree.get_ttl(b"expiring").is_some_and(|ttl| ttl <= Duration::from_secs(60)));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_iter_typed_streams_live_entries_in_key_order() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put_typed::<u64>("a", &1)?;
+        tree.put_typed::<u64>("b", &2)?;
+        tree.put_typed::<u64>("c", &3)?;
+        tree.flush()?;
+
+        tree.put_typed::<u64>("b", &20)?;
+        tree.put_typed::<u64>("d", &4)?;
+        tree.delete(b"c")?;
+        tree.put(b"e".to_vec(), vec![0xff])?;
+
+        let raw: Vec<Vec<u8>> = tree.iter()?.map(|entry| entry.map(|(key, _)| key)).collect::<TreeResult<_>>()?;
+        assert_eq!(raw, vec![b"a".to_vec(), b"b".to_vec(), b"d".to_vec(), b"e".to_vec()]);
+
+        let typed: Vec<TreeResult<(Vec<u8>, u64)>> = tree.iter_typed::<u64>()?.collect();
+        assert_eq!(typed.len(), 4);
+        assert_eq!(typed[0].as_ref().unwrap(), &(b"a".to_vec(), 1));
+        assert_eq!(typed[1].as_ref().unwrap(), &(b"b".to_vec(), 20));
+        assert_eq!(typed[2].as_ref().unwrap(), &(b"d".to_vec(), 4));
+        assert!(typed[3].is_err());
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_iter_range_typed_skips_foreign_values() -> TreeResult<()> {
+        #[derive(Debug, PartialEq, Encode, Decode)]
+        struct Score {
+            points: u64,
+            player: String,
+        }
+
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        let score = |points: u64, player: &str| Score { points, player: player.to_string() };
+        tree.put_typed("score:alice", &score(30, "alice"))?;
+        tree.put_typed("score:bob", &score(10, "bob"))?;
+        tree.put(b"score:config".to_vec(), b"not a score".to_vec())?;
+        tree.flush()?;
+        tree.put_typed("score:carol", &score(20, "carol"))?;
+        tree.put(b"score:dave".to_vec(), b"\xff".to_vec())?;
+        tree.put_typed("scorf:erin", &score(50, "erin"))?;
+        tree.put_typed("score", &score(40, "before"))?;
+
+        let mut iter = tree.iter_range_typed::<Score>(b"score:", b"score;")?;
+        let scores: Vec<(Vec<u8>, Score)> = iter.by_ref().collect::<TreeResult<_>>()?;
+        assert_eq!(
+            scores,
+            vec![
+                (b"score:alice".to_vec(), score(30, "alice")),
+                (b"score:bob".to_vec(), score(10, "bob")),
+                (b"score:carol".to_vec(), score(20, "carol")),
+            ]
+        );
+        assert_eq!(iter.skipped(), 2);
+        assert_eq!(iter.finish(), 2);
+
+        let mut iter = tree.iter_range_typed::<Score>(b"score:b", b"score:d")?;
+        assert_eq!(iter.next().transpose()?.map(|(key, _)| key), Some(b"score:bob".to_vec()));
+        assert_eq!(iter.skipped(), 0);
+        assert_eq!(iter.next().transpose()?.map(|(key, _)| key), Some(b"score:carol".to_vec()));
+        assert!(iter.next().is_none());
+        assert_eq!(iter.finish(), 1);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_value_size_histogram_counts_live_values() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .compressor(CompressionConfig::balanced())
+                .build(),
+        )?;
+        assert_eq!(tree.value_size_histogram(&[100])?, vec![(100, 0), (usize::MAX, 0)]);
+
+        for i in 0..10 {
+            tree.put(format!("small_{}", i).into_bytes(), vec![b'a'; 50])?;
+        }
+        tree.flush()?;
+        for i in 0..5 {
+            tree.put(format!("large_{}", i).into_bytes(), vec![b'b'; 5000])?;
+        }
+        tree.put(b"huge".to_vec(), (0..100_000u32).map(|i| (i * 7919 % 251) as u8).collect())?;
+        tree.delete(b"small_0")?;
+        tree.put_with_ttl(b"expired".to_vec(), vec![b'c'; 50], Some(Duration::from_millis(1)))?;
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert_eq!(
+            tree.value_size_histogram_decompressed(&[10_000, 100, 100])?,
+            vec![(100, 9), (10_000, 5), (usize::MAX, 1)]
+        );
+
+        let stored = tree.value_size_histogram(&[100, 10_000])?;
+        assert_eq!(stored.iter().map(|(_, count)| count).sum::<usize>(), 15);
+        assert!(stored[0].1 > 9, "compressed large values fall into the smallest bucket");
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_sample_returns_distinct_live_keys() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert!(tree.sample(10)?.is_empty());
+
+        for i in 0..5 {
+            tree.put(format!("few_{}", i).into_bytes(), b"value".to_vec())?;
+        }
+        let mut sample = tree.sample(100)?;
+        sample.sort();
+        assert_eq!(sample.len(), 5);
+        assert!(tree.sample(0)?.is_empty());
+
+        for i in 0..500 {
+            tree.put(format!("key_{:03}", i).into_bytes(), b"value".to_vec())?;
+            if i % 150 == 149 {
+                tree.flush()?;
+            }
+        }
+        for i in (0..500).step_by(10) {
+            tree.delete(format!("key_{:03}", i).as_bytes())?;
+        }
+        tree.clear_value_cache();
+
+        let sample = tree.sample(100)?;
+        assert_eq!(sample.len(), 100);
+        assert_eq!(sample.iter().collect::<std::collections::HashSet<_>>().len(), 100);
+        assert_eq!(tree.get_value_cache_stats().size, 0);
+        for key in &sample {
+            assert!(tree.get_metadata(key)?.is_some());
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_required_fails_on_missing_key() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"raw".to_vec(), b"value".to_vec())?;
+        tree.put_typed::<u64>("typed", &42)?;
+        tree.put_with_ttl(b"expiring".to_vec(), b"value".to_vec(), Some(Duration::from_millis(1)))?;
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert_eq!(tree.get_required(b"raw")?, b"value".to_vec());
+        assert_eq!(tree.get_typed_required::<u64>("typed")?, 42);
+        assert!(matches!(tree.get_required(b"missing"), Err(TreeError::InvalidKey { .. })));
+        assert!(matches!(tree.get_required(b"expiring"), Err(TreeError::InvalidKey { .. })));
+        assert!(matches!(tree.get_typed_required::<u64>("missing"), Err(TreeError::InvalidKey { .. })));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[cfg(feature = "testkit")]
+    #[test]
+    #[serial]
+    fn test_testkit_crash_and_reopen() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().mem_table_max_size(10).build())?;
+        for i in 0..25u32 {
+            tree.put(format!("key_{:02}", i).into_bytes(), i.to_le_bytes().to_vec())?;
+        }
+        let unlogged = WriteOptions { log_to_wal: false, ..WriteOptions::default() };
+        tree.put_with_options(b"unlogged".to_vec(), b"v".to_vec(), None, unlogged)?;
+
+        let recovered = tree.crash_and_reopen()?;
+        for i in 0..25u32 {
+            assert_eq!(recovered.get(format!("key_{:02}", i).as_bytes())?, Some(i.to_le_bytes().to_vec()));
+        }
+        assert_eq!(recovered.get(b"unlogged")?, None);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    #[serial]
+    fn test_async_tree_offloads_calls_to_blocking_pool() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        runtime.block_on(async {
+            let tree = crate::aio::Tree::load_with_settings(
+                TreeSettingsBuilder::new().mem_table_max_size(10).build(),
+            )
+            .await?;
+            let writers: Vec<_> = (0..4u32)
+                .map(|writer| {
+                    let tree = tree.clone();
+                    tokio::spawn(async move {
+                        for i in 0..10u32 {
+                            tree.put(format!("key_{}_{}", writer, i).into_bytes(), i.to_le_bytes().to_vec()).await?;
+                        }
+                        Ok::<(), TreeError>(())
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.await.unwrap()?;
+            }
+
+            assert!(tree.delete(b"key_0_0".to_vec()).await?);
+            tree.flush().await?;
+            tree.compact().await?;
+            assert_eq!(tree.get(b"key_3_9".to_vec()).await?, Some(9u32.to_le_bytes().to_vec()));
+            assert_eq!(tree.get(b"key_0_0".to_vec()).await?, None);
+            assert!(tree.with(|tree| tree.contains_key(b"key_2_5")).await?);
+            Ok::<(), TreeError>(())
+        })?;
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_delete_prefix_with_range_tombstone() -> TreeResult<()> {
+        clean_temp_dir();
+
+        fn assert_prefix_deleted(tree: &mut Tree) -> TreeResult<()> {
+            assert_eq!(tree.get(b"user:1")?, None);
+            assert_eq!(tree.get(b"user:5")?, None);
+            assert_eq!(tree.get(b"user:6")?, None);
+            assert_eq!(tree.get(b"user:3")?, Some(b"rewritten".to_vec()));
+            assert_eq!(tree.get(b"users")?, Some(b"kept".to_vec()));
+            assert_eq!(tree.get(b"other")?, Some(b"kept".to_vec()));
+            let keys: Vec<Vec<u8>> = tree.iter()?.map(|entry| entry.map(|(key, _)| key)).collect::<TreeResult<_>>()?;
+            assert_eq!(keys, vec![b"other".to_vec(), b"user:3".to_vec(), b"users".to_vec()]);
+            Ok(())
+        }
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            for i in 1..=5 {
+                tree.put(format!("user:{}", i).into_bytes(), b"old".to_vec())?;
+            }
+            tree.put(b"users".to_vec(), b"kept".to_vec())?;
+            tree.put(b"other".to_vec(), b"kept".to_vec())?;
+            tree.flush()?;
+
+            tree.put(b"user:6".to_vec(), b"old".to_vec())?;
+            tree.delete_prefix(b"user:")?;
+            tree.put(b"user:3".to_vec(), b"rewritten".to_vec())?;
+            assert!(!tree.mem_table.contains_key(b"user:6".as_slice()));
+            assert_prefix_deleted(&mut tree)?;
+            mem::forget(tree);
+        }
+
+        {
+            let mut recovered = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_prefix_deleted(&mut recovered)?;
+            recovered.flush()?;
+            assert_eq!(recovered.range_tombstones.values().flatten().count(), 1);
+        }
+
+        {
+            let mut reloaded = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_prefix_deleted(&mut reloaded)?;
+
+            reloaded.put(b"zzz".to_vec(), b"kept".to_vec())?;
+            reloaded.flush()?;
+            assert_eq!(reloaded.ss_tables.len(), 1);
+            assert!(reloaded.range_tombstones.is_empty());
+            assert_eq!(reloaded.len(), 4);
+            assert_eq!(reloaded.get(b"user:1")?, None);
+            assert_eq!(reloaded.get(b"user:3")?, Some(b"rewritten".to_vec()));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_rename_key_moves_value_atomically() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            tree.put_with_ttl(b"staging".to_vec(), b"flushed".to_vec(), Some(Duration::from_secs(3600)))?;
+            tree.flush()?;
+            let metadata = tree.get_metadata(b"staging")?.unwrap();
+
+            assert!(tree.rename_key(b"staging", b"live")?);
+            assert_eq!(tree.get(b"live")?, Some(b"flushed".to_vec()));
+            assert_eq!(tree.get_metadata(b"live")?, Some(metadata));
+            assert_eq!(tree.get_metadata(b"staging")?, None);
+            assert!(!tree.rename_key(b"staging", b"live")?);
+            assert!(!tree.rename_key(b"missing", b"live")?);
+            assert!(tree.rename_key(b"live", b"live")?);
+            assert_eq!(tree.get(b"live")?, Some(b"flushed".to_vec()));
+
+            tree.put(b"old".to_vec(), b"value".to_vec())?;
+            tree.delete_prefix(b"new_")?;
+            assert!(tree.rename_key(b"old", b"new_key")?);
+            assert_eq!(tree.get(b"new_key")?, Some(b"value".to_vec()));
+
+            tree.put(b"pending".to_vec(), b"in_wal".to_vec())?;
+            assert!(tree.rename_key(b"pending", b"promoted")?);
+            mem::forget(tree);
+        }
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_eq!(
+                tree.scan_limit(b"", 10)?,
+                vec![
+                    (b"live".to_vec(), b"flushed".to_vec()),
+                    (b"new_key".to_vec(), b"value".to_vec()),
+                    (b"promoted".to_vec(), b"in_wal".to_vec()),
+                ]
+            );
+            assert!(tree.get_metadata(b"live")?.unwrap().expires_at.is_some());
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_iterator_resolves_precedence() -> TreeResult<()> {
+        let value = |data: &[u8]| DataValue::new(data.to_vec(), None);
+        let mut expired = value(b"expired");
+        expired.expires_at = Some(SystemTime::UNIX_EPOCH);
+
+        let oldest = vec![
+            (b"a".to_vec(), value(b"a0")),
+            (b"b".to_vec(), value(b"b0")),
+            (b"d".to_vec(), value(b"d0")),
+        ];
+        let middle = vec![
+            (b"b".to_vec(), DataValue::tombstone()),
+            (b"c".to_vec(), value(b"c1")),
+            (b"d".to_vec(), value(b"d1")),
+        ];
+        let newest = vec![(b"c".to_vec(), expired.clone()), (b"e".to_vec(), value(b"e2"))];
+
+        let sources = [oldest, middle, newest];
+        let merged: Vec<(Vec<u8>, DataValue)> =
+            MergeIterator::new(sources.clone().map(|source| source.into_iter().map(Ok)))
+                .collect::<TreeResult<_>>()?;
+
+        let keys: Vec<&[u8]> = merged.iter().map(|(key, _)| key.as_slice()).collect();
+        assert_eq!(keys, vec![b"a".as_slice(), b"b", b"c", b"d", b"e"]);
+        assert_eq!(merged[0].1.data, b"a0".to_vec());
+        assert!(merged[1].1.is_tombstone());
+        assert_eq!(merged[2].1, expired);
+        assert_eq!(merged[3].1.data, b"d1".to_vec());
+        assert_eq!(merged[4].1.data, b"e2".to_vec());
+
+        let mut reversed: Vec<(Vec<u8>, DataValue)> =
+            MergeIterator::new_rev(sources.map(|source| source.into_iter().rev().map(Ok)))
+                .collect::<TreeResult<_>>()?;
+        reversed.reverse();
+        assert_eq!(reversed, merged);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_iterator_yields_source_errors() {
+        let healthy = vec![
+            Ok((b"a".to_vec(), DataValue::new(b"a".to_vec(), None))),
+            Ok((b"c".to_vec(), DataValue::new(b"c".to_vec(), None))),
+        ];
+        let failing = vec![
+            Ok((b"b".to_vec(), DataValue::new(b"b".to_vec(), None))),
+            Err(TreeError::internal("broken source")),
+            Ok((b"d".to_vec(), DataValue::new(b"d".to_vec(), None))),
+        ];
+
+        let merged: Vec<TreeResult<(Vec<u8>, DataValue)>> =
+            MergeIterator::new([healthy.into_iter(), failing.into_iter()]).collect();
+        assert_eq!(merged.len(), 4);
+        assert!(merged[2].is_err());
+        let keys: Vec<Vec<u8>> = merged.into_iter().filter_map(|entry| entry.ok()).map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_tombstones_in_newer_sstables_hide_older_values() -> TreeResult<()> {
+        for compression in [CompressionConfig::none(), CompressionConfig::fast()] {
+            clean_temp_dir();
+
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().compressor(compression).build())?;
+            tree.put(b"key".to_vec(), b"value".to_vec())?;
+            tree.put(b"memory".to_vec(), b"value".to_vec())?;
+            tree.flush()?;
+            assert_eq!(tree.get(b"key")?, Some(b"value".to_vec()));
+
+            assert!(tree.delete(b"key")?);
+            assert!(tree.delete(b"memory")?);
+            assert_eq!(tree.get(b"memory")?, None);
+            tree.flush()?;
+            assert_eq!(tree.ss_tables.len(), 2);
+
+            assert_eq!(tree.get(b"key")?, None);
+            let mut buf = Vec::new();
+            assert!(!tree.get_into(b"key", &mut buf)?);
+            assert!(!tree.contains_key(b"key")?);
+            assert!(!tree.delete(b"key")?);
+            assert_eq!(tree.get(b"memory")?, None);
+
+            clean_temp_dir();
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_value_cache_hits_hot_keys_right_after_merge() -> TreeResult<()> {
+        for repoint in [true, false] {
+            clean_temp_dir();
+
+            let mut tree = Tree::load_with_settings(
+                TreeSettingsBuilder::new()
+                    .max_queued_compactions(0)
+                    .repoint_value_cache_on_merge(repoint)
+                    .build(),
+            )?;
+            let hot_keys: Vec<Vec<u8>> = (0..20).map(|i| format!("hot_{:02}", i).into_bytes()).collect();
+            for (i, key) in hot_keys.iter().enumerate() {
+                tree.put(key.clone(), format!("value_{}", i).into_bytes())?;
+                if i == 9 {
+                    tree.flush()?;
+                }
+            }
+            tree.flush()?;
+            for key in &hot_keys {
+                tree.get(key)?;
+            }
+            tree.put(b"hot_05".to_vec(), b"updated".to_vec())?;
+            tree.put(b"other".to_vec(), b"value".to_vec())?;
+            tree.flush()?;
+            assert_eq!(tree.ss_tables.len(), 1);
+
+            let before = tree.get_value_cache_stats();
+            for (i, key) in hot_keys.iter().enumerate() {
+                let expected = if i == 5 { b"updated".to_vec() } else { format!("value_{}", i).into_bytes() };
+                assert_eq!(tree.get(key)?, Some(expected));
+            }
+            let after = tree.get_value_cache_stats();
+            let hits = after.hit_count - before.hit_count;
+            if repoint {
+                assert_eq!(hits, 19);
+                assert_eq!(after.size, 20);
+            } else {
+                assert_eq!(hits, 0);
+            }
+
+            clean_temp_dir();
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_compact_if_needed_merges_only_past_trigger() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let settings = TreeSettingsBuilder::new().sstable_max_entries(10).max_queued_compactions(0).build();
+        let mut tree = Tree::load_with_settings(settings)?;
+        assert!(!tree.compact_if_needed()?);
+
+        // One flush split into five tables gets a single merge of the oldest three
+        for i in 0..50 {
+            tree.put(format!("key_{:02}", i).into_bytes(), b"value".to_vec())?;
+        }
+        tree.flush()?;
+        assert_eq!(tree.ss_tables.len(), 3);
+
+        assert!(tree.compact_if_needed()?);
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert!(!tree.compact_if_needed()?);
+        for i in 0..50 {
+            assert_eq!(tree.get(format!("key_{:02}", i).as_bytes())?, Some(b"value".to_vec()));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_background_flush_serves_reads_and_survives_crash() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let settings = TreeSettingsBuilder::new()
+            .mem_table_max_size(50)
+            .sstable_max_entries(20)
+            .background_flush(true)
+            .build();
+        {
+            let mut tree = Tree::load_with_settings(settings.clone())?;
+            for i in 0..510 {
+                tree.put(format!("key_{:03}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+                if i >= 30 && i % 50 == 25 {
+                    assert_eq!(tree.get(format!("key_{:03}", i - 30).as_bytes())?, Some(format!("value_{}", i - 30).into_bytes()));
+                }
+            }
+            // The prefix deletion lands while the last full table may still be written
+            tree.delete_prefix(b"key_49")?;
+            for i in 0..510 {
+                let expected = (!(490..500).contains(&i)).then(|| format!("value_{}", i).into_bytes());
+                assert_eq!(tree.get(format!("key_{:03}", i).as_bytes())?, expected);
+            }
+
+            tree.poll_flush(true)?;
+            assert!(!tree.flush_in_flight);
+            assert!(tree.immutable_mem_tables.is_empty());
+            assert!(tree.compaction_stats().flushes >= 10);
+            mem::forget(tree);
+        }
+
+        let mut recovered = Tree::load_with_settings(settings)?;
+        for i in 0..510 {
+            let expected = (!(490..500).contains(&i)).then(|| format!("value_{}", i).into_bytes());
+            assert_eq!(recovered.get(format!("key_{:03}", i).as_bytes())?, expected);
+        }
+        recovered.flush()?;
+        assert!(recovered.immutable_mem_tables.is_empty());
+        assert!(recovered.mem_table.is_empty());
+
+        drop(recovered);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_compaction_config_sets_merge_trigger_and_width() -> TreeResult<()> {
+        clean_temp_dir();
+
+        fn put_and_flush(tree: &mut Tree, keys: std::ops::Range<usize>) -> TreeResult<()> {
+            for i in keys {
+                tree.put(format!("key_{:03}", i).into_bytes(), b"value".to_vec())?;
+            }
+            tree.flush()
+        }
+        fn assert_readable(tree: &mut Tree, keys: std::ops::Range<usize>) -> TreeResult<()> {
+            for i in keys {
+                assert_eq!(tree.get(format!("key_{:03}", i).as_bytes())?, Some(b"value".to_vec()));
+            }
+            Ok(())
+        }
+
+        // Three tables stay apart; ten get one merge of the oldest five per request
+        let config = CompactionConfig { min_tables: 4, max_tables: 5, ..CompactionConfig::default() };
+        let settings = TreeSettingsBuilder::new().max_queued_compactions(0).compaction(config);
+        let mut tree = Tree::load_with_settings(settings.sstable_max_entries(10).build())?;
+        put_and_flush(&mut tree, 0..30)?;
+        assert_eq!(tree.ss_tables.len(), 3);
+        put_and_flush(&mut tree, 30..100)?;
+        assert_eq!(tree.ss_tables.len(), 6);
+        assert!(tree.compact_if_needed()?);
+        assert_eq!(tree.ss_tables.len(), 2);
+        assert_readable(&mut tree, 0..100)?;
+        tree.truncate()?;
+        drop(tree);
+
+        // Small tables wait for each other until `max_tables` of them exist
+        let config = CompactionConfig { min_tables: 2, max_tables: 4, size_ratio: Some(1.0), ..CompactionConfig::default() };
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().max_queued_compactions(0).compaction(config).build())?;
+        put_and_flush(&mut tree, 0..200)?;
+        put_and_flush(&mut tree, 200..210)?;
+        put_and_flush(&mut tree, 210..220)?;
+        assert_eq!(tree.ss_tables.len(), 3);
+        assert!(!tree.compact_if_needed()?);
+        put_and_flush(&mut tree, 220..230)?;
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert_readable(&mut tree, 0..230)?;
+        tree.truncate()?;
+        drop(tree);
+
+        // A size limit narrows the merge to the two oldest tables
+        let config = CompactionConfig { max_sstable_size: Some(1), ..CompactionConfig::default() };
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().max_queued_compactions(0).compaction(config).build())?;
+        put_and_flush(&mut tree, 0..10)?;
+        put_and_flush(&mut tree, 10..20)?;
+        put_and_flush(&mut tree, 20..30)?;
+        assert_eq!(tree.ss_tables.len(), 2);
+        assert_readable(&mut tree, 0..30)?;
+
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_mem_table_max_bytes_flushes_by_size() -> TreeResult<()> {
+        clean_temp_dir();
+        let settings = TreeSettingsBuilder::new()
+            .mem_table_max_size(1_000_000)
+            .mem_table_max_bytes(100_000)
+            .compressor(CompressionConfig::none())
+            .compaction(CompactionConfig { min_tables: 100, max_tables: 100, ..CompactionConfig::default() })
+            .build();
+        let mut tree = Tree::load_with_settings(settings)?;
+
+        // Small values stay far below the byte budget
+        for i in 0..500 {
+            tree.put(format!("small_{:03}", i).into_bytes(), vec![1u8; 10])?;
+        }
+        assert!(tree.ss_tables.is_empty());
+        let bytes = tree.mem_table_bytes();
+        assert!((500 * (9 + 10)..100_000).contains(&bytes), "mem table holds {} bytes", bytes);
+
+        // Overwrites and deletes replace the counted value
+        tree.put(b"small_000".to_vec(), vec![1u8; 1_010])?;
+        assert_eq!(tree.mem_table_bytes(), bytes + 1_000);
+        tree.delete(b"small_000")?;
+        assert_eq!(tree.mem_table_bytes(), bytes - 10);
+        tree.clear_mem_table();
+        assert_eq!(tree.mem_table_bytes(), 0);
+
+        // Large values fill the budget after a handful of entries
+        for i in 0..20 {
+            tree.put(format!("large_{:02}", i).into_bytes(), vec![2u8; 30_000])?;
+        }
+        assert!(tree.ss_tables.len() >= 5, "{} SSTables", tree.ss_tables.len());
+        assert!(tree.mem_table_bytes() <= 100_000 + 30_000 + 100);
+        for i in 0..20 {
+            assert_eq!(tree.get(format!("large_{:02}", i).as_bytes())?.map(|value| value.len()), Some(30_000));
+        }
+
+        // WAL replay flushes by the byte budget too
+        tree.flush()?;
+        let flushed = tree.ss_tables.len();
+        for i in 20..23 {
+            tree.put(format!("large_{:02}", i).into_bytes(), vec![3u8; 30_000])?;
+        }
+        assert_eq!(tree.ss_tables.len(), flushed);
+        mem::forget(tree);
+        let settings = TreeSettingsBuilder::new()
+            .mem_table_max_size(1_000_000)
+            .mem_table_max_bytes(40_000)
+            .compressor(CompressionConfig::none())
+            .compaction(CompactionConfig { min_tables: 100, max_tables: 100, ..CompactionConfig::default() })
+            .build();
+        let mut tree = Tree::load_with_settings(settings)?;
+        assert!(tree.ss_tables.len() > flushed);
+        assert!(tree.mem_table_bytes() <= 40_000 + 30_000 + 100);
+        assert_eq!(tree.get(b"large_22")?, Some(vec![3u8; 30_000]));
+
+        tree.truncate()?;
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_rate_limiter_throttles_sstable_writes() -> TreeResult<()> {
+        clean_temp_dir();
+        let rate_limiter = Arc::new(RateLimiter::new(200_000));
+        let settings = TreeSettingsBuilder::new()
+            .rate_limiter(Arc::clone(&rate_limiter))
+            .compressor(CompressionConfig::none())
+            .build();
+        let mut tree = Tree::load_with_settings(settings)?;
+
+        // Incompressible values, about two seconds of writes at the limit
+        let mut rng = rand::rng();
+        for i in 0..40 {
+            let mut value = vec![0u8; 10_000];
+            rng.fill_bytes(&mut value);
+            tree.put(format!("key_{:02}", i).into_bytes(), value)?;
+        }
+
+        // The first second passes at full speed, the rest is paid for by the flush
+        let started = Instant::now();
+        tree.flush()?;
+        assert!(started.elapsed() >= Duration::from_millis(700), "flush took {:?}", started.elapsed());
+        assert_eq!(rate_limiter.bytes_per_second(), 200_000);
+        assert_eq!(tree.get(b"key_39")?.map(|value| value.len()), Some(10_000));
+
+        // An unlimited tree is not throttled
+        tree.truncate()?;
+        drop(tree);
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        for i in 0..40 {
+            tree.put(format!("key_{:02}", i).into_bytes(), vec![i as u8; 10_000])?;
+        }
+        assert!(tree.settings.rate_limiter.is_none());
+        tree.flush()?;
+
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_cancelled_flush_and_merge_keep_data() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let is_cancelled = |result: TreeResult<()>| matches!(result, Err(TreeError::Internal { message }) if message == "cancelled");
+        let settings = TreeSettingsBuilder::new().max_queued_compactions(0).sstable_max_entries(10).build();
+        {
+            let mut tree = Tree::load_with_settings(settings.clone())?;
+            for i in 0..30 {
+                tree.put(format!("key_{:02}", i).into_bytes(), b"first".to_vec())?;
+            }
+            tree.cancel_background_work();
+            assert!(is_cancelled(tree.flush()));
+            assert!(tree.ss_tables.is_empty());
+            assert_eq!(tree.get(b"key_15")?, Some(b"first".to_vec()));
+
+            tree.resume_background_work();
+            tree.flush()?;
+            assert_eq!(tree.ss_tables.len(), 1);
+
+            for i in 0..10 {
+                tree.put(format!("key_{:02}", i).into_bytes(), b"second".to_vec())?;
+            }
+            tree.flush()?;
+            let tables = tree.ss_tables.clone();
+            assert_eq!(tables.len(), 2);
+
+            tree.cancel_background_work();
+            assert!(is_cancelled(tree.compact_range(b"key_00", b"key_99")));
+            assert_eq!(tree.ss_tables, tables);
+            assert!(tables.iter().all(|path| path.exists()));
+            assert_eq!(tree.get(b"key_05")?, Some(b"second".to_vec()));
+            assert_eq!(tree.get(b"key_25")?, Some(b"first".to_vec()));
+
+            tree.put(b"unflushed".to_vec(), b"value".to_vec())?;
+        }
+
+        let mut tree = Tree::load_with_settings(settings)?;
+        assert_eq!(tree.get(b"unflushed")?, Some(b"value".to_vec()));
+        tree.compact_range(b"key_00", b"key_99")?;
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert_eq!(tree.get(b"key_05")?, Some(b"second".to_vec()));
+        assert_eq!(tree.get(b"key_25")?, Some(b"first".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_merge_keeps_deleted_keys_deleted() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"deleted".to_vec(), b"old".to_vec())?;
+        tree.put(b"kept".to_vec(), b"old".to_vec())?;
+        tree.flush()?;
+        tree.delete(b"deleted")?;
+        tree.flush()?;
+        tree.put(b"kept".to_vec(), b"new".to_vec())?;
+        tree.flush()?;
+
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert_eq!(tree.get(b"deleted")?, None);
+        assert_eq!(tree.get(b"kept")?, Some(b"new".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_merge_keeps_tombstones_within_grace_period() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .max_queued_compactions(0)
+                .tombstone_grace_period(Duration::from_secs(3600))
+                .build(),
+        )?;
+        tree.put(b"deleted".to_vec(), b"old".to_vec())?;
+        tree.put(b"prefix_a".to_vec(), b"old".to_vec())?;
+        tree.put(b"kept".to_vec(), b"old".to_vec())?;
+        tree.flush()?;
+        tree.delete(b"deleted")?;
+        tree.delete_prefix(b"prefix_")?;
+        tree.flush()?;
+        tree.put(b"kept".to_vec(), b"new".to_vec())?;
+        tree.flush()?;
+
+        assert_eq!(tree.ss_tables.len(), 1);
+        let merged_path = tree.ss_tables[0].clone();
+        let (merged, _) = tree.load_sstable_with_bloom_filter(&merged_path)?;
+        assert!(merged.get(b"deleted".as_slice()).is_some_and(|value| value.is_tombstone));
+        assert!(!merged.contains_key(b"prefix_a".as_slice()));
+        assert_eq!(tree.range_tombstones.get(&merged_path).map(Vec::len), Some(1));
+        assert_eq!(tree.scan_limit(b"", 10)?, vec![(b"kept".to_vec(), b"new".to_vec())]);
+
+        tree.settings.tombstone_grace_period = Duration::ZERO;
+        tree.compact_range(b"", b"\xff")?;
+        let merged_path = tree.ss_tables[0].clone();
+        let (merged, _) = tree.load_sstable_with_bloom_filter(&merged_path)?;
+        assert_eq!(merged.len(), 1);
+        assert!(tree.range_tombstones.get(&merged_path).is_none_or(Vec::is_empty));
+        assert_eq!(tree.scan_limit(b"", 10)?, vec![(b"kept".to_vec(), b"new".to_vec())]);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_value_heap_size() {
+        let base = size_of::<DataValue>();
+        assert_eq!(DataValue::tombstone().heap_size(), base);
+        assert_eq!(DataValue::new(Vec::new(), None).heap_size(), base);
+        assert_eq!(DataValue::new(vec![7; 100], None).heap_size(), base + 100);
+        assert_eq!(
+            DataValue::new(vec![7; 100], Some(Duration::from_secs(60))).with_transaction_id(3).heap_size(),
+            base + 100
+        );
+
+        let values = [DataValue::new(vec![1; 10], None), DataValue::new(vec![2; 1000], None)];
+        let memory_limit = 1 << 20;
+        let mut cache = LRUValueCache::new(10, memory_limit);
+        for (i, value) in values.iter().enumerate() {
+            cache.put(PathBuf::from("0.sst"), vec![i as u8], value.clone());
+        }
+        let expected: usize = values.iter().map(DataValue::heap_size).sum();
+        assert_eq!(cache.stats().memory_utilization, expected as f64 / memory_limit as f64);
+    }
+
+    #[test]
+    fn test_cache_eviction_watermark_evicts_in_batches() {
+        let path = PathBuf::from("0.sst");
+        let value = DataValue::new(vec![7; 100], None);
+
+        let mut per_insert = LRUValueCache::new(10, 1 << 20);
+        let mut batched = LRUValueCache::new(10, 1 << 20);
+        batched.set_eviction_watermark(0.5);
+        for i in 0..10u8 {
+            per_insert.put(path.clone(), vec![i], value.clone());
+            batched.put(path.clone(), vec![i], value.clone());
+        }
+
+        per_insert.put(path.clone(), vec![10], value.clone());
+        assert_eq!(per_insert.stats().size, 10);
+        assert_eq!(per_insert.stats().eviction_count, 1);
+
+        batched.put(path.clone(), vec![10], value.clone());
+        assert_eq!(batched.stats().size, 6);
+        assert_eq!(batched.stats().eviction_count, 5);
+        assert!(batched.get(&path, &[4]).is_none());
+        assert!(batched.get(&path, &[5]).is_some());
+        for i in 11..15u8 {
+            batched.put(path.clone(), vec![i], value.clone());
+        }
+        assert_eq!(batched.stats().size, 10);
+        assert_eq!(batched.stats().eviction_count, 5);
+
+        let memory_limit = value.heap_size() * 10;
+        let mut by_memory = LRUValueCache::new(100, memory_limit);
+        by_memory.set_eviction_watermark(0.5);
+        for i in 0..11u8 {
+            by_memory.put(path.clone(), vec![i], value.clone());
+        }
+        assert_eq!(by_memory.stats().size, 6);
+        assert_eq!(by_memory.stats().eviction_count, 5);
+    }
+
+    #[test]
+    #[serial]
+    fn test_overflowing_ttl_never_expires() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let huge_ttl = Duration::from_secs(u64::MAX);
+        assert_eq!(DataValue::new(b"value".to_vec(), Some(huge_ttl)).expires_at, None);
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            tree.put_with_ttl(b"huge".to_vec(), b"value".to_vec(), Some(huge_ttl))?;
+            tree.put_typed_with_ttl::<u64>("huge_typed", &7, huge_ttl)?;
+            tree.put(b"updated".to_vec(), b"value".to_vec())?;
+            assert!(tree.update_ttl(b"updated", Some(huge_ttl))?);
+
+            assert_eq!(tree.get(b"huge")?, Some(b"value".to_vec()));
+            assert_eq!(tree.get_ttl(b"huge"), None);
+            assert_eq!(tree.get_ttl(b"updated"), None);
+            tree.flush()?;
+        }
+
+        let tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(tree.get(b"huge")?, Some(b"value".to_vec()));
+        assert_eq!(tree.get_typed::<u64>("huge_typed")?, Some(7));
+        assert_eq!(tree.get_metadata(b"updated")?.map(|metadata| metadata.expires_at), Some(None));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_probes_value_cache_by_key() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"old".to_vec(), b"v1".to_vec())?;
+        tree.put(b"updated".to_vec(), b"v1".to_vec())?;
+        tree.flush()?;
+        tree.put(b"newer".to_vec(), b"v2".to_vec())?;
+        tree.put(b"updated".to_vec(), b"v2".to_vec())?;
+        tree.flush()?;
+        assert_eq!(tree.ss_tables.len(), 2);
+
+        assert_eq!(tree.get(b"old")?, Some(b"v1".to_vec()));
+        let index_lookups = |tree: &Tree| {
+            let stats = tree.get_index_cache_stats();
+            stats.hit_count + stats.miss_count
+        };
+        let index_lookups_before = index_lookups(&tree);
+        let value_hits_before = tree.get_value_cache_stats().hit_count;
+
+        assert_eq!(tree.get(b"old")?, Some(b"v1".to_vec()));
+        assert_eq!(index_lookups(&tree), index_lookups_before);
+        assert_eq!(tree.get_value_cache_stats().hit_count, value_hits_before + 1);
+
+        tree.value_cache().put(tree.ss_tables[0].clone(), b"updated".to_vec(), DataValue::new(b"v1".to_vec(), None));
+        assert_eq!(tree.get(b"updated")?, Some(b"v2".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_overwrite_invalidates_cached_value() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        tree.put(b"key".to_vec(), b"old".to_vec())?;
+        tree.put(b"deleted".to_vec(), b"old".to_vec())?;
+        tree.flush()?;
+        assert_eq!(tree.get(b"key")?, Some(b"old".to_vec()));
+        assert_eq!(tree.get(b"deleted")?, Some(b"old".to_vec()));
+        assert_eq!(tree.value_cache().cached_paths(b"key").len(), 1);
+
+        tree.put(b"key".to_vec(), b"new".to_vec())?;
+        tree.delete(b"deleted")?;
+        assert!(tree.value_cache().cached_paths(b"key").is_empty());
+        assert!(tree.value_cache().cached_paths(b"deleted").is_empty());
+
+        tree.flush()?;
+        assert_eq!(tree.get(b"key")?, Some(b"new".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_file_pool_reuses_and_invalidates_handles() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .value_cache(false)
+                .max_open_files(2)
+                .build(),
+        )?;
+        for i in 0..4 {
+            tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+            tree.flush()?;
+        }
+
+        for _ in 0..2 {
+            for i in 0..4 {
+                assert_eq!(tree.get(format!("key_{}", i).as_bytes())?, Some(format!("value_{}", i).into_bytes()));
+            }
+        }
+        assert_eq!(tree.file_pool.open_files(), 2);
+
+        tree.compact_range(b"key_0", b"key_9")?;
+        assert_eq!(tree.file_pool.open_files(), 0);
+        for i in 0..4 {
+            assert_eq!(tree.get(format!("key_{}", i).as_bytes())?, Some(format!("value_{}", i).into_bytes()));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_buffer_sizes_do_not_change_stored_data() -> TreeResult<()> {
+        let defaults = TreeSettingsBuilder::new().build();
+        assert_eq!(defaults.read_buffer_size, 8 * 1024);
+        assert_eq!(defaults.write_buffer_size, 8 * 1024);
+        assert_eq!(TreeSettingsBuilder::new().read_buffer_size(0).build().read_buffer_size, 1);
+
+        for buffer_size in [1, 256 * 1024] {
+            clean_temp_dir();
+            let settings = || TreeSettingsBuilder::new()
+                .value_cache(false)
+                .read_buffer_size(buffer_size)
+                .write_buffer_size(buffer_size)
+                .build();
+            let value = |i: usize| vec![i as u8; 20_000 + i];
+
+            {
+                let mut tree = Tree::load_with_settings(settings())?;
+                for i in 0..10 {
+                    tree.put(format!("key_{}", i).into_bytes(), value(i))?;
+                }
+                tree.flush()?;
+                for i in 10..20 {
+                    tree.put(format!("key_{}", i).into_bytes(), value(i))?;
+                }
+                mem::forget(tree);
+            }
+
+            let mut tree = Tree::load_with_settings(settings())?;
+            for i in 0..20 {
+                assert_eq!(tree.get(format!("key_{}", i).as_bytes())?, Some(value(i)));
+            }
+            assert_eq!(tree.iter()?.count(), 20);
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_settings_presets_can_be_overridden() -> TreeResult<()> {
+        let defaults = TreeSettingsBuilder::new().build();
+
+        let write_heavy = TreeSettingsBuilder::for_write_heavy().build();
+        assert_eq!(write_heavy.mem_table_max_size, 100_000);
+        assert_eq!(write_heavy.value_cache_max_capacity, defaults.value_cache_max_capacity);
+
+        let read_heavy = TreeSettingsBuilder::for_read_heavy().max_open_files(8).build();
+        assert_eq!(read_heavy.bloom_filter_error_probability, 0.001);
+        assert_eq!(read_heavy.max_open_files, 8);
+        assert_eq!(read_heavy.mem_table_max_size, defaults.mem_table_max_size);
+
+        let low_memory = TreeSettingsBuilder::for_low_memory().mem_table_max_size(500).build();
+        assert_eq!(low_memory.mem_table_max_size, 500);
+        assert!(low_memory.value_cache_memory_limit < defaults.value_cache_memory_limit);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_truncate_leaves_empty_writable_tree() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        for i in 0..100 {
+            tree.put(format!("key_{}", i).into_bytes(), b"value".to_vec())?;
+        }
+        tree.flush()?;
+        tree.put(b"unflushed".to_vec(), b"value".to_vec())?;
+
+        tree.truncate()?;
+        assert_eq!(tree.len(), 0);
+        assert_eq!(tree.get(b"key_1")?, None);
+        let sstables = std::fs::read_dir(DEFAULT_DB_PATH)?
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "sst"))
+            .count();
+        assert_eq!(sstables, 0);
+
+        tree.put(b"after".to_vec(), b"truncate".to_vec())?;
+        assert_eq!(tree.get(b"after")?, Some(b"truncate".to_vec()));
+        assert_eq!(tree.len(), 1);
+
+        drop(tree);
+        let tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(tree.get(b"after")?, Some(b"truncate".to_vec()));
+        assert_eq!(tree.get(b"unflushed")?, None);
+        assert_eq!(tree.len(), 1);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_background_compaction_keeps_reads_correct() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let settings = TreeSettingsBuilder::new()
+            .mem_table_max_size(50)
+            .max_queued_compactions(1)
+            .build();
+        let mut tree = Tree::load_with_settings(settings.clone())?;
+        assert!(tree.compaction_worker.is_some());
+        for round in 0..10 {
+            for i in 0..200 {
+                tree.put(format!("key_{:03}", i).into_bytes(), format!("v{}_{}", round, i).into_bytes())?;
+            }
+            assert!(tree.queued_compactions <= 1);
+            for i in (0..200).step_by(17) {
+                assert_eq!(
+                    tree.get(format!("key_{:03}", i).as_bytes())?,
+                    Some(format!("v{}_{}", round, i).into_bytes())
+                );
+            }
+        }
+
+        tree.flush()?;
+        assert!(!tree.compaction_in_flight);
+        assert_eq!(tree.queued_compactions, 0);
+        assert!(tree.ss_tables.len() <= 2);
+        let leftovers = std::fs::read_dir(DEFAULT_DB_PATH)?
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0);
+
+        drop(tree);
+        let tree = Tree::load_with_settings(settings)?;
+        for i in 0..200 {
+            assert_eq!(
+                tree.get(format!("key_{:03}", i).as_bytes())?,
+                Some(format!("v9_{}", i).into_bytes())
+            );
+        }
+        drop(tree);
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new().mem_table_max_size(50).max_queued_compactions(0).build(),
+        )?;
+        assert!(tree.compaction_worker.is_none());
+        for i in 0..200 {
+            tree.put(format!("key_{:03}", i).into_bytes(), b"sync".to_vec())?;
+            assert!(tree.ss_tables.len() <= 2);
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_par_multi_get_matches_multi_get() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .compressor(CompressionConfig::fast())
+                .read_parallelism(3)
+                .build(),
+        )?;
+        for i in 0..300 {
+            tree.put(format!("key_{:03}", i).into_bytes(), format!("v1_{}", i).into_bytes())?;
+            if i % 100 == 99 {
+                tree.flush()?;
+            }
+        }
+        for i in (0..300).step_by(7) {
+            tree.put(format!("key_{:03}", i).into_bytes(), format!("v2_{}", i).into_bytes())?;
+        }
+        tree.flush()?;
+        tree.put(b"key_001".to_vec(), b"unflushed".to_vec())?;
+        tree.delete(b"key_002")?;
+        tree.delete_prefix(b"key_29")?;
+
+        let key_strings: Vec<String> = (0..320).rev().map(|i| format!("key_{:03}", i)).collect();
+        let keys: Vec<&[u8]> = key_strings.iter().map(|key| key.as_bytes()).collect();
+
+        let parallel = tree.par_multi_get(keys.clone())?;
+        assert_eq!(parallel[319 - 2], None);
+        let live_keys: Vec<&[u8]> = keys.into_iter().filter(|key| *key != b"key_002").collect();
+        let expected = tree.multi_get(live_keys)?;
+        let mut parallel_live = parallel.clone();
+        parallel_live.remove(319 - 2);
+        assert_eq!(parallel_live, expected);
+        assert_eq!(parallel[319], Some(b"v2_0".to_vec()));
+        assert_eq!(parallel[318], Some(b"unflushed".to_vec()));
+        assert_eq!(parallel[319 - 7], Some(b"v2_7".to_vec()));
+        assert_eq!(parallel[319 - 295], None);
+        assert_eq!(parallel[0], None);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_reads_concurrently_through_shared_tree() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .compressor(CompressionConfig::fast())
+                .build(),
+        )?;
+        for i in 0..200 {
+            tree.put(format!("key_{:03}", i).into_bytes(), format!("v1_{}", i).into_bytes())?;
+            if i % 50 == 49 {
+                tree.flush()?;
+            }
+        }
+        tree.put(b"key_010".to_vec(), b"unflushed".to_vec())?;
+        tree.delete(b"key_020")?;
+        tree.delete_prefix(b"key_19")?;
+
+        let expected: Vec<Option<Vec<u8>>> = (0..210)
+            .map(|i| tree.get(format!("key_{:03}", i).as_bytes()))
+            .collect::<TreeResult<_>>()?;
+        assert_eq!(expected[10], Some(b"unflushed".to_vec()));
+        assert_eq!(expected[20], None);
+        assert_eq!(expected[195], None);
+
+        tree.clear_value_cache();
+        let hits_before = tree.get_value_cache_stats().hit_count;
+
+        let tree = Arc::new(tree);
+        let expected = Arc::new(expected);
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let tree = Arc::clone(&tree);
+                let expected = Arc::clone(&expected);
+                std::thread::spawn(move || -> TreeResult<()> {
+                    for (i, value) in expected.iter().enumerate() {
+                        assert_eq!(&tree.get(format!("key_{:03}", i).as_bytes())?, value);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap()?;
+        }
+        // The readers share the caches: values one of them read are hits for the others
+        assert!(tree.get_value_cache_stats().hit_count > hits_before);
+
+        drop(tree);
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_sparse_index_lookups() -> TreeResult<()> {
+        for index_cache in [true, false] {
+            clean_temp_dir();
+
+            let mut tree = Tree::load_with_settings(
+                TreeSettingsBuilder::new()
+                    .index_block_interval(16)
+                    .index_cache(index_cache)
+                    .value_cache(false)
+                    .build(),
+            )?;
+            for i in 0..1000 {
+                tree.put(format!("key_{:04}", i * 2).into_bytes(), format!("value_{}", i).into_bytes())?;
+            }
+            tree.flush()?;
+
+            if index_cache {
+                let path = tree.ss_tables[0].clone();
+                let index_cache = tree.index_cache();
+                let index = index_cache.peek(&path).unwrap();
+                assert_eq!(index.len(), 1000_usize.div_ceil(16) + 1);
+                assert!(index.contains_key(b"key_1998".as_slice()));
+            }
+
+            for i in 0..1000 {
+                assert_eq!(tree.get(format!("key_{:04}", i * 2).as_bytes())?, Some(format!("value_{}", i).into_bytes()));
+                assert_eq!(tree.get(format!("key_{:04}", i * 2 + 1).as_bytes())?, None);
+            }
+            assert_eq!(tree.get(b"key_")?, None);
+            assert_eq!(tree.get(b"key_9999")?, None);
+            assert_eq!(tree.estimate_count()?, 1000);
+            assert_eq!(tree.len(), 1000);
+
+            tree.compact_range(b"key_0000", b"key_0100")?;
+            assert_eq!(tree.get(b"key_0050")?, Some(b"value_25".to_vec()));
+            assert_eq!(tree.get(b"key_1502")?, Some(b"value_751".to_vec()));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_scan_limit_pages_over_live_keys() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().index_block_interval(4).build())?;
+        for i in 0..100 {
+            tree.put(format!("key_{:03}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+            if i % 30 == 29 {
+                tree.flush()?;
+            }
+        }
+        tree.delete(b"key_011")?;
+        tree.delete(b"key_012")?;
+        tree.delete_prefix(b"key_02")?;
+        tree.put(b"key_013".to_vec(), b"updated".to_vec())?;
+
+        let page = tree.scan_limit(b"key_010", 5)?;
+        let keys: Vec<&[u8]> = page.iter().map(|(key, _)| key.as_slice()).collect();
+        assert_eq!(keys, vec![b"key_010".as_slice(), b"key_013", b"key_014", b"key_015", b"key_016"]);
+        assert_eq!(page[1].1, b"updated".to_vec());
+
+        let page = tree.scan_limit(b"key_0175", 5)?;
+        let keys: Vec<&[u8]> = page.iter().map(|(key, _)| key.as_slice()).collect();
+        assert_eq!(keys, vec![b"key_018".as_slice(), b"key_019", b"key_030", b"key_031", b"key_032"]);
+
+        assert_eq!(tree.scan_limit(b"key_098", 5)?.len(), 2);
+        assert!(tree.scan_limit(b"key_000", 0)?.is_empty());
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_streaming_merge_skips_entries_failing_checksum() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().max_queued_compactions(0).build())?;
+        for i in 0..10 {
+            tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+        }
+        tree.flush()?;
+
+        let path = tree.ss_tables[0].clone();
+        let mut bytes = std::fs::read(&path)?;
+        let value_at = bytes.windows(7).position(|window| window == b"value_5").unwrap();
+        bytes[value_at] ^= 0xff;
+        std::fs::write(&path, bytes)?;
+        tree.clear_value_cache();
+        assert_eq!(tree.count_sstable_entries(&path)?, 9);
+
+        for table in 0..2 {
+            tree.put(format!("other_{}", table).into_bytes(), b"value".to_vec())?;
+            tree.flush()?;
+        }
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert_eq!(tree.get(b"key_5")?, None);
+        assert_eq!(tree.get(b"key_6")?, Some(b"value_6".to_vec()));
+        assert_eq!(tree.iter()?.count(), 11);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_scan_error_policy_skips_corrupt_entries() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut settings = TreeSettingsBuilder::new().compressor(CompressionConfig::balanced()).build();
+        {
+            let mut tree = Tree::load_with_settings(settings.clone())?;
+            for i in 0..10 {
+                tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+            }
+            tree.mem_table.insert(b"key_5".to_vec(), DataValue::new(vec![0xde, 0xad, 0xbe, 0xef], None));
+            tree.flush()?;
+
+            let entries: Vec<TreeResult<(Vec<u8>, Vec<u8>)>> = tree.iter()?.collect();
+            assert_eq!(entries.len(), 10);
+            assert!(matches!(entries[5], Err(TreeError::Compression { .. })));
+            assert!(tree.scan_limit(b"key_3", 5).is_err());
+        }
+
+        settings.scan_error_policy = ReadErrorPolicy::SkipCorrupt;
+        let mut tree = Tree::load_with_settings(settings)?;
+        let keys: Vec<Vec<u8>> = tree.scan_limit(b"key_3", 5)?.into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["key_3", "key_4", "key_6", "key_7", "key_8"].map(|key| key.as_bytes().to_vec()));
+        assert_eq!(tree.iter()?.collect::<TreeResult<Vec<_>>>()?.len(), 9);
+        assert_eq!(tree.scan_rev(None, 10)?.len(), 9);
+        assert!(matches!(tree.get(b"key_5"), Err(TreeError::Compression { .. })));
+        assert_eq!(tree.get(b"key_6")?, Some(b"value_6".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_reverse_iteration_mirrors_forward() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .index_block_interval(3)
+                .max_queued_compactions(0)
+                .build(),
+        )?;
+        for i in 0..80 {
+            tree.put(format!("key_{:03}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+            if i % 25 == 24 {
+                tree.flush()?;
+            }
+        }
+        tree.put(b"key_000".to_vec(), b"newest".to_vec())?;
+        tree.put(b"key_050".to_vec(), b"newest".to_vec())?;
+        tree.delete(b"key_001")?;
+        tree.delete(b"key_079")?;
+        tree.delete_prefix(b"key_04")?;
+        tree.put(b"key_045".to_vec(), b"rewritten".to_vec())?;
+        tree.put_with_ttl(b"key_030".to_vec(), b"expiring".to_vec(), Some(Duration::from_millis(1)))?;
+        tree.flush()?;
+        tree.put(b"key_060".to_vec(), b"in_memory".to_vec())?;
+        tree.delete(b"key_061")?;
+        std::thread::sleep(Duration::from_millis(5));
+
+        let forward: Vec<(Vec<u8>, Vec<u8>)> = tree.iter()?.collect::<TreeResult<_>>()?;
+        let mut reverse: Vec<(Vec<u8>, Vec<u8>)> = tree.iter_rev()?.collect::<TreeResult<_>>()?;
+        assert!(forward.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(forward.len(), 80 - 3 - 9 - 1);
+        assert_eq!(forward[0], (b"key_000".to_vec(), b"newest".to_vec()));
+        reverse.reverse();
+        assert_eq!(reverse, forward);
+
+        let mut paged = Vec::new();
+        let mut end: Option<Vec<u8>> = None;
+        loop {
+            let page = tree.scan_rev(end.as_deref(), 7)?;
+            let Some((last_key, _)) = page.last() else {
+                break;
+            };
+            end = Some(last_key.clone());
+            paged.extend(page);
+        }
+        paged.reverse();
+        assert_eq!(paged, forward);
+
+        let page = tree.scan_rev(Some(b"key_046"), 3)?;
+        let keys: Vec<&[u8]> = page.iter().map(|(key, _)| key.as_slice()).collect();
+        assert_eq!(keys, vec![b"key_045".as_slice(), b"key_039", b"key_038"]);
+        assert!(tree.scan_rev(Some(b"key_000"), 5)?.is_empty());
+        assert!(tree.scan_rev(None, 0)?.is_empty());
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_rw_100k_from_memtable() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(
+            TreeSettingsBuilder::new()
+                .compressor(CompressionConfig::none())
+                .index_cache(false)
+                .value_cache(false)
+                .mem_table_max_size(100000)
+                .build(),
+        )?;
+        let max_entries: u64 = 99999;
+
+        let start_time = Instant::now();
+        for i in 0..=max_entries {
+            let key = format!("test_key_{}", i);
+            tree.put(key.as_bytes().to_vec(), key.as_bytes().to_vec())?;
+        }
+        let write_duration = start_time.elapsed();
+        println!(
+            "Write time for {} entries: {:?}",
+            max_entries, write_duration
+        );
+
+        let start_time = Instant::now();
+        for i in 0..=max_entries {
+            let key = format!("test_key_{}", i);
+            tree.get(key.as_bytes())?;
+        }
+        let read_duration = start_time.elapsed();
+
+        println!("===> Performance statistics:");
+        println!(
+            "Write speed: {:.2} entries/ms",
+            max_entries as f64 / write_duration.as_millis() as f64
+        );
+        println!(
+            "Read speed: {:.2} entries/ms",
+            max_entries as f64 / read_duration.as_millis() as f64
+        );
+
+        println!("{}", tree.get_index_cache_stats());
+        println!("{}", tree.get_value_cache_stats());
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_compression_performance() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let test_data = generate_compressible_data(10000);
         let compression_configs = vec![
             CompressionConfig::fast(),
             CompressionConfig::balanced(),
@@ -695,6 +3924,141 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_compress_into_reuses_buffer() {
+        let small = b"Hello, World! Hello, World! Hello, World!".to_vec();
+        let large = "redish ".repeat(20_000).into_bytes();
+
+        for compression_type in [
+            CompressionType::None,
+            CompressionType::Lz4,
+            CompressionType::Zstd,
+            CompressionType::Snappy,
+            CompressionType::Gzip,
+        ] {
+            let compressor = Compressor::new(CompressionConfig::new(compression_type));
+            let mut compressed = Vec::new();
+            let mut decompressed = Vec::new();
+
+            for data in [&large, &small] {
+                compressor.compress_into(data, &mut compressed).unwrap();
+                assert_eq!(compressed, compressor.compress(data).unwrap());
+
+                compressor.decompress_into(&compressed, &mut decompressed).unwrap();
+                assert_eq!(&decompressed, data);
+                assert_eq!(compressor.decompress(&compressed).unwrap(), *data);
+            }
+            assert!(decompressed.capacity() >= large.len());
+        }
+    }
+
+    #[test]
+    fn test_lz4_rejects_oversized_size_prefix() {
+        let compressor = Compressor::new(CompressionConfig::balanced());
+        for level in [1, 9] {
+            let compressor = Compressor::new(CompressionConfig::new(CompressionType::Lz4).with_level(level));
+            let zeros = vec![0u8; 4 << 20];
+            assert_eq!(compressor.decompress(&compressor.compress(&zeros).unwrap()).unwrap(), zeros);
+        }
+
+        let mut corrupt = compressor.compress(b"value").unwrap();
+        corrupt[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(compressor.decompress(&corrupt).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_gzip_values_are_readable_by_standard_decoders() -> TreeResult<()> {
+        use std::io::Read;
+        clean_temp_dir();
+
+        let settings = || TreeSettingsBuilder::new()
+            .compressor(CompressionConfig::new(CompressionType::Gzip).with_level(9))
+            .build();
+        let payload = "interoperable payload ".repeat(100).into_bytes();
+        {
+            let mut tree = Tree::load_with_settings(settings())?;
+            tree.put(b"key".to_vec(), payload.clone())?;
+            tree.flush()?;
+
+            let path = tree.ss_tables[0].clone();
+            assert_eq!(std::fs::read(&path)?[8], CompressionType::Gzip.marker());
+
+            let (table, _) = tree.load_sstable_with_bloom_filter(&path)?;
+            let stored = &table[b"key".as_slice()].data;
+            assert!(stored.len() < payload.len());
+            let mut decoded = Vec::new();
+            flate2::read::GzDecoder::new(stored.as_slice()).read_to_end(&mut decoded)?;
+            assert_eq!(decoded, payload);
+        }
+
+        let tree = Tree::load_with_settings(settings())?;
+        assert_eq!(tree.get(b"key")?, Some(payload));
+        drop(tree);
+
+        // Values are decompressed with the configured compressor, so a table
+        // recorded with another one is refused instead of failing every read
+        for compressor in [CompressionConfig::balanced(), CompressionConfig::none()] {
+            let reopened = Tree::load_with_settings(TreeSettingsBuilder::new().compressor(compressor).build());
+            assert!(matches!(reopened, Err(TreeError::Configuration { .. })));
+        }
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_bincode_encoding_shortcuts() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            tree.put_typed("standard", &300u32)?;
+        }
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .bincode_fixed_int()
+            .bincode_big_endian()
+            .build())?;
+        tree.put_typed("fixed", &300u32)?;
+
+        assert_eq!(tree.get(b"fixed")?, Some(vec![0, 0, 1, 44]));
+        assert_eq!(tree.get_typed::<u32>("fixed")?, Some(300));
+        assert_eq!(
+            tree.get_typed_with_encoding::<u32>("standard", TypedEncoding::STANDARD)?,
+            Some(300)
+        );
+
+        let small = TypedEncoding { big_endian: true, fixed_int: true }.encode(&2u64)?;
+        let large = TypedEncoding { big_endian: true, fixed_int: true }.encode(&256u64)?;
+        assert!(small < large);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_failed_encode_is_a_recoverable_error() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let tree = Mutex::new(Tree::load_with_settings(TreeSettingsBuilder::new().build())?);
+        let result = tree.lock().unwrap().put_typed("broken", &Unencodable);
+        assert!(matches!(result, Err(TreeError::Serialization { .. })));
+        assert!(!tree.is_poisoned());
+
+        let mut tree = tree.into_inner().unwrap();
+        tree.put_typed::<u64>("fine", &7)?;
+        tree.flush()?;
+        assert_eq!(tree.get_typed::<u64>("fine")?, Some(7));
+        assert_eq!(tree.get(b"broken")?, None);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn test_compression_with_large_objects() -> TreeResult<()> {
@@ -906,6 +4270,382 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn test_load_and_migrate_legacy_sstable() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        std::fs::create_dir_all(&db_path)?;
+        let entries = vec![
+            (b"legacy_a".to_vec(), b"value_a".to_vec()),
+            (b"legacy_b".to_vec(), b"value_b".to_vec()),
+        ];
+        write_sstable_with_version(&db_path.join("sstable_0.sst"), 1, &entries);
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            assert_eq!(tree.get(b"legacy_a")?, Some(b"value_a".to_vec()));
+            assert_eq!(tree.get(b"legacy_b")?, Some(b"value_b".to_vec()));
+
+            tree.migrate()?;
+
+            let header = std::fs::read(db_path.join("sstable_0.sst"))?;
+            assert_eq!(u32::from_le_bytes(header[4..8].try_into().unwrap()), CURRENT_VERSION);
+            assert_eq!(tree.get(b"legacy_a")?, Some(b"value_a".to_vec()));
+            assert_eq!(tree.get(b"legacy_b")?, Some(b"value_b".to_vec()));
+        }
+
+        let reloaded = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(reloaded.get(b"legacy_b")?, Some(b"value_b".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_quarantines_duplicate_sstable_numbers() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        std::fs::create_dir_all(&db_path)?;
+        let complete = vec![
+            (b"key_a".to_vec(), b"complete".to_vec()),
+            (b"key_b".to_vec(), b"complete".to_vec()),
+            (b"key_c".to_vec(), b"complete".to_vec()),
+        ];
+        let partial = vec![(b"key_a".to_vec(), b"partial".to_vec())];
+        write_sstable_with_version(&db_path.join("sstable_0.sst"), 1, &partial);
+        write_sstable_with_version(&db_path.join("sstable_00.sst"), 1, &complete);
+        write_sstable_with_version(&db_path.join("sstable_1.sst"), 1, &partial);
+        write_sstable_with_version(&db_path.join("sstable_01.sst"), 1, &partial);
+
+        let tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(tree.ss_tables, vec![
+            db_path.join("sstable_00.sst"),
+            db_path.join("sstable_01.sst"),
+        ]);
+        assert!(db_path.join("sstable_0.sst.dup").exists());
+        assert!(db_path.join("sstable_1.sst.dup").exists());
+        assert_eq!(tree.get(b"key_b")?, Some(b"complete".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_strict_directory_rejects_unknown_files() -> TreeResult<()> {
+        clean_temp_dir();
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            tree.put(b"key".to_vec(), b"value".to_vec())?;
+            tree.flush()?;
+        }
+        std::fs::write(PathBuf::from(DEFAULT_DB_PATH).join(".DS_Store"), b"")?;
+
+        let strict = || TreeSettingsBuilder::new().strict_directory(true);
+        assert!(Tree::load_with_settings(strict().strict_directory_ignore([".DS_Store"]).build()).is_ok());
+
+        std::fs::write(PathBuf::from(DEFAULT_DB_PATH).join("other_db.sqlite"), b"")?;
+        match Tree::load_with_settings(strict().strict_directory_ignore([".DS_Store"]).build()) {
+            Err(TreeError::Configuration { message }) => assert!(message.contains("other_db.sqlite")),
+            Err(e) => panic!("Expected configuration error, got {}", e),
+            Ok(_) => panic!("Expected configuration error"),
+        }
+
+        let lenient = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+        assert_eq!(lenient.get(b"key")?, Some(b"value".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_open_existing_requires_database_files() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let assert_configuration_error = |result: TreeResult<Tree>| match result {
+            Err(TreeError::Configuration { .. }) => {}
+            Err(e) => panic!("Expected configuration error, got {}", e),
+            Ok(_) => panic!("Expected configuration error"),
+        };
+
+        let missing_path = "./missing_db";
+        assert_configuration_error(Tree::open_existing(missing_path));
+        assert!(!PathBuf::from(missing_path).exists());
+
+        let empty_path = "./empty_db";
+        std::fs::create_dir_all(empty_path)?;
+        std::fs::write(PathBuf::from(empty_path).join("notes.txt"), b"")?;
+        assert_configuration_error(Tree::open_existing(empty_path));
+
+        let file_path = PathBuf::from(empty_path).join("notes.txt");
+        assert_configuration_error(Tree::open_existing(file_path.to_str().unwrap()));
+        assert_configuration_error(Tree::load_with_path(file_path.to_str().unwrap()));
+        assert_configuration_error(Tree::load_with_settings(
+            TreeSettingsBuilder::new().db_path(&file_path).build(),
+        ));
+        assert!(file_path.is_file());
+        std::fs::remove_dir_all(empty_path)?;
+
+        {
+            let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+            tree.put(b"key".to_vec(), b"value".to_vec())?;
+            tree.flush()?;
+        }
+        let tree = Tree::open_existing(DEFAULT_DB_PATH)?;
+        assert_eq!(tree.get(b"key")?, Some(b"value".to_vec()));
+        drop(tree);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_unwritable_database_path_fails_at_construction() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let blocker = PathBuf::from("./db_blocker");
+        std::fs::write(&blocker, b"")?;
+        let db_path = blocker.join("db");
+        for result in [
+            Tree::new_with_settings(TreeSettingsBuilder::new().db_path(&db_path).build()),
+            Tree::load_with_settings(TreeSettingsBuilder::new().db_path(&db_path).build()),
+        ] {
+            match result {
+                Err(TreeError::Configuration { message }) => assert!(message.contains("not writable")),
+                Err(e) => panic!("Expected configuration error, got {}", e),
+                Ok(_) => panic!("Expected configuration error"),
+            }
+        }
+        let unchecked = Tree::new_with_settings(TreeSettingsBuilder::new().db_path(&db_path).check_writable(false).build());
+        assert!(unchecked.is_ok());
+        drop(unchecked);
+        std::fs::remove_file(&blocker)?;
+
+        let settings = TreeSettingsBuilder::new().strict_directory(true).build();
+        settings.validate_path_writable()?;
+        let mut tree = Tree::load_with_settings(settings)?;
+        tree.put(b"key".to_vec(), b"value".to_vec())?;
+        assert!(std::fs::read_dir(DEFAULT_DB_PATH)?.flatten().all(|entry| entry.file_name() != ".write_probe"));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_rejects_unsupported_sstable_version() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let db_path = PathBuf::from(DEFAULT_DB_PATH);
+        std::fs::create_dir_all(&db_path)?;
+        let entries = vec![(b"future".to_vec(), b"value".to_vec())];
+        write_sstable_with_version(&db_path.join("sstable_0.sst"), CURRENT_VERSION + 1, &entries);
+
+        let result = Tree::load_with_settings(TreeSettingsBuilder::new().build());
+        assert!(matches!(result, Err(TreeError::IncompatibleVersion { .. })));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_multi_get_with_mostly_missing_keys() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .mem_table_max_size(100)
+            .build())?;
+
+        for i in 0..250 {
+            tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", i).into_bytes())?;
+        }
+
+        let keys: Vec<Vec<u8>> = vec![
+            b"key_5".to_vec(),
+            b"missing_1".to_vec(),
+            b"key_240".to_vec(),
+            b"missing_2".to_vec(),
+        ];
+        let results = tree.multi_get(keys.iter().map(|k| k.as_slice()).collect())?;
+        assert_eq!(results, vec![
+            Some(b"value_5".to_vec()),
+            None,
+            Some(b"value_240".to_vec()),
+            None,
+        ]);
+
+        tree.put_typed("typed_key", &"typed".to_string())?;
+        let typed: Vec<Option<String>> = tree.multi_get_typed(vec!["typed_key", "missing_typed"])?;
+        assert_eq!(typed, vec![Some("typed".to_string()), None]);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_compact_range_only_touches_overlapping_sstables() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().build())?;
+
+        for i in 0..10 {
+            tree.put(format!("a_{}", i).into_bytes(), b"old".to_vec())?;
+        }
+        tree.flush()?;
+
+        for i in 0..10 {
+            tree.put(format!("b_{}", i).into_bytes(), b"new".to_vec())?;
+        }
+        for i in 0..5 {
+            tree.delete(format!("b_{}", i).as_bytes())?;
+        }
+        tree.flush()?;
+        assert_eq!(tree.ss_tables.len(), 2);
+
+        let untouched = tree.ss_tables[0].clone();
+        let untouched_modified = std::fs::metadata(&untouched)?.modified()?;
+
+        tree.compact_range(b"b", b"c")?;
+
+        assert_eq!(tree.ss_tables.len(), 2);
+        assert_eq!(tree.ss_tables[0], untouched);
+        assert_eq!(std::fs::metadata(&untouched)?.modified()?, untouched_modified);
+
+        let (compacted, _) = tree.load_sstable_with_bloom_filter(&tree.ss_tables[1].clone())?;
+        assert_eq!(compacted.len(), 5);
+        assert!(compacted.values().all(|v| !v.is_tombstone()));
+
+        for i in 0..10 {
+            assert_eq!(tree.get(format!("a_{}", i).as_bytes())?, Some(b"old".to_vec()));
+        }
+        for i in 0..5 {
+            assert_eq!(tree.get(format!("b_{}", i).as_bytes())?, None);
+        }
+        for i in 5..10 {
+            assert_eq!(tree.get(format!("b_{}", i).as_bytes())?, Some(b"new".to_vec()));
+        }
+
+        tree.compact_range(b"x", b"y")?;
+        tree.compact_range(b"c", b"a")?;
+        assert_eq!(tree.ss_tables.len(), 2);
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_lifecycle_events() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let (sender, receiver) = mpsc::sync_channel(64);
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .event_subscriber(sender)
+            .wal_rotate_on_flush(true)
+            .build())?;
+        assert_eq!(receiver.try_recv().ok(), Some(TreeEvent::Recovered { entries: 0 }));
+
+        for round in 0..3 {
+            for i in 0..10 {
+                tree.put(format!("key_{}", i).into_bytes(), format!("value_{}", round).into_bytes())?;
+            }
+            tree.flush()?;
+        }
+
+        let events: Vec<TreeEvent> = receiver.try_iter().collect();
+        let flushed = events.iter()
+            .filter(|e| matches!(e, TreeEvent::Flushed { entries: 10, .. }))
+            .count();
+        assert_eq!(flushed, 3);
+        assert!(events.iter().any(|e| matches!(e, TreeEvent::WalRotated { .. })));
+        match events.last() {
+            Some(TreeEvent::Merged { inputs, output, dropped }) => {
+                assert_eq!(inputs.len(), 3);
+                assert_eq!(*dropped, 20);
+                assert_eq!(tree.ss_tables, vec![output.clone()]);
+            }
+            other => panic!("Expected merge event, got {:?}", other),
+        }
+
+        drop(receiver);
+        tree.put(b"after_drop".to_vec(), b"value".to_vec())?;
+        tree.flush()?;
+
+        let receiver = tree.subscribe(1);
+        tree.put(b"bounded".to_vec(), b"value".to_vec())?;
+        tree.flush()?;
+        assert!(matches!(receiver.try_recv().ok(), Some(TreeEvent::Flushed { entries: 1, .. })));
+        assert!(receiver.try_recv().is_err());
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_flush_on_interval() -> TreeResult<()> {
+        clean_temp_dir();
+
+        let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new()
+            .flush_interval(Duration::from_millis(50))
+            .build())?;
+
+        tree.put(b"interval_key".to_vec(), b"interval_value".to_vec())?;
+        assert!(!tree.flush_if_due()?);
+        assert!(tree.ss_tables.is_empty());
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(tree.flush_if_due()?);
+        assert!(tree.mem_table.is_empty());
+        assert_eq!(tree.ss_tables.len(), 1);
+        assert_eq!(tree.get(b"interval_key")?, Some(b"interval_value".to_vec()));
+
+        clean_temp_dir();
+        Ok(())
+    }
+
+    /// Writes an SSTable using the version 1 layout: no entry checksums,
+    /// no bloom filter and a 16-byte footer.
+    fn write_sstable_with_version(path: &PathBuf, version: u32, entries: &[(Vec<u8>, Vec<u8>)]) {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"SSTB");
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+
+        let mut index = Vec::new();
+        for (key, value) in entries {
+            index.push((key.clone(), bytes.len() as u64));
+            let value_bytes =
+                bincode::encode_to_vec(DataValue::new(value.clone(), None), BINCODE_CONFIG).unwrap();
+            bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(key);
+            bytes.extend_from_slice(&(value_bytes.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&value_bytes);
+        }
+
+        let index_offset = bytes.len() as u64;
+        bytes.extend_from_slice(&(index.len() as u32).to_le_bytes());
+        for (key, offset) in &index {
+            bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(key);
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&index_offset.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 4]);
+        bytes.extend_from_slice(b"FTTB");
+        std::fs::write(path, bytes).unwrap();
+    }
+
     fn generate_random_string(length: usize) -> String {
         use rand::Rng;
         let mut rng = rand::rng();
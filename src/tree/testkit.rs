@@ -0,0 +1,74 @@
+//! Helpers for downstream crash-consistency tests, enabled by the `testkit` feature.
+//!
+//! A typical integration test writes through a tree built with the settings under
+//! test, simulates a crash and checks what the reopened tree still holds:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use redish::tree::{Tree, TreeSettingsBuilder};
+//!
+//! let mut tree = Tree::load_with_settings(TreeSettingsBuilder::new().db_path("./crash_db").build())?;
+//! tree.put(b"key".to_vec(), b"value".to_vec())?;
+//!
+//! let mut recovered = tree.crash_and_reopen()?;
+//! assert_eq!(recovered.get(b"key")?, Some(b"value".to_vec()));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tree::tree_error::TreeResult;
+use crate::tree::TreeSettings;
+use crate::Tree;
+
+impl Tree {
+    /// Simulates an abrupt termination of the process owning the tree.
+    ///
+    /// The tree is dropped without flushing: memory tables are discarded, no
+    /// SSTable is written and no checkpoint is appended to the WAL. WAL segments
+    /// are left on disk exactly as the last write left them.
+    ///
+    /// This models a process crash, not a power loss. Every write acknowledged
+    /// with WAL logging enabled survives it, because each WAL entry is handed to
+    /// the operating system before the write returns. Writes made with
+    /// `WriteOptions { log_to_wal: false, .. }` or with the WAL disabled are lost.
+    /// Data that was written but not synced is not discarded, so durability
+    /// against power loss still depends on `Tree::sync` or `WriteOptions::sync`.
+    pub fn simulate_crash(mut self) {
+        self.wal_writer = None;
+        self.flush_worker = None;
+        self.flush_in_flight = false;
+        self.mem_table.clear();
+        self.mem_table_bytes = 0;
+        self.immutable_mem_tables.clear();
+    }
+
+    /// Simulates a crash and reopens the database with the same settings.
+    ///
+    /// # Returns
+    /// The tree recovered from the SSTables and WAL segments left on disk
+    ///
+    /// # Errors
+    /// Returns `TreeError` if loading or WAL recovery fails
+    pub fn crash_and_reopen(self) -> TreeResult<Tree> {
+        let settings = self.settings.clone();
+        Self::reopen_after_crash(self, settings)
+    }
+
+    /// Simulates a crash and reopens the database with different settings.
+    ///
+    /// Useful to check that data written under one configuration is recovered
+    /// under another, for example after changing compression.
+    ///
+    /// # Arguments
+    /// * `settings` - Settings for the reopened tree
+    ///
+    /// # Returns
+    /// The tree recovered from the SSTables and WAL segments left on disk
+    ///
+    /// # Errors
+    /// Returns `TreeError` if loading or WAL recovery fails
+    pub fn reopen_after_crash(self, settings: TreeSettings) -> TreeResult<Tree> {
+        self.simulate_crash();
+        Tree::load_with_settings(settings)
+    }
+}
@@ -1,6 +1,6 @@
 use crate::tree::tree_error::{TreeError, TreeResult};
 use crate::{DataValue, Tree};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::{Duration, SystemTime};
 
 /// Represents the current state of a database transaction.
@@ -18,13 +18,79 @@ pub struct VersionStamp {
     pub timestamp: SystemTime,
 }
 
+/// A key range read by a serializable transaction.
+///
+/// `version` is the global version when the range was read; a committed
+/// write to any key in `[start, end)` with a newer version conflicts.
+#[derive(Debug, Clone)]
+pub struct ReadRange {
+    pub start: Vec<u8>,
+    pub end: Vec<u8>,
+    pub version: u64,
+}
+
 /// Complete context and state information for a database transaction.
 #[derive(Debug, Clone)]
 pub struct TransactionContext {
     pub read_set: HashMap<Vec<u8>, VersionStamp>,
+    pub read_ranges: Vec<ReadRange>,
     pub write_set: HashMap<Vec<u8>, DataValue>,
     pub validation_set: HashSet<Vec<u8>>,
     pub status: TransactionStatus,
+    pub pessimistic: bool,
+    pub isolation: IsolationLevel,
+}
+
+/// How much of a transaction's reads are checked for conflicts at commit.
+///
+/// # Variants
+/// - `Snapshot`: Only the keys the transaction read are checked, so a key
+///   inserted by another transaction into a range it scanned goes unnoticed
+/// - `Serializable`: The ranges read with `Tree::scan_tx` are checked too, and
+///   any write committed into one of them since the scan fails validation.
+///   This rules out phantoms at the cost of comparing every versioned key
+///   against every scanned range at commit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsolationLevel {
+    #[default]
+    Snapshot,
+    Serializable,
+}
+
+/// Options for a transaction started with `Tree::begin_transaction_with`.
+///
+/// # Fields
+/// - `pessimistic`: Lock every key the transaction reads or writes until it
+///   commits or rolls back, instead of detecting conflicts at commit
+/// - `isolation`: Whether ranges read with `Tree::scan_tx` are validated
+///   along with single keys
+///
+/// Locking is no-wait: a `get_tx` or `put_tx` on a key locked by another
+/// transaction fails immediately with `TreeError::Transaction` rather than
+/// blocking, so two transactions can never wait on each other. The caller
+/// decides whether to retry or roll back. Optimistic transactions that wrote a
+/// key locked by a pessimistic one fail validation at commit.
+///
+/// The default is an optimistic snapshot transaction, matching
+/// `Tree::begin_transaction`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxOptions {
+    pub pessimistic: bool,
+    pub isolation: IsolationLevel,
+}
+
+/// Counters of finished transactions, for monitoring optimistic concurrency contention.
+///
+/// # Fields
+/// - `commits`: Transactions committed successfully
+/// - `rollbacks`: Transactions rolled back with `Tree::rollback_transaction`
+/// - `validation_failures`: Commits aborted because a key they read was written
+///   by another transaction in the meantime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransactionStats {
+    pub commits: u64,
+    pub rollbacks: u64,
+    pub validation_failures: u64,
 }
 
 impl Tree {
@@ -38,8 +104,26 @@ impl Tree {
     /// - `Ok(u64)` - The unique transaction ID
     /// - `Err(TreeError)` - If the transaction cannot be created
     pub fn begin_transaction(&mut self) -> TreeResult<u64> {
+        self.begin_transaction_with(TxOptions::default())
+    }
+
+    /// Begins a new transaction with the given options.
+    ///
+    /// With `pessimistic: true` the transaction locks each key on its first
+    /// `get_tx` or `put_tx` and holds the locks until it commits or rolls
+    /// back, so hot keys are not lost to validation failures at commit. With
+    /// `IsolationLevel::Serializable` a commit also fails if another write
+    /// landed in a range the transaction read with `scan_tx`.
+    ///
+    /// # Arguments
+    /// - `options` - The locking mode and isolation level of the transaction
+    ///
+    /// # Returns
+    /// - `Ok(u64)` - The unique transaction ID
+    /// - `Err(TreeError)` - If the transaction cannot be created
+    pub fn begin_transaction_with(&mut self, options: TxOptions) -> TreeResult<u64> {
         let tx_manager = self.tx_manager.lock().unwrap();
-        tx_manager.begin_transaction()
+        tx_manager.begin_transaction(options)
     }
 
     /// Retrieves a value from the tree within the context of a transaction.
@@ -55,10 +139,12 @@ impl Tree {
     /// # Returns
     /// - `Ok(Some(Vec<u8>))` - The value if found and not expired
     /// - `Ok(None)` - If the key doesn't exist or the value has expired
-    /// - `Err(TreeError)` - If the transaction is invalid or a read error occurs
+    /// - `Err(TreeError)` - If the transaction is invalid, the key is locked by
+    ///   another transaction or a read error occurs
     pub fn get_tx(&mut self, tx_id: u64, key: &[u8]) -> TreeResult<Option<Vec<u8>>> {
         let local_value = {
             let tx_manager = self.tx_manager.lock().unwrap();
+            tx_manager.lock_key(tx_id, key)?;
             let active_txs = tx_manager.active_transactions.read().unwrap();
 
             if let Some(tx_context) = active_txs.get(&tx_id) {
@@ -105,6 +191,81 @@ impl Tree {
         Ok(result)
     }
 
+    /// Returns the live entries with keys in `[start, end)` as seen by a transaction.
+    ///
+    /// The transaction's own uncommitted writes in the range take precedence
+    /// over committed values. Every committed key returned is added to the read
+    /// set like a `get_tx`. Under `IsolationLevel::Serializable` the range itself
+    /// is recorded as well, so a key inserted into it and committed before this
+    /// transaction commits fails validation. Under `Snapshot` such phantom
+    /// inserts are not detected.
+    ///
+    /// # Arguments
+    /// - `tx_id` - The transaction ID
+    /// - `start` - Inclusive lower bound of the keys
+    /// - `end` - Exclusive upper bound of the keys
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(Vec<u8>, Vec<u8>)>)` - The entries in ascending key order
+    /// - `Err(TreeError)` - If the transaction is invalid, a returned key is
+    ///   locked by another transaction or a read error occurs
+    pub fn scan_tx(&mut self, tx_id: u64, start: &[u8], end: &[u8]) -> TreeResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let (local_values, read_version) = {
+            let tx_manager = self.tx_manager.lock().unwrap();
+            let active_txs = tx_manager.active_transactions.read().unwrap();
+            let tx_context = active_txs
+                .get(&tx_id)
+                .ok_or_else(|| TreeError::transaction("Transaction not found"))?;
+            let local_values: Vec<(Vec<u8>, DataValue)> = tx_context
+                .write_set
+                .iter()
+                .filter(|(key, _)| key.as_slice() >= start && key.as_slice() < end)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            // Taken before the scan, so a write racing the scan can only cause
+            // a spurious conflict, never a missed one
+            let read_version = *tx_manager.global_version.lock().unwrap();
+            (local_values, read_version)
+        };
+
+        let committed: Vec<(Vec<u8>, Vec<u8>)> = self.iter_range(start, end)?.collect::<TreeResult<_>>()?;
+
+        let tx_manager = self.tx_manager.lock().unwrap();
+        for (key, _) in &committed {
+            tx_manager.lock_key(tx_id, key)?;
+        }
+        let mut active_txs = tx_manager.active_transactions.write().unwrap();
+        let tx_context = active_txs
+            .get_mut(&tx_id)
+            .ok_or_else(|| TreeError::transaction("Transaction not found"))?;
+        let key_versions = tx_manager.key_versions.read().unwrap();
+        for (key, _) in &committed {
+            tx_context.validation_set.insert(key.clone());
+            let version_stamp = key_versions.get(key).cloned().unwrap_or(VersionStamp {
+                version: 0,
+                timestamp: SystemTime::UNIX_EPOCH,
+            });
+            tx_context.read_set.entry(key.clone()).or_insert(version_stamp);
+        }
+        if tx_context.isolation == IsolationLevel::Serializable {
+            tx_context.read_ranges.push(ReadRange {
+                start: start.to_vec(),
+                end: end.to_vec(),
+                version: read_version,
+            });
+        }
+
+        let mut entries: BTreeMap<Vec<u8>, Vec<u8>> = committed.into_iter().collect();
+        for (key, value) in local_values {
+            if value.is_expired() {
+                entries.remove(&key);
+            } else {
+                entries.insert(key, value.data);
+            }
+        }
+        Ok(entries.into_iter().collect())
+    }
+
     /// Stores a key-value pair within the context of a transaction.
     ///
     /// This method adds the key-value pair to the transaction's local write set
@@ -119,7 +280,9 @@ impl Tree {
     ///
     /// # Returns
     /// - `Ok(())` - If the operation succeeds
-    /// - `Err(TreeError)` - If the transaction is invalid or a write error occurs
+    /// - `Err(TreeError)` - If the transaction is invalid, the key is locked by
+    ///   another transaction, the TTL exceeds `max_ttl` under
+    ///   `TtlLimitPolicy::Reject` or a write error occurs
     pub fn put_tx(
         &mut self,
         tx_id: u64,
@@ -127,6 +290,7 @@ impl Tree {
         value: Vec<u8>,
         ttl: Option<Duration>,
     ) -> TreeResult<()> {
+        let ttl = self.resolve_ttl(ttl)?;
         let data_value = DataValue::new(value, ttl);
         let tx_manager = self.tx_manager.lock().unwrap();
         tx_manager.write_transaction(tx_id, key, data_value)
@@ -136,6 +300,7 @@ impl Tree {
     ///
     /// This method applies all changes from the transaction's write set to the main tree storage.
     /// It handles TTL expiration during commit and ensures that expired values are not persisted.
+    /// Every value written by the commit records `tx_id` as its `transaction_id`.
     /// The transaction is marked as committed and then removed from the active transactions list.
     ///
     /// # Arguments
@@ -149,7 +314,7 @@ impl Tree {
             let tx_manager = self.tx_manager.lock().unwrap();
             let validation_result = tx_manager.validate_transaction(tx_id)?;
             if !validation_result {
-                tx_manager.rollback_transaction(tx_id)?;
+                tx_manager.abort_transaction(tx_id)?;
                 return Err(TreeError::transaction("Transaction validation failed - conflicts detected"));
             }
 
@@ -168,11 +333,11 @@ impl Tree {
 
             match value.expires_at {
                 None => {
-                    self.put(key, value.data)?;
+                    self.put_committed(key, value.data, None, tx_id)?;
                 }
                 Some(expiry) => match expiry.duration_since(SystemTime::now()) {
                     Ok(remaining_ttl) => {
-                        self.put_to_tree(key, value.data, Some(remaining_ttl))?;
+                        self.put_committed(key, value.data, Some(remaining_ttl), tx_id)?;
                     }
                     Err(_) => {
                         continue;
@@ -206,4 +371,110 @@ impl Tree {
         let tx_manager = self.tx_manager.lock().unwrap();
         tx_manager.rollback_transaction(tx_id)
     }
+
+    /// Returns the number of committed, rolled back and validation-failed transactions.
+    ///
+    /// A high share of validation failures means transactions keep conflicting
+    /// on the same keys and are retried often.
+    ///
+    /// # Returns
+    /// A `TransactionStats` snapshot of the counters since the tree was created
+    pub fn transaction_stats(&self) -> TransactionStats {
+        let tx_manager = self.tx_manager.lock().unwrap();
+        tx_manager.stats()
+    }
+
+    /// Checks whether a transaction would pass validation if committed now.
+    ///
+    /// Runs the same checks as `commit_transaction` without changing any state:
+    /// the transaction stays active and nothing is written. A later write to a
+    /// key the transaction read can still make the real commit fail, so a
+    /// `true` result is a hint for deciding whether to proceed, not a guarantee.
+    ///
+    /// # Arguments
+    /// - `tx_id` - The transaction ID to check
+    ///
+    /// # Returns
+    /// - `Ok(true)` - If no key the transaction read was written since and none
+    ///   of its writes is locked by another transaction
+    /// - `Ok(false)` - If committing now would fail validation
+    /// - `Err(TreeError)` - If the transaction is not found
+    pub fn would_commit_succeed(&self, tx_id: u64) -> TreeResult<bool> {
+        let tx_manager = self.tx_manager.lock().unwrap();
+        tx_manager.validate_transaction(tx_id)
+    }
+
+    /// Returns the number of keys a transaction has read and written so far.
+    ///
+    /// Reads served from the transaction's own writes and reads of keys that
+    /// did not exist are not part of the read set.
+    ///
+    /// # Arguments
+    /// - `tx_id` - The transaction ID
+    ///
+    /// # Returns
+    /// `Some((read_set_size, write_set_size))`, or `None` if the transaction is
+    /// not active
+    pub fn transaction_footprint(&self, tx_id: u64) -> Option<(usize, usize)> {
+        let tx_manager = self.tx_manager.lock().unwrap();
+        let active_txs = tx_manager.active_transactions.read().unwrap();
+        active_txs
+            .get(&tx_id)
+            .map(|tx_context| (tx_context.read_set.len(), tx_context.write_set.len()))
+    }
+
+    /// Returns the current version of a key, for use with `put_if_version`.
+    ///
+    /// Versions are drawn from the counter used for transaction validation and
+    /// increase with every committed transactional write and every `put`,
+    /// `delete`, `delete_prefix`, `rename_key` or `update_ttl` made outside a
+    /// transaction. They are kept in memory only: a key not written since the
+    /// tree was opened has version 0.
+    ///
+    /// # Arguments
+    /// - `key` - The key to look up
+    pub fn key_version(&self, key: &[u8]) -> u64 {
+        let tx_manager = self.tx_manager.lock().unwrap();
+        tx_manager.key_version(key)
+    }
+
+    /// Stores a value only if the key's current version equals `expected_version`.
+    ///
+    /// An optimistic compare-and-set without opening a transaction: read the
+    /// version with `key_version`, compute the new value and write it back with
+    /// this method; `false` means another write got in between and the caller
+    /// should re-read and retry.
+    ///
+    /// The compare and the write happen in one call on `&mut self`, so no other
+    /// write can get in between. The version is bumped only once the value is in
+    /// the WAL and the memory table; a write that fails leaves it unchanged, and
+    /// the same `expected_version` can be retried.
+    ///
+    /// # Interaction with transactions
+    /// Transactional and non-transactional writes share one version counter. A
+    /// committed transaction bumps the version of every key it wrote, so it fails
+    /// a later `put_if_version` that expected the old version. In the other
+    /// direction, any non-transactional write, including a successful
+    /// `put_if_version`, bumps the version of the keys it writes, so a
+    /// transaction that read such a key before the write fails validation at
+    /// commit. Keys locked by a pessimistic transaction are not checked;
+    /// non-transactional writes never take or wait for transaction locks.
+    /// Versions restart at 0 when the tree is reopened.
+    ///
+    /// # Arguments
+    /// - `key` - The key to store
+    /// - `value` - The value to associate with the key
+    /// - `expected_version` - The version the key must have for the write to happen
+    ///
+    /// # Returns
+    /// - `Ok(true)` - If the versions matched and the value was written
+    /// - `Ok(false)` - If the key's version differs and nothing was written
+    /// - `Err(TreeError)` - If the write fails; the key's version is unchanged
+    pub fn put_if_version(&mut self, key: Vec<u8>, value: Vec<u8>, expected_version: u64) -> TreeResult<bool> {
+        if self.key_version(&key) != expected_version {
+            return Ok(false);
+        }
+        self.put(key, value)?;
+        Ok(true)
+    }
 }
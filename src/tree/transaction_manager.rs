@@ -1,4 +1,4 @@
-use crate::tree::transaction::{TransactionContext, TransactionStatus, VersionStamp};
+use crate::tree::transaction::{TransactionContext, TransactionStats, TransactionStatus, TxOptions, VersionStamp};
 use crate::tree::tree_error::{TreeError, TreeResult};
 use crate::DataValue;
 use std::collections::{HashMap, HashSet};
@@ -45,6 +45,15 @@ pub struct TransactionManager {
     /// version number and timestamp. Used for detecting conflicts during
     /// transaction validation.
     pub key_versions: Arc<RwLock<HashMap<Vec<u8>, VersionStamp>>>,
+
+    /// Keys locked by pessimistic transactions, mapped to the holding transaction ID.
+    ///
+    /// Locks are taken on first access and released when the holding
+    /// transaction commits or rolls back.
+    pub key_locks: Arc<Mutex<HashMap<Vec<u8>, u64>>>,
+
+    /// Counters of committed, rolled back and validation-failed transactions.
+    pub stats: Arc<Mutex<TransactionStats>>,
 }
 
 impl TransactionManager {
@@ -54,19 +63,24 @@ impl TransactionManager {
             next_transaction_id: Arc::new(Mutex::new(1)),
             global_version: Arc::new(Mutex::new(1)),
             key_versions: Arc::new(RwLock::new(HashMap::new())),
+            key_locks: Arc::new(Mutex::new(HashMap::new())),
+            stats: Arc::new(Mutex::new(TransactionStats::default())),
         }
     }
 
-    pub(crate) fn begin_transaction(&self) -> TreeResult<u64> {
+    pub(crate) fn begin_transaction(&self, options: TxOptions) -> TreeResult<u64> {
         let mut next_id = self.next_transaction_id.lock().unwrap();
         let tx_id = *next_id;
         *next_id += 1;
 
         let tx_context = TransactionContext {
             read_set: HashMap::new(),
+            read_ranges: Vec::new(),
             write_set: HashMap::new(),
             validation_set: HashSet::new(),
             status: TransactionStatus::Active,
+            pessimistic: options.pessimistic,
+            isolation: options.isolation,
         };
 
         let mut active_txs = self.active_transactions.write().unwrap();
@@ -76,6 +90,7 @@ impl TransactionManager {
     }
 
     pub(crate) fn write_transaction(&self, tx_id: u64, key: Vec<u8>, value: DataValue) -> TreeResult<()> {
+        self.lock_key(tx_id, &key)?;
         let mut active_txs = self.active_transactions.write().unwrap();
         let tx_context = active_txs
             .get_mut(&tx_id)
@@ -87,14 +102,63 @@ impl TransactionManager {
         Ok(())
     }
 
+    /// Locks a key for a pessimistic transaction; optimistic transactions take no locks.
+    ///
+    /// Fails without waiting when another transaction holds the lock, which
+    /// rules out deadlocks between transactions.
+    pub(crate) fn lock_key(&self, tx_id: u64, key: &[u8]) -> TreeResult<()> {
+        let pessimistic = {
+            let active_txs = self.active_transactions.read().unwrap();
+            active_txs
+                .get(&tx_id)
+                .ok_or_else(|| TreeError::transaction("Transaction not found"))?
+                .pessimistic
+        };
+        if !pessimistic {
+            return Ok(());
+        }
+
+        let mut key_locks = self.key_locks.lock().unwrap();
+        match key_locks.get(key) {
+            Some(&holder) if holder != tx_id => Err(TreeError::transaction(format!(
+                "Key is locked by transaction {}",
+                holder
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                key_locks.insert(key.to_vec(), tx_id);
+                Ok(())
+            }
+        }
+    }
+
+    fn release_locks(&self, tx_id: u64) {
+        self.key_locks.lock().unwrap().retain(|_, holder| *holder != tx_id);
+    }
+
     pub(crate) fn rollback_transaction(&self, tx_id: u64) -> TreeResult<()> {
+        if self.discard_transaction(tx_id) {
+            self.stats.lock().unwrap().rollbacks += 1;
+        }
+        Ok(())
+    }
+
+    /// Aborts a transaction whose validation failed at commit.
+    pub(crate) fn abort_transaction(&self, tx_id: u64) -> TreeResult<()> {
+        if self.discard_transaction(tx_id) {
+            self.stats.lock().unwrap().validation_failures += 1;
+        }
+        Ok(())
+    }
+
+    fn discard_transaction(&self, tx_id: u64) -> bool {
         let mut active_txs = self.active_transactions.write().unwrap();
         if let Some(tx_context) = active_txs.get_mut(&tx_id) {
             tx_context.status = TransactionStatus::Aborted;
             tx_context.write_set.clear();
         }
-        active_txs.remove(&tx_id);
-        Ok(())
+        self.release_locks(tx_id);
+        active_txs.remove(&tx_id).is_some()
     }
 
     pub(crate) fn validate_transaction(&self, tx_id: u64) -> TreeResult<bool> {
@@ -102,6 +166,16 @@ impl TransactionManager {
         let tx_context = active_txs.get(&tx_id)
             .ok_or_else(|| TreeError::transaction("Transaction not found"))?;
 
+        let key_locks = self.key_locks.lock().unwrap();
+        if tx_context
+            .write_set
+            .keys()
+            .any(|key| key_locks.get(key).is_some_and(|holder| *holder != tx_id))
+        {
+            return Ok(false);
+        }
+        drop(key_locks);
+
         let key_versions = self.key_versions.read().unwrap();
 
         for (key, read_version) in &tx_context.read_set {
@@ -115,6 +189,17 @@ impl TransactionManager {
             }
         }
 
+        for read_range in &tx_context.read_ranges {
+            let written_since = key_versions.iter().any(|(key, version_stamp)| {
+                key.as_slice() >= read_range.start.as_slice()
+                    && key.as_slice() < read_range.end.as_slice()
+                    && version_stamp.version > read_range.version
+            });
+            if written_since {
+                return Ok(false);
+            }
+        }
+
         Ok(true)
     }
 
@@ -137,13 +222,73 @@ impl TransactionManager {
         Ok(())
     }
 
+    /// Returns the current version of a key, or 0 if it was not written since the tree was opened.
+    pub(crate) fn key_version(&self, key: &[u8]) -> u64 {
+        self.key_versions
+            .read()
+            .unwrap()
+            .get(key)
+            .map_or(0, |version_stamp| version_stamp.version)
+    }
+
+    /// Assigns a key the next global version for a write made outside a
+    /// transaction and returns that version.
+    pub(crate) fn bump_key_version(&self, key: &[u8]) -> u64 {
+        let mut key_versions = self.key_versions.write().unwrap();
+        let mut global_version = self.global_version.lock().unwrap();
+
+        *global_version += 1;
+        let new_version_stamp = VersionStamp {
+            version: *global_version,
+            timestamp: SystemTime::now(),
+        };
+        key_versions.insert(key.to_vec(), new_version_stamp);
+        *global_version
+    }
+
+    /// Bumps the version of every key starting with `prefix` that is either
+    /// versioned or in the read set of an active transaction.
+    ///
+    /// Keys outside both sets need no version: no transaction could detect a
+    /// conflict on them.
+    pub(crate) fn bump_prefix_versions(&self, prefix: &[u8]) {
+        let mut keys: HashSet<Vec<u8>> = {
+            let active_txs = self.active_transactions.read().unwrap();
+            active_txs
+                .values()
+                .flat_map(|tx_context| tx_context.read_set.keys())
+                .filter(|key| key.starts_with(prefix))
+                .cloned()
+                .collect()
+        };
+
+        let mut key_versions = self.key_versions.write().unwrap();
+        let mut global_version = self.global_version.lock().unwrap();
+        keys.extend(key_versions.keys().filter(|key| key.starts_with(prefix)).cloned());
+
+        for key in keys {
+            *global_version += 1;
+            let new_version_stamp = VersionStamp {
+                version: *global_version,
+                timestamp: SystemTime::now(),
+            };
+            key_versions.insert(key, new_version_stamp);
+        }
+    }
+
     pub(crate) fn finalize_transaction(&self, tx_id: u64) -> TreeResult<()> {
         let mut active_txs = self.active_transactions.write().unwrap();
         if let Some(tx_context) = active_txs.get_mut(&tx_id) {
             tx_context.status = TransactionStatus::Committed;
         }
         active_txs.remove(&tx_id);
+        self.release_locks(tx_id);
+        self.stats.lock().unwrap().commits += 1;
 
         Ok(())
     }
+
+    pub(crate) fn stats(&self) -> TransactionStats {
+        *self.stats.lock().unwrap()
+    }
 }
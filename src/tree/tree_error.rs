@@ -45,6 +45,9 @@ pub enum TreeError {
 
     #[error("SystemTime error: {message}")]
     SystemTimeError { message: String },
+
+    #[error("Incompatible format version: {message}")]
+    IncompatibleVersion { message: String },
 }
 
 impl TreeError {
@@ -125,6 +128,12 @@ impl TreeError {
             message: message.to_string(),
         }
     }
+
+    pub fn incompatible_version<T: std::fmt::Display>(message: T) -> Self {
+        Self::IncompatibleVersion {
+            message: message.to_string(),
+        }
+    }
 }
 
 impl From<bincode::error::EncodeError> for TreeError {
@@ -1,15 +1,22 @@
+use crate::tree::manifest::{Manifest, WalBoundary};
+use crate::tree::range_tombstone::RangeTombstone;
 use crate::tree::tree_error::{TreeError, TreeResult};
 use crate::tree::wal_reader::WalReader;
 use crate::tree::wal_writer::WalWriter;
-use crate::{DataValue, Tree};
-use log::{debug, error, info};
-use std::path::PathBuf;
-use std::sync::mpsc;
+use crate::tree::TreeEvent;
+use crate::{inspect, DataValue, Tree};
+use log::{debug, error, info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
 
 pub(crate) enum WalOperation {
     Checkpoint = 1,
     Put = 2,
     Delete = 3,
+    DeletePrefix = 4,
+    /// Moves a value between keys. The key field holds both keys, see
+    /// `encode_rename_keys`, so one entry covers both halves of the move.
+    Rename = 5,
 }
 
 impl WalOperation {
@@ -18,10 +25,46 @@ impl WalOperation {
             WalOperation::Checkpoint => 1,
             WalOperation::Put => 2,
             WalOperation::Delete => 3,
+            WalOperation::DeletePrefix => 4,
+            WalOperation::Rename => 5,
         }
     }
 }
 
+/// Packs the keys of a rename into the key field of one WAL entry:
+/// the length of `from` (u32 LE), `from`, then `to`.
+pub(crate) fn encode_rename_keys(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut keys = Vec::with_capacity(4 + from.len() + to.len());
+    keys.extend_from_slice(&(from.len() as u32).to_le_bytes());
+    keys.extend_from_slice(from);
+    keys.extend_from_slice(to);
+    keys
+}
+
+fn decode_rename_keys(keys: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let from_len = u32::from_le_bytes(keys.get(0..4)?.try_into().ok()?) as usize;
+    let from = keys.get(4..4 + from_len)?;
+    Some((from.to_vec(), keys[4 + from_len..].to_vec()))
+}
+
+/// Cuts a record torn by a crash off the end of a WAL segment, so entries
+/// appended later start at a record boundary and `has_checkpoint_at_end`
+/// looks at a complete record.
+fn truncate_torn_tail(reader: &mut WalReader, path: &Path) -> std::io::Result<()> {
+    let valid_len = reader.valid_len()?;
+    let file = std::fs::OpenOptions::new().write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+    if file_len > valid_len {
+        warn!(
+            "Truncating torn tail of WAL segment {:?} from {} to {} bytes",
+            path, file_len, valid_len
+        );
+        file.set_len(valid_len)?;
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
 impl Tree {
     pub(crate) fn init_wal(&mut self) -> TreeResult<()> {
         if !&self.settings.db_path.exists() {
@@ -29,38 +72,47 @@ impl Tree {
                 .map_err(|e| TreeError::wal(format!("Failed to create DB directory: {}", e)))?;
         }
 
-        let (wal_segment_paths, wal_segments) = self.find_wal_segments()?;
-        self.wal_segments = wal_segments;
+        let wal_segments = self.find_wal_segments()?;
+        self.wal_segments = wal_segments.iter().map(|(num, _)| *num).collect();
+
+        let wal_path = match wal_segments.last() {
+            Some((_, last_path)) => {
+                let has_checkpoint_at_end = match WalReader::open(last_path, self.settings.read_buffer_size)
+                    .and_then(|mut reader| {
+                        truncate_torn_tail(&mut reader, last_path)?;
+                        reader.has_checkpoint_at_end()
+                    }) {
+                    Ok(has_checkpoint_at_end) => has_checkpoint_at_end,
+                    Err(e) => {
+                        warn!("WAL segment {:?} is not readable, starting a new one: {}", last_path, e);
+                        true
+                    }
+                };
 
-        if wal_segment_paths.is_empty() {
-            let segment_num = self.get_next_wal_segment_number();
-            self.add_wal_segment(segment_num);
-            let wal_path = &self.settings.db_path.join(format!("wal_{:04}.log", segment_num));
-            let writer = WalWriter::open(&wal_path)
-                .map_err(|e| TreeError::wal(format!("Failed to initialize WAL: {}", e)))?;
-            self.wal_writer = Some(writer);
+                if has_checkpoint_at_end {
+                    let next_segment_num = self.get_next_wal_segment_number();
+                    self.add_wal_segment(next_segment_num);
+                    self.wal_segment_path(next_segment_num)
+                } else {
+                    last_path.clone()
+                }
+            }
+            None => {
+                let segment_num = self.get_next_wal_segment_number();
+                self.add_wal_segment(segment_num);
+                self.wal_segment_path(segment_num)
+            }
+        };
 
-            Ok(())
-        } else {
-            let segment_num = self.get_last_wal_segment_number();
-            let wal_path = &self.settings.db_path.join(format!("wal_{:04}.log", segment_num));
-            let mut reader = WalReader::open(wal_path)?;
+        let writer = WalWriter::open(&wal_path, self.settings.write_buffer_size)
+            .map_err(|e| TreeError::wal(format!("Failed to initialize WAL: {}", e)))?;
+        self.wal_writer = Some(writer);
 
-            if reader.has_checkpoint_at_end()? {
-                let next_segment_num = self.get_next_wal_segment_number();
-                self.add_wal_segment(next_segment_num);
-                let writer = WalWriter::open(&wal_path)
-                    .map_err(|e| TreeError::wal(format!("Failed to initialize WAL: {}", e)))?;
-                self.wal_writer = Some(writer);
-            } else {
-                let wal_path = &self.settings.db_path.join(format!("wal_{:04}.log", segment_num));
-                let writer = WalWriter::open(&wal_path)
-                    .map_err(|e| TreeError::wal(format!("Failed to initialize WAL: {}", e)))?;
-                self.wal_writer = Some(writer);
-            }
+        Ok(())
+    }
 
-            Ok(())
-        }
+    fn wal_segment_path(&self, segment_num: u16) -> PathBuf {
+        self.settings.db_path.join(format!("wal_{:04}.log", segment_num))
     }
 
     pub(crate) fn write_to_wal(
@@ -69,75 +121,171 @@ impl Tree {
         key: &[u8],
         data_value_opt: Option<&DataValue>,
     ) -> TreeResult<()> {
-        let should_checkpoint = self.should_checkpoint_wal();
+        if self.wal_segment_is_full() {
+            self.rotate_wal_segment()?;
+        }
 
         if let Some(ref mut wal_writer) = self.wal_writer {
-            wal_writer.write_entry(op, &key, data_value_opt)
+            wal_writer.write_entry(op, key, data_value_opt)
                 .map_err(|e| TreeError::wal(format!("Failed to write to WAL: {}", e)))?;
+        }
+
+        Ok(())
+    }
 
-            if should_checkpoint {
-                wal_writer.write_checkpoint()
-                    .map_err(|e| TreeError::wal(format!("Failed to write checkpoint: {}", e)))?;
+    /// Appends a checkpoint marker after a flush, rotating the segment only when
+    /// `wal_rotate_on_flush` is set or a rotation threshold has been reached.
+    ///
+    /// Everything logged before the marker must already be stored in SSTables.
+    pub(crate) fn checkpoint_wal(&mut self) -> TreeResult<()> {
+        if let Some(ref mut wal_writer) = self.wal_writer {
+            wal_writer
+                .write_checkpoint()
+                .map_err(|e| TreeError::wal(format!("Failed to write checkpoint: {}", e)))?;
+
+            if self.settings.wal_rotate_on_flush || self.wal_segment_is_full() {
+                self.rotate_wal_segment()?;
             }
         }
+        Ok(())
+    }
+
+    /// Rotates the WAL segment after a background flush when
+    /// `wal_rotate_on_flush` is set or a rotation threshold has been reached.
+    ///
+    /// Unlike `checkpoint_wal`, no checkpoint is written: entries logged while
+    /// the flush was running are not in SSTables yet. Recovery skips the
+    /// flushed entries through the manifest's WAL boundary instead.
+    pub(crate) fn rotate_wal_after_flush(&mut self) -> TreeResult<()> {
+        if self.wal_writer.is_some() && (self.settings.wal_rotate_on_flush || self.wal_segment_is_full()) {
+            self.rotate_wal_segment()?;
+        }
+        Ok(())
+    }
 
+    /// Records in the manifest that the flush about to write SSTable
+    /// `sstable_number` stores every WAL entry logged so far.
+    ///
+    /// Recovery then skips those entries once the SSTable exists, even when a
+    /// crash prevents the checkpoint that follows the flush.
+    pub(crate) fn record_wal_boundary(&mut self, sstable_number: u64) -> TreeResult<()> {
+        if let Some(ref wal_writer) = self.wal_writer {
+            let boundary = WalBoundary {
+                segment: self.get_last_wal_segment_number(),
+                offset: wal_writer.size(),
+                sstable_number,
+            };
+            self.update_manifest(|manifest| manifest.wal_boundary = Some(boundary))
+                .map_err(|e| TreeError::wal(format!("Failed to write MANIFEST: {}", e)))?;
+        }
         Ok(())
     }
 
-    fn find_wal_segments(&self) -> TreeResult<(Vec<PathBuf>, Vec<u16>)> {
-        let entries = std::fs::read_dir(&self.settings.db_path)
-            .map_err(|e| TreeError::wal(format!("Failed to read DB directory: {}", e)))?;
-        let mut wal_files = Vec::new();
-        let mut wal_files_nums = Vec::new();
-
-        for entry in entries {
-            let entry = entry.map_err(|e| TreeError::wal(format!("Failed to read directory entry: {}", e)))?;
-            let path = entry.path();
-
-            if path.is_file() {
-                if let Some(filename) = path.clone().file_name().and_then(|n| n.to_str()) {
-                    if filename.starts_with("wal_") && filename.ends_with(".log") {
-                        wal_files.push(path);
-                        let wal_file_num = filename.strip_prefix("wal_").unwrap()
-                            .strip_suffix(".log").unwrap()
-                            .parse::<u16>()
-                            .unwrap_or(0);
-                        wal_files_nums.push(wal_file_num);
-                    }
-                }
+    /// Returns the manifest's WAL boundary if the flush of the SSTable it
+    /// refers to, or of a later one, was installed.
+    ///
+    /// Merged tables take new numbers too, so the number of a loaded table
+    /// says nothing about which flushes finished.
+    fn durable_wal_boundary(&self) -> Option<WalBoundary> {
+        let manifest = match Manifest::read(&self.settings.db_path) {
+            Ok(manifest) => manifest?,
+            Err(e) => {
+                warn!("Ignoring unreadable MANIFEST, recovering from checkpoints only: {}", e);
+                return None;
             }
+        };
+        let boundary = manifest.wal_boundary?;
+        (manifest.last_flushed? >= boundary.sstable_number).then_some(boundary)
+    }
+
+    fn rotate_wal_segment(&mut self) -> TreeResult<()> {
+        self.check_wal_segments_need_to_be_shifted()?;
+        let next_segment = self.get_next_wal_segment_number();
+        self.create_new_wal_segment(next_segment)
+    }
+
+    /// Deletes every WAL segment and starts an empty one.
+    ///
+    /// The new segment takes the next unused number, so a cleanup still pending
+    /// in the background worker cannot remove it.
+    pub(crate) fn reset_wal(&mut self) -> TreeResult<()> {
+        self.wal_writer = None;
+        self.clear_wal_boundary()
+            .map_err(|e| TreeError::wal(format!("Failed to update MANIFEST: {}", e)))?;
+        let next_segment = self.get_next_wal_segment_number();
+        for (_, wal_path) in self.find_wal_segments()? {
+            std::fs::remove_file(&wal_path).map_err(|e| {
+                TreeError::wal(format!("Failed to remove WAL segment {:?}: {}", wal_path, e))
+            })?;
         }
+        self.wal_segments.clear();
+        self.create_new_wal_segment(next_segment)
+    }
 
-        if wal_files.is_empty() {
-            return Ok((wal_files, wal_files_nums));
+    /// Makes every write acknowledged so far durable without flushing the memory table.
+    ///
+    /// Flushes the current WAL segment, syncs its data to disk and syncs the
+    /// database directory so the segment itself survives a crash. Unlike `flush`,
+    /// no SSTable is written and the memory table is left untouched, which makes
+    /// this a cheap durability barrier between regular flushes.
+    ///
+    /// Does nothing when the WAL is disabled, since writes are then only durable
+    /// once flushed to an SSTable.
+    ///
+    /// # Errors
+    /// Returns `TreeError::Wal` if the segment or directory cannot be synced
+    pub fn sync(&mut self) -> TreeResult<()> {
+        if let Some(ref mut wal_writer) = self.wal_writer {
+            wal_writer
+                .sync()
+                .map_err(|e| TreeError::wal(format!("Failed to sync WAL: {}", e)))?;
         }
+        Ok(())
+    }
 
-        wal_files.sort_by_cached_key(|path| {
-            path.file_name()
-                .and_then(|name| name.to_str())
-                .and_then(|name| {
-                    name.strip_prefix("wal_")?
-                        .strip_suffix(".log")?
-                        .parse::<u16>()
-                        .ok()
-                })
-                .unwrap_or(0)
-        });
-        wal_files_nums.sort();
+    /// Hands buffered WAL entries to the operating system without syncing them.
+    ///
+    /// A lighter checkpoint than `sync`: entries written so far survive a crash
+    /// of the process, but not a power loss or OS crash, and no fsync is paid.
+    /// The WAL writer already hands each entry over as it is written, so this
+    /// is close to free and can be called after every burst of writes.
+    ///
+    /// Does nothing when the WAL is disabled.
+    ///
+    /// # Errors
+    /// Returns `TreeError::Wal` if the buffer cannot be written to the segment
+    pub fn flush_wal_buffer(&mut self) -> TreeResult<()> {
+        if let Some(ref mut wal_writer) = self.wal_writer {
+            wal_writer
+                .flush_buffer()
+                .map_err(|e| TreeError::wal(format!("Failed to flush WAL buffer: {}", e)))?;
+        }
+        Ok(())
+    }
 
-        Ok((wal_files, wal_files_nums))
+    /// Lists the WAL segments present in the database directory, ordered by
+    /// segment number.
+    ///
+    /// This is the single source of truth for which segments exist; callers use
+    /// the returned paths instead of deriving them from segment numbers.
+    fn find_wal_segments(&self) -> TreeResult<Vec<(u16, PathBuf)>> {
+        inspect::wal_segments(&self.settings.db_path)
+            .map_err(|e| TreeError::wal(format!("Failed to read DB directory: {}", e)))
     }
 
     pub(crate) fn create_new_wal_segment(&mut self, segment_num: u16) -> TreeResult<()> {
-        let wal_path = self.settings.db_path.join(format!("wal_{:04}.log", segment_num));
+        let wal_path = self.wal_segment_path(segment_num);
 
         self.wal_writer = None;
 
-        let new_writer = WalWriter::open(&wal_path)
+        let new_writer = WalWriter::open(&wal_path, self.settings.write_buffer_size)
             .map_err(|e| TreeError::wal(format!("Failed to create new WAL segment: {}", e)))?;
+        self.sync_db_directory()
+            .map_err(|e| TreeError::wal(format!("Failed to sync DB directory: {}", e)))?;
 
         self.wal_writer = Some(new_writer);
         self.add_wal_segment(segment_num);
+        self.emit_event(TreeEvent::WalRotated { segment: segment_num });
 
         debug!("Created new WAL segment: wal_{:04}.log", segment_num);
         Ok(())
@@ -195,60 +343,169 @@ impl Tree {
         }
     }
 
-    fn should_checkpoint_wal(&self) -> bool {
-        let last_wal_segment_number = self.get_last_wal_segment_number();
-        let wal_path = self.settings.db_path
-            .join(format!("wal_{:04}.log", last_wal_segment_number));
-
-        if let Ok(metadata) = std::fs::metadata(wal_path) {
-            metadata.len() > self.settings.wal_max_size
-        } else {
-            false
-        }
+    fn wal_segment_is_full(&self) -> bool {
+        self.wal_writer.as_ref().is_some_and(|wal_writer| {
+            wal_writer.size() > self.settings.wal_max_size
+                || self
+                    .settings
+                    .wal_max_entries
+                    .is_some_and(|max_entries| wal_writer.entries() >= max_entries)
+        })
     }
 
     pub(crate) fn recover_from_wal(&mut self) -> TreeResult<()> {
-        let (wal_segment_paths, wal_segments) = self.find_wal_segments()?;
-        self.wal_segments = wal_segments;
-
+        // Flushes during replay must not log checkpoints; `init_wal` reopens the writer.
+        self.wal_writer = None;
+        let wal_segments = self.find_wal_segments()?;
+        self.wal_segments = wal_segments.iter().map(|(num, _)| *num).collect();
+
+        let durable_boundary = self.durable_wal_boundary();
+        if durable_boundary.is_none() {
+            // A boundary whose SSTable was never written must not become durable
+            // when replay flushes an SSTable with that number.
+            self.clear_wal_boundary()
+                .map_err(|e| TreeError::wal(format!("Failed to update MANIFEST: {}", e)))?;
+        }
         let mut all_entries = Vec::new();
+        let progress = self.settings.recovery_progress.clone();
+        let progress_interval = self.settings.recovery_progress_interval;
+        let total_segments = wal_segments.len();
 
-        for wal_path in &wal_segment_paths {
-            let mut reader = WalReader::open(wal_path)
-                .map_err(|e| TreeError::wal(format!("Failed to open WAL {:?} for recovery: {}", wal_path, e)))?;
+        for (segment_index, (segment_num, wal_path)) in wal_segments.iter().enumerate() {
+            if let Some(progress) = &progress {
+                progress(all_entries.len(), total_segments, segment_index + 1);
+            }
+            let start = match durable_boundary {
+                Some(boundary) if *segment_num < boundary.segment => {
+                    all_entries.clear();
+                    continue;
+                }
+                Some(boundary) if *segment_num == boundary.segment => {
+                    all_entries.clear();
+                    boundary.offset
+                }
+                _ => 0,
+            };
+            let mut reader = match WalReader::open(wal_path, self.settings.read_buffer_size) {
+                Ok(reader) => reader,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    warn!("WAL segment {:04} disappeared before recovery, skipping it", segment_num);
+                    continue;
+                }
+                Err(e) => {
+                    return Err(TreeError::wal(format!("Failed to open WAL {:?} for recovery: {}", wal_path, e)));
+                }
+            };
+
+            if segment_index + 1 == total_segments {
+                truncate_torn_tail(&mut reader, wal_path)
+                    .map_err(|e| TreeError::wal(format!("Failed to read WAL entries from {:?}: {}", wal_path, e)))?;
+            }
 
+            // Only entries logged after the last checkpoint are missing from SSTables
             if reader.has_checkpoint_at_end()? {
+                all_entries.clear();
                 continue;
             }
-            let entries = reader.read_entries()
+            let entries = reader.read_entries_from(start)
                 .map_err(|e| TreeError::wal(format!("Failed to read WAL entries from {:?}: {}", wal_path, e)))?;
 
-            all_entries.extend(entries);
+            for entry in entries {
+                match entry.0 {
+                    WalOperation::Checkpoint => all_entries.clear(),
+                    _ => all_entries.push(entry),
+                }
+                if let Some(progress) = &progress {
+                    if !all_entries.is_empty() && all_entries.len() % progress_interval == 0 {
+                        progress(all_entries.len(), total_segments, segment_index + 1);
+                    }
+                }
+            }
+            debug!("Read {} WAL entries to replay up to segment {:04}", all_entries.len(), segment_num);
+        }
+
+        if let (Some((first, _)), Some((last, _))) = (wal_segments.first(), wal_segments.last()) {
+            let expected = (*last - *first) as usize + 1;
+            if expected != wal_segments.len() {
+                warn!(
+                    "{} WAL segment(s) between {:04} and {:04} are missing, recovering from the remaining ones",
+                    expected - wal_segments.len(), first, last
+                );
+            }
         }
 
+        // Replayed entries are flushed whenever the memory table outgrows
+        // `mem_table_max_size` or `mem_table_max_bytes`, like regular writes.
+        // The WAL writer is not open yet, so these flushes record no
+        // checkpoint: a crash before recovery finishes replays the same
+        // entries again. No merge is requested, as installing one removes old
+        // WAL segments still needed by the replay.
         let mut recovered_count = 0;
         for (op, key, data_value) in all_entries.into_iter() {
             match op {
                 WalOperation::Put => {
-                    self.mem_table.insert(key, data_value);
+                    self.mem_table_insert(key, data_value);
                     recovered_count += 1;
                 }
                 WalOperation::Delete => {
-                    self.mem_table.insert(key, DataValue::tombstone());
+                    self.mem_table_insert(key, DataValue::tombstone());
+                    recovered_count += 1;
+                }
+                WalOperation::DeletePrefix => {
+                    self.apply_range_tombstone(RangeTombstone::for_prefix(&key, data_value.created_at));
+                    recovered_count += 1;
+                }
+                WalOperation::Rename => {
+                    let (from, to) = decode_rename_keys(&key)
+                        .ok_or_else(|| TreeError::wal("Malformed rename entry in WAL"))?;
+                    self.mem_table_insert(to, data_value);
+                    self.mem_table_insert(from, DataValue::tombstone());
                     recovered_count += 1;
                 }
                 WalOperation::Checkpoint => {
                     continue;
                 }
             }
+            if self.mem_table_full() {
+                let immutable = self.take_mem_table();
+                self.immutable_mem_tables.push_back(Arc::new(immutable));
+                self.write_oldest_mem_table()?;
+            }
         }
 
         info!("Recovered {} entries from WAL", recovered_count);
+        if let Some(progress) = &progress {
+            progress(recovered_count, total_segments, total_segments);
+        }
+        self.emit_event(TreeEvent::Recovered { entries: recovered_count });
         self.init_wal()?;
 
         Ok(())
     }
 
+    /// Points the manifest's WAL boundary at the number its segment gets from
+    /// `rename_wal_segments_from_zero`, or drops it once the segment is gone.
+    ///
+    /// Runs before the renames: segments only move to lower numbers in order,
+    /// so a crash part-way through never lets the boundary skip entries that
+    /// are not in an SSTable.
+    fn renumber_wal_boundary(&self, segments: &[(u16, PathBuf)]) -> TreeResult<()> {
+        let result = match Manifest::read(&self.settings.db_path) {
+            Ok(Some(Manifest { wal_boundary: Some(boundary), .. })) => {
+                let new_index = segments.iter().position(|(segment_num, _)| *segment_num == boundary.segment);
+                self.update_manifest(|manifest| {
+                    manifest.wal_boundary = new_index.map(|new_index| WalBoundary {
+                        segment: new_index as u16,
+                        ..boundary
+                    })
+                })
+            }
+            Ok(_) => Ok(()),
+            Err(_) => self.clear_wal_boundary(),
+        };
+        result.map_err(|e| TreeError::wal(format!("Failed to update MANIFEST: {}", e)))
+    }
+
     pub(crate) fn check_wal_segments_need_to_be_shifted(&mut self) -> TreeResult<()> {
         let max_segment = self.get_last_wal_segment_number();
         if max_segment > 10 {
@@ -259,15 +516,15 @@ impl Tree {
     }
 
     fn rename_wal_segments_from_zero(&mut self) -> TreeResult<()> {
-        let (_, segments) = self.find_wal_segments()?;
+        let segments = self.find_wal_segments()?;
+        self.renumber_wal_boundary(&segments)?;
         let mut new_segments = Vec::new();
 
-        for (new_index, &old_segment_num) in segments.iter().enumerate() {
-            let old_path = self.settings.db_path.join(format!("wal_{:04}.log", old_segment_num));
-            let new_path = self.settings.db_path.join(format!("wal_{:04}.log", new_index));
+        for (new_index, (old_segment_num, old_path)) in segments.iter().enumerate() {
+            let new_path = self.wal_segment_path(new_index as u16);
 
-            if old_path.exists() && old_segment_num != new_index as u16 {
-                std::fs::rename(&old_path, &new_path)
+            if *old_segment_num != new_index as u16 {
+                std::fs::rename(old_path, &new_path)
                     .map_err(|e| TreeError::wal(
                         format!("Error renaming WAL segment {} -> {}: {}",
                                 old_segment_num, new_index, e)
@@ -277,13 +534,15 @@ impl Tree {
             }
             new_segments.push(new_index as u16);
         }
+        self.sync_db_directory()
+            .map_err(|e| TreeError::wal(format!("Failed to sync DB directory: {}", e)))?;
         self.wal_segments = new_segments;
 
-        if let Some(_) = self.wal_writer {
+        if self.wal_writer.is_some() {
             if let Some(&current_segment) = self.wal_segments.last() {
                 self.wal_writer = None;
-                let current_wal_path = self.settings.db_path.join(format!("wal_{:04}.log", current_segment));
-                let writer = WalWriter::open(&current_wal_path)
+                let current_wal_path = self.wal_segment_path(current_segment);
+                let writer = WalWriter::open(&current_wal_path, self.settings.write_buffer_size)
                     .map_err(|e| TreeError::wal(format!("Failed to initialize WAL: {}", e)))?;
                 self.wal_writer = Some(writer);
             }
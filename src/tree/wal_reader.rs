@@ -8,73 +8,41 @@ use std::path::Path;
 
 pub struct WalReader {
     reader: BufReader<File>,
+    position: u64,
 }
 
+/// Bytes of a WAL record besides its key and value: crc(4)+op(1)+key_len(4)+value_len(4).
+const RECORD_OVERHEAD: u64 = 13;
+
+/// A record's operation, key and still encoded value.
+type Record = (WalOperation, Vec<u8>, Vec<u8>);
+
 impl WalReader {
-    pub(crate) fn open(path: &Path) -> std::io::Result<Self> {
+    pub(crate) fn open(path: &Path, buffer_size: usize) -> std::io::Result<Self> {
         let file = OpenOptions::new().read(true).open(path)?;
         Ok(Self {
-            reader: BufReader::new(file),
+            reader: BufReader::with_capacity(buffer_size, file),
+            position: 0,
         })
     }
 
-    pub(crate) fn read_entries(&mut self) -> std::io::Result<Vec<(WalOperation, Vec<u8>, DataValue)>> {
-        use std::io::{Read, Seek, SeekFrom};
+    /// Reads the entries starting at byte `offset`, which must be an entry boundary.
+    ///
+    /// Reading stops at a torn tail, see `valid_len`.
+    pub(crate) fn read_entries_from(
+        &mut self,
+        offset: u64,
+    ) -> std::io::Result<Vec<(WalOperation, Vec<u8>, DataValue)>> {
+        use std::io::{Seek, SeekFrom};
         let file_size = self.reader.seek(SeekFrom::End(0))?;
-        if file_size == 0 {
+        if file_size <= offset {
             return Ok(Vec::new());
         }
-        self.reader.seek(SeekFrom::Start(0))?;
+        self.position = self.reader.seek(SeekFrom::Start(offset))?;
 
         let mut entries = Vec::new();
 
-        loop {
-            let mut crc_buf = [0u8; 4];
-            if self.reader.read_exact(&mut crc_buf).is_err() {
-                break;
-            }
-
-            let mut op_buf = [0u8; 1];
-            self.reader.read_exact(&mut op_buf)?;
-            let op = match op_buf[0] {
-                1 => WalOperation::Checkpoint,
-                2 => WalOperation::Put,
-                3 => WalOperation::Delete,
-                _ => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "Invalid WAL operation",
-                    ))
-                }
-            };
-
-            let mut key_len_buf = [0u8; 4];
-            self.reader.read_exact(&mut key_len_buf)?;
-            let key_len = u32::from_le_bytes(key_len_buf) as usize;
-
-            let mut key = vec![0u8; key_len];
-            self.reader.read_exact(&mut key)?;
-
-            let mut value_len_buf = [0u8; 4];
-            self.reader.read_exact(&mut value_len_buf)?;
-            let value_len = u32::from_le_bytes(value_len_buf) as usize;
-
-            let mut value_bytes = vec![0u8; value_len];
-            self.reader.read_exact(&mut value_bytes)?;
-
-            let mut hasher = Hasher::new();
-            hasher.update(&op_buf);
-            hasher.update(&key_len_buf);
-            hasher.update(&key);
-            hasher.update(&value_len_buf);
-            hasher.update(&value_bytes);
-            if hasher.finalize() != u32::from_le_bytes(crc_buf) {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "WAL operation CRC mismatch",
-                ));
-            }
-
+        while let Some((op, key, value_bytes)) = self.read_record(file_size)? {
             let data_value = if value_bytes.is_empty() {
                 match op {
                     WalOperation::Delete => DataValue::tombstone(),
@@ -103,6 +71,91 @@ impl WalReader {
         Ok(entries)
     }
 
+    /// Returns the length of the segment up to the end of its last intact record.
+    ///
+    /// A record cut short by the end of the file, or a final record failing its
+    /// CRC check, is a torn tail left by a crash during a write and is not
+    /// counted. A damaged record followed by further data is reported as an error.
+    pub(crate) fn valid_len(&mut self) -> std::io::Result<u64> {
+        use std::io::{Seek, SeekFrom};
+        let file_size = self.reader.seek(SeekFrom::End(0))?;
+        self.position = self.reader.seek(SeekFrom::Start(0))?;
+
+        let mut valid_len = 0;
+        while self.read_record(file_size)?.is_some() {
+            valid_len = self.position;
+        }
+        Ok(valid_len)
+    }
+
+    /// Reads the record at the current position, or `None` at the end of the
+    /// file or a torn tail.
+    fn read_record(&mut self, file_size: u64) -> std::io::Result<Option<Record>> {
+        use std::io::Read;
+        let remaining = file_size - self.position;
+        if remaining < RECORD_OVERHEAD {
+            return Ok(None);
+        }
+
+        let mut crc_buf = [0u8; 4];
+        self.reader.read_exact(&mut crc_buf)?;
+
+        let mut op_buf = [0u8; 1];
+        self.reader.read_exact(&mut op_buf)?;
+        let op = match op_buf[0] {
+            1 => WalOperation::Checkpoint,
+            2 => WalOperation::Put,
+            3 => WalOperation::Delete,
+            4 => WalOperation::DeletePrefix,
+            5 => WalOperation::Rename,
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Invalid WAL operation",
+                ))
+            }
+        };
+
+        let mut key_len_buf = [0u8; 4];
+        self.reader.read_exact(&mut key_len_buf)?;
+        let key_len = u32::from_le_bytes(key_len_buf) as u64;
+        if key_len > remaining - RECORD_OVERHEAD {
+            return Ok(None);
+        }
+
+        let mut key = vec![0u8; key_len as usize];
+        self.reader.read_exact(&mut key)?;
+
+        let mut value_len_buf = [0u8; 4];
+        self.reader.read_exact(&mut value_len_buf)?;
+        let value_len = u32::from_le_bytes(value_len_buf) as u64;
+        if value_len > remaining - RECORD_OVERHEAD - key_len {
+            return Ok(None);
+        }
+
+        let mut value_bytes = vec![0u8; value_len as usize];
+        self.reader.read_exact(&mut value_bytes)?;
+        self.position += RECORD_OVERHEAD + key_len + value_len;
+
+        let mut hasher = Hasher::new();
+        hasher.update(&op_buf);
+        hasher.update(&key_len_buf);
+        hasher.update(&key);
+        hasher.update(&value_len_buf);
+        hasher.update(&value_bytes);
+        if hasher.finalize() != u32::from_le_bytes(crc_buf) {
+            if self.position == file_size {
+                return Ok(None);
+            }
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "WAL operation CRC mismatch",
+            ));
+        }
+
+        Ok(Some((op, key, value_bytes)))
+    }
+
     pub(crate) fn has_checkpoint_at_end(&mut self) -> std::io::Result<bool> {
         use std::io::{Read, Seek, SeekFrom};
 
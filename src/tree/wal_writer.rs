@@ -4,17 +4,24 @@ use crate::DataValue;
 use crc32fast::Hasher;
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct WalWriter {
     writer: BufWriter<File>,
+    path: PathBuf,
+    size: u64,
+    entries: usize,
 }
 
 impl WalWriter {
-    pub(crate) fn open(path: &Path) -> std::io::Result<Self> {
+    pub(crate) fn open(path: &Path, buffer_size: usize) -> std::io::Result<Self> {
         let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
         Ok(Self {
-            writer: BufWriter::new(file),
+            writer: BufWriter::with_capacity(buffer_size, file),
+            path: path.to_path_buf(),
+            size,
+            entries: 0,
         })
     }
 
@@ -48,9 +55,39 @@ impl WalWriter {
         self.writer
             .write_all(&(value_bytes.len() as u32).to_le_bytes())?;
         self.writer.write_all(&value_bytes)?;
+        self.writer.flush()?;
+
+        self.size += (13 + key.len() + value_bytes.len()) as u64;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Size of the segment file in bytes, including entries written before it was opened.
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Number of entries written to the segment since it was opened.
+    pub(crate) fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Hands buffered entries to the operating system without syncing them.
+    pub(crate) fn flush_buffer(&mut self) -> std::io::Result<()> {
         self.writer.flush()
     }
 
+    /// Flushes buffered entries and syncs the segment file and its directory
+    /// entry to durable storage.
+    pub(crate) fn sync(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        if let Some(dir) = self.path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn write_checkpoint(&mut self) -> std::io::Result<()> {
         self.write_entry(WalOperation::Checkpoint, b"CHCKPT", None)?;
         Ok(())
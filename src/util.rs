@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 pub(crate) fn logo() {
     if cfg!(not(debug_assertions)) {
@@ -13,21 +13,9 @@ pub(crate) fn logo() {
     }
 }
 
-pub(crate) fn find_last_sstable_number(directory: &PathBuf) -> Option<usize> {
-    use std::fs::read_dir;
-
-    read_dir(directory)
+pub(crate) fn find_last_sstable_number(directory: &Path) -> Option<usize> {
+    crate::inspect::sstables(directory)
         .ok()?
-        .filter_map(|entry| entry.ok())
-        .filter_map(|entry| {
-            let file_name = entry.file_name();
-            let file_name_str = file_name.to_string_lossy();
-            if file_name_str.starts_with("sstable_") && file_name_str.ends_with(".sst") {
-                let number_part = &file_name_str[8..file_name_str.len() - 4];
-                number_part.parse::<usize>().ok()
-            } else {
-                None
-            }
-        })
-        .max()
+        .last()
+        .map(|(number, _)| *number)
 }